@@ -1,4 +1,24 @@
 //! Algorithms using the Hugr.
 
+pub mod const_fold;
+pub mod convex;
+pub mod copies;
+pub mod def_use;
+pub mod diff;
+pub mod forward_wires;
 mod half_node;
+pub mod identity;
+pub mod lanes;
+pub mod linear_balance;
 pub mod nest_cfgs;
+pub mod pattern;
+pub mod resource_delta;
+pub mod routing;
+pub mod schedule;
+
+pub use copies::materialise_copies;
+pub use forward_wires::forward_wires;
+pub use identity::{find_identity_dfgs, is_identity_region};
+pub use lanes::lane_assignment;
+pub use routing::route;
+pub use schedule::schedule;
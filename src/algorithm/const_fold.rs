@@ -0,0 +1,622 @@
+//! Constant folding, evaluating operations whose inputs are all constants.
+//!
+//! Operations are evaluated with [`OpTrait::const_fold`], or for custom
+//! operations with the constant folder of their [`OpDef`], found through the
+//! operation itself or through a [`ResourceRegistry`]. A folded operation is
+//! replaced by new [`Const`] nodes loaded into the dataflow graph, and the
+//! constants it consumed are removed if they are no longer used.
+//! [`Conditional`] nodes branching on a constant predicate are replaced by a
+//! [`DFG`] containing the taken case.
+//!
+//! [`fold_rotations`] similarly merges consecutive rotations by constant
+//! angles.
+//!
+//! [`OpDef`]: crate::resource::OpDef
+//! [`Const`]: crate::ops::Const
+//! [`Conditional`]: crate::ops::Conditional
+//! [`DFG`]: crate::ops::DFG
+
+use std::collections::HashSet;
+
+use crate::hugr::{HugrMut, HugrView};
+use crate::ops::{Const, ConstRow, ConstValue, LeafOp, LoadConstant, OpTag, OpTrait, OpType, DFG};
+use crate::resource::{ResourceRegistry, ResourceSet};
+use crate::types::angle::AngleValue;
+use crate::types::SimpleType;
+use crate::{Direction, Hugr, Node, Port};
+
+/// Fold the operations in the HUGR whose value inputs are all constants, and
+/// the Conditionals whose predicate is a constant, until no more operations
+/// can be folded.
+///
+/// The resources required by the outputs of a folded operation are added back
+/// to the loaded constants with [`LeafOp::Lift`] nodes, so the resource delta
+/// of the operation is preserved.
+///
+/// Returns the number of operations folded.
+pub fn constant_fold(hugr: &mut Hugr, registry: &ResourceRegistry) -> usize {
+    let mut folded = 0;
+    loop {
+        let candidates: Vec<Node> = hugr
+            .nodes_with_tag(OpTag::Leaf)
+            .chain(hugr.nodes_with_tag(OpTag::Conditional))
+            .collect();
+        let mut removed = HashSet::new();
+        let before = folded;
+        for node in candidates {
+            if removed.contains(&node) {
+                continue;
+            }
+            let nodes = match hugr.get_optype(node) {
+                OpType::Conditional(_) => fold_conditional(hugr, node),
+                _ => fold_node(hugr, node, registry),
+            };
+            if let Some(nodes) = nodes {
+                removed.extend(nodes);
+                folded += 1;
+            }
+        }
+        if folded == before {
+            return folded;
+        }
+    }
+}
+
+/// Merge the consecutive [`LeafOp::Rz`] rotations of a qubit by constant
+/// angles into a single rotation by the sum of the angles, until no more
+/// rotations can be merged. Rotations by angles equal to zero up to
+/// [`TOLERANCE`] are removed.
+///
+/// Returns the number of rotations removed.
+///
+/// [`TOLERANCE`]: crate::types::angle::TOLERANCE
+pub fn fold_rotations(hugr: &mut Hugr) -> usize {
+    let mut folded = 0;
+    loop {
+        let candidates: Vec<Node> = hugr.nodes_named("Rz").collect();
+        let mut removed = HashSet::new();
+        let before = folded;
+        for node in candidates {
+            if removed.contains(&node) || hugr.get_optype(node) != &LeafOp::Rz.into() {
+                continue;
+            }
+            if let Some(nodes) = fold_rotation(hugr, node) {
+                removed.extend(nodes);
+                folded += 1;
+            }
+        }
+        if folded == before {
+            return folded;
+        }
+    }
+}
+
+/// Try to fold a single operation, returning the nodes removed from the HUGR.
+fn fold_node(hugr: &mut Hugr, node: Node, registry: &ResourceRegistry) -> Option<Vec<Node>> {
+    if has_order_edges(hugr, node) {
+        return None;
+    }
+    let optype = hugr.get_optype(node);
+    let sig = optype.signature();
+
+    let mut chains = Vec::with_capacity(sig.input.len());
+    let mut consts = Vec::with_capacity(sig.input.len());
+    for i in 0..sig.input.len() {
+        let (chain, value) = const_source(hugr, node, Port::new_incoming(i))?;
+        chains.push(chain);
+        consts.push(value);
+    }
+    let outputs = match optype {
+        OpType::LeafOp(LeafOp::CustomOp(ext)) => ext.op_def(registry)?.constant_fold(&consts)?,
+        _ => optype.const_fold(&consts)?,
+    };
+    if outputs.len() != sig.output.len()
+        || outputs
+            .iter()
+            .zip(sig.output.iter())
+            .any(|(v, t)| &SimpleType::Classic(v.const_type()) != t)
+    {
+        return None;
+    }
+
+    let parent = hugr.get_parent(node)?;
+    for (i, value) in outputs.into_iter().enumerate() {
+        let port = Port::new_outgoing(i);
+        let targets: Vec<(Node, Port)> = hugr.linked_ports(node, port).collect();
+        let load = load_const(hugr, node, value);
+        let (mut src, mut resources) = (load, ResourceSet::new());
+        for resource in sig.output_resources.iter() {
+            let lift = hugr
+                .add_op_with_parent(
+                    parent,
+                    LeafOp::Lift {
+                        type_row: vec![sig.output[i].clone()].into(),
+                        input_resources: resources.clone(),
+                        new_resource: resource.clone(),
+                    },
+                )
+                .unwrap();
+            hugr.connect(src, 0, lift, 0).unwrap();
+            resources.insert(resource);
+            src = lift;
+        }
+        for (tgt, tgt_port) in targets {
+            hugr.disconnect(tgt, tgt_port).unwrap();
+            hugr.connect(src, 0, tgt, tgt_port.index()).unwrap();
+        }
+    }
+    hugr.remove_node(node).unwrap();
+
+    let mut removed = vec![node];
+    remove_unused(hugr, chains.into_iter().flatten(), &mut removed);
+    Some(removed)
+}
+
+/// Try to replace a Conditional whose predicate is a constant by a [`DFG`]
+/// node containing the taken case, with the values of the predicate variant
+/// loaded as constants. Returns the nodes removed from the HUGR.
+fn fold_conditional(hugr: &mut Hugr, node: Node) -> Option<Vec<Node>> {
+    if has_order_edges(hugr, node) {
+        return None;
+    }
+    let (chain, predicate) = const_source(hugr, node, Port::new_incoming(0))?;
+    let ConstValue::Sum { tag, val, .. } = predicate else {
+        return None;
+    };
+    let ConstValue::Tuple(values) = *val else {
+        return None;
+    };
+    let case = hugr.children(node).nth(tag)?;
+    let OpType::Case(case_op) = hugr.get_optype(case) else {
+        return None;
+    };
+    let dfg_op = DFG {
+        signature: case_op.signature.clone(),
+        resource_delta: None,
+    };
+
+    let inputs: Vec<Option<(Node, Port)>> = hugr
+        .node_inputs(node)
+        .skip(1)
+        .map(|port| hugr.linked_ports(node, port).next())
+        .collect();
+    let outputs: Vec<Vec<(Node, Port)>> = hugr
+        .node_outputs(node)
+        .map(|port| hugr.linked_ports(node, port).collect())
+        .collect();
+    let dfg = hugr.add_op_after(node, dfg_op).unwrap();
+    hugr.transplant_children(case, dfg).unwrap();
+    for (i, value) in values.into_iter().enumerate() {
+        let load = load_const(hugr, node, value);
+        hugr.connect(load, 0, dfg, i).unwrap();
+    }
+    let offset = hugr.num_inputs(dfg) - inputs.len();
+    for (i, src) in inputs.into_iter().enumerate() {
+        if let Some((src, src_port)) = src {
+            hugr.disconnect(node, Port::new_incoming(i + 1)).unwrap();
+            hugr.connect(src, src_port.index(), dfg, offset + i)
+                .unwrap();
+        }
+    }
+    for (i, targets) in outputs.into_iter().enumerate() {
+        for (tgt, tgt_port) in targets {
+            hugr.disconnect(tgt, tgt_port).unwrap();
+            hugr.connect(dfg, i, tgt, tgt_port.index()).unwrap();
+        }
+    }
+
+    let mut removed = vec![node];
+    let mut stack: Vec<Node> = hugr.children(node).collect();
+    while let Some(n) = stack.pop() {
+        stack.extend(hugr.children(n));
+        removed.push(n);
+    }
+    hugr.remove_subtree(node).unwrap();
+    remove_unused(hugr, chain, &mut removed);
+    Some(removed)
+}
+
+/// Try to merge a [`LeafOp::Rz`] rotation by a constant angle into the
+/// rotation following it, or to remove it if the angle is zero. Returns the
+/// nodes removed from the HUGR.
+fn fold_rotation(hugr: &mut Hugr, node: Node) -> Option<Vec<Node>> {
+    if has_order_edges(hugr, node) {
+        return None;
+    }
+    let (chain, angle) = const_angle(hugr, node)?;
+    let qubit_src = hugr.linked_ports(node, Port::new_incoming(0)).next()?;
+    let qubit_tgt = hugr.linked_ports(node, Port::new_outgoing(0)).next()?;
+
+    let mut chains = vec![chain];
+    let (tgt, tgt_port) = if angle.approx_eq(&AngleValue::zero()) {
+        qubit_tgt
+    } else {
+        // Merge into the next rotation, replacing its angle by the sum.
+        let (next, next_port) = qubit_tgt;
+        if hugr.get_optype(next) != &LeafOp::Rz.into() || has_order_edges(hugr, next) {
+            return None;
+        }
+        let (next_chain, next_angle) = const_angle(hugr, next)?;
+        chains.push(next_chain);
+        let sum = ConstValue::Angle((angle + next_angle).canonicalize());
+        let load = load_const(hugr, next, sum);
+        hugr.disconnect(next, Port::new_incoming(1)).unwrap();
+        hugr.connect(load, 0, next, 1).unwrap();
+        (next, next_port)
+    };
+    hugr.disconnect(tgt, tgt_port).unwrap();
+    hugr.connect(qubit_src.0, qubit_src.1.index(), tgt, tgt_port.index())
+        .unwrap();
+    hugr.remove_node(node).unwrap();
+
+    let mut removed = vec![node];
+    remove_unused(hugr, chains.into_iter().flatten(), &mut removed);
+    Some(removed)
+}
+
+/// Returns true if any of the order edges of the node are connected.
+///
+/// Order edges would be lost when removing the operation.
+fn has_order_edges(hugr: &Hugr, node: Node) -> bool {
+    let optype = hugr.get_optype(node);
+    optype
+        .other_port_index(Direction::Incoming)
+        .into_iter()
+        .chain(optype.other_port_index(Direction::Outgoing))
+        .any(|p| hugr.is_linked(node, p))
+}
+
+/// Add a [`Const`] node with the given value before `node`, loaded into the
+/// region of `node` by a new [`LoadConstant`] node, which is returned.
+fn load_const(hugr: &mut Hugr, node: Node, value: ConstValue) -> Node {
+    let parent = hugr.get_parent(node).unwrap();
+    let datatype = value.const_type();
+    let cst = hugr.add_op_before(node, Const(value)).unwrap();
+    let load = hugr
+        .add_op_with_parent(parent, LoadConstant { datatype })
+        .unwrap();
+    hugr.connect(cst, 0, load, 0).unwrap();
+    // Keep the new node reachable from the Input node of the region.
+    let input = hugr.children(parent).next().unwrap();
+    hugr.add_other_edge(input, load).unwrap();
+    load
+}
+
+/// Remove the nodes which produced the inputs of removed operations, if they
+/// have no other uses, adding them to `removed`.
+fn remove_unused(hugr: &mut Hugr, nodes: impl IntoIterator<Item = Node>, removed: &mut Vec<Node>) {
+    for n in nodes {
+        if !removed.contains(&n)
+            && !hugr
+                .node_outputs(n)
+                .any(|p| hugr.linked_ports(n, p).next().is_some())
+        {
+            hugr.remove_node(n).unwrap();
+            removed.push(n);
+        }
+    }
+}
+
+/// The constant angle of a [`LeafOp::Rz`] rotation, with the nodes producing
+/// it as returned by [`const_source`].
+fn const_angle(hugr: &Hugr, node: Node) -> Option<(Vec<Node>, AngleValue)> {
+    match const_source(hugr, node, Port::new_incoming(1))? {
+        (chain, ConstValue::Angle(angle)) => Some((chain, angle)),
+        _ => None,
+    }
+}
+
+/// Trace the value on an input port back to a [`Const`] or [`ConstRow`] node,
+/// through [`LoadConstant`] nodes and operations passing the value through.
+///
+/// Returns the nodes visited, ending with the constant node, and its value.
+fn const_source(hugr: &Hugr, node: Node, port: Port) -> Option<(Vec<Node>, ConstValue)> {
+    let mut chain = Vec::new();
+    let (mut node, mut port) = (node, port);
+    loop {
+        let (src, src_port) = hugr.linked_ports(node, port).next()?;
+        chain.push(src);
+        match hugr.get_optype(src) {
+            OpType::LeafOp(LeafOp::Noop { .. } | LeafOp::Lift { .. }) => {
+                port = Port::new_incoming(src_port.index());
+            }
+            OpType::LoadConstant(_) => port = Port::new_incoming(0),
+            OpType::Const(Const(value)) => return Some((chain, value.clone())),
+            OpType::ConstRow(ConstRow(values)) => {
+                return Some((chain, values.get(src_port.index())?.clone()))
+            }
+            _ => return None,
+        }
+        node = src;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use smol_str::SmolStr;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+        HugrBuilder, ModuleBuilder, SubContainer,
+    };
+    use crate::extensions::rotation;
+    use crate::ops::custom::{ExternalOp, OpaqueOp, ResourceOp};
+    use crate::resource::{CustomSignatureFunc, OpDef, ResourceId, SignatureError};
+    use crate::type_row;
+    use crate::types::{angle, type_param::TypeArg, ClassicType, LinearType, Signature, TypeRow};
+    use crate::{Resource, Wire};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const ANGLE: SimpleType = SimpleType::Classic(angle::ANGLE);
+
+    struct AddSig;
+
+    impl CustomSignatureFunc for AddSig {
+        fn compute_signature(
+            &self,
+            _name: &SmolStr,
+            _arg_values: &[TypeArg],
+            _misc: &HashMap<String, serde_yaml::Value>,
+        ) -> Result<(TypeRow, TypeRow, ResourceSet), SignatureError> {
+            Ok((
+                type_row![NAT, NAT],
+                type_row![NAT],
+                ResourceSet::singleton(&arith()),
+            ))
+        }
+    }
+
+    fn arith() -> ResourceId {
+        "arith".into()
+    }
+
+    /// A resource with an integer addition operation that can be folded.
+    fn registry() -> ResourceRegistry {
+        let mut add = OpDef::new_with_custom_sig(
+            "add".into(),
+            "Integer addition".into(),
+            vec![],
+            HashMap::new(),
+            AddSig,
+        );
+        add.set_constant_folder(|consts| match consts {
+            [ConstValue::Int { value: a, width }, ConstValue::Int { value: b, .. }] => {
+                Some(vec![ConstValue::Int {
+                    value: a + b,
+                    width: *width,
+                }])
+            }
+            _ => None,
+        });
+        let mut resource = Resource::new(arith());
+        resource.add_op(add).unwrap();
+        HashMap::from([(arith(), resource)])
+    }
+
+    /// Load a constant into `builder`, with the resources required by `add`.
+    fn load(builder: &mut impl Dataflow, value: i64) -> Result<Wire, BuildError> {
+        let loaded = builder.add_load_const(ConstValue::i64(value))?;
+        lift(builder, loaded)
+    }
+
+    /// Add the resources required by `add` to a loaded constant.
+    fn lift(builder: &mut impl Dataflow, loaded: Wire) -> Result<Wire, BuildError> {
+        let lift = LeafOp::Lift {
+            type_row: type_row![NAT],
+            input_resources: ResourceSet::new(),
+            new_resource: arith(),
+        };
+        Ok(builder.add_dataflow_op(lift, [loaded])?.out_wire(0))
+    }
+
+    fn consts(hugr: &Hugr) -> Vec<&ConstValue> {
+        hugr.nodes()
+            .filter_map(|n| match hugr.get_optype(n) {
+                OpType::Const(Const(value)) => Some(value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Build a function computing `(2 + 3) + 4` with the given add operation.
+    fn add_hugr(add: LeafOp) -> Result<Hugr, BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let mut sig = Signature::new_df(type_row![], type_row![NAT]);
+        sig.output_resources = ResourceSet::singleton(&arith());
+        let mut f_build = module_builder.define_function("main", sig)?;
+        let a = load(&mut f_build, 2)?;
+        let b = load(&mut f_build, 3)?;
+        let sum = f_build.add_dataflow_op(add.clone(), [a, b])?;
+        let c = load(&mut f_build, 4)?;
+        let sum = f_build.add_dataflow_op(add, [sum.out_wire(0), c])?;
+        f_build.finish_with_outputs(sum.outputs())?;
+        module_builder.finish_hugr()
+    }
+
+    #[test]
+    fn fold_opaque() -> Result<(), BuildError> {
+        let add = ExternalOp::from(OpaqueOp::new(
+            arith(),
+            "add",
+            "Integer addition".into(),
+            vec![],
+            Some(Signature::new_df(type_row![NAT, NAT], type_row![NAT])),
+        ));
+        let mut hugr = add_hugr(add.into())?;
+
+        // The operation can only be folded once its definition is known.
+        assert_eq!(constant_fold(&mut hugr, &HashMap::new()), 0);
+        assert_eq!(constant_fold(&mut hugr, &registry()), 2);
+        assert_eq!(consts(&hugr), [&ConstValue::i64(9)]);
+        assert_eq!(hugr.validate(), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    fn fold_resolved() -> Result<(), BuildError> {
+        let registry = registry();
+        let def = registry[&arith()].operations()["add"].clone();
+        let add = ExternalOp::Resource(ResourceOp::new(def, &[], &ResourceSet::new()).unwrap());
+        let mut hugr = add_hugr(add.into())?;
+
+        assert_eq!(constant_fold(&mut hugr, &HashMap::new()), 2);
+        assert_eq!(consts(&hugr), [&ConstValue::i64(9)]);
+        assert_eq!(hugr.validate(), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    fn fold_const_row() -> Result<(), BuildError> {
+        let registry = registry();
+        let def = registry[&arith()].operations()["add"].clone();
+        let add = LeafOp::from(ExternalOp::Resource(
+            ResourceOp::new(def, &[], &ResourceSet::new()).unwrap(),
+        ));
+
+        // Compute `(2 + 3) + 4` with the elements of a constant tuple loaded
+        // from a single row.
+        let mut module_builder = ModuleBuilder::new();
+        let mut sig = Signature::new_df(type_row![], type_row![NAT]);
+        sig.output_resources = ResourceSet::singleton(&arith());
+        let mut f_build = module_builder.define_function("main", sig)?;
+        let tuple = ConstValue::Tuple([2, 3, 4].map(ConstValue::i64).to_vec());
+        let loaded = f_build.add_load_const_with(tuple, true)?;
+        let [a, b, c] = <[Wire; 3]>::try_from(loaded).unwrap();
+        let [a, b, c] = [
+            lift(&mut f_build, a)?,
+            lift(&mut f_build, b)?,
+            lift(&mut f_build, c)?,
+        ];
+        let sum = f_build.add_dataflow_op(add.clone(), [a, b])?;
+        let sum = f_build.add_dataflow_op(add, [sum.out_wire(0), c])?;
+        f_build.finish_with_outputs(sum.outputs())?;
+        let mut hugr = module_builder.finish_hugr()?;
+
+        let row = hugr.nodes_named("const_row:[const:int<64>:2, const:int<64>:3, const:int<64>:4]");
+        assert_eq!(row.count(), 1);
+        assert_eq!(hugr.nodes_named("LoadConstant").count(), 3);
+        assert_eq!(hugr.lints(), vec![]);
+
+        assert_eq!(constant_fold(&mut hugr, &HashMap::new()), 2);
+        assert_eq!(consts(&hugr), [&ConstValue::i64(9)]);
+        assert_eq!(hugr.nodes_with_tag(OpTag::Const).count(), 1);
+        assert_eq!(hugr.validate(), Ok(()));
+        Ok(())
+    }
+
+    /// Build a circuit applying Rz rotations by the given angles to a qubit.
+    fn rotations_hugr(angles: &[AngleValue]) -> Result<Hugr, BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB], type_row![QB])?;
+        let [mut q] = builder.input_wires_arr();
+        for &angle in angles {
+            let angle = builder.add_load_const(ConstValue::Angle(angle))?;
+            [q] = builder
+                .add_dataflow_op(LeafOp::Rz, [q, angle])?
+                .outputs_arr();
+        }
+        builder.finish_hugr_with_outputs([q])
+    }
+
+    #[test]
+    fn fold_rz() -> Result<(), BuildError> {
+        let quarter_turn = AngleValue::rational(1, 2).unwrap();
+        let mut hugr = rotations_hugr(&[quarter_turn, quarter_turn])?;
+
+        assert_eq!(fold_rotations(&mut hugr), 1);
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(hugr.nodes_named("Rz").count(), 1);
+        assert_eq!(
+            consts(&hugr),
+            [&ConstValue::Angle(AngleValue::rational(1, 1).unwrap())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fold_rz_identity() -> Result<(), BuildError> {
+        // The rotations add up to a full turn, and are both removed.
+        let angles = [AngleValue::rational(1, 2).unwrap(), AngleValue::F64(1.5)];
+        let mut hugr = rotations_hugr(&angles)?;
+
+        assert_eq!(fold_rotations(&mut hugr), 2);
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(hugr.nodes_named("Rz").next(), None);
+        assert_eq!(consts(&hugr), Vec::<&ConstValue>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn fold_angle_add() -> Result<(), BuildError> {
+        let resource = rotation::resource();
+        let def = resource.operations()["AngleAdd"].clone();
+        let add = ExternalOp::Resource(ResourceOp::new(def, &[], &ResourceSet::new()).unwrap());
+        let mut module_builder = ModuleBuilder::new();
+        let mut sig = Signature::new_df(type_row![], type_row![ANGLE]);
+        sig.output_resources = ResourceSet::singleton(&rotation::resource_id());
+        let mut f_build = module_builder.define_function("main", sig)?;
+        let [a, b] = [
+            AngleValue::rational(3, 2).unwrap(),
+            AngleValue::rational(3, 4).unwrap(),
+        ]
+        .map(|angle| {
+            let loaded = f_build.add_load_const(ConstValue::Angle(angle))?;
+            let lift = LeafOp::Lift {
+                type_row: type_row![ANGLE],
+                input_resources: ResourceSet::new(),
+                new_resource: rotation::resource_id(),
+            };
+            Ok::<_, BuildError>(f_build.add_dataflow_op(lift, [loaded])?.out_wire(0))
+        });
+        let sum = f_build.add_dataflow_op(LeafOp::from(add), [a?, b?])?;
+        f_build.finish_with_outputs(sum.outputs())?;
+        let mut hugr = module_builder.finish_hugr()?;
+
+        assert_eq!(constant_fold(&mut hugr, &HashMap::new()), 1);
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(
+            consts(&hugr),
+            [&ConstValue::Angle(AngleValue::rational(1, 4).unwrap())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fold_bit_branch() -> Result<(), BuildError> {
+        const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+        let mut builder = DFGBuilder::new(type_row![QB], type_row![QB, BIT])?;
+        let [q] = builder.input_wires_arr();
+        let bit = builder.add_load_const(ConstValue::Int { value: 1, width: 1 })?;
+        let mut conditional = builder.conditional_builder(
+            ([type_row![], type_row![]], bit),
+            [(QB, q)],
+            type_row![QB],
+        )?;
+        let case = conditional.case_builder(0)?;
+        let inputs = case.input_wires();
+        case.finish_with_outputs(inputs)?;
+        let mut case = conditional.case_builder(1)?;
+        let x = case.add_dataflow_op(LeafOp::X, case.input_wires())?;
+        case.finish_with_outputs(x.outputs())?;
+        let [q] = conditional.finish_sub_container()?.outputs_arr();
+        let [predicate] = builder
+            .add_dataflow_op(LeafOp::BitToPredicate, [bit])?
+            .outputs_arr();
+        let [bit] = builder
+            .add_dataflow_op(LeafOp::PredicateToBit, [predicate])?
+            .outputs_arr();
+        let mut hugr = builder.finish_hugr_with_outputs([q, bit])?;
+
+        // Both conversions, and the Conditional once its predicate is folded.
+        assert_eq!(constant_fold(&mut hugr, &HashMap::new()), 4);
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(hugr.nodes_with_tag(OpTag::Conditional).next(), None);
+        assert_eq!(hugr.nodes_named("X").count(), 1);
+        assert_eq!(hugr.nodes_with_tag(OpTag::Dfg).count(), 2);
+        assert_eq!(consts(&hugr), [&ConstValue::Int { value: 1, width: 1 }]);
+        Ok(())
+    }
+}
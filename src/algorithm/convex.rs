@@ -0,0 +1,561 @@
+//! Convexity checks for sets of sibling nodes.
+//!
+//! A set of nodes in a dataflow region can only be replaced by a new subgraph
+//! if no path leaves the set and re-enters it. For quantum programs the linear
+//! wires impose an additional constraint, as each qubit must be threaded
+//! through the replacement in a single piece.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use thiserror::Error;
+
+use crate::hugr::view::HugrView;
+use crate::ops::{OpTag, OpTrait};
+use crate::types::EdgeKind;
+use crate::{Direction, Node, Port};
+
+/// Reasons why a set of nodes cannot be replaced, returned by [`is_replaceable`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[allow(missing_docs)]
+pub enum ReplaceabilityError {
+    /// A node in the set is not a child of the region.
+    #[error("The node {node:?} is not a child of the region {region:?}.")]
+    NotInRegion { node: Node, region: Node },
+    /// A node in the set is an Input, Output or Const node.
+    #[error("The node {node:?} with tag {tag:?} cannot be replaced.")]
+    InvalidNode { node: Node, tag: OpTag },
+    /// A linear wire leaves the set and later re-enters it.
+    #[error(
+        "The linear wire leaving {node:?} through port {port:?} re-enters the set at {reentry:?}."
+    )]
+    LinearWireReenters {
+        node: Node,
+        port: Port,
+        reentry: Node,
+    },
+    /// A path of Value or StateOrder edges leaves the set and re-enters it.
+    #[error("The set is not convex: a path leaving {node:?} re-enters the set at {reentry:?}.")]
+    NotConvex { node: Node, reentry: Node },
+}
+
+/// Check whether a set of children of `region` can be replaced by a new
+/// subgraph.
+///
+/// This requires that
+/// - no node in the set is an Input, Output or Const node,
+/// - each linear wire enters and exits the set at most once, following the
+///   linear inputs of each operation to the outputs at the same offset, and
+/// - the set is convex with respect to the Value and StateOrder edges of the
+///   region.
+pub fn is_replaceable(
+    hugr: &impl HugrView,
+    region: Node,
+    nodes: &HashSet<Node>,
+) -> Result<(), ReplaceabilityError> {
+    for &node in nodes {
+        if hugr.get_parent(node) != Some(region) {
+            return Err(ReplaceabilityError::NotInRegion { node, region });
+        }
+        let tag = hugr.get_optype(node).tag();
+        if [OpTag::Input, OpTag::Output, OpTag::Const].contains(&tag) {
+            return Err(ReplaceabilityError::InvalidNode { node, tag });
+        }
+    }
+
+    for &node in nodes {
+        for port in hugr.node_outputs(node) {
+            if let Some(reentry) = linear_reentry(hugr, nodes, node, port) {
+                return Err(ReplaceabilityError::LinearWireReenters {
+                    node,
+                    port,
+                    reentry,
+                });
+            }
+        }
+    }
+
+    for &node in nodes {
+        // Search the nodes outside the set reachable from `node`.
+        let mut visited = HashSet::new();
+        let mut stack = vec![node];
+        while let Some(current) = stack.pop() {
+            for next in ordering_neighbours(hugr, current, Direction::Outgoing) {
+                if hugr.get_parent(next) != Some(region) {
+                    continue;
+                }
+                if nodes.contains(&next) {
+                    if current != node {
+                        return Err(ReplaceabilityError::NotConvex {
+                            node,
+                            reentry: next,
+                        });
+                    }
+                } else if visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Convexity checks for sets of children of a dataflow region, backed by a
+/// topological order of the region which is kept up to date as the region is
+/// rewritten.
+///
+/// The children are given sparse indices in the order, so the nodes inserted
+/// by a rewrite can usually be placed between the indices of their
+/// neighbours with [`ConvexChecker::apply_update`]. The order is only rebuilt
+/// when there is no room left, which is counted by
+/// [`ConvexChecker::rebuild_count`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvexChecker {
+    region: Node,
+    /// The index of each child of the region in a topological order.
+    index: HashMap<Node, u64>,
+    rebuilds: usize,
+}
+
+impl ConvexChecker {
+    /// The gap between the indices of consecutive nodes in a rebuilt order.
+    const INDEX_GAP: u64 = 1 << 20;
+
+    /// Create a convexity checker for the children of `region`.
+    pub fn new(hugr: &impl HugrView, region: Node) -> Self {
+        let mut checker = Self {
+            region,
+            index: HashMap::new(),
+            rebuilds: 0,
+        };
+        checker.rebuild(hugr);
+        checker.rebuilds = 0;
+        checker
+    }
+
+    /// The region whose children are checked.
+    pub fn region(&self) -> Node {
+        self.region
+    }
+
+    /// The number of times the topological order was rebuilt from scratch by
+    /// [`ConvexChecker::apply_update`].
+    pub fn rebuild_count(&self) -> usize {
+        self.rebuilds
+    }
+
+    /// Check whether a set of children of the region is convex with respect to
+    /// its Value and StateOrder edges, that is, whether no path leaving the
+    /// set re-enters it.
+    ///
+    /// Returns `false` if a node is not a child of the region known to the
+    /// checker.
+    pub fn is_convex(&self, hugr: &impl HugrView, nodes: &HashSet<Node>) -> bool {
+        let Some(indices) = nodes
+            .iter()
+            .map(|n| self.index.get(n).copied())
+            .collect::<Option<Vec<u64>>>()
+        else {
+            return false;
+        };
+        let Some(&max) = indices.iter().max() else {
+            return true;
+        };
+        // A path re-entering the set only goes through nodes before its last
+        // node in the order.
+        let mut visited = HashSet::new();
+        let mut stack: Vec<Node> = nodes.iter().copied().collect();
+        while let Some(current) = stack.pop() {
+            for next in ordering_neighbours(hugr, current, Direction::Outgoing) {
+                if nodes.contains(&next) {
+                    if !nodes.contains(&current) {
+                        return false;
+                    }
+                } else if self.index.get(&next).is_some_and(|&i| i < max) && visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        true
+    }
+
+    /// Update the order after a rewrite of the region, which removed the
+    /// `removed` nodes and inserted the `inserted` ones.
+    ///
+    /// The inserted nodes are placed between the indices of their neighbours
+    /// if possible, and the order is rebuilt otherwise.
+    pub fn apply_update(&mut self, hugr: &impl HugrView, removed: &[Node], inserted: &[Node]) {
+        for node in removed {
+            self.index.remove(node);
+        }
+        if !self.insert_locally(hugr, inserted) {
+            self.rebuild(hugr);
+        }
+    }
+
+    /// Place the `inserted` nodes between their neighbours in the order.
+    /// Returns `false`, leaving the order unchanged, if there is no room
+    /// between them.
+    fn insert_locally(&mut self, hugr: &impl HugrView, inserted: &[Node]) -> bool {
+        let inserted_set: HashSet<Node> = inserted.iter().copied().collect();
+        let mut lower = 0;
+        let mut upper = u64::MAX;
+        for &node in inserted {
+            for dir in [Direction::Incoming, Direction::Outgoing] {
+                for other in ordering_neighbours(hugr, node, dir) {
+                    if inserted_set.contains(&other) || hugr.get_parent(other) != Some(self.region)
+                    {
+                        continue;
+                    }
+                    let Some(&i) = self.index.get(&other) else {
+                        return false;
+                    };
+                    match dir {
+                        Direction::Incoming => lower = lower.max(i),
+                        Direction::Outgoing => upper = upper.min(i),
+                    }
+                }
+            }
+        }
+        let Some(order) = topological_order(hugr, self.region, inserted) else {
+            return false;
+        };
+        let step = upper.saturating_sub(lower) / (order.len() as u64 + 1);
+        if step == 0 {
+            return false;
+        }
+        for (i, node) in order.into_iter().enumerate() {
+            self.index.insert(node, lower + step * (i as u64 + 1));
+        }
+        true
+    }
+
+    /// Recompute the order of all the children of the region.
+    fn rebuild(&mut self, hugr: &impl HugrView) {
+        self.rebuilds += 1;
+        let children: Vec<Node> = hugr.children(self.region).collect();
+        let order = topological_order(hugr, self.region, &children)
+            .expect("The region is not a directed acyclic graph.");
+        self.index = order
+            .into_iter()
+            .enumerate()
+            .map(|(i, node)| (node, (i as u64 + 1) * Self::INDEX_GAP))
+            .collect();
+    }
+}
+
+/// Sort the `nodes`, children of `region`, in a topological order of the
+/// edges between them. Returns `None` if they are not acyclic.
+fn topological_order(hugr: &impl HugrView, region: Node, nodes: &[Node]) -> Option<Vec<Node>> {
+    let in_set: HashSet<Node> = nodes.iter().copied().collect();
+    let successors = |node| {
+        ordering_neighbours(hugr, node, Direction::Outgoing)
+            .filter(|n| in_set.contains(n) && hugr.get_parent(*n) == Some(region))
+    };
+    let mut in_degree: HashMap<Node, usize> = nodes.iter().map(|&n| (n, 0)).collect();
+    for &node in nodes {
+        for next in successors(node) {
+            *in_degree.get_mut(&next).unwrap() += 1;
+        }
+    }
+    let mut queue: VecDeque<Node> = nodes
+        .iter()
+        .copied()
+        .filter(|n| in_degree[n] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(nodes.len());
+    while let Some(node) = queue.pop_front() {
+        order.push(node);
+        for next in successors(node) {
+            let degree = in_degree.get_mut(&next).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                queue.push_back(next);
+            }
+        }
+    }
+    (order.len() == nodes.len()).then_some(order)
+}
+
+/// The nodes connected to the ports of `node` in direction `dir` by Value or
+/// StateOrder edges.
+pub(super) fn ordering_neighbours(
+    hugr: &impl HugrView,
+    node: Node,
+    dir: Direction,
+) -> impl Iterator<Item = Node> + '_ {
+    let optype = hugr.get_optype(node);
+    hugr.node_ports(node, dir)
+        .filter(|&port| {
+            matches!(
+                optype.port_kind(port),
+                Some(EdgeKind::Value(_) | EdgeKind::StateOrder)
+            )
+        })
+        .flat_map(move |port| hugr.linked_ports(node, port).map(|(n, _)| n))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Follow the linear wire leaving `node` through `port`, if it leaves the set,
+/// and return the first node of the set it re-enters.
+fn linear_reentry(
+    hugr: &impl HugrView,
+    nodes: &HashSet<Node>,
+    node: Node,
+    port: Port,
+) -> Option<Node> {
+    let mut kind = hugr.get_optype(node).port_kind(port)?;
+    if !matches!(kind, EdgeKind::Value(_)) || !kind.is_linear() {
+        return None;
+    }
+    let (mut current, mut in_port) = hugr.linked_ports(node, port).next()?;
+    if nodes.contains(&current) {
+        return None;
+    }
+    loop {
+        if nodes.contains(&current) {
+            return Some(current);
+        }
+        // The wire continues through the output at the same offset, if it
+        // carries the same linear type.
+        let out_port = Port::new(Direction::Outgoing, in_port.index());
+        if hugr.get_optype(current).port_kind(out_port).as_ref() != Some(&kind) {
+            return None;
+        }
+        (current, in_port) = hugr.linked_ports(current, out_port).next()?;
+        kind = hugr.get_optype(current).port_kind(in_port)?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, Container, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::hugr::SimpleReplacement;
+    use crate::ops::{handle::NodeHandle, ConstValue, LeafOp};
+    use crate::types::{ClassicType, LinearType, SimpleType};
+    use crate::{type_row, Hugr};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    /// Pseudo-random circuit of `n_gates` H and CX gates on three qubits.
+    fn random_circuit(seed: u64, n_gates: usize) -> Result<Hugr, BuildError> {
+        let mut state = seed;
+        let mut next = move |bound: u64| {
+            // xorshift64
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound) as usize
+        };
+        let mut builder = DFGBuilder::new(type_row![QB, QB, QB], type_row![QB, QB, QB])?;
+        let mut circ = builder.as_circuit(builder.input_wires().collect());
+        for _ in 0..n_gates {
+            let q0 = next(3);
+            if next(2) == 0 {
+                circ.append(LeafOp::H, [q0])?;
+            } else {
+                let q1 = (q0 + 1 + next(2)) % 3;
+                circ.append(LeafOp::CX, [q0, q1])?;
+            }
+        }
+        let wires = circ.finish();
+        builder.finish_hugr_with_outputs(wires)
+    }
+
+    /// Brute-force convexity check: no node outside the set is both reachable
+    /// from the set and reaches the set.
+    fn brute_force_convex(hugr: &Hugr, nodes: &HashSet<Node>) -> bool {
+        let reachable = |from: Node| {
+            let mut seen = HashSet::new();
+            let mut stack = vec![from];
+            while let Some(n) = stack.pop() {
+                for m in hugr.output_neighbours(n) {
+                    if seen.insert(m) {
+                        stack.push(m);
+                    }
+                }
+            }
+            seen
+        };
+        hugr.children(hugr.root())
+            .filter(|n| !nodes.contains(n))
+            .all(|m| {
+                !(nodes.iter().any(|&a| reachable(a).contains(&m))
+                    && reachable(m).iter().any(|b| nodes.contains(b)))
+            })
+    }
+
+    #[test]
+    fn replaceable_matches_brute_force() -> Result<(), BuildError> {
+        for seed in 1..=8 {
+            let hugr = random_circuit(seed * 0x9E37_79B9, 7)?;
+            let children: Vec<Node> = hugr.children(hugr.root()).collect();
+            for mask in 1u32..(1 << children.len()) {
+                let nodes: HashSet<Node> = children
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & (1 << i) != 0)
+                    .map(|(_, &n)| n)
+                    .collect();
+                let has_io = nodes.contains(&children[0]) || nodes.contains(&children[1]);
+                let expected = !has_io && brute_force_convex(&hugr, &nodes);
+                assert_eq!(
+                    is_replaceable(&hugr, hugr.root(), &nodes).is_ok(),
+                    expected,
+                    "seed {seed}, nodes {nodes:?}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn checker_matches_brute_force() -> Result<(), BuildError> {
+        for seed in 1..=8 {
+            let hugr = random_circuit(seed * 0x9E37_79B9, 7)?;
+            let checker = ConvexChecker::new(&hugr, hugr.root());
+            let children: Vec<Node> = hugr.children(hugr.root()).collect();
+            for mask in 1u32..(1 << children.len()) {
+                let nodes: HashSet<Node> = children
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & (1 << i) != 0)
+                    .map(|(_, &n)| n)
+                    .collect();
+                assert_eq!(
+                    checker.is_convex(&hugr, &nodes),
+                    brute_force_convex(&hugr, &nodes),
+                    "seed {seed}, nodes {nodes:?}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn checker_incremental_updates() -> Result<(), Box<dyn std::error::Error>> {
+        const N_GATES: usize = 100;
+        let mut builder = DFGBuilder::new(type_row![QB], type_row![QB])?;
+        let mut circ = builder.as_circuit(builder.input_wires().collect());
+        for _ in 0..N_GATES {
+            circ.append(LeafOp::H, [0])?;
+        }
+        let wires = circ.finish();
+        let mut hugr = builder.finish_hugr_with_outputs(wires)?;
+        let root = hugr.root();
+        let mut checker = ConvexChecker::new(&hugr, root);
+
+        // Replace the gates one at a time, from the start of the chain.
+        let mut gates: Vec<Node> = hugr.children(root).skip(2).collect();
+        for i in 0..N_GATES {
+            let replacement = {
+                let mut builder = DFGBuilder::new(type_row![QB], type_row![QB])?;
+                let x = builder.add_dataflow_op(LeafOp::X, builder.input_wires())?;
+                builder.finish_hugr_with_outputs(x.outputs())?
+            };
+            let x = replacement.nth_child(replacement.root(), 2).unwrap();
+            let in_port = Port::new_incoming(0);
+            let successor = hugr
+                .linked_ports(gates[i], Port::new_outgoing(0))
+                .next()
+                .unwrap();
+            let before: HashSet<Node> = hugr.children(root).collect();
+            hugr.apply_rewrite(SimpleReplacement::new(
+                root,
+                HashSet::from([gates[i]]),
+                replacement,
+                HashMap::from([((x, in_port), (gates[i], in_port))]),
+                HashMap::from([(successor, in_port)]),
+            ))?;
+            let inserted: Vec<Node> = hugr
+                .children(root)
+                .filter(|n| !before.contains(n))
+                .collect();
+            checker.apply_update(&hugr, &[gates[i]], &inserted);
+            gates[i] = inserted[0];
+
+            if i % 10 == 0 {
+                let fresh = ConvexChecker::new(&hugr, root);
+                let window = HashSet::from([gates[i], gates[i + 1]]);
+                let gapped = HashSet::from([gates[i], gates[i + 2]]);
+                assert!(checker.is_convex(&hugr, &window));
+                assert!(!checker.is_convex(&hugr, &gapped));
+                for nodes in [window, gapped] {
+                    assert_eq!(
+                        checker.is_convex(&hugr, &nodes),
+                        fresh.is_convex(&hugr, &nodes)
+                    );
+                }
+            }
+        }
+        assert_eq!(checker.rebuild_count(), 0);
+
+        let all: HashSet<Node> = gates.iter().copied().collect();
+        assert!(checker.is_convex(&hugr, &all));
+        let ends = HashSet::from([gates[0], gates[N_GATES - 1]]);
+        assert!(!checker.is_convex(&hugr, &ends));
+        Ok(())
+    }
+
+    #[test]
+    fn replaceability_errors() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, BIT], type_row![QB, BIT])?;
+        let [q, b] = builder.input_wires_arr();
+        let h0 = builder.add_dataflow_op(LeafOp::H, [q])?;
+        let xor0 = builder.add_dataflow_op(LeafOp::Xor, [b, b])?;
+        let h1 = builder.add_dataflow_op(LeafOp::H, h0.outputs())?;
+        let h2 = builder.add_dataflow_op(LeafOp::H, h1.outputs())?;
+        let [input, _] = builder.io();
+        let c = builder.add_constant(ConstValue::i64(1))?;
+        let xor1 = builder.add_dataflow_op(LeafOp::Xor, xor0.outputs().chain([b]))?;
+        builder.set_order(&xor0, &h1)?;
+        builder.set_order(&h1, &xor1)?;
+        let hugr = builder.finish_hugr_with_outputs(h2.outputs().chain(xor1.outputs()))?;
+        let root = hugr.root();
+
+        assert_eq!(
+            is_replaceable(
+                &hugr,
+                root,
+                &HashSet::from([h0.node(), h1.node(), h2.node()])
+            ),
+            Ok(())
+        );
+        assert_matches!(
+            is_replaceable(&hugr, root, &HashSet::from([h0.node(), input])),
+            Err(ReplaceabilityError::InvalidNode { node, tag: OpTag::Input }) => assert_eq!(node, input)
+        );
+        assert_matches!(
+            is_replaceable(&hugr, root, &HashSet::from([c.node()])),
+            Err(ReplaceabilityError::InvalidNode {
+                tag: OpTag::Const,
+                ..
+            })
+        );
+        assert_matches!(
+            is_replaceable(&hugr, h0.node(), &HashSet::from([h1.node()])),
+            Err(ReplaceabilityError::NotInRegion { .. })
+        );
+        assert_eq!(
+            is_replaceable(&hugr, root, &HashSet::from([h0.node(), h2.node()])),
+            Err(ReplaceabilityError::LinearWireReenters {
+                node: h0.node(),
+                port: Port::new(Direction::Outgoing, 0),
+                reentry: h2.node()
+            })
+        );
+        // The path through `h1` only uses classical and order edges.
+        assert_eq!(
+            is_replaceable(&hugr, root, &HashSet::from([xor0.node(), xor1.node()])),
+            Err(ReplaceabilityError::NotConvex {
+                node: xor0.node(),
+                reentry: xor1.node()
+            })
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Explicit copies of classical values.
+//!
+//! A classical output may feed any number of inputs. Backends forbidding this
+//! implicit fan-out validate with
+//! [`ValidationOptions::forbid_implicit_copies`], after [`materialise_copies`]
+//! has replaced each fan-out by a [`LeafOp::Copy`].
+//!
+//! [`ValidationOptions::forbid_implicit_copies`]: crate::hugr::validate::ValidationOptions::forbid_implicit_copies
+
+use crate::hugr::{HugrMut, HugrView};
+use crate::ops::LeafOp;
+use crate::types::{EdgeKind, SimpleType};
+use crate::{Direction, Hugr, Node, Port};
+
+/// Replace the classical Value outputs connected more than once, in a region
+/// and in all the regions nested in it, by a single connection to a
+/// [`LeafOp::Copy`] feeding the previous targets.
+///
+/// The copy is added to the region of the source. Targets in nested regions
+/// keep an inter-graph edge, and the copy gets the order edge these require.
+///
+/// Returns the number of copies inserted.
+pub fn materialise_copies(hugr: &mut Hugr, region: Node) -> usize {
+    let children: Vec<Node> = hugr.children(region).collect();
+    let mut inserted = 0;
+    for node in children {
+        inserted += materialise_copies(hugr, node);
+        let optype = hugr.get_optype(node).clone();
+        for port in hugr
+            .node_ports(node, Direction::Outgoing)
+            .collect::<Vec<_>>()
+        {
+            let Some(EdgeKind::Value(SimpleType::Classic(ty))) = optype.port_kind(port) else {
+                continue;
+            };
+            let targets: Vec<(Node, Port)> = hugr.linked_ports(node, port).collect();
+            if targets.len() < 2 {
+                continue;
+            }
+            let copy = LeafOp::Copy {
+                n_copies: targets.len(),
+                ty,
+            };
+            let copy = hugr.add_op_with_parent(region, copy).unwrap();
+            let mut ordered = Vec::new();
+            for (i, (tgt, tgt_port)) in targets.into_iter().enumerate() {
+                hugr.disconnect(tgt, tgt_port).unwrap();
+                hugr.connect(copy, i, tgt, tgt_port.index()).unwrap();
+                // External edges require an order edge from their source to
+                // the ancestor of the target in the region.
+                let ancestor = hugr
+                    .ancestors(tgt)
+                    .find(|&n| hugr.get_parent(n) == Some(region));
+                if let Some(ancestor) = ancestor.filter(|&a| a != tgt) {
+                    if !ordered.contains(&ancestor) {
+                        hugr.add_other_edge(copy, ancestor).unwrap();
+                        ordered.push(ancestor);
+                    }
+                }
+            }
+            hugr.connect(node, port.index(), copy, 0).unwrap();
+            inserted += 1;
+        }
+    }
+    inserted
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer};
+    use crate::hugr::validate::{ValidationError, ValidationOptions};
+    use crate::type_row;
+    use crate::types::{ClassicType, Signature};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    #[test]
+    fn materialise_fan_out() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![BIT], type_row![BIT, BIT])?;
+        let [b] = builder.input_wires_arr();
+        let [input, _] = builder.io();
+        let xor = builder.add_dataflow_op(LeafOp::Xor, [b, b])?;
+        // A nested region using the bit through an external edge.
+        let mut nested = builder.dfg_builder(Signature::new_df(type_row![], type_row![BIT]), [])?;
+        let noop = nested.add_dataflow_op(LeafOp::Noop { ty: BIT }, [b])?;
+        let nested = nested.finish_with_outputs(noop.outputs())?;
+        let mut hugr = builder.finish_hugr_with_outputs([xor.out_wire(0), nested.out_wire(0)])?;
+
+        let root = hugr.root();
+        let strict = ValidationOptions {
+            forbid_implicit_copies: true,
+            ..Default::default()
+        };
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_matches!(
+            hugr.validate_with_options(&strict),
+            Err(ValidationError::TooManyConnections { node, .. }) => assert_eq!(node, input)
+        );
+
+        assert_eq!(materialise_copies(&mut hugr, root), 1);
+        assert_eq!(hugr.validate_with_options(&strict), Ok(()));
+        let (copy, _) = hugr
+            .linked_ports(input, Port::new_outgoing(0))
+            .exactly_one()
+            .unwrap();
+        assert_eq!(
+            hugr.get_optype(copy),
+            &LeafOp::Copy {
+                n_copies: 3,
+                ty: ClassicType::bit()
+            }
+            .into()
+        );
+        assert_eq!(materialise_copies(&mut hugr, root), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+//! Def-use chains, tracing where the value on a wire comes from and where it
+//! is used.
+//!
+//! Operations that pass their input value through unchanged are seen through:
+//! [`LeafOp::Noop`] nodes, [`LoadConstant`] nodes (back to their [`Const`]
+//! node), and optionally the boundaries of [`DFG`] nodes.
+//!
+//! [`LeafOp::Noop`]: crate::ops::LeafOp::Noop
+//! [`LoadConstant`]: crate::ops::LoadConstant
+//! [`Const`]: crate::ops::Const
+//! [`DFG`]: crate::ops::DFG
+
+use crate::hugr::view::HugrView;
+use crate::ops::{LeafOp, OpType};
+use crate::types::EdgeKind;
+use crate::{Direction, Node, Port, Wire};
+
+/// Options controlling the traversals of [`source_chain`] and [`uses`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TraversalOpts {
+    /// Follow wires through the input and output boundaries of [`DFG`] nodes.
+    ///
+    /// [`DFG`]: crate::ops::DFG
+    pub through_dfg: bool,
+    /// Follow external edges, connecting ports of nodes in different regions.
+    ///
+    /// Static edges from [`Const`] nodes are always followed.
+    ///
+    /// [`Const`]: crate::ops::Const
+    pub external_edges: bool,
+}
+
+/// Trace the value on an input port back to the operation producing it.
+///
+/// Returns the chain of ports visited, starting with `(node, port)` and
+/// alternating between the outgoing port the value comes from and, for
+/// operations that pass the value through, their corresponding input port.
+/// The last element is the output port of the originating operation, unless
+/// the chain stops at an unconnected input port.
+pub fn source_chain(
+    hugr: &impl HugrView,
+    node: Node,
+    port: Port,
+    opts: TraversalOpts,
+) -> Vec<(Node, Port)> {
+    debug_assert_eq!(port.direction(), Direction::Incoming);
+    let mut chain = vec![(node, port)];
+    let (mut node, mut port) = (node, port);
+    while let Some((src, src_port)) = hugr.linked_ports(node, port).next() {
+        if !follow_edge(hugr, (src, src_port), node, opts) {
+            break;
+        }
+        chain.push((src, src_port));
+        let Some(next) = pass_through(hugr, src, src_port, opts) else {
+            break;
+        };
+        chain.push(next);
+        (node, port) = next;
+    }
+    chain
+}
+
+/// Find the transitive uses of the value on a wire.
+///
+/// Returns the input ports consuming the value, in depth-first order. Input
+/// ports of operations passing the value through are not included; the uses
+/// of their outputs are listed instead. Classical values may have multiple
+/// uses, while linear values have at most one.
+pub fn uses(hugr: &impl HugrView, wire: Wire, opts: TraversalOpts) -> Vec<(Node, Port)> {
+    let mut uses = Vec::new();
+    collect_uses(hugr, wire.node(), wire.source(), opts, &mut uses);
+    uses
+}
+
+/// Add the uses of the value on an outgoing port to `uses`.
+fn collect_uses(
+    hugr: &impl HugrView,
+    node: Node,
+    port: Port,
+    opts: TraversalOpts,
+    uses: &mut Vec<(Node, Port)>,
+) {
+    for (tgt, tgt_port) in hugr.linked_ports(node, port) {
+        if !follow_edge(hugr, (node, port), tgt, opts) {
+            continue;
+        }
+        match pass_through(hugr, tgt, tgt_port, opts) {
+            Some((next, next_port)) => collect_uses(hugr, next, next_port, opts, uses),
+            None => uses.push((tgt, tgt_port)),
+        }
+    }
+}
+
+/// Whether to follow the edge from the outgoing port `src` to a port of `tgt`.
+fn follow_edge(hugr: &impl HugrView, src: (Node, Port), tgt: Node, opts: TraversalOpts) -> bool {
+    let (src, src_port) = src;
+    opts.external_edges
+        || hugr.get_parent(src) == hugr.get_parent(tgt)
+        || matches!(
+            hugr.get_optype(src).port_kind(src_port),
+            Some(EdgeKind::Static(_))
+        )
+}
+
+/// If `node` passes the value on `port` through unchanged, return the port on
+/// the other side of it.
+///
+/// For input ports this is the output port carrying the value onwards, and
+/// for output ports the input port it was received on.
+fn pass_through(
+    hugr: &impl HugrView,
+    node: Node,
+    port: Port,
+    opts: TraversalOpts,
+) -> Option<(Node, Port)> {
+    let dir = port.direction();
+    let other = Port::new(dir.reverse(), port.index());
+    match hugr.get_optype(node) {
+        OpType::LeafOp(LeafOp::Noop { .. }) | OpType::LoadConstant(_) => {
+            Some((node, Port::new(dir.reverse(), 0)))
+        }
+        OpType::DFG(_) if opts.through_dfg => {
+            // Enter the child graph through its Input or Output node.
+            let io_index = match dir {
+                Direction::Incoming => 0,
+                Direction::Outgoing => 1,
+            };
+            let io_node = hugr.children(node).nth(io_index)?;
+            Some((io_node, other))
+        }
+        OpType::Input(_) if opts.through_dfg && dir == Direction::Outgoing => {
+            leave_dfg(hugr, node, other)
+        }
+        OpType::Output(_) if opts.through_dfg && dir == Direction::Incoming => {
+            leave_dfg(hugr, node, other)
+        }
+        _ => None,
+    }
+}
+
+/// The port of the parent of an Input or Output node corresponding to
+/// `port`, if the parent is a [`DFG`] with such a port.
+///
+/// [`DFG`]: crate::ops::DFG
+fn leave_dfg(hugr: &impl HugrView, io_node: Node, port: Port) -> Option<(Node, Port)> {
+    let parent = hugr.get_parent(io_node)?;
+    let is_dfg = matches!(hugr.get_optype(parent), OpType::DFG(_));
+    (is_dfg && port.index() < hugr.num_ports(parent, port.direction())).then_some((parent, port))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer};
+    use crate::hugr::Hugr;
+    use crate::ops::{handle::NodeHandle, ConstValue};
+    use crate::type_row;
+    use crate::types::{ClassicType, Signature, SimpleType};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    /// A constant passed through a Noop into a nested DFG and the output.
+    struct Fixture {
+        hugr: Hugr,
+        cst: Node,
+        load: Node,
+        noop: Node,
+        nested: Node,
+        nested_io: [Node; 2],
+        output: Node,
+    }
+
+    fn fixture() -> Result<Fixture, BuildError> {
+        let mut builder = DFGBuilder::new(type_row![], type_row![NAT, NAT])?;
+        let loaded = builder.add_load_const(ConstValue::i64(1))?;
+        let noop = builder.add_dataflow_op(LeafOp::Noop { ty: NAT }, [loaded])?;
+        let [value] = noop.outputs_arr();
+        let nested =
+            builder.dfg_builder(Signature::new_df(type_row![NAT], type_row![NAT]), [value])?;
+        let nested_io = nested.io();
+        let nested_inputs = nested.input_wires();
+        let nested = nested.finish_with_outputs(nested_inputs)?;
+        let [_, output] = builder.io();
+        let hugr = builder.finish_hugr_with_outputs(nested.outputs().chain([value]))?;
+
+        let load = loaded.node();
+        let (cst, _) = hugr
+            .linked_ports(load, Port::new_incoming(0))
+            .next()
+            .unwrap();
+        Ok(Fixture {
+            hugr,
+            cst,
+            load,
+            noop: noop.node(),
+            nested: nested.node(),
+            nested_io,
+            output,
+        })
+    }
+
+    #[test]
+    fn trace_source() -> Result<(), BuildError> {
+        let f = fixture()?;
+        let through_dfg = TraversalOpts {
+            through_dfg: true,
+            ..Default::default()
+        };
+
+        let load_chain = [
+            (f.noop, Port::new_outgoing(0)),
+            (f.noop, Port::new_incoming(0)),
+            (f.load, Port::new_outgoing(0)),
+            (f.load, Port::new_incoming(0)),
+            (f.cst, Port::new_outgoing(0)),
+        ];
+        let chain = source_chain(&f.hugr, f.output, Port::new_incoming(1), Default::default());
+        assert_eq!(chain[0], (f.output, Port::new_incoming(1)));
+        assert_eq!(chain[1..], load_chain);
+
+        // The nested DFG is only seen through if requested.
+        assert_eq!(
+            source_chain(&f.hugr, f.output, Port::new_incoming(0), Default::default()),
+            [
+                (f.output, Port::new_incoming(0)),
+                (f.nested, Port::new_outgoing(0))
+            ]
+        );
+        let chain = source_chain(&f.hugr, f.output, Port::new_incoming(0), through_dfg);
+        let [nested_in, nested_out] = f.nested_io;
+        assert_eq!(
+            chain[..6],
+            [
+                (f.output, Port::new_incoming(0)),
+                (f.nested, Port::new_outgoing(0)),
+                (nested_out, Port::new_incoming(0)),
+                (nested_in, Port::new_outgoing(0)),
+                (f.nested, Port::new_incoming(0)),
+                (f.noop, Port::new_outgoing(0)),
+            ]
+        );
+        assert_eq!(chain[5..], load_chain);
+        Ok(())
+    }
+
+    #[test]
+    fn trace_uses() -> Result<(), BuildError> {
+        let f = fixture()?;
+        let through_dfg = TraversalOpts {
+            through_dfg: true,
+            ..Default::default()
+        };
+        let wire = Wire::new(f.cst, Port::new_outgoing(0));
+
+        assert_eq!(
+            uses(&f.hugr, wire, Default::default()),
+            [
+                (f.nested, Port::new_incoming(0)),
+                (f.output, Port::new_incoming(1))
+            ]
+        );
+        assert_eq!(
+            uses(&f.hugr, wire, through_dfg),
+            [
+                (f.output, Port::new_incoming(0)),
+                (f.output, Port::new_incoming(1))
+            ]
+        );
+
+        // The uses of the Input node of the nested DFG are in the nested graph,
+        // or outside it when looking through the boundary.
+        let [nested_in, nested_out] = f.nested_io;
+        let nested_wire = Wire::new(nested_in, Port::new_outgoing(0));
+        assert_eq!(
+            uses(&f.hugr, nested_wire, Default::default()),
+            [(nested_out, Port::new_incoming(0))]
+        );
+        assert_eq!(
+            uses(&f.hugr, nested_wire, through_dfg),
+            [(f.output, Port::new_incoming(0))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn external_edges() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![], type_row![NAT])?;
+        let loaded = builder.add_load_const(ConstValue::i64(1))?;
+        let mut nested = builder.dfg_builder(Signature::new_df(type_row![], type_row![NAT]), [])?;
+        let noop = nested.add_dataflow_op(LeafOp::Noop { ty: NAT }, [loaded])?;
+        let [_, nested_out] = nested.io();
+        let nested = nested.finish_with_outputs(noop.outputs())?;
+        let [_, output] = builder.io();
+        builder.set_order(&loaded.node(), &nested)?;
+        let hugr = builder.finish_hugr_with_outputs(nested.outputs())?;
+
+        let mut opts = TraversalOpts {
+            external_edges: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            uses(&hugr, loaded, opts),
+            [(nested_out, Port::new_incoming(0))]
+        );
+        opts.through_dfg = true;
+        assert_eq!(uses(&hugr, loaded, opts), [(output, Port::new_incoming(0))]);
+        assert!(source_chain(&hugr, output, Port::new_incoming(0), opts)
+            .contains(&(loaded.node(), loaded.source())));
+
+        // Without external edges the use in the nested graph is not found.
+        assert_eq!(uses(&hugr, loaded, Default::default()), []);
+        assert_eq!(
+            source_chain(
+                &hugr,
+                noop.node(),
+                Port::new_incoming(0),
+                Default::default()
+            ),
+            [(noop.node(), Port::new_incoming(0))]
+        );
+        Ok(())
+    }
+}
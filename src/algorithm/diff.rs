@@ -0,0 +1,428 @@
+//! Structured differences between two HUGRs.
+//!
+//! [`diff`] matches the nodes of two HUGRs region by region, starting from
+//! their roots. In each pair of matched regions, the children in the anchor
+//! positions (such as Input and Output nodes) are matched first, and the
+//! matching is then propagated along the edges connecting the children, so a
+//! small local change only affects the nodes around it. The nodes left
+//! unmatched are reported as inserted or deleted, along with whole regions
+//! when a container could not be matched.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt::{self, Display};
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use crate::hugr::view::HugrView;
+use crate::ops::{OpName, OpType};
+use crate::{Direction, Node, Port};
+
+/// A node of one of the compared HUGRs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeDesc {
+    /// The node in its HUGR.
+    pub node: Node,
+    /// The path of the node from the root, as the name and sibling position
+    /// of each of its ancestors.
+    pub path: String,
+    /// The name of the operation of the node.
+    pub op: SmolStr,
+}
+
+/// A matched node whose operation changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpChange {
+    /// The node in the old HUGR.
+    pub old: NodeDesc,
+    /// The node in the new HUGR.
+    pub new: NodeDesc,
+}
+
+/// An edge between two nodes of a HUGR.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeDesc {
+    /// The source of the edge.
+    pub source: NodeDesc,
+    /// The outgoing port offset of the edge at its source.
+    pub source_port: usize,
+    /// The target of the edge.
+    pub target: NodeDesc,
+    /// The incoming port offset of the edge at its target.
+    pub target_port: usize,
+}
+
+/// The differences between two HUGRs, as computed by [`diff`].
+///
+/// The inserted and deleted nodes are the topmost ones of the unmatched
+/// subtrees, whose descendants are inserted or deleted too. The edges are the
+/// ones between matched nodes; those connected to inserted or deleted nodes
+/// are not reported.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HugrDiff {
+    /// The nodes of the new HUGR with no match in the old one.
+    pub inserted: Vec<NodeDesc>,
+    /// The nodes of the old HUGR with no match in the new one.
+    pub deleted: Vec<NodeDesc>,
+    /// The matched nodes whose operation changed.
+    pub retyped: Vec<OpChange>,
+    /// The edges of the new HUGR missing from the old one.
+    pub added_edges: Vec<EdgeDesc>,
+    /// The edges of the old HUGR missing from the new one.
+    pub removed_edges: Vec<EdgeDesc>,
+}
+
+impl HugrDiff {
+    /// Returns `true` if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty()
+            && self.deleted.is_empty()
+            && self.retyped.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
+impl Display for NodeDesc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?})", self.path, self.node)
+    }
+}
+
+impl Display for EdgeDesc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} -> {}:{}",
+            self.source, self.source_port, self.target, self.target_port
+        )
+    }
+}
+
+impl Display for HugrDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.inserted {
+            writeln!(f, "+ {node}")?;
+        }
+        for node in &self.deleted {
+            writeln!(f, "- {node}")?;
+        }
+        for change in &self.retyped {
+            writeln!(f, "~ {} -> {}", change.old, change.new)?;
+        }
+        for edge in &self.added_edges {
+            writeln!(f, "+ edge {edge}")?;
+        }
+        for edge in &self.removed_edges {
+            writeln!(f, "- edge {edge}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the differences between the `old` and `new` HUGRs.
+pub fn diff(old: &impl HugrView, new: &impl HugrView) -> HugrDiff {
+    let matching = Matching::compute(old, new);
+    let mut diff = HugrDiff::default();
+
+    let mut queue = VecDeque::from([(old.root(), new.root())]);
+    while let Some((o, n)) = queue.pop_front() {
+        if old.get_optype(o) != new.get_optype(n) {
+            diff.retyped.push(OpChange {
+                old: describe(old, o),
+                new: describe(new, n),
+            });
+        }
+        for child in old.children(o) {
+            match matching.old_to_new.get(&child) {
+                Some(&m) => queue.push_back((child, m)),
+                None => diff.deleted.push(describe(old, child)),
+            }
+        }
+        diff.inserted.extend(
+            new.children(n)
+                .filter(|child| !matching.new_to_old.contains_key(child))
+                .map(|child| describe(new, child)),
+        );
+    }
+
+    let old_edges = matched_edges(old, &matching.old_to_new, |node| node);
+    let new_edges = matched_edges(new, &matching.new_to_old, |node| matching.new_to_old[&node]);
+    let edge_desc = |(source, source_port, target, target_port): (Node, usize, Node, usize),
+                     hugr: &dyn Fn(Node) -> NodeDesc| EdgeDesc {
+        source: hugr(source),
+        source_port,
+        target: hugr(target),
+        target_port,
+    };
+    // Both edge sets are keyed by the nodes of the old HUGR, and ordered.
+    diff.removed_edges = old_edges
+        .iter()
+        .filter(|edge| !new_edges.contains(edge))
+        .map(|&edge| edge_desc(edge, &|node| describe(old, node)))
+        .collect();
+    diff.added_edges = new_edges
+        .iter()
+        .filter(|edge| !old_edges.contains(edge))
+        .map(|&edge| edge_desc(edge, &|node| describe(new, matching.old_to_new[&node])))
+        .collect();
+    diff
+}
+
+/// A partial bijection between the nodes of two HUGRs.
+#[derive(Debug, Default)]
+struct Matching {
+    old_to_new: HashMap<Node, Node>,
+    new_to_old: HashMap<Node, Node>,
+}
+
+impl Matching {
+    /// Match the nodes of two HUGRs, region by region.
+    fn compute(old: &impl HugrView, new: &impl HugrView) -> Self {
+        let mut matching = Self::default();
+        matching.insert(old.root(), new.root());
+        let mut regions = VecDeque::from([(old.root(), new.root())]);
+        while let Some((o, n)) = regions.pop_front() {
+            let matched = matching.match_children(old, new, o, n);
+            regions.extend(matched);
+        }
+        matching
+    }
+
+    fn insert(&mut self, old: Node, new: Node) {
+        self.old_to_new.insert(old, new);
+        self.new_to_old.insert(new, old);
+    }
+
+    /// Match the children of two matched nodes, returning the newly matched
+    /// pairs.
+    fn match_children(
+        &mut self,
+        old: &impl HugrView,
+        new: &impl HugrView,
+        old_parent: Node,
+        new_parent: Node,
+    ) -> Vec<(Node, Node)> {
+        let mut matched = Vec::new();
+        // The first two children have fixed roles in dataflow and control
+        // flow regions, such as the Input and Output nodes.
+        for (o, n) in old
+            .children(old_parent)
+            .zip(new.children(new_parent))
+            .take(2)
+        {
+            if same_kind(old.get_optype(o), new.get_optype(n)) {
+                self.insert(o, n);
+                matched.push((o, n));
+            }
+        }
+        self.propagate(old, new, &mut matched, 0, same_op);
+
+        // Pair the remaining identical siblings in order, such as constants
+        // and function definitions which are not connected to the rest.
+        let mut unmatched_new: Vec<Node> = new
+            .children(new_parent)
+            .filter(|n| !self.new_to_old.contains_key(n))
+            .collect();
+        let unmatched_old: Vec<Node> = old
+            .children(old_parent)
+            .filter(|o| !self.old_to_new.contains_key(o))
+            .collect();
+        let start = matched.len();
+        for o in unmatched_old {
+            let op = old.get_optype(o);
+            if let Some(i) = unmatched_new.iter().position(|&n| new.get_optype(n) == op) {
+                let n = unmatched_new.remove(i);
+                self.insert(o, n);
+                matched.push((o, n));
+            }
+        }
+        self.propagate(old, new, &mut matched, start, same_op);
+
+        // Finally, match operations of the same kind in corresponding
+        // positions, which have been retyped.
+        self.propagate(old, new, &mut matched, 0, same_kind);
+        matched
+    }
+
+    /// Extend the matching along the edges of the matched pairs in
+    /// `matched[start..]`, pairing the unmatched siblings connected to the
+    /// same port of matched nodes when `compatible`.
+    fn propagate(
+        &mut self,
+        old: &impl HugrView,
+        new: &impl HugrView,
+        matched: &mut Vec<(Node, Node)>,
+        start: usize,
+        compatible: fn(&OpType, &OpType) -> bool,
+    ) {
+        let mut i = start;
+        while let Some(&(o, n)) = matched.get(i) {
+            i += 1;
+            for port in old.all_node_ports(o) {
+                if port.index() >= new.num_ports(n, port.direction()) {
+                    continue;
+                }
+                let mut new_links: Vec<(Node, Port)> = new.linked_ports(n, port).collect();
+                for (o_other, o_port) in old.linked_ports(o, port) {
+                    if self.old_to_new.contains_key(&o_other) {
+                        continue;
+                    }
+                    let Some(o_parent) = old.get_parent(o_other) else {
+                        continue;
+                    };
+                    let Some(&n_parent) = self.old_to_new.get(&o_parent) else {
+                        continue;
+                    };
+                    let Some(pos) = new_links.iter().position(|&(n_other, n_port)| {
+                        n_port == o_port
+                            && !self.new_to_old.contains_key(&n_other)
+                            && new.get_parent(n_other) == Some(n_parent)
+                            && compatible(old.get_optype(o_other), new.get_optype(n_other))
+                    }) else {
+                        continue;
+                    };
+                    let (n_other, _) = new_links.remove(pos);
+                    self.insert(o_other, n_other);
+                    matched.push((o_other, n_other));
+                }
+            }
+        }
+    }
+}
+
+fn same_op(old: &OpType, new: &OpType) -> bool {
+    old == new
+}
+
+fn same_kind(old: &OpType, new: &OpType) -> bool {
+    mem::discriminant(old) == mem::discriminant(new)
+}
+
+/// The edges between matched nodes of a HUGR, with their endpoints mapped to
+/// the nodes of the old HUGR by `to_old`.
+fn matched_edges(
+    hugr: &impl HugrView,
+    matched: &HashMap<Node, Node>,
+    to_old: impl Fn(Node) -> Node,
+) -> BTreeSet<(Node, usize, Node, usize)> {
+    matched
+        .keys()
+        .flat_map(|&node| hugr.all_linked_ports(node, Direction::Outgoing))
+        .filter(|((_, _), (target, _))| matched.contains_key(target))
+        .map(|((source, source_port), (target, target_port))| {
+            (
+                to_old(source),
+                source_port.index(),
+                to_old(target),
+                target_port.index(),
+            )
+        })
+        .collect()
+}
+
+/// Describe a node with its path from the root.
+fn describe(hugr: &impl HugrView, node: Node) -> NodeDesc {
+    let mut segments = Vec::new();
+    let mut current = node;
+    while let Some(parent) = hugr.get_parent(current) {
+        let position = hugr.children(parent).position(|c| c == current).unwrap();
+        segments.push(format!("{}#{}", hugr.get_optype(current).name(), position));
+        current = parent;
+    }
+    segments.push(hugr.get_optype(current).name().to_string());
+    segments.reverse();
+    NodeDesc {
+        node,
+        path: segments.join("/"),
+        op: hugr.get_optype(node).name(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::test::circuit;
+    use crate::builder::BuildError;
+    use crate::ops::LeafOp;
+
+    #[test]
+    fn identical() -> Result<(), BuildError> {
+        let gates: [(LeafOp, &[usize]); 3] =
+            [(LeafOp::H, &[0]), (LeafOp::CX, &[0, 1]), (LeafOp::H, &[1])];
+        let d = diff(&circuit(&gates)?, &circuit(&gates)?);
+        assert!(d.is_empty(), "{d}");
+        assert_eq!(d.to_string(), "");
+        Ok(())
+    }
+
+    #[test]
+    fn insert_gate() -> Result<(), BuildError> {
+        let old = circuit(&[(LeafOp::CX, &[0, 1])])?;
+        let new = circuit(&[(LeafOp::H, &[0]), (LeafOp::CX, &[0, 1])])?;
+        let d = diff(&old, &new);
+
+        assert_eq!(d.inserted.len(), 1);
+        assert_eq!(d.inserted[0].op, "H");
+        assert_eq!(d.inserted[0].path, "DFG/H#2");
+        assert!(d.deleted.is_empty() && d.retyped.is_empty());
+        // The edge from the input to the CX gate now goes through H.
+        assert_eq!(d.removed_edges.len(), 1);
+        let edge = &d.removed_edges[0];
+        assert_eq!((edge.source.op.as_str(), edge.source_port), ("Input", 0));
+        assert_eq!((edge.target.op.as_str(), edge.target_port), ("CX", 0));
+        assert!(d.added_edges.is_empty());
+
+        // The reverse diff deletes the gate.
+        let d = diff(&new, &old);
+        assert_eq!(d.deleted.len(), 1);
+        assert_eq!(d.deleted[0].op, "H");
+        assert!(d.inserted.is_empty() && d.retyped.is_empty());
+        assert_eq!(d.added_edges.len(), 1);
+        assert!(d.removed_edges.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn delete_gate() -> Result<(), BuildError> {
+        let old = circuit(&[(LeafOp::CX, &[0, 1]), (LeafOp::Z, &[1])])?;
+        let new = circuit(&[(LeafOp::CX, &[0, 1])])?;
+        let d = diff(&old, &new);
+
+        assert_eq!(d.deleted.len(), 1);
+        assert_eq!(d.deleted[0].op, "Z");
+        assert_eq!(d.deleted[0].path, "DFG/Z#3");
+        assert!(d.inserted.is_empty() && d.retyped.is_empty());
+        assert_eq!(d.added_edges.len(), 1);
+        let edge = &d.added_edges[0];
+        assert_eq!((edge.source.op.as_str(), edge.source_port), ("CX", 1));
+        assert_eq!((edge.target.op.as_str(), edge.target_port), ("Output", 1));
+        assert!(d.removed_edges.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn retype_gate() -> Result<(), BuildError> {
+        let old = circuit(&[(LeafOp::H, &[0]), (LeafOp::CX, &[0, 1])])?;
+        let new = circuit(&[(LeafOp::X, &[0]), (LeafOp::CX, &[0, 1])])?;
+        let d = diff(&old, &new);
+
+        assert_eq!(d.retyped.len(), 1);
+        assert_eq!(d.retyped[0].old.op, "H");
+        assert_eq!(d.retyped[0].new.op, "X");
+        assert!(d.inserted.is_empty() && d.deleted.is_empty());
+        assert!(d.added_edges.is_empty() && d.removed_edges.is_empty());
+        assert_eq!(
+            d.to_string(),
+            format!(
+                "~ DFG/H#2 ({:?}) -> DFG/X#2 ({:?})\n",
+                d.retyped[0].old.node, d.retyped[0].new.node
+            )
+        );
+
+        let json = serde_json::to_string(&d).unwrap();
+        assert_eq!(serde_json::from_str::<HugrDiff>(&json).unwrap(), d);
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//! Forwarding of wires through operations that pass their inputs through.
+//!
+//! [`forward_wires`] connects the users of the outputs of such operations
+//! directly to the sources of the corresponding inputs, and removes the
+//! operations. This covers [`LeafOp::Noop`] nodes, [`DFG`] nodes whose body
+//! connects its Input straight to its Output, possibly permuting the wires,
+//! and [`Conditional`] nodes with a single such case.
+//!
+//! [`DFG`]: crate::ops::DFG
+//! [`Conditional`]: crate::ops::Conditional
+
+use crate::hugr::{HugrMut, HugrView};
+use crate::ops::{LeafOp, OpTrait, OpType};
+use crate::{Direction, Hugr, Node, Port};
+
+/// Forward the wires through the pass-through operations in a region and in
+/// all the regions nested in it, removing the operations.
+///
+/// Nested regions are simplified first, so a [`DFG`] whose body becomes an
+/// identity is forwarded too. Operations with order edges are kept.
+///
+/// Returns the number of operations removed.
+///
+/// [`DFG`]: crate::ops::DFG
+pub fn forward_wires(hugr: &mut Hugr, region: Node) -> usize {
+    let children: Vec<Node> = hugr.children(region).collect();
+    let mut forwarded = 0;
+    for node in children {
+        forwarded += forward_wires(hugr, node);
+        let Some(sources) = forwarded_sources(hugr, node) else {
+            continue;
+        };
+        for (i, (src, src_port)) in sources.into_iter().enumerate() {
+            let port = Port::new_outgoing(i);
+            let targets: Vec<(Node, Port)> = hugr.linked_ports(node, port).collect();
+            for (tgt, tgt_port) in targets {
+                hugr.disconnect(tgt, tgt_port).unwrap();
+                hugr.connect(src, src_port.index(), tgt, tgt_port.index())
+                    .unwrap();
+            }
+        }
+        hugr.remove_subtree(node).unwrap();
+        forwarded += 1;
+    }
+    forwarded
+}
+
+/// The sources of the inputs of a pass-through operation that each of its
+/// outputs forwards, or `None` if the operation cannot be forwarded.
+fn forwarded_sources(hugr: &Hugr, node: Node) -> Option<Vec<(Node, Port)>> {
+    let optype = hugr.get_optype(node);
+    // Order edges would be lost when removing the operation.
+    if Direction::BOTH
+        .iter()
+        .filter_map(|&dir| optype.other_port_index(dir))
+        .any(|p| hugr.is_linked(node, p))
+    {
+        return None;
+    }
+    let inputs = match optype {
+        OpType::LeafOp(LeafOp::Noop { .. }) => vec![0],
+        OpType::DFG(_) => identity_inputs(hugr, node, 0)?,
+        OpType::Conditional(cond) => {
+            let [variant] = cond.predicate_inputs.as_slice() else {
+                return None;
+            };
+            // The predicate is discarded, so it must not hold linear values.
+            if variant.iter().any(|t| t.is_linear()) {
+                return None;
+            }
+            let [case] = hugr.children(node).collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            // The case inputs start with the contents of the predicate,
+            // which is the first input of the Conditional.
+            identity_inputs(hugr, case, variant.len())?
+                .into_iter()
+                .map(|i| i + 1)
+                .collect()
+        }
+        _ => return None,
+    };
+    inputs
+        .into_iter()
+        .map(|i| hugr.linked_ports(node, Port::new_incoming(i)).next())
+        .collect()
+}
+
+/// For a dataflow region whose Input is connected straight to its Output,
+/// the index of the input of the region forwarded to each of its outputs.
+///
+/// The first `skip` outputs of the Input node may not be forwarded.
+fn identity_inputs(hugr: &Hugr, region: Node, skip: usize) -> Option<Vec<usize>> {
+    let [input, output] = hugr.children(region).collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let num_outputs = hugr.get_optype(output).signature().input.len();
+    (0..num_outputs)
+        .map(|i| {
+            let (src, src_port) = hugr.linked_ports(output, Port::new_incoming(i)).next()?;
+            (src == input).then_some(src_port.index().checked_sub(skip)?)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{
+        BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer,
+    };
+    use crate::ops::handle::NodeHandle;
+    use crate::type_row;
+    use crate::types::{ClassicType, LinearType, Signature, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    #[test]
+    fn identity_dfg() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, NAT], type_row![QB, NAT])?;
+        let [q, n] = builder.input_wires_arr();
+        let noop = builder.add_dataflow_op(LeafOp::Noop { ty: QB }, [q])?;
+        let nested = builder.dfg_builder(
+            Signature::new_df(type_row![QB, NAT], type_row![QB, NAT]),
+            noop.outputs().chain([n]),
+        )?;
+        let wires = nested.input_wires();
+        let nested = nested.finish_with_outputs(wires)?;
+        let h = builder.add_dataflow_op(LeafOp::H, [nested.out_wire(0)])?;
+        let mut hugr = builder.finish_hugr_with_outputs(h.outputs().chain([nested.out_wire(1)]))?;
+
+        let root = hugr.root();
+        assert_eq!(forward_wires(&mut hugr, root), 2);
+        assert_eq!(hugr.validate(), Ok(()));
+        // Only the Input, Output and H nodes are left in the region.
+        assert_eq!(hugr.children(root).count(), 3);
+        let [input, output] = [0, 1].map(|i| hugr.nth_child(root, i).unwrap());
+        assert_eq!(
+            hugr.linked_ports(h.node(), Port::new_incoming(0)).next(),
+            Some((input, Port::new_outgoing(0)))
+        );
+        assert_eq!(
+            hugr.linked_ports(output, Port::new_incoming(1)).next(),
+            Some((input, Port::new_outgoing(1)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn permuting_dfg() -> Result<(), BuildError> {
+        let row = type_row![QB, NAT, QB];
+        let mut builder = DFGBuilder::new(row.clone(), type_row![QB, QB, NAT])?;
+        let nested = builder.dfg_builder(
+            Signature::new_df(row, type_row![QB, QB, NAT]),
+            builder.input_wires(),
+        )?;
+        let [a, b, c] = nested.input_wires_arr();
+        let nested = nested.finish_with_outputs([c, a, b])?;
+        let mut hugr = builder.finish_hugr_with_outputs(nested.outputs())?;
+
+        let root = hugr.root();
+        assert_eq!(forward_wires(&mut hugr, root), 1);
+        assert_eq!(hugr.validate(), Ok(()));
+        let [input, output] = [0, 1].map(|i| hugr.nth_child(root, i).unwrap());
+        let sources: Vec<_> = (0..3)
+            .map(|i| hugr.linked_ports(output, Port::new_incoming(i)).next())
+            .collect();
+        assert_eq!(
+            sources,
+            [2, 0, 1].map(|i| Some((input, Port::new_outgoing(i))))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn single_case_conditional() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![NAT, QB], type_row![QB])?;
+        let [n, q] = builder.input_wires_arr();
+        let predicate = builder.make_predicate(0, [type_row![NAT]], [n])?;
+        let mut conditional =
+            builder.conditional_builder(([type_row![NAT]], predicate), [(QB, q)], type_row![QB])?;
+        let case = conditional.case_builder(0)?;
+        let [_, q] = case.input_wires_arr();
+        case.finish_with_outputs([q])?;
+        let conditional = conditional.finish_sub_container()?;
+        let mut hugr = builder.finish_hugr_with_outputs(conditional.outputs())?;
+
+        let root = hugr.root();
+        assert_eq!(forward_wires(&mut hugr, root), 1);
+        assert_eq!(hugr.validate(), Ok(()));
+        let [input, output] = [0, 1].map(|i| hugr.nth_child(root, i).unwrap());
+        assert_eq!(
+            hugr.linked_ports(output, Port::new_incoming(0)).next(),
+            Some((input, Port::new_outgoing(1)))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! Detection of dataflow regions computing the identity.
+//!
+//! A region is the identity when each of its outputs is the input at the same
+//! index, passed through at most a chain of [`LeafOp::Noop`] nodes. Regions
+//! permuting their wires are not the identity, although [`forward_wires`]
+//! removes them too.
+//!
+//! [`forward_wires`]: super::forward_wires()
+
+use crate::hugr::HugrView;
+use crate::ops::{LeafOp, OpTag, OpTrait, OpType};
+use crate::{Direction, Node, Port};
+
+/// Returns whether a dataflow region maps each of its inputs to the output at
+/// the same index, with no operation other than [`LeafOp::Noop`] applied.
+///
+/// Returns `false` if `region` has no [`Input`] and [`Output`] children, see
+/// [`HugrView::get_io`].
+///
+/// [`Input`]: crate::ops::Input
+/// [`Output`]: crate::ops::Output
+pub fn is_identity_region(hugr: &impl HugrView, region: Node) -> bool {
+    let Some((input, output)) = hugr.get_io(region) else {
+        return false;
+    };
+    let in_types = hugr.get_optype(input).signature().output;
+    let out_types = hugr.get_optype(output).signature().input;
+    if in_types != out_types {
+        return false;
+    }
+    let only_noops = hugr
+        .children(region)
+        .filter(|&n| n != input && n != output)
+        .all(|n| matches!(hugr.get_optype(n), OpType::LeafOp(LeafOp::Noop { .. })));
+    only_noops
+        && (0..out_types.len()).all(|i| {
+            forwarded_source(hugr, output, Port::new_incoming(i))
+                == Some((input, Port::new_outgoing(i)))
+        })
+}
+
+/// Lists the [`DFG`] nodes other than the root whose body is the identity, as
+/// defined by [`is_identity_region`].
+///
+/// [`DFG`]: crate::ops::DFG
+pub fn find_identity_dfgs(hugr: &impl HugrView) -> Vec<Node> {
+    hugr.nodes_with_tag(OpTag::Dfg)
+        .filter(|&node| node != hugr.root() && is_identity_region(hugr, node))
+        .collect()
+}
+
+/// The source of the value at an input port, looking through the chains of
+/// [`LeafOp::Noop`] nodes feeding it.
+fn forwarded_source(hugr: &impl HugrView, node: Node, port: Port) -> Option<(Node, Port)> {
+    let (mut src, mut src_port) = hugr.linked_ports(node, port).next()?;
+    while let OpType::LeafOp(LeafOp::Noop { .. }) = hugr.get_optype(src) {
+        let noop_input = Port::new(Direction::Incoming, 0);
+        (src, src_port) = hugr.linked_ports(src, noop_input).next()?;
+    }
+    Some((src, src_port))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer};
+    use crate::ops::handle::NodeHandle;
+    use crate::type_row;
+    use crate::types::{ClassicType, LinearType, Signature, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    #[test]
+    fn identity_dfgs() -> Result<(), BuildError> {
+        let row = type_row![QB, NAT, QB];
+        let mut builder = DFGBuilder::new(row.clone(), row.clone())?;
+        let signature = Signature::new_df(row.clone(), row.clone());
+
+        let mut empty = builder.dfg_builder(Signature::new_df(type_row![], type_row![]), [])?;
+        let [empty_input, empty_output] = empty.io();
+        empty.set_order(&empty_input, &empty_output)?;
+        let empty = empty.finish_with_outputs([])?;
+        let [input, _] = builder.io();
+        builder.set_order(&input, &empty)?;
+
+        let mut noops = builder.dfg_builder(signature.clone(), builder.input_wires())?;
+        let [a, b, c] = noops.input_wires_arr();
+        let [a] = noops
+            .add_dataflow_op(LeafOp::Noop { ty: QB }, [a])?
+            .outputs_arr();
+        let [a] = noops
+            .add_dataflow_op(LeafOp::Noop { ty: QB }, [a])?
+            .outputs_arr();
+        let [b] = noops
+            .add_dataflow_op(LeafOp::Noop { ty: NAT }, [b])?
+            .outputs_arr();
+        let noops = noops.finish_with_outputs([a, b, c])?;
+
+        let permuting = builder.dfg_builder(signature.clone(), noops.outputs())?;
+        let [a, b, c] = permuting.input_wires_arr();
+        let permuting = permuting.finish_with_outputs([c, b, a])?;
+
+        let mut with_h = builder.dfg_builder(signature, permuting.outputs())?;
+        let [a, b, c] = with_h.input_wires_arr();
+        let [a] = with_h.add_dataflow_op(LeafOp::H, [a])?.outputs_arr();
+        let with_h = with_h.finish_with_outputs([a, b, c])?;
+
+        let hugr = builder.finish_hugr_with_outputs(with_h.outputs())?;
+
+        assert!(is_identity_region(&hugr, empty.node()));
+        assert!(is_identity_region(&hugr, noops.node()));
+        assert!(!is_identity_region(&hugr, permuting.node()));
+        assert!(!is_identity_region(&hugr, with_h.node()));
+        // The root passes its inputs through the nested DFGs.
+        assert!(!is_identity_region(&hugr, hugr.root()));
+        assert_eq!(find_identity_dfgs(&hugr), [empty.node(), noops.node()]);
+        Ok(())
+    }
+}
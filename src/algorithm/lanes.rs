@@ -0,0 +1,190 @@
+//! Tracking of the linear wires of a dataflow region back to its inputs.
+//!
+//! Each linear input of a region starts a lane, numbered by its position among
+//! the linear inputs. [`lane_assignment`] follows the lanes forward through
+//! the operations of the region, so that backends can tell which input qubit
+//! every linear wire carries.
+
+use std::collections::HashMap;
+
+use petgraph::visit::{Topo, Walker};
+use thiserror::Error;
+
+use crate::hugr::region::{FlatRegionView, Region};
+use crate::hugr::HugrView;
+use crate::ops::{LeafOp, OpTag, OpTrait, OpType};
+use crate::types::EdgeKind;
+use crate::{Direction, Node, Port, Wire};
+
+/// Errors reported by [`lane_assignment`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[allow(missing_docs)]
+pub enum LaneError {
+    /// The region is not a dataflow region with Input and Output nodes.
+    #[error("The node {0:?} is not a dataflow region.")]
+    NotDataflowRegion(Node),
+    /// An operation does not output as many linear values as it consumes, so
+    /// its outputs cannot be assigned lanes.
+    #[error("The operation at {node:?} has {inputs} linear inputs but {outputs} linear outputs.")]
+    ArityMismatch {
+        node: Node,
+        inputs: usize,
+        outputs: usize,
+    },
+    /// A linear input of an operation is not connected.
+    #[error("The linear input {port:?} of {node:?} is not connected.")]
+    Disconnected { node: Node, port: Port },
+}
+
+/// Assign to each linear wire of a dataflow region the lane of the region
+/// input it descends from.
+///
+/// The `i`-th linear input of the region starts lane `i`. Operations pass
+/// the lanes of their linear inputs to their linear outputs positionally, so
+/// the first output of a [`LeafOp::CX`] continues the lane of its first
+/// input. Permutations listed by [`lane_permutation`], such as
+/// [`LeafOp::Swap`], exchange the lanes of their wires instead.
+pub fn lane_assignment(
+    hugr: &impl HugrView,
+    region: Node,
+) -> Result<HashMap<Wire, usize>, LaneError> {
+    let mut children = hugr.children(region);
+    let (Some(input), Some(_)) = (children.next(), children.next()) else {
+        return Err(LaneError::NotDataflowRegion(region));
+    };
+    if hugr.get_optype(input).tag() != OpTag::Input {
+        return Err(LaneError::NotDataflowRegion(region));
+    }
+
+    let mut lanes: HashMap<Wire, usize> = linear_ports(hugr, input, Direction::Outgoing)
+        .into_iter()
+        .enumerate()
+        .map(|(lane, port)| (Wire::new(input, port), lane))
+        .collect();
+
+    let view = FlatRegionView::new(hugr, region);
+    let order: Vec<Node> = Topo::new(&view)
+        .iter(&view)
+        .filter(|&n| n != region && n != input)
+        .collect();
+    for node in order {
+        let ins = linear_ports(hugr, node, Direction::Incoming);
+        let outs = linear_ports(hugr, node, Direction::Outgoing);
+        let in_lanes = ins
+            .iter()
+            .map(|&port| {
+                let (src, src_port) = hugr
+                    .linked_ports(node, port)
+                    .next()
+                    .ok_or(LaneError::Disconnected { node, port })?;
+                Ok(lanes[&Wire::new(src, src_port)])
+            })
+            .collect::<Result<Vec<usize>, LaneError>>()?;
+        if hugr.get_optype(node).tag() == OpTag::Output {
+            continue;
+        }
+        if ins.len() != outs.len() {
+            return Err(LaneError::ArityMismatch {
+                node,
+                inputs: ins.len(),
+                outputs: outs.len(),
+            });
+        }
+        let permutation =
+            lane_permutation(hugr.get_optype(node)).unwrap_or_else(|| (0..outs.len()).collect());
+        for (port, i) in outs.into_iter().zip(permutation) {
+            lanes.insert(Wire::new(node, port), in_lanes[i]);
+        }
+    }
+    Ok(lanes)
+}
+
+/// The linear inputs continued by the linear outputs of an operation that
+/// permutes its wires, or `None` for operations preserving their order.
+///
+/// Entry `i` is the index of the linear input whose lane the `i`-th linear
+/// output continues.
+pub fn lane_permutation(op: &OpType) -> Option<Vec<usize>> {
+    match op {
+        OpType::LeafOp(LeafOp::Swap) => Some(vec![1, 0]),
+        _ => None,
+    }
+}
+
+/// The ports of a node carrying linear values, in the given direction.
+fn linear_ports(hugr: &impl HugrView, node: Node, dir: Direction) -> Vec<Port> {
+    let optype = hugr.get_optype(node);
+    hugr.node_ports(node, dir)
+        .filter(|&p| matches!(optype.port_kind(p), Some(EdgeKind::Value(t)) if t.is_linear()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::ops::handle::NodeHandle;
+    use crate::type_row;
+    use crate::types::{ClassicType, LinearType, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    #[test]
+    fn swap_exchanges_lanes() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![BIT, QB, QB, QB], type_row![QB, QB, QB, BIT])?;
+        let [b, q0, q1, q2] = builder.input_wires_arr();
+        let h = builder.add_dataflow_op(LeafOp::H, [q0])?;
+        let cx = builder.add_dataflow_op(LeafOp::CX, [q2, h.out_wire(0)])?;
+        let swap = builder.add_dataflow_op(LeafOp::Swap, [cx.out_wire(1), q1])?;
+        let measure = builder.add_dataflow_op(LeafOp::Measure, [swap.out_wire(0)])?;
+        let [q1, q0] = swap.outputs_arr();
+        let [q2, _] = cx.outputs_arr();
+        let [q1_measured, bit] = measure.outputs_arr();
+        let xor = builder.add_dataflow_op(LeafOp::Xor, [b, bit])?;
+        let hugr = builder.finish_hugr_with_outputs([q0, q1_measured, q2, xor.out_wire(0)])?;
+
+        let lanes = lane_assignment(&hugr, hugr.root()).unwrap();
+        let input = hugr.children(hugr.root()).next().unwrap();
+        let lanes_of = |node: Node| {
+            linear_ports(&hugr, node, Direction::Outgoing)
+                .into_iter()
+                .map(|p| lanes[&Wire::new(node, p)])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lanes_of(input), [0, 1, 2]);
+        assert_eq!(lanes_of(h.node()), [0]);
+        assert_eq!(lanes_of(cx.node()), [2, 0]);
+        assert_eq!(lanes_of(swap.node()), [1, 0]);
+        assert_eq!(lanes_of(measure.node()), [1]);
+        assert_eq!(lanes[&q1], 1);
+        // Only the linear wires are assigned lanes.
+        assert!(!lanes.contains_key(&bit));
+        assert!(!lanes.contains_key(&b));
+        assert_eq!(lanes.len(), 9);
+        Ok(())
+    }
+
+    #[test]
+    fn arity_mismatch() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB], type_row![BIT])?;
+        let [q] = builder.input_wires_arr();
+        let measure = builder.add_dataflow_op(LeafOp::MeasureDestructive, [q])?;
+        let hugr = builder.finish_hugr_with_outputs(measure.outputs())?;
+        assert_eq!(
+            lane_assignment(&hugr, hugr.root()),
+            Err(LaneError::ArityMismatch {
+                node: measure.node(),
+                inputs: 1,
+                outputs: 0
+            })
+        );
+        assert_matches!(
+            lane_assignment(&hugr, measure.node()),
+            Err(LaneError::NotDataflowRegion(_))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Conservation of linear values, such as qubits, in dataflow regions.
+//!
+//! Linear values must be used exactly once, so an operation that consumes a
+//! different number of linear values than it produces must be explicitly
+//! declared as allocating or discarding them, with
+//! [`OpTrait::creates_or_destroys_linear`]. The remaining operations are
+//! expected to conserve the number of linear values going through them.
+
+use thiserror::Error;
+
+use crate::hugr::view::HugrView;
+use crate::ops::{LeafOp, OpTag, OpTrait, OpType};
+use crate::types::EdgeKind;
+use crate::{Direction, Node, Port};
+
+/// Errors reported by [`check_linear_balance`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[allow(missing_docs)]
+pub enum LinearBalanceError {
+    /// An operation consumes a different number of linear values than it
+    /// produces, without creating or destroying them.
+    #[error("The operation at {node:?} has {inputs} linear inputs but {outputs} linear outputs.")]
+    UnbalancedOp {
+        node: Node,
+        inputs: usize,
+        outputs: usize,
+    },
+    /// The classical result of a measurement is connected to a linear port.
+    #[error("The measurement result of {node:?} is connected to the linear port {port:?} of {target:?}.")]
+    MeasurementIntoLinear {
+        node: Node,
+        target: Node,
+        port: Port,
+    },
+}
+
+/// Check that the operations in a region, and in all the regions nested in
+/// it, conserve their linear values.
+///
+/// Tuple and sum construction operations, which repackage linear values, are
+/// not checked. Neither are container nodes, whose balance follows from the
+/// operations in their regions, so a region may destroy linear values through
+/// operations such as [`LeafOp::MeasureDestructive`].
+pub fn check_linear_balance(hugr: &impl HugrView, region: Node) -> Result<(), LinearBalanceError> {
+    for node in hugr.children(region) {
+        let optype = hugr.get_optype(node);
+        let tag = optype.tag();
+        let checked = OpTag::DataflowChild.is_superset(tag)
+            && tag != OpTag::Input
+            && tag != OpTag::Output
+            && hugr.children(node).next().is_none()
+            && !matches!(
+                optype,
+                OpType::LeafOp(
+                    LeafOp::MakeTuple { .. } | LeafOp::UnpackTuple { .. } | LeafOp::Tag { .. }
+                )
+            );
+        if checked && !optype.creates_or_destroys_linear() {
+            let sig = optype.signature();
+            let inputs = sig.input.iter().filter(|t| t.is_linear()).count();
+            let outputs = sig.output.iter().filter(|t| t.is_linear()).count();
+            if inputs != outputs {
+                return Err(LinearBalanceError::UnbalancedOp {
+                    node,
+                    inputs,
+                    outputs,
+                });
+            }
+        }
+        if matches!(
+            optype,
+            OpType::LeafOp(LeafOp::Measure | LeafOp::MeasureDestructive)
+        ) {
+            check_measurement(hugr, node)?;
+        }
+        check_linear_balance(hugr, node)?;
+    }
+    Ok(())
+}
+
+/// Check that the classical outputs of a measurement are not connected to
+/// linear ports.
+fn check_measurement(hugr: &impl HugrView, node: Node) -> Result<(), LinearBalanceError> {
+    let optype = hugr.get_optype(node);
+    for port in hugr.node_ports(node, Direction::Outgoing) {
+        if !matches!(optype.port_kind(port), Some(EdgeKind::Value(t)) if t.is_classical()) {
+            continue;
+        }
+        for (target, target_port) in hugr.linked_ports(node, port) {
+            if hugr
+                .get_optype(target)
+                .port_kind(target_port)
+                .is_some_and(|kind| kind.is_linear())
+            {
+                return Err(LinearBalanceError::MeasurementIntoLinear {
+                    node,
+                    target,
+                    port: target_port,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use cool_asserts::assert_matches;
+    use smol_str::SmolStr;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+    };
+    use crate::hugr::HugrMut;
+    use crate::ops::custom::{ExternalOp, OpaqueOp, ResourceOp};
+    use crate::ops::handle::NodeHandle;
+    use crate::resource::{CustomSignatureFunc, OpDef, Resource, ResourceSet, SignatureError};
+    use crate::type_row;
+    use crate::types::type_param::TypeArg;
+    use crate::types::{ClassicType, LinearType, Signature, SimpleType, TypeRow};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    #[test]
+    fn balanced_circuit() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+        let [q0, q1] = builder.input_wires_arr();
+        let h = builder.add_dataflow_op(LeafOp::H, [q0])?;
+        let cx = builder.add_dataflow_op(LeafOp::CX, h.outputs().chain([q1]))?;
+        let [q0, q1] = cx.outputs_arr();
+        let mut nested =
+            builder.dfg_builder(Signature::new_df(type_row![QB], type_row![QB]), [q1])?;
+        let [q] = nested.input_wires_arr();
+        let measure = nested.add_dataflow_op(LeafOp::Measure, [q])?;
+        let [q, _] = measure.outputs_arr();
+        let nested = nested.finish_with_outputs([q])?;
+        let hugr = builder.finish_hugr_with_outputs([q0].into_iter().chain(nested.outputs()))?;
+
+        assert_eq!(check_linear_balance(&hugr, hugr.root()), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    fn dropped_qubit() -> Result<(), BuildError> {
+        let discard: LeafOp = ExternalOp::from(OpaqueOp::new(
+            "MyRsrc".into(),
+            "Discard",
+            "Drops a qubit".into(),
+            vec![],
+            Some(Signature::new_df(type_row![QB], type_row![])),
+        ))
+        .into();
+
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB])?;
+        let [q0, q1] = builder.input_wires_arr();
+        let dropped = builder.add_dataflow_op(discard, [q1])?;
+        builder.set_outputs([q0])?;
+        let hugr = builder.hugr();
+
+        assert_matches!(
+            check_linear_balance(hugr, hugr.root()),
+            Err(LinearBalanceError::UnbalancedOp { node, inputs: 1, outputs: 0, .. })
+                => assert_eq!(node, dropped.node())
+        );
+        Ok(())
+    }
+
+    /// A signature allocating a qubit, requiring the `alloc` resource.
+    struct AllocSig;
+
+    impl CustomSignatureFunc for AllocSig {
+        fn compute_signature(
+            &self,
+            _name: &SmolStr,
+            _arg_values: &[TypeArg],
+            _misc: &HashMap<String, serde_yaml::Value>,
+        ) -> Result<(TypeRow, TypeRow, ResourceSet), SignatureError> {
+            Ok((
+                type_row![],
+                type_row![QB],
+                ResourceSet::singleton(&"alloc".into()),
+            ))
+        }
+    }
+
+    #[test]
+    fn allocated_qubit() -> Result<(), BuildError> {
+        let mut resource = Resource::new("alloc".into());
+        for (name, flag) in [("Alloc", true), ("Leak", false)] {
+            let mut def = OpDef::new_with_custom_sig(
+                name.into(),
+                String::new(),
+                vec![],
+                HashMap::new(),
+                AllocSig,
+            );
+            def.creates_or_destroys_linear = flag;
+            resource.add_op(def).unwrap();
+        }
+        let op = |name: &str| -> LeafOp {
+            let def = resource.operations()[name].clone();
+            ExternalOp::Resource(ResourceOp::new(def, &[], &ResourceSet::new()).unwrap()).into()
+        };
+
+        let mut builder = DFGBuilder::new(type_row![], type_row![QB])?;
+        let alloc = builder.add_dataflow_op(op("Alloc"), [])?;
+        builder.set_outputs(alloc.outputs())?;
+        let hugr = builder.hugr();
+        assert_eq!(check_linear_balance(hugr, hugr.root()), Ok(()));
+
+        // The flag is kept by the serialized operation.
+        let json = serde_json::to_value(hugr.get_optype(alloc.node())).unwrap();
+        let opaque: OpType = serde_json::from_value(json).unwrap();
+        assert_matches!(
+            &opaque,
+            OpType::LeafOp(LeafOp::CustomOp(ExternalOp::Opaque(_)))
+        );
+        assert!(opaque.creates_or_destroys_linear());
+
+        let mut builder = DFGBuilder::new(type_row![], type_row![QB])?;
+        let leak = builder.add_dataflow_op(op("Leak"), [])?;
+        builder.set_outputs(leak.outputs())?;
+        let hugr = builder.hugr();
+        assert_matches!(
+            check_linear_balance(hugr, hugr.root()),
+            Err(LinearBalanceError::UnbalancedOp { node, inputs: 0, outputs: 1, .. })
+                => assert_eq!(node, leak.node())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn measurement_into_linear() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+        let [q0, q1] = builder.input_wires_arr();
+        let measure = builder.add_dataflow_op(LeafOp::Measure, [q0])?;
+        let [q0, _] = measure.outputs_arr();
+        let h = builder.add_dataflow_op(LeafOp::H, [q1])?;
+        builder.set_outputs([q0].into_iter().chain(h.outputs()))?;
+
+        // Feed the measurement result back into the Hadamard gate.
+        let mut hugr = builder.hugr().clone();
+        hugr.disconnect(h.node(), Port::new_incoming(0)).unwrap();
+        hugr.connect(measure.node(), 1, h.node(), 0).unwrap();
+        assert_eq!(
+            check_linear_balance(&hugr, hugr.root()),
+            Err(LinearBalanceError::MeasurementIntoLinear {
+                node: measure.node(),
+                target: h.node(),
+                port: Port::new_incoming(0),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn measurements() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, BIT, BIT])?;
+        let [q0, q1] = builder.input_wires_arr();
+        let measure = builder.add_dataflow_op(LeafOp::Measure, [q0])?;
+        let [q0, b0] = measure.outputs_arr();
+        // The qubit is consumed inside a region with no qubit outputs.
+        let mut nested =
+            builder.dfg_builder(Signature::new_df(type_row![QB], type_row![BIT]), [q1])?;
+        let [q] = nested.input_wires_arr();
+        let measure = nested.add_dataflow_op(LeafOp::MeasureDestructive, [q])?;
+        let nested = nested.finish_with_outputs(measure.outputs())?;
+        let hugr =
+            builder.finish_hugr_with_outputs([q0, b0].into_iter().chain(nested.outputs()))?;
+
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(check_linear_balance(&hugr, hugr.root()), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    fn destructive_measurement_into_linear() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB])?;
+        let [q0, q1] = builder.input_wires_arr();
+        let measure = builder.add_dataflow_op(LeafOp::MeasureDestructive, [q0])?;
+        let h = builder.add_dataflow_op(LeafOp::H, [q1])?;
+        builder.set_outputs(h.outputs())?;
+        assert_eq!(builder.hugr().validate(), Ok(()));
+
+        // Feed the measurement result into the Hadamard gate.
+        let mut hugr = builder.hugr().clone();
+        hugr.disconnect(h.node(), Port::new_incoming(0)).unwrap();
+        hugr.connect(measure.node(), 0, h.node(), 0).unwrap();
+        assert_matches!(
+            check_linear_balance(&hugr, hugr.root()),
+            Err(LinearBalanceError::MeasurementIntoLinear { node, .. }) => assert_eq!(node, measure.node())
+        );
+        Ok(())
+    }
+}
@@ -437,7 +437,7 @@ pub(crate) mod test {
         let (head, tail) = build_loop(&mut cfg_builder, &pred_const, &const_unit)?;
         cfg_builder.branch(&head, 0, &tail)?; // trivial "loop body"
         cfg_builder.branch(&merge, 0, &head)?;
-        let exit = cfg_builder.exit_block();
+        let exit = cfg_builder.exit_block()?;
         cfg_builder.branch(&tail, 0, &exit)?;
 
         let h = cfg_builder.finish_hugr()?;
@@ -662,7 +662,7 @@ pub(crate) mod test {
         };
         let tail = build_loop_from_header(&mut cfg_builder, &pred_const, head)?;
         cfg_builder.branch(&head, 0, &tail)?; // trivial "loop body"
-        let exit = cfg_builder.exit_block();
+        let exit = cfg_builder.exit_block()?;
         cfg_builder.branch(&tail, 0, &exit)?;
 
         let h = cfg_builder.finish_hugr()?;
@@ -695,7 +695,7 @@ pub(crate) mod test {
         };
         cfg_builder.branch(&merge, 0, &tail)?;
 
-        let exit = cfg_builder.exit_block();
+        let exit = cfg_builder.exit_block()?;
 
         cfg_builder.branch(&entry, 0, &head)?;
         cfg_builder.branch(&tail, 0, &exit)?;
@@ -0,0 +1,656 @@
+//! Matching of small dataflow patterns in a region of a HUGR.
+//!
+//! A [`Pattern`] is a connected set of operations taken from the body of a
+//! dataflow graph, such as a pair of consecutive CX gates. Its embeddings in
+//! a region are found by [`Pattern::find_matches`], which yields them lazily
+//! so that callers only wanting a few matches do not pay for all of them.
+//!
+//! When matching many patterns against the same region, a [`RegionIndex`]
+//! can be built once and passed to [`Pattern::find_matches_indexed`] to avoid
+//! scanning the whole region for each pattern.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use smol_str::SmolStr;
+use thiserror::Error;
+
+use crate::hugr::view::HugrView;
+use crate::ops::{OpName, OpType};
+use crate::{Direction, Node, Port};
+
+/// Errors that can occur when building a [`Pattern`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InvalidPattern {
+    /// The pattern has no operations besides its Input and Output nodes.
+    #[error("The pattern has no operations.")]
+    Empty,
+    /// The operations of the pattern are not connected by its edges.
+    #[error("The operations of the pattern are not connected.")]
+    NotConnected,
+}
+
+/// A connected set of operations to search for in a HUGR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    /// The operations of the pattern.
+    ops: Vec<OpType>,
+    /// The edges between the operations, as source and target pairs of an
+    /// operation index and a port.
+    edges: Vec<[(usize, Port); 2]>,
+    /// The order in which the operations after the first are matched, with
+    /// the index of an edge connecting each one to a previous operation.
+    plan: Vec<(usize, usize)>,
+    /// The neighbourhood of each operation inside the pattern.
+    fingerprints: Vec<Fingerprint>,
+}
+
+/// Options for [`Pattern::find_matches`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// The maximum number of matches to yield.
+    pub max_matches: Option<usize>,
+    /// Skip the matches sharing nodes with a previously yielded match.
+    ///
+    /// The matches are chosen greedily, so they can all be rewritten in a
+    /// single pass.
+    pub disjoint_only: bool,
+}
+
+/// An embedding of a [`Pattern`] in a HUGR.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Match {
+    nodes: Vec<Node>,
+}
+
+impl Match {
+    /// The nodes matched by the operations of the pattern, in the order of
+    /// [`Pattern::ops`].
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+}
+
+impl Pattern {
+    /// Create a pattern from the children of the root of a dataflow graph,
+    /// excluding its Input and Output nodes, and the edges between them.
+    pub fn try_from_hugr(hugr: &impl HugrView) -> Result<Self, InvalidPattern> {
+        let nodes: Vec<Node> = hugr.children(hugr.root()).skip(2).collect();
+        if nodes.is_empty() {
+            return Err(InvalidPattern::Empty);
+        }
+        let ops: Vec<OpType> = nodes.iter().map(|&n| hugr.get_optype(n).clone()).collect();
+        let index = |n: Node| nodes.iter().position(|&m| m == n);
+        let edges: Vec<[(usize, Port); 2]> = nodes
+            .iter()
+            .flat_map(|&n| hugr.all_linked_ports(n, Direction::Outgoing))
+            .filter_map(|((src, src_port), (tgt, tgt_port))| {
+                Some([(index(src)?, src_port), (index(tgt)?, tgt_port)])
+            })
+            .collect();
+
+        // Visit the operations in breadth-first order from the first one.
+        let mut plan = Vec::with_capacity(nodes.len() - 1);
+        let mut visited = vec![false; nodes.len()];
+        visited[0] = true;
+        let mut queue = VecDeque::from([0]);
+        while let Some(i) = queue.pop_front() {
+            for (e, [(src, _), (tgt, _)]) in edges.iter().enumerate() {
+                let next = match (*src == i, *tgt == i) {
+                    (true, false) => *tgt,
+                    (false, true) => *src,
+                    _ => continue,
+                };
+                if !visited[next] {
+                    visited[next] = true;
+                    plan.push((next, e));
+                    queue.push_back(next);
+                }
+            }
+        }
+        if plan.len() + 1 != nodes.len() {
+            return Err(InvalidPattern::NotConnected);
+        }
+
+        let mut fingerprints = vec![Fingerprint::default(); nodes.len()];
+        for [(src, _), (tgt, _)] in &edges {
+            fingerprints[*src].add(&ops[*tgt].name());
+            fingerprints[*tgt].add(&ops[*src].name());
+        }
+        Ok(Self {
+            ops,
+            edges,
+            plan,
+            fingerprints,
+        })
+    }
+
+    /// The operations of the pattern.
+    pub fn ops(&self) -> &[OpType] {
+        &self.ops
+    }
+
+    /// Iterates over the matches of the pattern among the children of
+    /// `region`.
+    ///
+    /// The matches are computed as the iterator advances, which remains valid
+    /// as long as the HUGR is not modified.
+    pub fn find_matches<'a, H: HugrView>(
+        &'a self,
+        hugr: &'a H,
+        region: Node,
+        options: MatchOptions,
+    ) -> Matches<'a, H> {
+        Matches {
+            pattern: self,
+            hugr,
+            region,
+            index: None,
+            anchors: Anchors::Children(hugr.children(region)),
+            pending: VecDeque::new(),
+            matched: HashSet::new(),
+            yielded: 0,
+            options,
+        }
+    }
+
+    /// Iterates over the matches of the pattern in the region of `index`.
+    ///
+    /// Only the nodes with the name of the first operation of the pattern are
+    /// tried as anchors, and the nodes whose neighbourhood cannot contain
+    /// that of the pattern are pruned early. The matches are the same as the
+    /// ones of [`Pattern::find_matches`], but the anchors are visited in node
+    /// order, so the greedy selection of [`MatchOptions::disjoint_only`] may
+    /// differ.
+    ///
+    /// The index must be up to date with the HUGR, see
+    /// [`RegionIndex::update`].
+    pub fn find_matches_indexed<'a, H: HugrView>(
+        &'a self,
+        hugr: &'a H,
+        index: &'a RegionIndex,
+        options: MatchOptions,
+    ) -> Matches<'a, H> {
+        let anchors = index.nodes_with_name(&self.ops[0].name());
+        Matches {
+            pattern: self,
+            hugr,
+            region: index.region(),
+            index: Some(index),
+            anchors: Anchors::Indexed(anchors.iter()),
+            pending: VecDeque::new(),
+            matched: HashSet::new(),
+            yielded: 0,
+            options,
+        }
+    }
+
+    /// Returns the first match of the pattern among the children of `region`,
+    /// if any.
+    pub fn find_first_match(&self, hugr: &impl HugrView, region: Node) -> Option<Match> {
+        self.find_matches(hugr, region, MatchOptions::default())
+            .next()
+    }
+
+    /// All the matches mapping the first operation of the pattern to
+    /// `anchor`.
+    fn matches_at(
+        &self,
+        hugr: &impl HugrView,
+        region: Node,
+        index: Option<&RegionIndex>,
+        anchor: Node,
+    ) -> Vec<Match> {
+        let mut matches = Vec::new();
+        if self.admits(index, 0, anchor) && hugr.get_optype(anchor) == &self.ops[0] {
+            let mut assigned = vec![None; self.ops.len()];
+            assigned[0] = Some(anchor);
+            self.extend(hugr, region, index, &mut assigned, 0, &mut matches);
+        }
+        matches
+    }
+
+    /// Check with the fingerprints of the index, if any, whether `node` may
+    /// be matched by the `op`-th operation.
+    fn admits(&self, index: Option<&RegionIndex>, op: usize, node: Node) -> bool {
+        match index {
+            Some(index) => index
+                .fingerprint(node)
+                .is_some_and(|fp| fp.covers(&self.fingerprints[op])),
+            None => true,
+        }
+    }
+
+    /// Extend a partial match following the `step`-th entry of the plan.
+    fn extend(
+        &self,
+        hugr: &impl HugrView,
+        region: Node,
+        index: Option<&RegionIndex>,
+        assigned: &mut Vec<Option<Node>>,
+        step: usize,
+        matches: &mut Vec<Match>,
+    ) {
+        let Some(&(op, edge)) = self.plan.get(step) else {
+            let nodes: Vec<Node> = assigned.iter().map(|n| n.unwrap()).collect();
+            if self.has_all_edges(hugr, &nodes) {
+                matches.push(Match { nodes });
+            }
+            return;
+        };
+        // Follow the edge from its endpoint which is already matched.
+        let [(src, src_port), (tgt, tgt_port)] = self.edges[edge];
+        let ((known, known_port), new_port) = match op == tgt {
+            true => ((src, src_port), tgt_port),
+            false => ((tgt, tgt_port), src_port),
+        };
+        let candidates: Vec<Node> = hugr
+            .linked_ports(assigned[known].unwrap(), known_port)
+            .filter(|&(_, port)| port == new_port)
+            .map(|(node, _)| node)
+            .collect();
+        for node in candidates {
+            if hugr.get_parent(node) != Some(region)
+                || !self.admits(index, op, node)
+                || hugr.get_optype(node) != &self.ops[op]
+                || assigned.contains(&Some(node))
+            {
+                continue;
+            }
+            assigned[op] = Some(node);
+            self.extend(hugr, region, index, assigned, step + 1, matches);
+            assigned[op] = None;
+        }
+    }
+
+    /// Check that the edges of the pattern are present between the matched
+    /// nodes.
+    fn has_all_edges(&self, hugr: &impl HugrView, nodes: &[Node]) -> bool {
+        self.edges
+            .iter()
+            .all(|&[(src, src_port), (tgt, tgt_port)]| {
+                hugr.linked_ports(nodes[src], src_port)
+                    .any(|link| link == (nodes[tgt], tgt_port))
+            })
+    }
+}
+
+/// A summary of the operation names of the neighbours of a node, counting
+/// each link to them.
+///
+/// The names are hashed into 16 buckets counting up to 15 links each. A node
+/// can only be matched by a pattern operation if its fingerprint
+/// [covers](Fingerprint::covers) the fingerprint of the operation inside the
+/// pattern.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    /// Count a link to a neighbour with the given operation name.
+    fn add(&mut self, name: &str) {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        let shift = (hasher.finish() % 16) * 4;
+        if (self.0 >> shift) & 0xf < 0xf {
+            self.0 += 1 << shift;
+        }
+    }
+
+    /// Returns `true` if each bucket of `self` counts at least as many links
+    /// as the one of `other`.
+    pub fn covers(&self, other: &Fingerprint) -> bool {
+        (0..16).all(|i| (self.0 >> (i * 4)) & 0xf >= (other.0 >> (i * 4)) & 0xf)
+    }
+}
+
+/// An index of the nodes of a region by operation name, with the
+/// [`Fingerprint`] of each node, for [`Pattern::find_matches_indexed`].
+///
+/// The index is built once with [`RegionIndex::new`] and kept up to date
+/// after rewrites with [`RegionIndex::update`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionIndex {
+    region: Node,
+    /// The nodes of the region with each operation name, in increasing order.
+    by_name: HashMap<SmolStr, Vec<Node>>,
+    /// The indexed nodes of the region.
+    nodes: HashMap<Node, IndexedNode>,
+}
+
+/// The data of a node in a [`RegionIndex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IndexedNode {
+    name: SmolStr,
+    /// The neighbours of the node when it was last indexed, with repetitions
+    /// for multiple links.
+    neighbours: Vec<Node>,
+    fingerprint: Fingerprint,
+}
+
+impl RegionIndex {
+    /// Index the children of `region`.
+    pub fn new(hugr: &impl HugrView, region: Node) -> Self {
+        let mut by_name: HashMap<SmolStr, Vec<Node>> = HashMap::new();
+        let mut nodes = HashMap::new();
+        for node in hugr.children(region) {
+            let name = hugr.get_optype(node).name();
+            by_name.entry(name.clone()).or_default().push(node);
+            nodes.insert(
+                node,
+                IndexedNode {
+                    name,
+                    neighbours: Vec::new(),
+                    fingerprint: Fingerprint::default(),
+                },
+            );
+        }
+        by_name.values_mut().for_each(|nodes| nodes.sort_unstable());
+        let mut index = Self {
+            region,
+            by_name,
+            nodes,
+        };
+        let all: Vec<Node> = index.nodes.keys().copied().collect();
+        index.refresh(hugr, all);
+        index
+    }
+
+    /// The indexed region.
+    pub fn region(&self) -> Node {
+        self.region
+    }
+
+    /// The nodes of the region with an operation of the given name, in
+    /// increasing order.
+    pub fn nodes_with_name(&self, name: &str) -> &[Node] {
+        self.by_name.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The fingerprint of a node of the region, if it is indexed.
+    pub fn fingerprint(&self, node: Node) -> Option<Fingerprint> {
+        self.nodes.get(&node).map(|n| n.fingerprint)
+    }
+
+    /// Update the index after the `removed` nodes of the region were
+    /// deleted and the `inserted` ones added, as when applying a
+    /// replacement.
+    ///
+    /// Only the given nodes and their previous and current neighbours are
+    /// reindexed. Inserted nodes which are not children of the region are
+    /// ignored.
+    pub fn update(
+        &mut self,
+        hugr: &impl HugrView,
+        removed: impl IntoIterator<Item = Node>,
+        inserted: impl IntoIterator<Item = Node>,
+    ) {
+        let mut stale = Vec::new();
+        for node in removed {
+            let Some(entry) = self.nodes.remove(&node) else {
+                continue;
+            };
+            if let Some(nodes) = self.by_name.get_mut(&entry.name) {
+                if let Ok(pos) = nodes.binary_search(&node) {
+                    nodes.remove(pos);
+                }
+                if nodes.is_empty() {
+                    self.by_name.remove(&entry.name);
+                }
+            }
+            stale.extend(entry.neighbours);
+        }
+        for node in inserted {
+            if hugr.get_parent(node) != Some(self.region) || self.nodes.contains_key(&node) {
+                continue;
+            }
+            let name = hugr.get_optype(node).name();
+            let nodes = self.by_name.entry(name.clone()).or_default();
+            if let Err(pos) = nodes.binary_search(&node) {
+                nodes.insert(pos, node);
+            }
+            self.nodes.insert(
+                node,
+                IndexedNode {
+                    name,
+                    neighbours: Vec::new(),
+                    fingerprint: Fingerprint::default(),
+                },
+            );
+            stale.push(node);
+            stale.extend(hugr.all_neighbours(node));
+        }
+        self.refresh(hugr, stale);
+    }
+
+    /// Recompute the neighbours and fingerprints of the given nodes, skipping
+    /// the ones not in the index.
+    fn refresh(&mut self, hugr: &impl HugrView, nodes: impl IntoIterator<Item = Node>) {
+        for node in nodes {
+            if !self.nodes.contains_key(&node) {
+                continue;
+            }
+            let neighbours: Vec<Node> = hugr.all_neighbours(node).collect();
+            let mut fingerprint = Fingerprint::default();
+            for &neighbour in &neighbours {
+                fingerprint.add(&hugr.get_optype(neighbour).name());
+            }
+            let entry = self.nodes.get_mut(&node).unwrap();
+            entry.neighbours = neighbours;
+            entry.fingerprint = fingerprint;
+        }
+    }
+}
+
+/// Iterator over the matches of a [`Pattern`], returned by
+/// [`Pattern::find_matches`] and [`Pattern::find_matches_indexed`].
+pub struct Matches<'a, H: HugrView + 'a> {
+    pattern: &'a Pattern,
+    hugr: &'a H,
+    region: Node,
+    /// The index used for pruning, if any.
+    index: Option<&'a RegionIndex>,
+    /// The nodes of the region not yet tried for the first operation.
+    anchors: Anchors<'a, H>,
+    /// The matches at the last anchor not yet yielded.
+    pending: VecDeque<Match>,
+    /// The nodes of the yielded matches, when only disjoint matches are
+    /// yielded.
+    matched: HashSet<Node>,
+    yielded: usize,
+    options: MatchOptions,
+}
+
+impl<'a, H: HugrView> Iterator for Matches<'a, H> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.options.max_matches == Some(self.yielded) {
+            return None;
+        }
+        loop {
+            let Some(m) = self.pending.pop_front() else {
+                let anchor = self.anchors.next()?;
+                self.pending = self
+                    .pattern
+                    .matches_at(self.hugr, self.region, self.index, anchor)
+                    .into();
+                continue;
+            };
+            if self.options.disjoint_only {
+                if m.nodes.iter().any(|n| self.matched.contains(n)) {
+                    continue;
+                }
+                self.matched.extend(m.nodes.iter().copied());
+            }
+            self.yielded += 1;
+            return Some(m);
+        }
+    }
+}
+
+/// The candidate nodes for the first operation of a pattern.
+enum Anchors<'a, H: HugrView + 'a> {
+    /// All the children of the region.
+    Children(H::Children<'a>),
+    /// The nodes of a [`RegionIndex`] with the name of the operation.
+    Indexed(std::slice::Iter<'a, Node>),
+}
+
+impl<'a, H: HugrView> Iterator for Anchors<'a, H> {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Node> {
+        match self {
+            Anchors::Children(children) => children.next(),
+            Anchors::Indexed(nodes) => nodes.next().copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::test::circuit;
+    use crate::builder::BuildError;
+    use crate::hugr::HugrMut;
+    use crate::ops::LeafOp;
+
+    /// Gates with the qubits they act on.
+    type Gates<'a> = &'a [(LeafOp, &'a [usize])];
+
+    #[test]
+    fn lazy_matches() -> Result<(), BuildError> {
+        let layer: [(LeafOp, &[usize]); 3] =
+            [(LeafOp::H, &[0]), (LeafOp::H, &[1]), (LeafOp::CX, &[0, 1])];
+        let hugr = circuit(&[layer.clone(), layer.clone(), layer].concat())?;
+        let pattern = Pattern::try_from_hugr(&circuit(&[(LeafOp::H, &[0])])?).unwrap();
+        let root = hugr.root();
+
+        let all = MatchOptions::default();
+        assert_eq!(pattern.find_matches(&hugr, root, all).count(), 6);
+        let limited = MatchOptions {
+            max_matches: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(pattern.find_matches(&hugr, root, limited).count(), 4);
+
+        let first = pattern.find_first_match(&hugr, root).unwrap();
+        assert_eq!(first.nodes().len(), 1);
+        assert_eq!(hugr.get_optype(first.nodes()[0]), &LeafOp::H.into());
+        assert_eq!(pattern.find_matches(&hugr, root, all).next(), Some(first));
+        Ok(())
+    }
+
+    #[test]
+    fn disjoint_matches() -> Result<(), BuildError> {
+        let cx: (LeafOp, &[usize]) = (LeafOp::CX, &[0, 1]);
+        let hugr = circuit(&[cx.clone(), cx.clone(), cx.clone()])?;
+        let pattern = Pattern::try_from_hugr(&circuit(&[cx.clone(), cx.clone()])?).unwrap();
+        let root = hugr.root();
+        let cxs: Vec<Node> = hugr.children(root).skip(2).collect();
+
+        let overlapping: Vec<Match> = pattern
+            .find_matches(&hugr, root, MatchOptions::default())
+            .collect();
+        assert_eq!(
+            overlapping.iter().map(Match::nodes).collect::<Vec<_>>(),
+            [&cxs[0..2], &cxs[1..3]]
+        );
+        let disjoint = MatchOptions {
+            disjoint_only: true,
+            ..Default::default()
+        };
+        let disjoint: Vec<Match> = pattern.find_matches(&hugr, root, disjoint).collect();
+        assert_eq!(disjoint, overlapping[..1]);
+
+        // CX gates with swapped qubits are not matched.
+        let swapped = circuit(&[cx, (LeafOp::CX, &[1, 0])])?;
+        let root = swapped.root();
+        assert_eq!(pattern.find_first_match(&swapped, root), None);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_patterns() -> Result<(), BuildError> {
+        assert_eq!(
+            Pattern::try_from_hugr(&circuit(&[])?),
+            Err(InvalidPattern::Empty)
+        );
+        assert_eq!(
+            Pattern::try_from_hugr(&circuit(&[(LeafOp::H, &[0]), (LeafOp::H, &[1])])?),
+            Err(InvalidPattern::NotConnected)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn indexed_matches() -> Result<(), BuildError> {
+        let layer: [(LeafOp, &[usize]); 4] = [
+            (LeafOp::H, &[0]),
+            (LeafOp::CX, &[0, 1]),
+            (LeafOp::H, &[1]),
+            (LeafOp::CX, &[1, 0]),
+        ];
+        let gates: Vec<_> = layer.into_iter().cycle().take(500).collect();
+        let hugr = circuit(&gates)?;
+        let root = hugr.root();
+        assert_eq!(hugr.children(root).count(), 502);
+        let index = RegionIndex::new(&hugr, root);
+        assert_eq!(index.nodes_with_name("H").len(), 250);
+        assert_eq!(index.nodes_with_name("X"), []);
+
+        // Patterns with the number of their matches.
+        let patterns: [(Gates, usize); 4] = [
+            (&[(LeafOp::H, &[0]), (LeafOp::CX, &[0, 1])], 250),
+            (
+                &[
+                    (LeafOp::CX, &[0, 1]),
+                    (LeafOp::H, &[1]),
+                    (LeafOp::CX, &[1, 0]),
+                ],
+                249,
+            ),
+            (&[(LeafOp::CX, &[1, 0]), (LeafOp::CX, &[0, 1])], 0),
+            (&[(LeafOp::CX, &[0, 1]), (LeafOp::CX, &[0, 1])], 0),
+        ];
+        let all = MatchOptions::default();
+        for (gates, count) in patterns {
+            let pattern = Pattern::try_from_hugr(&circuit(gates)?).unwrap();
+            let plain: HashSet<Match> = pattern.find_matches(&hugr, root, all).collect();
+            let indexed: HashSet<Match> =
+                pattern.find_matches_indexed(&hugr, &index, all).collect();
+            assert_eq!(plain.len(), count);
+            assert_eq!(plain, indexed);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn index_update() -> Result<(), BuildError> {
+        let mut hugr = circuit(&[(LeafOp::H, &[0]), (LeafOp::CX, &[0, 1]), (LeafOp::H, &[0])])?;
+        let root = hugr.root();
+        let mut index = RegionIndex::new(&hugr, root);
+
+        // Replace the first H gate by an X gate.
+        let h = index.nodes_with_name("H")[0];
+        let (pred, pred_port) = hugr.linked_ports(h, Port::new_incoming(0)).next().unwrap();
+        let (succ, succ_port) = hugr.linked_ports(h, Port::new_outgoing(0)).next().unwrap();
+        hugr.remove_node(h).unwrap();
+        let x = hugr.add_op_with_parent(root, LeafOp::X).unwrap();
+        hugr.connect(pred, pred_port.index(), x, 0).unwrap();
+        hugr.connect(x, 0, succ, succ_port.index()).unwrap();
+        assert_eq!(hugr.validate(), Ok(()));
+
+        index.update(&hugr, [h], [x]);
+        assert_eq!(index, RegionIndex::new(&hugr, root));
+        let pattern =
+            Pattern::try_from_hugr(&circuit(&[(LeafOp::X, &[0]), (LeafOp::CX, &[0, 1])])?).unwrap();
+        let matches: Vec<Match> = pattern
+            .find_matches_indexed(&hugr, &index, MatchOptions::default())
+            .collect();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].nodes()[0], x);
+        Ok(())
+    }
+}
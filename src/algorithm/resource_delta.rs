@@ -0,0 +1,160 @@
+//! Inference of the resource deltas declared by container operations.
+//!
+//! A [`DFG`] may declare the resources required by its nested graph beyond
+//! those of its inputs, which the validation checks against the resources
+//! introduced by its children. [`infer_resource_deltas`] computes the declared
+//! deltas instead, starting from the innermost containers so that their
+//! updated outputs are taken into account by the enclosing ones.
+//!
+//! The resources of the Input and Output nodes of each dataflow region are
+//! also set to the ones of the inner signature of their container, fixing up
+//! hand-built HUGRs.
+
+use crate::hugr::{CompilationStage, HugrMut, HugrView};
+use crate::ops::{self, OpType, DFG};
+use crate::{Hugr, Node};
+
+/// Set the declared resource delta of every [`DFG`] node to the resources
+/// introduced by its children. Nodes not declaring a delta are left unchanged.
+///
+/// The Input and Output nodes of every dataflow region are updated to carry
+/// the resources of the inner signature of their container.
+///
+/// Advances the HUGR to [`CompilationStage::ResourcesInferred`], or moves it
+/// back there if a delta was updated.
+///
+/// Returns the number of updated nodes.
+pub fn infer_resource_deltas(hugr: &mut Hugr) -> usize {
+    // Visit the nodes in reverse pre-order, so children come before parents.
+    let mut nodes: Vec<Node> = Vec::with_capacity(hugr.node_count());
+    let mut stack = vec![hugr.root()];
+    while let Some(node) = stack.pop() {
+        nodes.push(node);
+        stack.extend(hugr.children(node));
+    }
+
+    let mut updated = 0;
+    for node in nodes.into_iter().rev() {
+        updated += infer_io_resources(hugr, node);
+        let OpType::DFG(DFG {
+            signature,
+            resource_delta: Some(declared),
+        }) = hugr.get_optype(node)
+        else {
+            continue;
+        };
+        let delta = hugr.region_resource_delta(node);
+        if delta != *declared {
+            let op = DFG {
+                signature: signature.clone(),
+                resource_delta: Some(delta),
+            };
+            hugr.replace_op_unchecked(node, op);
+            updated += 1;
+        }
+    }
+    if updated > 0 || hugr.stage() < CompilationStage::ResourcesInferred {
+        hugr.set_stage(CompilationStage::ResourcesInferred);
+    }
+    updated
+}
+
+/// Set the resources of the Input and Output children of a dataflow container
+/// to the ones of its inner signature.
+///
+/// Returns the number of updated nodes.
+fn infer_io_resources(hugr: &mut Hugr, node: Node) -> usize {
+    let Some(inner) = hugr.get_optype(node).inner_signature() else {
+        return 0;
+    };
+    let Some((input, output)) = hugr.get_io(node) else {
+        return 0;
+    };
+    let mut updated = 0;
+    if let OpType::Input(op) = hugr.get_optype(input) {
+        if op.resources != inner.input_resources {
+            let op = ops::Input {
+                types: op.types.clone(),
+                resources: inner.input_resources.clone(),
+            };
+            hugr.replace_op_unchecked(input, op);
+            updated += 1;
+        }
+    }
+    if let OpType::Output(op) = hugr.get_optype(output) {
+        if op.resources != inner.output_resources {
+            let op = ops::Output {
+                types: op.types.clone(),
+                resources: inner.output_resources.clone(),
+            };
+            hugr.replace_op_unchecked(output, op);
+            updated += 1;
+        }
+    }
+    updated
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder,
+    };
+    use crate::hugr::ValidationError;
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::LeafOp;
+    use crate::resource::{ResourceId, ResourceSet};
+    use crate::type_row;
+    use crate::types::{ClassicType, Signature, SimpleType};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    #[test]
+    fn infer_dfg_delta() -> Result<(), BuildError> {
+        let (a, b): (ResourceId, ResourceId) = ("A".into(), "B".into());
+        let mut sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        sig.output_resources = ResourceSet::singleton(&a).union(&ResourceSet::singleton(&b));
+
+        // A DFG whose children introduce the resources A and B.
+        let mut module_builder = ModuleBuilder::new();
+        let mut main = module_builder.define_function("main", sig.clone())?;
+        let mut dfg = main.dfg_builder(sig.clone(), main.input_wires())?;
+        let [mut wire] = dfg.input_wires_arr();
+        let mut input_resources = ResourceSet::new();
+        for new_resource in [a.clone(), b] {
+            let lift = LeafOp::Lift {
+                type_row: type_row![NAT],
+                input_resources: input_resources.clone(),
+                new_resource: new_resource.clone(),
+            };
+            [wire] = dfg.add_dataflow_op(lift, [wire])?.outputs_arr();
+            input_resources.insert(&new_resource);
+        }
+        let dfg = dfg.finish_with_outputs([wire])?;
+        main.finish_with_outputs(dfg.outputs())?;
+        let mut hugr = module_builder.finish_hugr()?;
+        assert_eq!(infer_resource_deltas(&mut hugr), 0);
+
+        // Declaring only A is rejected.
+        let declared = DFG {
+            signature: sig,
+            resource_delta: Some(ResourceSet::singleton(&a)),
+        };
+        hugr.replace_op(dfg.node(), declared).unwrap();
+        assert_matches!(
+            hugr.validate(),
+            Err(ValidationError::RegionExceedsResourceDelta { node, actual, .. })
+                => { assert_eq!(node, dfg.node()); assert_eq!(actual, input_resources) }
+        );
+
+        assert_eq!(infer_resource_deltas(&mut hugr), 1);
+        assert_matches!(
+            hugr.get_optype(dfg.node()),
+            OpType::DFG(DFG { resource_delta: Some(delta), .. }) => assert_eq!(delta, &input_resources)
+        );
+        assert_eq!(hugr.validate(), Ok(()));
+        Ok(())
+    }
+}
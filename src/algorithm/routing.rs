@@ -0,0 +1,427 @@
+//! Routing of qubits on hardware with limited connectivity.
+//!
+//! The qubits of a dataflow region are placed on lanes, one for each linear
+//! input of the region. A coupling graph lists the pairs of lanes on which
+//! two-qubit operations may act. [`route`] inserts SWAP gates, decomposed
+//! into [`LeafOp::CX`] gates, so that every operation acts on coupled lanes.
+
+use std::collections::{HashMap, VecDeque};
+
+use petgraph::visit::{Topo, Walker};
+use thiserror::Error;
+
+use super::lanes::{lane_assignment, LaneError};
+use crate::hugr::region::{FlatRegionView, Region};
+use crate::hugr::{HugrMut, HugrView};
+use crate::ops::{LeafOp, OpTag, OpTrait};
+use crate::types::EdgeKind;
+use crate::{Direction, Hugr, Node, Port, Wire};
+
+/// Errors reported by [`route`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[allow(missing_docs)]
+pub enum RoutingError {
+    /// The region is not a dataflow region with Input and Output nodes.
+    #[error("The node {0:?} is not a dataflow region.")]
+    NotDataflowRegion(Node),
+    /// The coupling graph refers to a lane the region does not have.
+    #[error("The coupling graph refers to lane {lane}, but the region only has {lanes} lanes.")]
+    InvalidLane { lane: usize, lanes: usize },
+    /// An operation acts on more than two qubits, or does not output the
+    /// qubits it consumes.
+    #[error("The operation at {0:?} cannot be routed.")]
+    UnsupportedOp(Node),
+    /// An operation acts on two lanes that are not connected in the coupling
+    /// graph.
+    #[error("The operation at {node:?} acts on the disconnected lanes {lanes:?}.")]
+    Disconnected { node: Node, lanes: (usize, usize) },
+}
+
+/// The lanes the linear outputs of a routed region end up on.
+///
+/// Entry `i` is the lane of the `i`-th linear output of the region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QubitPermutation(Vec<usize>);
+
+impl QubitPermutation {
+    /// The identity permutation over `n` lanes.
+    pub fn identity(n: usize) -> Self {
+        Self((0..n).collect())
+    }
+
+    /// The lane of the `output`-th linear output of the region.
+    pub fn lane(&self, output: usize) -> usize {
+        self.0[output]
+    }
+
+    /// The number of lanes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the permutation is over no lanes.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The inverse permutation, mapping each lane to its linear output.
+    pub fn inverse(&self) -> Self {
+        let mut inverse = vec![0; self.0.len()];
+        for (output, &lane) in self.0.iter().enumerate() {
+            inverse[lane] = output;
+        }
+        Self(inverse)
+    }
+
+    /// The permutation applying `self` and then `other`.
+    pub fn then(&self, other: &Self) -> Self {
+        Self(self.0.iter().map(|&lane| other.0[lane]).collect())
+    }
+}
+
+/// Rewrite a dataflow region so that every operation acting on two qubits
+/// acts on lanes coupled in `coupling`.
+///
+/// The `i`-th linear input of the region starts on lane `i`. Operations are
+/// visited in topological order, and when one acts on uncoupled lanes its
+/// first qubit is swapped along a shortest path in the coupling graph until
+/// it is next to the second one. The SWAPs are decomposed into three
+/// [`LeafOp::CX`] gates.
+///
+/// Returns the lanes the linear outputs of the region end up on.
+pub fn route(
+    hugr: &mut Hugr,
+    region: Node,
+    coupling: &[(usize, usize)],
+) -> Result<QubitPermutation, RoutingError> {
+    let mut children = hugr.children(region);
+    let (Some(input), Some(output)) = (children.next(), children.next()) else {
+        return Err(RoutingError::NotDataflowRegion(region));
+    };
+    if hugr.get_optype(input).tag() != OpTag::Input {
+        return Err(RoutingError::NotDataflowRegion(region));
+    }
+
+    let in_ports = linear_ports(hugr, input, Direction::Outgoing);
+    let lanes = in_ports.len();
+    let mut neighbours = vec![Vec::new(); lanes];
+    for &(a, b) in coupling {
+        if let Some(&lane) = [a, b].iter().find(|&&l| l >= lanes) {
+            return Err(RoutingError::InvalidLane { lane, lanes });
+        }
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+    }
+
+    // The qubit of the original circuit carried by each wire and expected on
+    // each input port, before any SWAP is inserted.
+    let wire_qubits = lane_assignment(&*hugr, region).map_err(|e| match e {
+        LaneError::ArityMismatch { node, .. } | LaneError::Disconnected { node, .. } => {
+            RoutingError::UnsupportedOp(node)
+        }
+        LaneError::NotDataflowRegion(node) => RoutingError::NotDataflowRegion(node),
+    })?;
+    let port_qubits: HashMap<(Node, Port), usize> = wire_qubits
+        .iter()
+        .flat_map(|(wire, &q)| {
+            hugr.linked_ports(wire.node(), wire.source())
+                .map(move |tgt| (tgt, q))
+        })
+        .collect();
+
+    let view = FlatRegionView::new(&*hugr, region);
+    let order: Vec<Node> = Topo::new(&view)
+        .iter(&view)
+        .filter(|&n| n != region && n != input)
+        .collect();
+
+    // Plan the SWAPs before inserting any, so that the HUGR is left untouched
+    // if the region cannot be routed.
+    let mut qubit_lanes: Vec<usize> = (0..lanes).collect();
+    let mut steps = Vec::new();
+    let mut perm = None;
+    for node in order {
+        let ins = linear_ports(hugr, node, Direction::Incoming);
+        let qubits: Vec<usize> = ins.iter().map(|&p| port_qubits[&(node, p)]).collect();
+        let mut swaps = Vec::new();
+        if node == output {
+            perm = Some(QubitPermutation(
+                qubits.iter().map(|&q| qubit_lanes[q]).collect(),
+            ));
+        } else if ins.len() > 2 {
+            return Err(RoutingError::UnsupportedOp(node));
+        } else if let [q0, q1] = qubits[..] {
+            let (a, b) = (qubit_lanes[q0], qubit_lanes[q1]);
+            let Some(path) = shortest_path(&neighbours, a, b) else {
+                return Err(RoutingError::Disconnected {
+                    node,
+                    lanes: (a, b),
+                });
+            };
+            // Move the first qubit next to the second one.
+            for pair in path[..path.len() - 1].windows(2) {
+                let (from, to) = (pair[0], pair[1]);
+                swaps.push((from, to));
+                let moved = qubit_lanes.iter().position(|&l| l == to).unwrap();
+                qubit_lanes[moved] = from;
+                qubit_lanes[q0] = to;
+            }
+        }
+        steps.push(Step {
+            node,
+            ins,
+            qubits,
+            swaps,
+            qubit_lanes: qubit_lanes.clone(),
+        });
+        if node == output {
+            break;
+        }
+    }
+    let Some(perm) = perm else {
+        return Err(RoutingError::NotDataflowRegion(region));
+    };
+
+    // The wire currently on each lane.
+    let mut lane_wires: Vec<Wire> = in_ports.iter().map(|&p| Wire::new(input, p)).collect();
+    for step in steps {
+        for (from, to) in step.swaps {
+            insert_swap(hugr, region, &mut lane_wires, from, to);
+        }
+        rewire_inputs(
+            hugr,
+            step.node,
+            &step.ins,
+            &step.qubits,
+            &step.qubit_lanes,
+            &lane_wires,
+        );
+        for port in linear_ports(hugr, step.node, Direction::Outgoing) {
+            let wire = Wire::new(step.node, port);
+            lane_wires[step.qubit_lanes[wire_qubits[&wire]]] = wire;
+        }
+    }
+    Ok(perm)
+}
+
+/// The rewiring of a node planned by [`route`].
+struct Step {
+    node: Node,
+    /// The linear inputs of the node.
+    ins: Vec<Port>,
+    /// The qubit expected on each linear input.
+    qubits: Vec<usize>,
+    /// The SWAPs to insert before the node, as pairs of lanes.
+    swaps: Vec<(usize, usize)>,
+    /// The lane of each qubit after the SWAPs.
+    qubit_lanes: Vec<usize>,
+}
+
+/// The ports of a node carrying linear values, in the given direction.
+fn linear_ports(hugr: &Hugr, node: Node, dir: Direction) -> Vec<Port> {
+    let optype = hugr.get_optype(node);
+    hugr.node_ports(node, dir)
+        .filter(|&p| matches!(optype.port_kind(p), Some(EdgeKind::Value(t)) if t.is_linear()))
+        .collect()
+}
+
+/// Connect the linear inputs of a node to the wires currently carrying their
+/// qubits.
+fn rewire_inputs(
+    hugr: &mut Hugr,
+    node: Node,
+    ins: &[Port],
+    qubits: &[usize],
+    qubit_lanes: &[usize],
+    lane_wires: &[Wire],
+) {
+    for (&port, &q) in ins.iter().zip(qubits) {
+        let wire = lane_wires[qubit_lanes[q]];
+        if hugr.linked_ports(node, port).next() == Some((wire.node(), wire.source())) {
+            continue;
+        }
+        hugr.disconnect(node, port).unwrap();
+        hugr.connect(wire.node(), wire.source().index(), node, port.index())
+            .unwrap();
+    }
+}
+
+/// Swap the qubits on two lanes with three CX gates.
+fn insert_swap(hugr: &mut Hugr, region: Node, lane_wires: &mut [Wire], a: usize, b: usize) {
+    for (control, target) in [(a, b), (b, a), (a, b)] {
+        let cx = hugr.add_op_with_parent(region, LeafOp::CX).unwrap();
+        for (offset, lane) in [control, target].into_iter().enumerate() {
+            let wire = lane_wires[lane];
+            // The previous consumer of the wire is reconnected when visited.
+            if hugr.is_linked(wire.node(), wire.source()) {
+                hugr.disconnect(wire.node(), wire.source()).unwrap();
+            }
+            hugr.connect(wire.node(), wire.source().index(), cx, offset)
+                .unwrap();
+            lane_wires[lane] = Wire::new(cx, Port::new_outgoing(offset));
+        }
+    }
+}
+
+/// A shortest path between two lanes in the coupling graph, including both
+/// ends.
+fn shortest_path(neighbours: &[Vec<usize>], from: usize, to: usize) -> Option<Vec<usize>> {
+    let mut previous = vec![None; neighbours.len()];
+    let mut queue = VecDeque::from([from]);
+    while let Some(lane) = queue.pop_front() {
+        if lane == to {
+            let mut path = vec![to];
+            while let Some(prev) = previous[*path.last().unwrap()] {
+                path.push(prev);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for &next in &neighbours[lane] {
+            if next != from && previous[next].is_none() {
+                previous[next] = Some(lane);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::ops::OpType;
+    use crate::type_row;
+    use crate::types::{LinearType, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+
+    /// CX gates between all pairs of three qubits, then a Hadamard.
+    fn all_pairs() -> Result<Hugr, BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB, QB], type_row![QB, QB, QB])?;
+        let [q0, q1, q2] = builder.input_wires_arr();
+        let [q0, q1] = builder.add_dataflow_op(LeafOp::CX, [q0, q1])?.outputs_arr();
+        let [q0, q2] = builder.add_dataflow_op(LeafOp::CX, [q0, q2])?.outputs_arr();
+        let [q2, q1] = builder.add_dataflow_op(LeafOp::CX, [q2, q1])?.outputs_arr();
+        let [q0] = builder.add_dataflow_op(LeafOp::H, [q0])?.outputs_arr();
+        builder.finish_hugr_with_outputs([q0, q1, q2])
+    }
+
+    /// Assign lanes to the qubit wires of a routed region, checking that
+    /// every CX acts on coupled lanes. Returns the lanes of the outputs.
+    fn check_lanes(hugr: &Hugr, coupling: &[(usize, usize)]) -> Vec<usize> {
+        let region = hugr.root();
+        let mut lanes: HashMap<Wire, usize> = HashMap::new();
+        let view = FlatRegionView::new(hugr, region);
+        for node in Topo::new(&view).iter(&view).filter(|&n| n != region) {
+            let ins: Vec<usize> = linear_ports(hugr, node, Direction::Incoming)
+                .into_iter()
+                .map(|p| {
+                    let (src, src_port) = hugr.linked_ports(node, p).next().unwrap();
+                    lanes[&Wire::new(src, src_port)]
+                })
+                .collect();
+            match hugr.get_optype(node) {
+                OpType::Input(_) => {
+                    for (lane, port) in linear_ports(hugr, node, Direction::Outgoing)
+                        .into_iter()
+                        .enumerate()
+                    {
+                        lanes.insert(Wire::new(node, port), lane);
+                    }
+                    continue;
+                }
+                OpType::Output(_) => return ins,
+                OpType::LeafOp(LeafOp::CX) => {
+                    let pair = (ins[0].min(ins[1]), ins[0].max(ins[1]));
+                    assert!(coupling.contains(&pair), "CX between lanes {pair:?}");
+                }
+                _ => {}
+            }
+            for (port, lane) in linear_ports(hugr, node, Direction::Outgoing)
+                .into_iter()
+                .zip(ins)
+            {
+                lanes.insert(Wire::new(node, port), lane);
+            }
+        }
+        unreachable!("The region has an Output node")
+    }
+
+    #[test]
+    fn route_line() -> Result<(), BuildError> {
+        let line = [(0, 1), (1, 2)];
+        let mut hugr = all_pairs()?;
+        let root = hugr.root();
+        let perm = route(&mut hugr, root, &line).unwrap();
+        assert_eq!(hugr.validate(), Ok(()));
+
+        // Two SWAPs were inserted, each decomposed into three CX gates.
+        let cx_count = |hugr: &Hugr| {
+            hugr.nodes()
+                .filter(|&n| hugr.get_optype(n) == &LeafOp::CX.into())
+                .count()
+        };
+        assert_eq!(cx_count(&hugr), 9);
+
+        let output_lanes = check_lanes(&hugr, &line);
+        assert_eq!(
+            output_lanes,
+            (0..3).map(|i| perm.lane(i)).collect::<Vec<_>>()
+        );
+        assert_ne!(perm, QubitPermutation::identity(3));
+        assert_eq!(perm.then(&perm.inverse()), QubitPermutation::identity(3));
+        Ok(())
+    }
+
+    #[test]
+    fn route_coupled() -> Result<(), BuildError> {
+        let triangle = [(0, 1), (0, 2), (1, 2)];
+        let mut hugr = all_pairs()?;
+        let original = hugr.clone();
+        let root = hugr.root();
+        assert_eq!(
+            route(&mut hugr, root, &triangle),
+            Ok(QubitPermutation::identity(3))
+        );
+        assert_eq!(hugr, original);
+        Ok(())
+    }
+
+    #[test]
+    fn route_errors() -> Result<(), BuildError> {
+        let mut hugr = all_pairs()?;
+        let root = hugr.root();
+        assert_eq!(
+            route(&mut hugr, root, &[(0, 3)]),
+            Err(RoutingError::InvalidLane { lane: 3, lanes: 3 })
+        );
+        assert_matches!(
+            route(&mut hugr, root, &[(1, 2)]),
+            Err(RoutingError::Disconnected { lanes: (0, 1), .. })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn route_error_after_swap() -> Result<(), BuildError> {
+        // The first CX needs a SWAP, and the second one acts on an isolated
+        // lane.
+        let mut builder = DFGBuilder::new(type_row![QB, QB, QB, QB], type_row![QB, QB, QB, QB])?;
+        let [q0, q1, q2, q3] = builder.input_wires_arr();
+        let [q0, q2] = builder.add_dataflow_op(LeafOp::CX, [q0, q2])?.outputs_arr();
+        let [q0, q3] = builder.add_dataflow_op(LeafOp::CX, [q0, q3])?.outputs_arr();
+        let mut hugr = builder.finish_hugr_with_outputs([q0, q1, q2, q3])?;
+        let original = hugr.clone();
+        let root = hugr.root();
+        assert_matches!(
+            route(&mut hugr, root, &[(0, 1), (1, 2)]),
+            Err(RoutingError::Disconnected { lanes: (1, 3), .. })
+        );
+        assert_eq!(hugr, original);
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+//! Layered schedules of dataflow regions.
+//!
+//! [`schedule`] splits the children of a region into time steps, for
+//! visualisation and for hardware executing layers of operations.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::convex::ordering_neighbours;
+use crate::hugr::HugrView;
+use crate::ops::{OpTag, OpTrait};
+use crate::{Direction, Node};
+
+/// Options for [`schedule_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScheduleOptions {
+    /// Schedule each node as late as possible, instead of as soon as
+    /// possible.
+    pub alap: bool,
+    /// Treat the operations without linear inputs or outputs as taking no
+    /// time. They are placed in the layer of their last predecessor (or
+    /// first successor, for ALAP schedules) instead of opening a new layer.
+    pub zero_duration_classical: bool,
+}
+
+/// Split the children of a dataflow region into ASAP layers.
+///
+/// See [`schedule_with`].
+pub fn schedule(hugr: &impl HugrView, region: Node) -> Vec<Vec<Node>> {
+    schedule_with(hugr, region, &ScheduleOptions::default())
+}
+
+/// Split the children of a dataflow region into layers, such that no node
+/// depends on a node of a later layer through Value or StateOrder edges.
+///
+/// The Input node is excluded. The nodes of each layer are in a topological
+/// order of the region, following the order of the children where possible.
+/// Dependencies between nodes of the same layer only occur with
+/// [`ScheduleOptions::zero_duration_classical`], and for the Output node:
+/// it does not open a layer of its own but is appended to the last layer,
+/// after its predecessors there. Nodes in cycles are not scheduled.
+pub fn schedule_with(
+    hugr: &impl HugrView,
+    region: Node,
+    options: &ScheduleOptions,
+) -> Vec<Vec<Node>> {
+    let mut output = None;
+    let children: Vec<Node> = hugr
+        .children(region)
+        .filter(|&n| match hugr.get_optype(n).tag() {
+            OpTag::Input => false,
+            OpTag::Output => {
+                output = Some(n);
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    let order = stable_topological_order(hugr, &children);
+
+    let zero_duration = |node: Node| {
+        let sig = hugr.get_optype(node).signature();
+        options.zero_duration_classical
+            && sig.input.purely_classical()
+            && sig.output.purely_classical()
+    };
+    // Schedule ALAP layers as ASAP layers of the reversed region.
+    let mut nodes = order.clone();
+    let dir = match options.alap {
+        false => Direction::Incoming,
+        true => {
+            nodes.reverse();
+            Direction::Outgoing
+        }
+    };
+
+    // The layer of each node, and the first layer its dependents can use.
+    let mut layers: HashMap<Node, (usize, usize)> = HashMap::new();
+    let mut n_layers = 0;
+    for node in nodes {
+        let (mut layer, mut ready) = (0, 0);
+        for other in ordering_neighbours(hugr, node, dir) {
+            if let Some(&(other_layer, other_ready)) = layers.get(&other) {
+                layer = layer.max(other_layer);
+                ready = ready.max(other_ready);
+            }
+        }
+        let entry = match zero_duration(node) {
+            true => (layer, ready),
+            false => (ready, ready + 1),
+        };
+        n_layers = n_layers.max(entry.0 + 1);
+        layers.insert(node, entry);
+    }
+
+    let mut schedule = vec![Vec::new(); n_layers];
+    for node in order {
+        let layer = layers[&node].0;
+        let layer = match options.alap {
+            false => layer,
+            true => n_layers - 1 - layer,
+        };
+        schedule[layer].push(node);
+    }
+    if let Some(output) = output {
+        match schedule.last_mut() {
+            Some(last) => last.push(output),
+            None => schedule.push(vec![output]),
+        }
+    }
+    schedule
+}
+
+/// Sort the `nodes` in a topological order of the edges between them, taking
+/// the first available node in the order of `nodes` at each step. The nodes
+/// in cycles are left out.
+fn stable_topological_order(hugr: &impl HugrView, nodes: &[Node]) -> Vec<Node> {
+    let position: HashMap<Node, usize> = nodes.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+    let successors = |node| {
+        ordering_neighbours(hugr, node, Direction::Outgoing).filter(|n| position.contains_key(n))
+    };
+    let mut in_degree = vec![0; nodes.len()];
+    for &node in nodes {
+        for next in successors(node) {
+            in_degree[position[&next]] += 1;
+        }
+    }
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..nodes.len())
+        .filter(|&i| in_degree[i] == 0)
+        .map(Reverse)
+        .collect();
+    let mut order = Vec::with_capacity(nodes.len());
+    while let Some(Reverse(i)) = ready.pop() {
+        order.push(nodes[i]);
+        for next in successors(nodes[i]) {
+            let next = position[&next];
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                ready.push(Reverse(next));
+            }
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::hugr::HugrMut;
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::LeafOp;
+    use crate::type_row;
+    use crate::types::{ClassicType, LinearType, SimpleType};
+    use crate::Hugr;
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    /// A depth-3 circuit on two qubits, with a measured third qubit whose bit
+    /// is combined with a classical input.
+    ///
+    /// Returns the nodes `[h0, cx, h1, measure, xor, output]`.
+    fn circuit() -> Result<(Hugr, [Node; 6]), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB, QB, BIT], type_row![QB, QB, QB, BIT])?;
+        let [q0, q1, q2, b] = builder.input_wires_arr();
+        let h0 = builder.add_dataflow_op(LeafOp::H, [q0])?;
+        let cx = builder.add_dataflow_op(LeafOp::CX, [h0.out_wire(0), q1])?;
+        let h1 = builder.add_dataflow_op(LeafOp::H, [cx.out_wire(1)])?;
+        let measure = builder.add_dataflow_op(LeafOp::Measure, [q2])?;
+        let xor = builder.add_dataflow_op(LeafOp::Xor, [b, measure.out_wire(1)])?;
+        let [_, output] = builder.io();
+        let hugr = builder.finish_hugr_with_outputs([
+            cx.out_wire(0),
+            h1.out_wire(0),
+            measure.out_wire(0),
+            xor.out_wire(0),
+        ])?;
+        let [h0, cx, h1, measure, xor] = [h0, cx, h1, measure, xor].map(|n| n.node());
+        Ok((hugr, [h0, cx, h1, measure, xor, output]))
+    }
+
+    #[test]
+    fn asap_and_alap() -> Result<(), BuildError> {
+        let (hugr, [h0, cx, h1, measure, xor, output]) = circuit()?;
+        assert_eq!(
+            schedule(&hugr, hugr.root()),
+            [vec![h0, measure], vec![cx, xor], vec![h1, output]]
+        );
+
+        let alap = ScheduleOptions {
+            alap: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            schedule_with(&hugr, hugr.root(), &alap),
+            [vec![h0], vec![cx, measure], vec![h1, xor, output]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn zero_duration_classical() -> Result<(), BuildError> {
+        let (hugr, [h0, cx, h1, measure, xor, output]) = circuit()?;
+        let options = ScheduleOptions {
+            zero_duration_classical: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            schedule_with(&hugr, hugr.root(), &options),
+            [vec![h0, measure, xor], vec![cx], vec![h1, output]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn order_edge_delays() -> Result<(), BuildError> {
+        let (mut hugr, [h0, cx, h1, measure, xor, output]) = circuit()?;
+        hugr.add_other_edge(cx, measure).unwrap();
+        assert_eq!(
+            schedule(&hugr, hugr.root()),
+            [vec![h0], vec![cx], vec![h1, measure], vec![xor, output]]
+        );
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
 //! Tools for building valid HUGRs.
 //!
+use smol_str::SmolStr;
 use thiserror::Error;
 
 use crate::hugr::{HugrError, Node, ValidationError, Wire};
 use crate::ops::handle::{BasicBlockID, CfgID, ConditionalID, DfgID, FuncID, TailLoopID};
+use crate::ops::LeafOp;
+use crate::resource::ResourceId;
 
-use crate::types::LinearType;
+use crate::types::{LinearType, Signature, SignatureDescription, SimpleType, TypeRow};
 
 pub mod handle;
 pub use handle::BuildHandle;
@@ -33,6 +36,8 @@ pub use conditional::{CaseBuilder, ConditionalBuilder};
 mod circuit_builder;
 pub use circuit_builder::{AppendWire, CircuitBuilder};
 
+pub mod typed;
+
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 /// Error while building the HUGR.
 pub enum BuildError {
@@ -61,6 +66,54 @@ pub enum BuildError {
     #[error("Wire not found in Hugr: {0:?}.")]
     WireNotFound(Wire),
 
+    /// A wire was used in a region not nested in the region of its source.
+    #[error("Wire {wire:?} is not visible from the region it is used in.")]
+    WireOutOfScope {
+        /// The out of scope wire.
+        wire: Wire,
+    },
+
+    /// A constant was loaded in a region that is not nested in the region
+    /// defining it.
+    #[error("Constant {constant:?} is not defined in a region enclosing {region:?}.")]
+    ConstOutOfScope {
+        /// The constant node.
+        constant: Node,
+        /// The region the constant was loaded in.
+        region: Node,
+    },
+
+    /// A resource value was loaded from a registry that does not define it.
+    #[error("The registry has no value {name} in resource {resource}.")]
+    UnknownResourceValue {
+        /// The name of the resource.
+        resource: ResourceId,
+        /// The name of the value.
+        name: SmolStr,
+    },
+
+    /// A typed wire was requested for a wire carrying another kind of value.
+    #[error("Wire {wire:?} of type {typ:?} does not have the requested kind.")]
+    WireKindMismatch {
+        /// The wire with the unexpected type.
+        wire: Wire,
+        /// The type of the wire.
+        typ: SimpleType,
+    },
+
+    /// The types of the wires connected to a node do not match its inputs.
+    #[error("Input wires of types {found} do not match the expected inputs {expected}.")]
+    InputWiresMismatch {
+        /// The input types of the node.
+        expected: TypeRow,
+        /// The types of the wires.
+        found: TypeRow,
+    },
+
+    /// A classically-controlled operation is not a single-qubit gate.
+    #[error("Operation {0:?} is not a single-qubit gate.")]
+    NotSingleQubitGate(LeafOp),
+
     /// Can't copy a linear type
     #[error("Can't copy linear type: {0:?}.")]
     NoCopyLinear(LinearType),
@@ -68,16 +121,64 @@ pub enum BuildError {
     /// Error in CircuitBuilder
     #[error("Error in CircuitBuilder: {0}.")]
     CircuitError(#[from] circuit_builder::CircuitBuildError),
+
+    /// The port names do not match the signature.
+    #[error(
+        "Signature description {signature_desc:?} does not match the signature {signature:?}."
+    )]
+    SignatureDescriptionMismatch {
+        /// The signature being described.
+        signature: Signature,
+        /// The mismatched port names.
+        signature_desc: SignatureDescription,
+    },
+
+    /// The CFG has no entry block.
+    #[error("CFG node {0:?} has no entry block.")]
+    MissingEntryBlock(Node),
+    /// The CFG has no exit block.
+    #[error("CFG node {0:?} has no exit block.")]
+    MissingExitBlock(Node),
+    /// A successor port of a CFG block is not connected.
+    #[error("Branch {branch} of CFG block {block:?} has no successor.")]
+    UnwiredBranch {
+        /// The block with the unconnected successor port.
+        block: Node,
+        /// The index of the successor port.
+        branch: usize,
+    },
+
+    /// A branch of a CFG block does not correspond to a variant of its
+    /// predicate.
+    #[error("Branch {branch} is out of range for CFG block {block:?} with {n_branches} branches.")]
+    BranchOutOfRange {
+        /// The block being built.
+        block: Node,
+        /// The requested branch.
+        branch: usize,
+        /// The number of variants of the predicate of the block.
+        n_branches: usize,
+    },
+
+    /// A function with the same name is already defined or declared in the module.
+    #[error("A function named {name:?} already exists in node {existing:?}.")]
+    DuplicateFunctionName {
+        /// The name of the function.
+        name: String,
+        /// The existing function with that name.
+        existing: Node,
+    },
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
+    use crate::ops::LeafOp;
     use crate::types::{ClassicType, LinearType, Signature, SimpleType};
-    use crate::Hugr;
+    use crate::{type_row, Hugr};
 
     use super::handle::BuildHandle;
     use super::{BuildError, Container, FuncID, FunctionBuilder, ModuleBuilder};
-    use super::{DataflowSubContainer, HugrBuilder};
+    use super::{DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, HugrBuilder};
 
     pub(super) const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
     pub(super) const F64: SimpleType = SimpleType::Classic(ClassicType::F64);
@@ -100,6 +201,18 @@ mod test {
         let f_builder = module_builder.define_function("main", signature)?;
 
         f(f_builder)?;
-        Ok(module_builder.finish_hugr()?)
+        module_builder.finish_hugr()
+    }
+
+    /// A circuit on two qubits applying the given gates, with the qubits they
+    /// act on.
+    pub(crate) fn circuit(gates: &[(LeafOp, &[usize])]) -> Result<Hugr, BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+        let mut circ = builder.as_circuit(builder.input_wires().collect());
+        for (gate, qubits) in gates {
+            circ.append(gate.clone(), qubits.iter().copied())?;
+        }
+        let wires = circ.finish();
+        builder.finish_hugr_with_outputs(wires)
     }
 }
@@ -1,7 +1,9 @@
+use crate::hugr::typecheck::typecheck_const;
 use crate::hugr::validate::InterGraphEdgeError;
 use crate::hugr::view::HugrView;
 use crate::hugr::{Node, NodeMetadata, Port, ValidationError};
-use crate::ops::{self, ConstValue, LeafOp, OpTrait, OpType};
+use crate::ops::{self, ConstValue, LeafOp, OpTag, OpTrait, OpType};
+use crate::resource::ResourceRegistry;
 
 use std::iter;
 
@@ -12,11 +14,12 @@ use super::{
 };
 
 use crate::{
-    ops::handle::{ConstID, DataflowOpID, FuncID, NodeHandle},
+    ops::handle::{ConstID, DataflowOpID, DfgID, FuncID, NodeHandle},
     types::EdgeKind,
 };
 
-use crate::types::{LinearType, Signature, SimpleType, TypeRow};
+use crate::type_row;
+use crate::types::{ClassicType, LinearType, Signature, SimpleType, TypeRow};
 
 use itertools::Itertools;
 
@@ -65,7 +68,42 @@ pub trait Container {
         let typ = val.const_type();
         let const_n = self.add_child_op(ops::Const(val))?;
 
-        Ok((const_n, typ).into())
+        Ok((const_n, typ, 0).into())
+    }
+
+    /// Add a row of constant values to the container as a single
+    /// [`ops::ConstRow`] node, and return a handle to each value.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there is an error in adding the
+    /// [`OpType::ConstRow`] node.
+    fn add_constant_row(
+        &mut self,
+        vals: impl IntoIterator<Item = ConstValue>,
+    ) -> Result<Vec<ConstID>, BuildError> {
+        let vals: Vec<ConstValue> = vals.into_iter().collect();
+        let types: Vec<ClassicType> = vals.iter().map(ConstValue::const_type).collect();
+        let const_n = self.add_child_op(ops::ConstRow(vals))?;
+
+        Ok(types
+            .into_iter()
+            .enumerate()
+            .map(|(i, typ)| (const_n, typ, i).into())
+            .collect())
+    }
+
+    /// Check that a function named `name` can be added to the container.
+    ///
+    /// All names are accepted by default.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the name is not allowed in the
+    /// container.
+    fn check_function_name(&self, name: &str) -> Result<(), BuildError> {
+        let _ = name;
+        Ok(())
     }
 
     /// Add a [`ops::FuncDefn`] node and returns a builder to define the function
@@ -73,16 +111,20 @@ pub trait Container {
     ///
     /// # Errors
     ///
-    /// This function will return an error if there is an error in adding the
-    /// [`ops::FuncDefn`] node.
+    /// This function will return an error if the name is rejected by
+    /// [`Container::check_function_name`], or if there is an error in adding
+    /// the [`ops::FuncDefn`] node.
     fn define_function(
         &mut self,
         name: impl Into<String>,
         signature: Signature,
     ) -> Result<FunctionBuilder<&mut Hugr>, BuildError> {
+        let name = name.into();
+        self.check_function_name(&name)?;
         let f_node = self.add_child_op(ops::FuncDefn {
-            name: name.into(),
+            name,
             signature: signature.clone(),
+            signature_desc: Default::default(),
         })?;
 
         let db = DFGBuilder::create_with_io(self.hugr_mut(), f_node, signature)?;
@@ -117,7 +159,7 @@ pub trait Container {
 /// (with varying root node types)
 pub trait HugrBuilder: Container {
     /// Finish building the HUGR, perform any validation checks and return it.
-    fn finish_hugr(self) -> Result<Hugr, ValidationError>;
+    fn finish_hugr(self) -> Result<Hugr, BuildError>;
 }
 
 /// Types implementing this trait build a container graph region by borrowing a HUGR
@@ -128,6 +170,18 @@ pub trait SubContainer: Container {
     /// Consume the container builder and return the handle, may perform some
     /// checks before finishing.
     fn finish_sub_container(self) -> Result<Self::ContainerHandle, BuildError>;
+
+    /// Discard the container, detaching its node and everything added to it so
+    /// that the parent builder can carry on as if it had never been started.
+    ///
+    /// Dropping a builder without finishing it has the same effect. Builders
+    /// of HUGR roots have no parent to restore, and are simply dropped.
+    fn abandon(self)
+    where
+        Self: Sized,
+    {
+        drop(self);
+    }
 }
 /// Trait for building dataflow regions of a HUGR.
 pub trait Dataflow: Container {
@@ -192,6 +246,47 @@ pub trait Dataflow: Container {
         Ok((node, num_outputs).into())
     }
 
+    /// Insert a DFG-rooted hugr as a nested [`crate::ops::DFG`] node of the
+    /// sibling graph, wiring up the `input_wires` to its inputs.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the root of `hugr` is not a
+    /// [`crate::ops::DFG`], or if the types of the `input_wires` do not match
+    /// its inputs. The checks are performed before inserting any node.
+    fn add_hugr_as_op(
+        &mut self,
+        hugr: Hugr,
+        input_wires: impl IntoIterator<Item = Wire>,
+    ) -> Result<BuildHandle<DfgID>, BuildError> {
+        let root_op = hugr.get_optype(hugr.root());
+        if root_op.tag() != OpTag::Dfg {
+            return Err(BuildError::UnexpectedType {
+                node: hugr.root(),
+                op_desc: "crate::ops::DFG",
+            });
+        }
+        let signature = root_op.signature();
+        let input_wires = input_wires.into_iter().collect_vec();
+        let wire_types: TypeRow = input_wires
+            .iter()
+            .map(|&wire| self.get_wire_type(wire))
+            .collect::<Result<Vec<_>, _>>()?
+            .into();
+        if wire_types != signature.input {
+            return Err(BuildError::InputWiresMismatch {
+                expected: signature.input,
+                found: wire_types,
+            });
+        }
+
+        let node = self.add_hugr(hugr)?;
+        let [inp, _] = self.io();
+        wire_up_inputs(input_wires, node, self, inp)?;
+
+        Ok((node, signature.output.len()).into())
+    }
+
     /// Copy a hugr-defined op into the sibling graph, wiring up the
     /// `input_wires` to the incoming ports of the resulting root node.
     ///
@@ -257,6 +352,7 @@ pub trait Dataflow: Container {
             self,
             ops::DFG {
                 signature: signature.clone(),
+                resource_delta: None,
             },
             input_wires.into_iter().collect(),
         )?;
@@ -296,33 +392,142 @@ pub trait Dataflow: Container {
 
     /// Load a static constant and return the local dataflow wire for that constant.
     /// Adds a [`OpType::LoadConstant`] node.
+    ///
+    /// The constant may be defined in this region, in any region enclosing
+    /// it, or in a block of an enclosing CFG. Each call adds a new
+    /// [`OpType::LoadConstant`] node, see [`Dataflow::load_const_cached`] to
+    /// reuse one.
+    ///
     /// # Errors
     ///
-    /// This function will return an error if there is an error when adding the node.
+    /// This function will return an error if the constant is not in scope, or
+    /// if there is an error when adding the node.
     fn load_const(&mut self, cid: &ConstID) -> Result<Wire, BuildError> {
         let const_node = cid.node();
+        let region = self.container_node();
+        let base = self.hugr();
+        let encloses = |p: Node| p == region || base.is_ancestor_of(p, region);
+        // Constants in a block of an enclosing CFG may be used by the blocks
+        // it dominates. Dominance is left to the validator.
+        let in_scope = base.get_parent(const_node).is_some_and(|p| {
+            encloses(p)
+                || (OpTag::BasicBlock.is_superset(base.get_optype(p).tag())
+                    && base.get_parent(p).is_some_and(encloses))
+        });
+        if base.contains_node(const_node) && !in_scope {
+            return Err(BuildError::ConstOutOfScope {
+                constant: const_node,
+                region,
+            });
+        }
 
         let load_n = self.add_dataflow_op(
             ops::LoadConstant {
                 datatype: cid.const_type(),
             },
             // Constant wire from the constant value node
-            vec![Wire::new(const_node, Port::new_outgoing(0))],
+            vec![Wire::new(const_node, cid.port())],
         )?;
 
         Ok(load_n.out_wire(0))
     }
 
-    /// Load a static constant and return the local dataflow wire for that constant.
-    /// Adds a [`ops::LoadConstant`] node.
+    /// Load a static constant, reusing a [`OpType::LoadConstant`] node already
+    /// loading it in this region if there is one, and return the local
+    /// dataflow wire for that constant.
+    ///
     /// # Errors
     ///
-    /// This function will return an error if there is an error when adding the node.
+    /// This function will return an error if a new node is needed and
+    /// [`Dataflow::load_const`] fails.
+    fn load_const_cached(&mut self, cid: &ConstID) -> Result<Wire, BuildError> {
+        let (const_node, port) = (cid.node(), cid.port());
+        let region = self.container_node();
+        let base = self.hugr();
+        // Unknown constants are reported by `load_const`.
+        let known = base.contains_node(const_node) && port.index() < base.num_outputs(const_node);
+        let loaded = known.then(|| {
+            base.linked_ports(const_node, port)
+                .map(|(node, _)| node)
+                .find(|&node| {
+                    base.get_parent(node) == Some(region)
+                        && matches!(base.get_optype(node), OpType::LoadConstant(_))
+                })
+        });
+        match loaded.flatten() {
+            Some(node) => Ok(Wire::new(node, Port::new_outgoing(0))),
+            None => self.load_const(cid),
+        }
+    }
+
+    /// Add a [`ops::Const`] node as a sibling in this dataflow region, load it
+    /// and return the local dataflow wire for that constant.
+    /// Adds a [`ops::Const`] and a [`ops::LoadConstant`] node.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the constant does not typecheck,
+    /// or if there is an error when adding the nodes.
     fn add_load_const(&mut self, val: ConstValue) -> Result<Wire, BuildError> {
+        typecheck_const(&val.const_type(), &val).map_err(ValidationError::from)?;
         let cid = self.add_constant(val)?;
         self.load_const(&cid)
     }
 
+    /// Add a [`ops::Const`] node with the value called `name` in the
+    /// `resource` of the registry, load it and return the local dataflow wire
+    /// for that constant, as in [`Dataflow::add_load_const`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the registry has no such value,
+    /// or if [`Dataflow::add_load_const`] fails.
+    fn load_resource_value(
+        &mut self,
+        registry: &ResourceRegistry,
+        resource: &str,
+        name: &str,
+    ) -> Result<Wire, BuildError> {
+        let value = registry
+            .get(resource)
+            .and_then(|r| r.get_value(name))
+            .ok_or_else(|| BuildError::UnknownResourceValue {
+                resource: resource.into(),
+                name: name.into(),
+            })?;
+        self.add_load_const(value.clone())
+    }
+
+    /// Add a constant as a sibling in this dataflow region, load it and return
+    /// the local dataflow wires for it, as in [`Dataflow::add_load_const`].
+    ///
+    /// If `split_tuple` is set and the value is a [`ConstValue::Tuple`], its
+    /// elements are added as a single [`ops::ConstRow`] node and loaded
+    /// separately, returning a wire per element. This avoids unpacking the
+    /// loaded tuple.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the constant does not typecheck,
+    /// or if there is an error when adding the nodes.
+    fn add_load_const_with(
+        &mut self,
+        val: ConstValue,
+        split_tuple: bool,
+    ) -> Result<Vec<Wire>, BuildError> {
+        typecheck_const(&val.const_type(), &val).map_err(ValidationError::from)?;
+        match val {
+            ConstValue::Tuple(vals) if split_tuple => {
+                let cids = self.add_constant_row(vals)?;
+                cids.iter().map(|cid| self.load_const(cid)).collect()
+            }
+            val => {
+                let cid = self.add_constant(val)?;
+                Ok(vec![self.load_const(&cid)?])
+            }
+        }
+    }
+
     /// Return a builder for a [`crate::ops::TailLoop`] node.
     /// The `inputs` must be an iterable over pairs of the type of the input and
     /// the corresponding wire.
@@ -358,6 +563,10 @@ pub trait Dataflow: Container {
     /// `predicate_inputs` and `predicate_wire` define the type of the predicate
     /// variants and the wire carrying the predicate respectively.
     ///
+    /// If the predicate has two empty variants and `predicate_wire` carries a
+    /// bit, the bit is converted into the predicate with a
+    /// [`LeafOp::BitToPredicate`] operation.
+    ///
     /// The `other_inputs` must be an iterable over pairs of the type of the input and
     /// the corresponding wire.
     /// The `outputs` are the types of the outputs.
@@ -372,13 +581,23 @@ pub trait Dataflow: Container {
         other_inputs: impl IntoIterator<Item = (SimpleType, Wire)>,
         output_types: TypeRow,
     ) -> Result<ConditionalBuilder<&mut Hugr>, BuildError> {
+        const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+        let predicate_inputs: Vec<_> = predicate_inputs.into_iter().collect();
+        let predicate_wire = if predicate_inputs == [type_row![], type_row![]]
+            && self.get_wire_type(predicate_wire)? == BIT
+        {
+            self.add_dataflow_op(LeafOp::BitToPredicate, [predicate_wire])?
+                .out_wire(0)
+        } else {
+            predicate_wire
+        };
         let mut input_wires = vec![predicate_wire];
         let (input_types, rest_input_wires): (Vec<SimpleType>, Vec<Wire>) =
             other_inputs.into_iter().unzip();
 
         input_wires.extend(rest_input_wires);
         let inputs: TypeRow = input_types.into();
-        let predicate_inputs: Vec<_> = predicate_inputs.into_iter().collect();
         let n_cases = predicate_inputs.len();
         let n_out_wires = output_types.len();
 
@@ -396,9 +615,54 @@ pub trait Dataflow: Container {
             conditional_node: conditional_id.node(),
             n_out_wires,
             case_nodes: vec![None; n_cases],
+            rollback: Some(Rollback::new(conditional_id.node())),
         })
     }
 
+    /// Apply the single-qubit gate `op` to `qubit` if the `bit` is set.
+    ///
+    /// The bit selects between the two cases of a [`ops::Conditional`], see
+    /// [`Dataflow::conditional_builder`]: the first passes the qubit through,
+    /// the second applies the gate.
+    ///
+    /// Returns the qubit wire output by the Conditional.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `op` is not a single-qubit gate,
+    /// if `bit` does not carry a bit, or if there is an error when building
+    /// the Conditional node.
+    fn conditional_gate(&mut self, bit: Wire, qubit: Wire, op: LeafOp) -> Result<Wire, BuildError> {
+        const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+        const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+        let sig = op.signature();
+        if sig.input.as_ref() != [QB] || sig.output.as_ref() != [QB] {
+            return Err(BuildError::NotSingleQubitGate(op));
+        }
+        let bit_type = self.get_wire_type(bit)?;
+        if bit_type != BIT {
+            return Err(BuildError::InputWiresMismatch {
+                expected: type_row![BIT],
+                found: vec![bit_type].into(),
+            });
+        }
+
+        let mut conditional = self.conditional_builder(
+            ([type_row![], type_row![]], bit),
+            [(QB, qubit)],
+            type_row![QB],
+        )?;
+        let case = conditional.case_builder(0)?;
+        let [q] = case.input_wires_arr();
+        case.finish_with_outputs([q])?;
+        let mut case = conditional.case_builder(1)?;
+        let [q] = case.input_wires_arr();
+        let gate = case.add_dataflow_op(op, [q])?;
+        case.finish_with_outputs(gate.outputs())?;
+        Ok(conditional.finish_sub_container()?.out_wire(0))
+    }
+
     /// Add an order edge from `before` to `after`. Assumes any additional edges
     /// to both nodes will be Order kind.
     fn set_order(
@@ -538,7 +802,7 @@ pub trait Dataflow: Container {
                 })
             }
         };
-        let const_in_port = signature.output.len();
+        let const_in_port = signature.input.len();
         let op_id = self.add_dataflow_op(ops::Call { signature }, input_wires)?;
         let src_port = self.hugr_mut().num_outputs(function.node()) - 1;
 
@@ -547,6 +811,32 @@ pub trait Dataflow: Container {
         Ok(op_id)
     }
 
+    /// Add a [`ops::CallIndirect`] node calling the function carried by the
+    /// `function` wire, of [`ClassicType::Graph`] type, with `input_wires`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `function` does not carry a
+    /// graph, or if there is an error when adding the node.
+    fn call_indirect(
+        &mut self,
+        function: Wire,
+        input_wires: impl IntoIterator<Item = Wire>,
+    ) -> Result<BuildHandle<DataflowOpID>, BuildError> {
+        let typ = self.get_wire_type(function)?;
+        let SimpleType::Classic(ClassicType::Graph(graph)) = &typ else {
+            return Err(BuildError::WireKindMismatch {
+                wire: function,
+                typ,
+            });
+        };
+        let signature = graph.1.clone();
+        self.add_dataflow_op(
+            ops::CallIndirect { signature },
+            iter::once(function).chain(input_wires),
+        )
+    }
+
     /// For the vector of `wires`, produce a `CircuitBuilder` where ops can be
     /// added using indices in to the vector.
     fn as_circuit(&mut self, wires: Vec<Wire>) -> CircuitBuilder<Self> {
@@ -565,11 +855,83 @@ fn add_op_with_wires<T: Dataflow + ?Sized>(
     let sig = op.signature();
     let op_node = data_builder.add_child_op(op)?;
 
-    wire_up_inputs(inputs, op_node, data_builder, inp)?;
+    if let Err(e) = wire_up_inputs(inputs, op_node, data_builder, inp) {
+        data_builder.hugr_mut().remove_node(op_node)?;
+        return Err(e);
+    }
 
     Ok((op_node, sig.output.len()))
 }
 
+/// Detaches the container node of a sub-builder, with all its descendants, if
+/// the builder is dropped before being finished.
+///
+/// The detached nodes stay in the HUGR, without edges, so that wires from them
+/// are rejected rather than resolved to reused indices. [`purge_detached`]
+/// removes them when the outermost builder is finished.
+pub(super) struct Rollback<T> {
+    node: Node,
+    detach: fn(&mut T, Node),
+}
+
+impl<T: AsMut<Hugr>> Rollback<T> {
+    pub(super) fn new(node: Node) -> Self {
+        Self {
+            node,
+            detach: |base, node| {
+                let hugr = base.as_mut();
+                // Dropping must not panic, so nodes already removed are skipped.
+                if hugr.contains_node(node) && node != hugr.root() {
+                    let _ = hugr.detach_subtree(node);
+                }
+            },
+        }
+    }
+}
+
+impl<T> Rollback<T> {
+    /// Undo the construction of the container node.
+    pub(super) fn apply(self, base: &mut T) {
+        (self.detach)(base, self.node)
+    }
+}
+
+impl<T> Clone for Rollback<T> {
+    fn clone(&self) -> Self {
+        Self {
+            node: self.node,
+            detach: self.detach,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Rollback<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Rollback").field(&self.node).finish()
+    }
+}
+
+impl<T> PartialEq for Rollback<T> {
+    /// Rollbacks are equal if they detach the same node, as all of them are
+    /// created by [`Rollback::new`].
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+/// Remove the subtrees detached by [`Rollback`] from a HUGR whose building is
+/// finished, whether it is owned by the builder or borrowed.
+pub(super) fn purge_detached(hugr: &mut Hugr) {
+    let root = hugr.root();
+    let detached: Vec<Node> = hugr
+        .nodes()
+        .filter(|&node| node != root && hugr.get_parent(node).is_none())
+        .collect();
+    for node in detached {
+        hugr.remove_subtree(node).unwrap();
+    }
+}
+
 fn wire_up_inputs<T: Dataflow + ?Sized>(
     inputs: Vec<Wire>,
     op_node: Node,
@@ -598,7 +960,7 @@ fn wire_up_inputs<T: Dataflow + ?Sized>(
 }
 
 /// Add edge from src to dst and report back if they do share a parent
-fn wire_up<T: Dataflow + ?Sized>(
+pub(super) fn wire_up<T: Dataflow + ?Sized>(
     data_builder: &mut T,
     src: Node,
     src_port: usize,
@@ -608,14 +970,37 @@ fn wire_up<T: Dataflow + ?Sized>(
     let base = data_builder.hugr_mut();
     let src_offset = Port::new_outgoing(src_port);
 
+    // Wires from an abandoned sub-builder refer to detached nodes.
+    let root = base.root();
+    if !base.contains_node(src)
+        || !(src == root || base.is_ancestor_of(root, src))
+        || src_port >= base.num_outputs(src)
+    {
+        return Err(BuildError::WireNotFound(Wire::new(src, src_offset)));
+    }
+
     let src_parent = base.get_parent(src);
     let dst_parent = base.get_parent(dst);
     let local_source = src_parent == dst_parent;
+    // Wires are visible in their region and the regions nested in it. Wires
+    // from a block of an enclosing CFG are too, dominance is left to the
+    // validator.
+    let encloses = |p: Node| dst_parent.is_some_and(|d| d == p || base.is_ancestor_of(p, d));
+    let in_scope = src_parent.is_some_and(|p| {
+        encloses(p)
+            || (OpTag::BasicBlock.is_superset(base.get_optype(p).tag())
+                && base.get_parent(p).is_some_and(encloses))
+    });
+    if !in_scope {
+        return Err(BuildError::WireOutOfScope {
+            wire: Wire::new(src, src_offset),
+        });
+    }
     // Non-local value sources require a state edge to an ancestor of dst
     if !local_source && get_value_kind(base, src, src_offset) == ValueKind::Classic {
         let src_parent = src_parent.expect("Node has no parent");
         let Some(src_sibling) =
-                iter::successors(dst_parent, |&p| base.get_parent(p))
+                base.ancestors(dst)
                     .tuple_windows()
                     .find_map(|(ancestor, ancestor_parent)| {
                         (ancestor_parent == src_parent).then_some(ancestor)
@@ -695,7 +1080,7 @@ pub trait DataflowHugr: HugrBuilder + Dataflow {
         Self: Sized,
     {
         self.set_outputs(outputs)?;
-        Ok(self.finish_hugr()?)
+        self.finish_hugr()
     }
 }
 
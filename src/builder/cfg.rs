@@ -1,21 +1,22 @@
 use itertools::Itertools;
 
 use super::{
-    build_traits::SubContainer,
+    build_traits::{purge_detached, Rollback, SubContainer},
     dataflow::{DFGBuilder, DFGWrapper},
     handle::BuildHandle,
     BasicBlockID, BuildError, CfgID, Container, Dataflow, HugrBuilder, Wire,
 };
 
-use crate::{hugr::view::HugrView, type_row, types::SimpleType};
+use crate::{hugr::view::HugrView, type_row};
 
 use crate::ops::handle::NodeHandle;
 use crate::ops::{self, BasicBlock, OpType};
-use crate::types::Signature;
 
 use crate::Node;
 use crate::{hugr::HugrMut, types::TypeRow, Hugr};
 
+use std::mem;
+
 /// Builder for a [`crate::ops::CFG`] child control
 /// flow graph
 #[derive(Debug, PartialEq)]
@@ -23,8 +24,10 @@ pub struct CFGBuilder<T> {
     pub(super) base: T,
     pub(super) cfg_node: Node,
     pub(super) inputs: Option<TypeRow>,
-    pub(super) exit_node: Node,
+    pub(super) entry_node: Option<Node>,
+    pub(super) exit_node: Option<Node>,
     pub(super) n_out_wires: usize,
+    pub(super) rollback: Option<Rollback<T>>,
 }
 
 impl<B: AsMut<Hugr> + AsRef<Hugr>> Container for CFGBuilder<B> {
@@ -47,11 +50,21 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> Container for CFGBuilder<B> {
 impl<H: AsMut<Hugr> + AsRef<Hugr>> SubContainer for CFGBuilder<H> {
     type ContainerHandle = BuildHandle<CfgID>;
     #[inline]
-    fn finish_sub_container(self) -> Result<Self::ContainerHandle, BuildError> {
+    fn finish_sub_container(mut self) -> Result<Self::ContainerHandle, BuildError> {
+        self.check_complete()?;
+        self.rollback = None;
         Ok((self.cfg_node, self.n_out_wires).into())
     }
 }
 
+impl<T> Drop for CFGBuilder<T> {
+    fn drop(&mut self) {
+        if let Some(rollback) = self.rollback.take() {
+            rollback.apply(&mut self.base);
+        }
+    }
+}
+
 impl CFGBuilder<Hugr> {
     /// New CFG rooted HUGR builder
     pub fn new(input: impl Into<TypeRow>, output: impl Into<TypeRow>) -> Result<Self, BuildError> {
@@ -66,12 +79,45 @@ impl CFGBuilder<Hugr> {
         let cfg_node = base.root();
         CFGBuilder::create(base, cfg_node, input, output)
     }
+
+    /// New CFG rooted HUGR builder, without an exit block.
+    ///
+    /// The exit block must be added with [`CFGBuilder::add_exit_block`] before
+    /// finishing the HUGR.
+    pub fn new_without_exit(
+        input: impl Into<TypeRow>,
+        output: impl Into<TypeRow>,
+    ) -> Result<Self, BuildError> {
+        let input = input.into();
+        let output = output.into();
+        let n_out_wires = output.len();
+        let base = Hugr::new(ops::CFG {
+            inputs: input.clone(),
+            outputs: output,
+        });
+        let cfg_node = base.root();
+        Ok(Self {
+            base,
+            cfg_node,
+            inputs: Some(input),
+            entry_node: None,
+            exit_node: None,
+            n_out_wires,
+            rollback: None,
+        })
+    }
 }
 
 impl HugrBuilder for CFGBuilder<Hugr> {
-    fn finish_hugr(self) -> Result<Hugr, crate::hugr::ValidationError> {
+    /// Check that the CFG is complete, and return the HUGR if it is valid.
+    ///
+    /// Missing blocks and unwired branches are reported before validating the
+    /// HUGR.
+    fn finish_hugr(mut self) -> Result<Hugr, BuildError> {
+        self.check_complete()?;
+        purge_detached(&mut self.base);
         self.base.validate()?;
-        Ok(self.base)
+        Ok(mem::take(&mut self.base))
     }
 }
 
@@ -89,12 +135,16 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> CFGBuilder<B> {
         let exit_node = base
             .as_mut()
             .add_op_with_parent(cfg_node, exit_block_type)?;
+        // Only sub-builders remove their container when dropped unfinished.
+        let rollback = (cfg_node != base.as_ref().root()).then(|| Rollback::new(cfg_node));
         Ok(Self {
             base,
             cfg_node,
             n_out_wires,
-            exit_node,
+            entry_node: None,
+            exit_node: Some(exit_node),
             inputs: Some(input),
+            rollback,
         })
     }
 
@@ -108,8 +158,10 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> CFGBuilder<B> {
             base,
             cfg_node,
             inputs: None, // This will prevent creating an entry node
-            exit_node,
+            entry_node: None,
+            exit_node: Some(exit_node),
             n_out_wires,
+            rollback: None,
         })
     }
 
@@ -142,12 +194,15 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> CFGBuilder<B> {
             predicate_variants: predicate_variants.clone(),
         });
         let parent = self.container_node();
-        let block_n = if entry {
-            let exit = self.exit_node;
-            self.hugr_mut().add_op_before(exit, op)
+        let first_child = self.hugr().children(parent).next();
+        let block_n = if let (true, Some(first)) = (entry, first_child) {
+            self.hugr_mut().add_op_before(first, op)
         } else {
             self.hugr_mut().add_op_with_parent(parent, op)
         }?;
+        if entry {
+            self.entry_node = Some(block_n);
+        }
 
         BlockBuilder::create(
             self.hugr_mut(),
@@ -185,13 +240,22 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> CFGBuilder<B> {
         predicate_variants: Vec<TypeRow>,
         other_outputs: TypeRow,
     ) -> Result<BlockBuilder<&mut Hugr>, BuildError> {
-        let inputs = self
-            .inputs
-            .take()
-            .ok_or(BuildError::EntryBuiltError(self.cfg_node))?;
+        let inputs = match &self.inputs {
+            Some(inputs) if !self.entry_built() => inputs.clone(),
+            _ => return Err(BuildError::EntryBuiltError(self.cfg_node)),
+        };
         self.any_block_builder(inputs, predicate_variants, other_outputs, true)
     }
 
+    /// Whether the CFG has an entry block, either from before the builder was
+    /// created or from an entry block builder that was not abandoned.
+    fn entry_built(&self) -> bool {
+        self.inputs.is_none()
+            || self
+                .entry_node
+                .is_some_and(|entry| self.hugr().get_parent(entry) == Some(self.cfg_node))
+    }
+
     /// Return a builder for the entry [`BasicBlock::DFB`] child graph with `inputs`
     /// and `outputs` and a simple predicate type: a Sum of `n_cases` unit types.
     ///
@@ -207,8 +271,79 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> CFGBuilder<B> {
     }
 
     /// Returns the exit block of this [`CFGBuilder`].
-    pub fn exit_block(&self) -> BasicBlockID {
-        self.exit_node.into()
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the builder was created without
+    /// an exit block, and it has not been added with
+    /// [`CFGBuilder::add_exit_block`].
+    pub fn exit_block(&self) -> Result<BasicBlockID, BuildError> {
+        self.exit_node
+            .map(Into::into)
+            .ok_or(BuildError::MissingExitBlock(self.cfg_node))
+    }
+
+    /// Returns the exit block of this [`CFGBuilder`], adding it if the builder
+    /// was created without one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there is an error adding the node.
+    pub fn add_exit_block(&mut self) -> Result<BasicBlockID, BuildError> {
+        if let Some(exit) = self.exit_node {
+            return Ok(exit.into());
+        }
+        let OpType::CFG(ops::CFG { outputs, .. }) = self.hugr().get_optype(self.cfg_node) else {
+            return Err(BuildError::UnexpectedType {
+                node: self.cfg_node,
+                op_desc: "Any CFG",
+            });
+        };
+        let exit_op = BasicBlock::Exit {
+            cfg_outputs: outputs.clone(),
+        };
+        // The exit block is the second child, after the entry block.
+        let first_child = self.hugr().children(self.cfg_node).next();
+        let exit = match (self.entry_built(), first_child) {
+            (true, Some(entry)) => self.hugr_mut().add_op_after(entry, exit_op),
+            (false, Some(first)) => self.hugr_mut().add_op_before(first, exit_op),
+            (_, None) => {
+                let parent = self.cfg_node;
+                self.hugr_mut().add_op_with_parent(parent, exit_op)
+            }
+        }?;
+        self.exit_node = Some(exit);
+        Ok(exit.into())
+    }
+
+    /// Check that the CFG has an entry block followed by an exit block, and
+    /// that all the successor ports of its blocks are connected.
+    fn check_complete(&self) -> Result<(), BuildError> {
+        let hugr = self.hugr();
+        if self.exit_node.is_none() {
+            return Err(BuildError::MissingExitBlock(self.cfg_node));
+        }
+        let is_dfb = |n: Node| {
+            matches!(
+                hugr.get_optype(n),
+                OpType::BasicBlock(BasicBlock::DFB { .. })
+            )
+        };
+        if !hugr.children(self.cfg_node).next().is_some_and(is_dfb) {
+            return Err(BuildError::MissingEntryBlock(self.cfg_node));
+        }
+        for block in hugr.children(self.cfg_node).filter(|&n| is_dfb(n)) {
+            if let Some(port) = hugr
+                .node_outputs(block)
+                .find(|&p| !hugr.is_linked(block, p))
+            {
+                return Err(BuildError::UnwiredBranch {
+                    block,
+                    branch: port.index(),
+                });
+            }
+        }
+        Ok(())
     }
 
     /// Set the `branch` index `successor` block of `predecessor`.
@@ -248,11 +383,13 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> BlockBuilder<B> {
         other_outputs: TypeRow,
         inputs: TypeRow,
     ) -> Result<Self, BuildError> {
-        // The node outputs a predicate before the data outputs of the block node
-        let predicate_type = SimpleType::new_predicate(predicate_variants);
-        let mut node_outputs = vec![predicate_type];
-        node_outputs.extend_from_slice(&other_outputs);
-        let signature = Signature::new_df(inputs, TypeRow::from(node_outputs));
+        let signature = BasicBlock::DFB {
+            inputs,
+            other_outputs,
+            predicate_variants,
+        }
+        .inner_signature()
+        .expect("DFB blocks contain a dataflow graph");
         let db = DFGBuilder::create_with_io(base, block_n, signature)?;
         Ok(BlockBuilder::from_dfg_builder(db))
     }
@@ -272,6 +409,76 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> BlockBuilder<B> {
     }
 }
 
+impl<B: AsMut<Hugr> + AsRef<Hugr>> BlockBuilder<B> {
+    /// Construct the predicate value selecting the `branch` successor of the
+    /// block, carrying `values` to it.
+    ///
+    /// The predicate is built with [`LeafOp::MakeTuple`] and [`LeafOp::Tag`]
+    /// operations.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `branch` is not a variant of the
+    /// predicate of the block, or if the types of `values` do not match the
+    /// variant's row.
+    ///
+    /// [`LeafOp::MakeTuple`]: crate::ops::LeafOp::MakeTuple
+    /// [`LeafOp::Tag`]: crate::ops::LeafOp::Tag
+    pub fn make_branch(
+        &mut self,
+        branch: usize,
+        values: impl IntoIterator<Item = Wire>,
+    ) -> Result<Wire, BuildError> {
+        let block = self.container_node();
+        let OpType::BasicBlock(BasicBlock::DFB {
+            predicate_variants, ..
+        }) = self.hugr().get_optype(block)
+        else {
+            return Err(BuildError::UnexpectedType {
+                node: block,
+                op_desc: "BasicBlock::DFB",
+            });
+        };
+        let predicate_variants = predicate_variants.clone();
+        let Some(row) = predicate_variants.get(branch) else {
+            return Err(BuildError::BranchOutOfRange {
+                block,
+                branch,
+                n_branches: predicate_variants.len(),
+            });
+        };
+        let values = values.into_iter().collect_vec();
+        let types: TypeRow = values
+            .iter()
+            .map(|&wire| self.get_wire_type(wire))
+            .collect::<Result<Vec<_>, _>>()?
+            .into();
+        if &types != row {
+            return Err(BuildError::InputWiresMismatch {
+                expected: row.clone(),
+                found: types,
+            });
+        }
+        self.make_predicate(branch, predicate_variants, values)
+    }
+
+    /// [Make the predicate](BlockBuilder::make_branch) for the `branch`
+    /// successor, then [set the outputs](BlockBuilder::set_outputs) and
+    /// [finish](`BlockBuilder::finish_sub_container`).
+    ///
+    /// A block jumping unconditionally to its only successor is finished with
+    /// `finish_with_branch(0, [], other_outputs)`.
+    pub fn finish_with_branch(
+        mut self,
+        branch: usize,
+        values: impl IntoIterator<Item = Wire>,
+        other_outputs: impl IntoIterator<Item = Wire>,
+    ) -> Result<<Self as SubContainer>::ContainerHandle, BuildError> {
+        let predicate = self.make_branch(branch, values)?;
+        self.finish_with_outputs(predicate, other_outputs)
+    }
+}
+
 impl BlockBuilder<Hugr> {
     /// Initialize a [`BasicBlock::DFB`] rooted HUGR builder
     pub fn new(
@@ -301,8 +508,10 @@ mod test {
     use cool_asserts::assert_matches;
 
     use crate::builder::build_traits::HugrBuilder;
+    use crate::builder::test::{build_main, BIT, NAT};
     use crate::builder::{DataflowSubContainer, ModuleBuilder};
-    use crate::{builder::test::NAT, ops::ConstValue, type_row, types::Signature};
+    use crate::types::{Signature, SimpleType};
+    use crate::{ops::ConstValue, type_row};
 
     use super::*;
     #[test]
@@ -356,19 +565,134 @@ mod test {
             type_row![NAT],
             1,
         )?;
-        let new_bb = block_builder.container_node();
         let [pred, nat]: [Wire; 2] = block_builder.input_wires_arr();
-        block_builder.finish_with_outputs(pred, [nat])?;
+        let new_bb = block_builder.finish_with_outputs(pred, [nat])?;
+        let exit = new_builder.exit_block()?;
+        new_builder.branch(&new_bb, 0, &exit)?;
         let h2 = new_builder.finish_hugr()?;
         let expected_nodes = h
             .children(h.root())
-            .chain([new_bb])
+            .chain([new_bb.node()])
             .collect::<HashSet<Node>>();
         assert_eq!(expected_nodes, HashSet::from_iter(h2.children(h2.root())));
 
         Ok(())
     }
 
+    #[test]
+    fn incomplete_cfg() -> Result<(), BuildError> {
+        let mut cfg_builder = CFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let cfg_node = cfg_builder.container_node();
+        let mut block = cfg_builder.simple_block_builder(type_row![NAT], type_row![NAT], 2)?;
+        let [inw] = block.input_wires_arr();
+        let pred = block.add_load_const(ConstValue::simple_predicate(0, 2))?;
+        let block = block.finish_with_outputs(pred, [inw])?;
+        let exit = cfg_builder.exit_block()?;
+        cfg_builder.branch(&block, 0, &exit)?;
+        assert_eq!(
+            cfg_builder.check_complete(),
+            Err(BuildError::MissingEntryBlock(cfg_node))
+        );
+
+        let mut entry = cfg_builder.simple_entry_builder(type_row![NAT], 1)?;
+        let [inw] = entry.input_wires_arr();
+        let pred = entry.add_load_const(ConstValue::simple_unary_predicate())?;
+        let entry = entry.finish_with_outputs(pred, [inw])?;
+        cfg_builder.branch(&entry, 0, &block)?;
+        assert_eq!(
+            cfg_builder.check_complete(),
+            Err(BuildError::UnwiredBranch {
+                block: block.node(),
+                branch: 1
+            })
+        );
+
+        cfg_builder.branch(&block, 1, &block)?;
+        assert_matches!(cfg_builder.finish_hugr(), Ok(_));
+        Ok(())
+    }
+
+    #[test]
+    fn without_exit() -> Result<(), BuildError> {
+        let mut cfg_builder = CFGBuilder::new_without_exit(type_row![NAT], type_row![NAT])?;
+        let cfg_node = cfg_builder.container_node();
+        // An abandoned entry block can be built again.
+        cfg_builder
+            .simple_entry_builder(type_row![NAT], 1)?
+            .abandon();
+        let mut entry = cfg_builder.simple_entry_builder(type_row![NAT], 1)?;
+        let [inw] = entry.input_wires_arr();
+        let pred = entry.add_load_const(ConstValue::simple_unary_predicate())?;
+        let entry = entry.finish_with_outputs(pred, [inw])?;
+        assert_eq!(
+            cfg_builder.check_complete(),
+            Err(BuildError::MissingExitBlock(cfg_node))
+        );
+        assert_eq!(
+            cfg_builder.exit_block(),
+            Err(BuildError::MissingExitBlock(cfg_node))
+        );
+        // Finishing through the trait reports the missing block too.
+        let unfinished = CFGBuilder::new_without_exit(type_row![NAT], type_row![NAT])?;
+        assert_matches!(
+            HugrBuilder::finish_hugr(unfinished),
+            Err(BuildError::MissingExitBlock(_))
+        );
+
+        let exit = cfg_builder.add_exit_block()?;
+        assert_eq!(cfg_builder.add_exit_block()?, exit);
+        assert_eq!(cfg_builder.exit_block()?, exit);
+        cfg_builder.branch(&entry, 0, &exit)?;
+        let h = cfg_builder.finish_hugr()?;
+        assert_eq!(
+            h.children(h.root()).collect::<Vec<_>>(),
+            [entry.node(), exit.node()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn branch_helpers() -> Result<(), BuildError> {
+        // The CFG of `cfg_children_restrictions` in the validation tests: a
+        // single block jumping unconditionally to the exit.
+        let hugr = build_main(
+            Signature::new_df(type_row![BIT], type_row![BIT]),
+            |mut func_builder| {
+                let [bit] = func_builder.input_wires_arr();
+                let mut cfg_builder = func_builder.cfg_builder([(BIT, bit)], type_row![BIT])?;
+                let entry_b = cfg_builder.simple_entry_builder(type_row![BIT], 1)?;
+                let [inw] = entry_b.input_wires_arr();
+                let entry = entry_b.finish_with_branch(0, [], [inw])?;
+                let exit = cfg_builder.exit_block()?;
+                cfg_builder.branch(&entry, 0, &exit)?;
+                let cfg = cfg_builder.finish_sub_container()?;
+                func_builder.finish_with_outputs(cfg.outputs())
+            },
+        )?;
+        assert_eq!(hugr.validate(), Ok(()));
+
+        // A block passing different values to each successor.
+        let variants = vec![type_row![NAT], type_row![NAT, BIT]];
+        let mut block = BlockBuilder::new(type_row![NAT, BIT], variants, type_row![])?;
+        let block_node = block.container_node();
+        let [nat, bit] = block.input_wires_arr();
+        assert_matches!(
+            block.make_branch(2, [nat]),
+            Err(BuildError::BranchOutOfRange { block, branch: 2, n_branches: 2 }) => assert_eq!(block, block_node)
+        );
+        assert_matches!(
+            block.make_branch(0, [bit]),
+            Err(BuildError::InputWiresMismatch { .. })
+        );
+        let predicate = block.make_branch(1, [nat, bit])?;
+        assert_eq!(
+            block.get_wire_type(predicate)?,
+            SimpleType::new_predicate([type_row![NAT], type_row![NAT, BIT]])
+        );
+        assert_matches!(block.finish_with_branch(0, [nat], []), Ok(_));
+        Ok(())
+    }
+
     fn build_basic_cfg<T: AsMut<Hugr> + AsRef<Hugr>>(
         cfg_builder: &mut CFGBuilder<T>,
     ) -> Result<(), BuildError> {
@@ -386,7 +710,7 @@ mod test {
             let [inw] = middle_b.input_wires_arr();
             middle_b.finish_with_outputs(c, [inw])?
         };
-        let exit = cfg_builder.exit_block();
+        let exit = cfg_builder.exit_block()?;
         cfg_builder.branch(&entry, 0, &middle)?;
         cfg_builder.branch(&middle, 0, &exit)?;
         cfg_builder.branch(&entry, 1, &exit)?;
@@ -4,7 +4,7 @@ use crate::types::{Signature, TypeRow};
 use crate::ops;
 use crate::ops::handle::CaseID;
 
-use super::build_traits::SubContainer;
+use super::build_traits::{purge_detached, Rollback, SubContainer};
 use super::handle::BuildHandle;
 use super::HugrBuilder;
 use super::{
@@ -17,6 +17,7 @@ use crate::Node;
 use crate::{hugr::HugrMut, Hugr};
 
 use std::collections::HashSet;
+use std::mem;
 
 use thiserror::Error;
 
@@ -46,6 +47,7 @@ pub struct ConditionalBuilder<T> {
     pub(super) conditional_node: Node,
     pub(super) n_out_wires: usize,
     pub(super) case_nodes: Vec<Option<Node>>,
+    pub(super) rollback: Option<Rollback<T>>,
 }
 
 impl<T: AsMut<Hugr> + AsRef<Hugr>> Container for ConditionalBuilder<T> {
@@ -68,12 +70,9 @@ impl<T: AsMut<Hugr> + AsRef<Hugr>> Container for ConditionalBuilder<T> {
 impl<H: AsMut<Hugr> + AsRef<Hugr>> SubContainer for ConditionalBuilder<H> {
     type ContainerHandle = BuildHandle<ConditionalID>;
 
-    fn finish_sub_container(self) -> Result<Self::ContainerHandle, BuildError> {
-        let cases: HashSet<usize> = self
-            .case_nodes
-            .iter()
-            .enumerate()
-            .filter_map(|(i, node)| if node.is_none() { Some(i) } else { None })
+    fn finish_sub_container(mut self) -> Result<Self::ContainerHandle, BuildError> {
+        let cases: HashSet<usize> = (0..self.case_nodes.len())
+            .filter(|&case| self.built_case(case).is_none())
             .collect();
         if !cases.is_empty() {
             return Err(ConditionalBuildError::NotAllCasesBuilt {
@@ -82,9 +81,18 @@ impl<H: AsMut<Hugr> + AsRef<Hugr>> SubContainer for ConditionalBuilder<H> {
             }
             .into());
         }
+        self.rollback = None;
         Ok((self.conditional_node, self.n_out_wires).into())
     }
 }
+
+impl<T> Drop for ConditionalBuilder<T> {
+    fn drop(&mut self) {
+        if let Some(rollback) = self.rollback.take() {
+            rollback.apply(&mut self.base);
+        }
+    }
+}
 impl<B: AsMut<Hugr> + AsRef<Hugr>> ConditionalBuilder<B> {
     /// Return a builder the Case node with index `case`.
     ///
@@ -108,17 +116,18 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> ConditionalBuilder<B> {
             .case_input_row(case)
             .ok_or(ConditionalBuildError::NotCase { conditional, case })?;
 
-        if self.case_nodes.get(case).unwrap().is_some() {
+        if self.built_case(case).is_some() {
             return Err(ConditionalBuildError::CaseBuilt { conditional, case }.into());
         }
 
         let outputs = cond.outputs;
         let case_op = ops::Case {
+            tag: case,
             signature: Signature::new_df(inputs.clone(), outputs.clone()),
         };
         let case_node =
             // add case before any existing subsequent cases
-            if let Some(&sibling_node) = self.case_nodes[case + 1..].iter().flatten().next() {
+            if let Some(sibling_node) = (case + 1..self.case_nodes.len()).find_map(|c| self.built_case(c)) {
                 self.hugr_mut().add_op_before(sibling_node, case_op)?
             } else {
                 self.add_child_op(case_op)?
@@ -134,12 +143,20 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> ConditionalBuilder<B> {
 
         Ok(CaseBuilder::from_dfg_builder(dfg_builder))
     }
+
+    /// The node of case `case`, unless it has not been built or its builder
+    /// was abandoned.
+    fn built_case(&self, case: usize) -> Option<Node> {
+        self.case_nodes[case]
+            .filter(|&node| self.hugr().get_parent(node) == Some(self.conditional_node))
+    }
 }
 
 impl HugrBuilder for ConditionalBuilder<Hugr> {
-    fn finish_hugr(self) -> Result<Hugr, crate::hugr::ValidationError> {
+    fn finish_hugr(mut self) -> Result<Hugr, BuildError> {
+        purge_detached(&mut self.base);
         self.base.validate()?;
-        Ok(self.base)
+        Ok(mem::take(&mut self.base))
     }
 }
 
@@ -170,6 +187,7 @@ impl ConditionalBuilder<Hugr> {
             conditional_node,
             n_out_wires,
             case_nodes: vec![None; n_cases],
+            rollback: None,
         })
     }
 }
@@ -181,6 +199,7 @@ impl CaseBuilder<Hugr> {
         let output = output.into();
         let signature = Signature::new_df(input, output);
         let op = ops::Case {
+            tag: 0,
             signature: signature.clone(),
         };
         let base = Hugr::new(op);
@@ -194,14 +213,21 @@ impl CaseBuilder<Hugr> {
 mod test {
     use cool_asserts::assert_matches;
 
-    use crate::builder::{DataflowSubContainer, HugrBuilder, ModuleBuilder};
+    use crate::builder::{
+        DFGBuilder, DataflowHugr, DataflowSubContainer, HugrBuilder, ModuleBuilder,
+    };
+    use crate::hugr::{HugrMut, ValidationError};
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::validate::ChildrenValidationError;
     use crate::{
         builder::{
-            test::{n_identity, NAT},
+            test::{n_identity, BIT, NAT, QB},
             Dataflow,
         },
-        ops::ConstValue,
+        ops::{ConstValue, LeafOp, OpTrait, OpType},
         type_row,
+        types::SimpleType,
+        Port,
     };
 
     use super::*;
@@ -218,6 +244,121 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn conditional_cases() -> Result<(), BuildError> {
+        let predicate_inputs = vec![type_row![], type_row![NAT]];
+        let mut conditional_b =
+            ConditionalBuilder::new(predicate_inputs, type_row![NAT], type_row![NAT])?;
+
+        let case_0 = n_identity(conditional_b.case_builder(0)?)?;
+        let case_1 = {
+            let case_b = conditional_b.case_builder(1)?;
+            let [w, _] = case_b.input_wires_arr();
+            case_b.finish_with_outputs([w])?
+        };
+        let hugr = conditional_b.finish_hugr()?;
+
+        let cases: Vec<_> = hugr.conditional_cases(hugr.root()).collect();
+        assert_eq!(cases, [(0, case_0.node()), (1, case_1.node())]);
+        assert_eq!(
+            hugr.case_signature(case_0.node()),
+            Some(&Signature::new_df(type_row![NAT], type_row![NAT]))
+        );
+        assert_eq!(
+            hugr.case_signature(case_1.node()),
+            Some(&Signature::new_df(type_row![NAT, NAT], type_row![NAT]))
+        );
+        assert_eq!(hugr.case_signature(hugr.root()), None);
+        assert_eq!(hugr.conditional_cases(case_0.node()).count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn abandoned_case() -> Result<(), BuildError> {
+        let predicate_inputs = vec![type_row![]; 2];
+        let mut conditional_b =
+            ConditionalBuilder::new(predicate_inputs, type_row![NAT], type_row![NAT])?;
+
+        conditional_b.case_builder(1)?.abandon();
+        let case_0 = n_identity(conditional_b.case_builder(0)?)?;
+        assert_matches!(
+            conditional_b.case_builder(0).err(),
+            Some(BuildError::ConditionalError(
+                ConditionalBuildError::CaseBuilt { .. }
+            ))
+        );
+        let case_1 = n_identity(conditional_b.case_builder(1)?)?;
+        let hugr = conditional_b.finish_hugr()?;
+
+        let cases: Vec<_> = hugr.conditional_cases(hugr.root()).collect();
+        assert_eq!(cases, [(0, case_0.node()), (1, case_1.node())]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn case_tags() -> Result<(), BuildError> {
+        let predicate_inputs = vec![type_row![]; 2];
+        let mut conditional_b =
+            ConditionalBuilder::new(predicate_inputs, type_row![NAT], type_row![NAT])?;
+        let case_1 = n_identity(conditional_b.case_builder(1)?)?;
+        let case_0 = n_identity(conditional_b.case_builder(0)?)?;
+        let mut hugr = conditional_b.finish_hugr()?;
+        for (tag, case) in [(0, case_0.node()), (1, case_1.node())] {
+            assert_matches!(hugr.get_optype(case), OpType::Case(op) => assert_eq!(op.tag(), tag));
+        }
+
+        // Cases serialized without tags are tagged by position.
+        let mut json = serde_json::to_value(&hugr).unwrap();
+        for node in json["nodes"].as_array_mut().unwrap() {
+            node.as_object_mut().unwrap().remove("tag");
+        }
+        let legacy: Hugr = serde_json::from_value(json).unwrap();
+        assert_eq!(legacy.validate(), Ok(()));
+        for (tag, case) in legacy.children(legacy.root()).enumerate() {
+            assert_matches!(legacy.get_optype(case), OpType::Case(op) => assert_eq!(op.tag(), tag));
+        }
+        // Outside a HUGR, a case has no position to be tagged by.
+        let mut case = serde_json::to_value(hugr.get_optype(case_0.node())).unwrap();
+        case.as_object_mut().unwrap().remove("tag");
+        assert!(serde_json::from_value::<OpType>(case).is_err());
+
+        // Swapping the cases changes their meaning, even if their signatures
+        // coincide.
+        hugr.move_before_sibling(case_1.node(), case_0.node())
+            .unwrap();
+        assert_matches!(
+            hugr.validate(),
+            Err(ValidationError::InvalidChildren {
+                source: ChildrenValidationError::ConditionalCaseTag {
+                    position: 0,
+                    tag: 1,
+                    ..
+                },
+                ..
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn load_const_in_case() -> Result<(), BuildError> {
+        let predicate_inputs = vec![type_row![]; 2];
+        let mut conditional_b =
+            ConditionalBuilder::new(predicate_inputs, type_row![NAT], type_row![NAT])?;
+
+        n_identity(conditional_b.case_builder(0)?)?;
+        let mut case_b = conditional_b.case_builder(1)?;
+        let w = case_b.add_load_const(ConstValue::i64(3))?;
+        case_b.finish_with_outputs([w])?;
+
+        let hugr = conditional_b.finish_hugr()?;
+        assert_eq!(hugr.validate(), Ok(()));
+
+        Ok(())
+    }
+
     #[test]
     fn basic_conditional_module() -> Result<(), BuildError> {
         let build_result: Result<Hugr, BuildError> = {
@@ -253,4 +394,87 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn classically_controlled_gate() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB, BIT])?;
+        let [q0, q1] = builder.input_wires_arr();
+        let [q0, bit] = builder
+            .add_dataflow_op(LeafOp::Measure, [q0])?
+            .outputs_arr();
+        let q1 = builder.conditional_gate(bit, q1, LeafOp::X)?;
+        let conditional = q1.node();
+        let hugr = builder.finish_hugr_with_outputs([q0, q1, bit])?;
+        assert_eq!(hugr.validate(), Ok(()));
+
+        let OpType::Conditional(op) = hugr.get_optype(conditional) else {
+            panic!("Expected a Conditional node");
+        };
+        assert_eq!(
+            op.signature(),
+            Signature::new_df(vec![SimpleType::new_simple_predicate(2), QB], type_row![QB])
+        );
+        let cases: Vec<_> = hugr.conditional_cases(conditional).collect();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(hugr.children(cases[0].1).count(), 2);
+        assert_matches!(
+            hugr.children(cases[1].1).map(|n| hugr.get_optype(n)).nth(2),
+            Some(OpType::LeafOp(LeafOp::X))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn branch_on_bit() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB, BIT])?;
+        let [q0, q1] = builder.input_wires_arr();
+        let [q0, bit] = builder
+            .add_dataflow_op(LeafOp::Measure, [q0])?
+            .outputs_arr();
+        let mut conditional = builder.conditional_builder(
+            ([type_row![], type_row![]], bit),
+            [(QB, q1)],
+            type_row![QB],
+        )?;
+        n_identity(conditional.case_builder(0)?)?;
+        let mut case = conditional.case_builder(1)?;
+        let h = case.add_dataflow_op(LeafOp::H, case.input_wires())?;
+        case.finish_with_outputs(h.outputs())?;
+        let conditional = conditional.finish_sub_container()?;
+
+        // The predicate can be converted back into a bit.
+        let [predicate] = builder
+            .add_dataflow_op(LeafOp::BitToPredicate, [bit])?
+            .outputs_arr();
+        let [bit] = builder
+            .add_dataflow_op(LeafOp::PredicateToBit, [predicate])?
+            .outputs_arr();
+        let hugr = builder.finish_hugr_with_outputs([q0, conditional.out_wire(0), bit])?;
+        assert_eq!(hugr.validate(), Ok(()));
+
+        let (converted, _) = hugr
+            .linked_ports(conditional.node(), Port::new_incoming(0))
+            .next()
+            .unwrap();
+        assert_eq!(hugr.get_optype(converted), &LeafOp::BitToPredicate.into());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_controlled_gate() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, BIT], type_row![QB, BIT])?;
+        let [q, bit] = builder.input_wires_arr();
+        assert_eq!(
+            builder.conditional_gate(bit, q, LeafOp::CX),
+            Err(BuildError::NotSingleQubitGate(LeafOp::CX))
+        );
+        assert_eq!(
+            builder.conditional_gate(q, q, LeafOp::X),
+            Err(BuildError::InputWiresMismatch {
+                expected: type_row![BIT],
+                found: type_row![QB],
+            })
+        );
+        Ok(())
+    }
 }
@@ -1,16 +1,19 @@
-use super::build_traits::{HugrBuilder, SubContainer};
+use super::build_traits::{purge_detached, wire_up, HugrBuilder, Rollback, SubContainer};
 use super::handle::BuildHandle;
-use super::{BuildError, Container, Dataflow, DfgID, FuncID};
+use super::{BuildError, Container, Dataflow, DfgID, FuncID, Wire};
 
 use std::marker::PhantomData;
+use std::mem;
 
-use crate::hugr::{HugrView, ValidationError};
-use crate::ops;
+use portgraph::Direction;
 
-use crate::types::{Signature, TypeRow};
+use crate::hugr::{HugrError, HugrView};
+use crate::ops::{self, OpTrait, OpType};
+
+use crate::types::{Signature, SimpleType, TypeRow};
 
-use crate::Node;
 use crate::{hugr::HugrMut, Hugr};
+use crate::{Node, Port};
 
 /// Builder for a [`ops::DFG`] node.
 #[derive(Debug, Clone, PartialEq)]
@@ -19,6 +22,7 @@ pub struct DFGBuilder<T> {
     pub(crate) dfg_node: Node,
     pub(crate) num_in_wires: usize,
     pub(crate) num_out_wires: usize,
+    pub(super) rollback: Option<Rollback<T>>,
 }
 
 impl<T: AsMut<Hugr> + AsRef<Hugr>> DFGBuilder<T> {
@@ -44,11 +48,14 @@ impl<T: AsMut<Hugr> + AsRef<Hugr>> DFGBuilder<T> {
             },
         )?;
 
+        // Only sub-builders remove their container when dropped unfinished.
+        let rollback = (parent != base.as_ref().root()).then(|| Rollback::new(parent));
         Ok(Self {
             base,
             dfg_node: parent,
             num_in_wires,
             num_out_wires,
+            rollback,
         })
     }
 }
@@ -68,6 +75,7 @@ impl DFGBuilder<Hugr> {
         let signature = Signature::new_df(input, output);
         let dfg_op = ops::DFG {
             signature: signature.clone(),
+            resource_delta: None,
         };
         let base = Hugr::new(dfg_op);
         let root = base.root();
@@ -76,9 +84,10 @@ impl DFGBuilder<Hugr> {
 }
 
 impl HugrBuilder for DFGBuilder<Hugr> {
-    fn finish_hugr(self) -> Result<Hugr, ValidationError> {
+    fn finish_hugr(mut self) -> Result<Hugr, BuildError> {
+        purge_detached(&mut self.base);
         self.base.validate()?;
-        Ok(self.base)
+        Ok(mem::take(&mut self.base))
     }
 }
 
@@ -102,11 +111,20 @@ impl<T: AsMut<Hugr> + AsRef<Hugr>> Container for DFGBuilder<T> {
 impl<T: AsMut<Hugr> + AsRef<Hugr>> SubContainer for DFGBuilder<T> {
     type ContainerHandle = BuildHandle<DfgID>;
     #[inline]
-    fn finish_sub_container(self) -> Result<Self::ContainerHandle, BuildError> {
+    fn finish_sub_container(mut self) -> Result<Self::ContainerHandle, BuildError> {
+        self.rollback = None;
         Ok((self.dfg_node, self.num_out_wires).into())
     }
 }
 
+impl<T> Drop for DFGBuilder<T> {
+    fn drop(&mut self) {
+        if let Some(rollback) = self.rollback.take() {
+            rollback.apply(&mut self.base);
+        }
+    }
+}
+
 impl<T: AsMut<Hugr> + AsRef<Hugr>> Dataflow for DFGBuilder<T> {
     #[inline]
     fn num_inputs(&self) -> usize {
@@ -137,6 +155,7 @@ impl FunctionBuilder<Hugr> {
         let op = ops::FuncDefn {
             signature: signature.clone(),
             name: name.into(),
+            signature_desc: Default::default(),
         };
 
         let base = Hugr::new(op);
@@ -147,6 +166,93 @@ impl FunctionBuilder<Hugr> {
     }
 }
 
+impl<B: AsMut<Hugr> + AsRef<Hugr>> FunctionBuilder<B> {
+    /// Append an input of type `ty` to the signature of the function, and
+    /// return its wire.
+    ///
+    /// The ports of the Input node are appended to, so the previously returned
+    /// input wires stay valid.
+    pub fn append_input(&mut self, ty: SimpleType) -> Result<Wire, BuildError> {
+        let [inp, _] = self.io();
+        let mut input = self.hugr().get_optype(inp).clone();
+        let OpType::Input(ops::Input { types, .. }) = &mut input else {
+            panic!("First child of a function should be an Input node.")
+        };
+        let port = Port::new_outgoing(types.len());
+        types.to_mut().push(ty.clone());
+        set_io_op(self.hugr_mut(), inp, Direction::Outgoing, input)?;
+        self.update_signature(|sig| sig.input.to_mut().push(ty));
+        self.0.num_in_wires += 1;
+        Ok(Wire::new(inp, port))
+    }
+
+    /// Append `wire` to the outputs of the function, adding its type to the
+    /// signature.
+    ///
+    /// The wire is connected to a new last port of the Output node, so the
+    /// outputs passed to [`finish_with_outputs`] are connected before it.
+    ///
+    /// [`finish_with_outputs`]: super::DataflowSubContainer::finish_with_outputs
+    pub fn append_output(&mut self, wire: Wire) -> Result<(), BuildError> {
+        let ty = self.get_wire_type(wire)?;
+        let [_, out] = self.io();
+        let old_output = self.hugr().get_optype(out).clone();
+        let mut output = old_output.clone();
+        let OpType::Output(ops::Output { types, .. }) = &mut output else {
+            panic!("Second child of a function should be an Output node.")
+        };
+        let port = types.len();
+        types.to_mut().push(ty.clone());
+        set_io_op(self.hugr_mut(), out, Direction::Incoming, output)?;
+        if let Err(e) = wire_up(self, wire.node(), wire.source().index(), out, port) {
+            set_io_op(self.hugr_mut(), out, Direction::Incoming, old_output)?;
+            return Err(e);
+        }
+        self.update_signature(|sig| sig.output.to_mut().push(ty));
+        self.0.num_out_wires += 1;
+        Ok(())
+    }
+
+    /// Update the signature of the function definition node.
+    fn update_signature(&mut self, f: impl FnOnce(&mut Signature)) {
+        let node = self.container_node();
+        let mut def = self.hugr().get_optype(node).clone();
+        let OpType::FuncDefn(ops::FuncDefn { signature, .. }) = &mut def else {
+            panic!("Function builder container should be a FuncDefn node.")
+        };
+        f(signature);
+        self.hugr_mut().replace_op_unchecked(node, def);
+    }
+}
+
+/// Replace the operation of an Input or Output node by one with a different
+/// number of dataflow ports in direction `dir`, keeping the order edges
+/// connected to its non-dataflow port.
+fn set_io_op(hugr: &mut Hugr, node: Node, dir: Direction, op: OpType) -> Result<(), HugrError> {
+    let old_port = hugr.get_optype(node).other_port_index(dir);
+    let links: Vec<(Node, Port)> = old_port
+        .map(|port| hugr.linked_ports(node, port).collect())
+        .unwrap_or_default();
+    if let Some(port) = old_port {
+        hugr.disconnect(node, port)?;
+    }
+    hugr.set_num_ports(node, op.input_count(), op.output_count())?;
+    hugr.replace_op_unchecked(node, op);
+    if let Some(port) = hugr.get_optype(node).other_port_index(dir) {
+        for (other, other_port) in links {
+            match dir {
+                Direction::Outgoing => {
+                    hugr.connect(node, port.index(), other, other_port.index())?
+                }
+                Direction::Incoming => {
+                    hugr.connect(other, other_port.index(), node, port.index())?
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 impl<B: AsMut<Hugr> + AsRef<Hugr>, T> Container for DFGWrapper<B, T> {
     #[inline]
     fn container_node(&self) -> Node {
@@ -181,7 +287,7 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>, T: From<BuildHandle<DfgID>>> SubContainer for
 }
 
 impl<T> HugrBuilder for DFGWrapper<Hugr, T> {
-    fn finish_hugr(self) -> Result<Hugr, ValidationError> {
+    fn finish_hugr(self) -> Result<Hugr, BuildError> {
         self.0.finish_hugr()
     }
 }
@@ -193,17 +299,18 @@ mod test {
 
     use crate::builder::build_traits::DataflowHugr;
     use crate::builder::{DataflowSubContainer, ModuleBuilder};
-    use crate::ops::OpTag;
     use crate::ops::OpTrait;
+    use crate::ops::{handle::NodeHandle, OpTag};
     use crate::{
         builder::{
-            test::{n_identity, BIT, NAT, QB},
+            test::{build_main, n_identity, BIT, NAT, QB},
             BuildError,
         },
-        ops::LeafOp,
+        hugr::{typecheck::ConstTypeError, HugrError, ValidationError},
+        ops::{ConstValue, LeafOp},
         resource::ResourceSet,
         type_row,
-        types::{LinearType, Signature},
+        types::{LinearType, Signature, TypeRow},
         Wire,
     };
 
@@ -237,6 +344,47 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn nested_load_const() -> Result<(), BuildError> {
+        let hugr = build_main(
+            Signature::new_df(type_row![], type_row![NAT]),
+            |mut f_build| {
+                let mut inner_builder =
+                    f_build.dfg_builder(Signature::new_df(type_row![], type_row![NAT]), [])?;
+                let w = inner_builder.add_load_const(ConstValue::i64(42))?;
+                let inner_id = inner_builder.finish_with_outputs([w])?;
+                f_build.finish_with_outputs(inner_id.outputs())
+            },
+        )?;
+        assert_eq!(hugr.validate(), Ok(()));
+
+        let mut builder = DFGBuilder::new(type_row![], type_row![NAT])?;
+        assert_eq!(
+            builder.add_load_const(ConstValue::Int { value: 1, width: 3 }),
+            Err(BuildError::InvalidHUGR(ValidationError::ConstTypeError(
+                ConstTypeError::IntWidthInvalid(3)
+            )))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_const_split_tuple() -> Result<(), BuildError> {
+        let tuple = || ConstValue::Tuple(vec![ConstValue::i64(1), ConstValue::i64(2)]);
+
+        let mut builder = DFGBuilder::new(type_row![], type_row![NAT, NAT])?;
+        let ws = builder.add_load_const_with(tuple(), true)?;
+        assert_eq!(ws.len(), 2);
+        builder.finish_hugr_with_outputs(ws)?;
+
+        let mut builder = DFGBuilder::new(type_row![], type_row![NAT, NAT])?;
+        let ws = builder.add_load_const_with(tuple(), false)?;
+        assert_eq!(ws.len(), 1);
+
+        Ok(())
+    }
+
     // Scaffolding for copy insertion tests
     fn copy_scaffold<F>(f: F, msg: &'static str) -> Result<(), BuildError>
     where
@@ -301,7 +449,7 @@ mod test {
             let [q1] = f_build.input_wires_arr();
             f_build.finish_with_outputs([q1, q1])?;
 
-            Ok(module_builder.finish_hugr()?)
+            module_builder.finish_hugr()
         };
 
         assert_eq!(builder(), Err(BuildError::NoCopyLinear(LinearType::Qubit)));
@@ -343,6 +491,25 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn too_many_ports() -> Result<(), BuildError> {
+        // The Input node has an extra order port.
+        let max = Hugr::MAX_PORTS_PER_NODE;
+        let inputs: TypeRow = vec![BIT; max - 1].into();
+        DFGBuilder::new(inputs, type_row![])?;
+
+        let inputs: TypeRow = vec![BIT; max].into();
+        assert_matches!(
+            DFGBuilder::new(inputs, type_row![]),
+            Err(BuildError::ConstructError(HugrError::TooManyPorts {
+                node: None,
+                requested,
+                max: _,
+            })) => assert_eq!(requested, max + 1)
+        );
+        Ok(())
+    }
+
     #[test]
     fn insert_hugr() -> Result<(), BuildError> {
         // Create a simple DFG
@@ -368,6 +535,57 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn insert_hugr_as_op() -> Result<(), BuildError> {
+        let bell_pair = {
+            let mut dfg_builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+            let [q0, q1] = dfg_builder.input_wires_arr();
+            let h = dfg_builder.add_dataflow_op(LeafOp::H, [q0])?;
+            let cx = dfg_builder.add_dataflow_op(LeafOp::CX, [h.out_wire(0), q1])?;
+            dfg_builder.finish_hugr_with_outputs(cx.outputs())?
+        };
+
+        let mut module_builder = ModuleBuilder::new();
+        let mut f_build = module_builder.define_function(
+            "main",
+            Signature::new_df(type_row![QB, QB, BIT], type_row![QB, QB, BIT]),
+        )?;
+        let [q0, q1, b] = f_build.input_wires_arr();
+        let nodes = f_build.hugr().node_count();
+
+        // Mismatched wires are rejected before inserting anything.
+        assert_eq!(
+            f_build.add_hugr_as_op(bell_pair.clone(), [q0]).err(),
+            Some(BuildError::InputWiresMismatch {
+                expected: type_row![QB, QB],
+                found: type_row![QB],
+            })
+        );
+        assert_matches!(
+            f_build.add_hugr_as_op(bell_pair.clone(), [q0, b]).err(),
+            Some(BuildError::InputWiresMismatch { .. })
+        );
+        assert_matches!(
+            f_build.add_hugr_as_op(Hugr::default(), []).err(),
+            Some(BuildError::UnexpectedType { .. })
+        );
+        assert_eq!(f_build.hugr().node_count(), nodes);
+
+        let first = f_build.add_hugr_as_op(bell_pair.clone(), [q0, q1])?;
+        let second = f_build.add_hugr_as_op(bell_pair, first.outputs())?;
+        let [q0, q1] = second.outputs_arr();
+        f_build.finish_with_outputs([q0, q1, b])?;
+
+        let hugr = module_builder.finish_hugr()?;
+        assert_eq!(
+            hugr.nodes()
+                .filter(|&n| hugr.get_optype(n).tag() == OpTag::Dfg)
+                .count(),
+            2
+        );
+        Ok(())
+    }
+
     #[test]
     fn lift_node() -> Result<(), BuildError> {
         let mut module_builder = ModuleBuilder::new();
@@ -437,4 +655,145 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn abandoned_sub_builder() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, NAT], type_row![QB, NAT])?;
+        let [q, n] = builder.input_wires_arr();
+        let node_count = builder.hugr().node_count();
+        let n_children = |b: &DFGBuilder<Hugr>| b.hugr().children(b.container_node()).count();
+        let children = n_children(&builder);
+        let signature = Signature::new_df(type_row![QB], type_row![QB]);
+
+        // A nested DFG whose inputs cannot be wired is not added.
+        let bad_wire = Wire::new(q.node(), crate::Port::new_outgoing(5));
+        assert_matches!(
+            builder.dfg_builder(signature.clone(), [bad_wire]),
+            Err(BuildError::WireNotFound(w)) => assert_eq!(w, bad_wire)
+        );
+        assert_eq!(builder.hugr().node_count(), node_count);
+
+        // Dropping a nested DFG after an error detaches everything added to it.
+        let stale = {
+            let mut nested = builder.dfg_builder(signature.clone(), [q])?;
+            let [inner_q] = nested.input_wires_arr();
+            let h = nested.add_dataflow_op(LeafOp::H, [inner_q])?;
+            assert_matches!(
+                nested.add_dataflow_op(LeafOp::X, [inner_q]).err(),
+                Some(BuildError::NoCopyLinear(_))
+            );
+            h.out_wire(0)
+        };
+        assert_eq!(n_children(&builder), children);
+        assert_matches!(
+            builder.add_dataflow_op(LeafOp::X, [stale]).err(),
+            Some(BuildError::WireNotFound(w)) => assert_eq!(w, stale)
+        );
+        builder
+            .dfg_builder(Signature::new_df(type_row![NAT], type_row![NAT]), [n])?
+            .abandon();
+        assert_eq!(n_children(&builder), children);
+
+        // The detached nodes keep their indices, so the wire stays stale once
+        // more nodes are added.
+        let [n] = builder
+            .add_dataflow_op(LeafOp::Noop { ty: NAT }, [n])?
+            .outputs_arr();
+        assert_matches!(
+            builder.add_dataflow_op(LeafOp::X, [stale]).err(),
+            Some(BuildError::WireNotFound(w)) => assert_eq!(w, stale)
+        );
+
+        // The qubit can be consumed by a replacement.
+        let nested = {
+            let mut nested = builder.dfg_builder(signature, [q])?;
+            let [inner_q] = nested.input_wires_arr();
+            let h = nested.add_dataflow_op(LeafOp::H, [inner_q])?;
+            nested.finish_with_outputs(h.outputs())?
+        };
+        let hugr = builder.finish_hugr_with_outputs([nested.out_wire(0), n])?;
+        assert_eq!(hugr.node_count(), node_count + 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wire_out_of_scope() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, NAT], type_row![QB, NAT])?;
+        let [q, n] = builder.input_wires_arr();
+        let (nested, inner_q, inner_n) = {
+            let mut nested = builder.dfg_builder(
+                Signature::new_df(type_row![QB, NAT], type_row![QB, NAT]),
+                [q, n],
+            )?;
+            let [inner_q, inner_n] = nested.input_wires_arr();
+            let h = nested.add_dataflow_op(LeafOp::H, [inner_q])?;
+            let nested = nested.finish_with_outputs([h.out_wire(0), inner_n])?;
+            (nested, h.out_wire(0), inner_n)
+        };
+
+        for (ty, wire) in [(QB, inner_q), (NAT, inner_n)] {
+            assert_matches!(
+                builder.add_dataflow_op(LeafOp::Noop { ty }, [wire]).err(),
+                Some(BuildError::WireOutOfScope { wire: w }) => assert_eq!(w, wire)
+            );
+        }
+        // Nor are they visible from a sibling region.
+        let mut sibling = builder.dfg_builder(Signature::new_df(type_row![], type_row![QB]), [])?;
+        assert_matches!(
+            sibling.add_dataflow_op(LeafOp::H, [inner_q]).err(),
+            Some(BuildError::WireOutOfScope { wire: w }) => assert_eq!(w, inner_q)
+        );
+        sibling.abandon();
+        let hugr = builder.finish_hugr_with_outputs(nested.outputs())?;
+        assert_eq!(hugr.validate(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn append_function_io() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let mut f = module_builder
+            .define_function("f", Signature::new_df(type_row![BIT], type_row![BIT]))?;
+        let [b] = f.input_wires_arr();
+        let noop = f.add_dataflow_op(LeafOp::Noop { ty: BIT }, [b])?;
+        let [inp, _] = f.io();
+        f.add_other_wire(inp, noop.node())?;
+
+        // The number of qubits is only known while building the body.
+        let mut gates = Vec::new();
+        for _ in 0..3 {
+            let q = f.append_input(QB)?;
+            let h = f.add_dataflow_op(LeafOp::H, [q])?;
+            f.append_output(h.out_wire(0))?;
+            gates.push(h);
+        }
+        assert_eq!(f.input_wires().count(), 4);
+
+        // Linear wires cannot be copied, and the signature is left unchanged.
+        assert_matches!(
+            f.append_output(gates[0].out_wire(0)),
+            Err(BuildError::NoCopyLinear(_))
+        );
+
+        let f = f.finish_with_outputs(noop.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+        let expected = Signature::new_df(type_row![BIT, QB, QB, QB], type_row![BIT, QB, QB, QB]);
+        let OpType::FuncDefn(def) = hugr.get_optype(f.node()) else {
+            panic!("Expected a function definition.")
+        };
+        assert_eq!(def.signature, expected);
+
+        // The order edge moved to the new non-dataflow port of the Input node.
+        let order_port = hugr.get_optype(inp).other_port_index(Direction::Outgoing);
+        let linked: Vec<_> = hugr.linked_ports(inp, order_port.unwrap()).collect();
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].0, noop.node());
+        for (i, h) in gates.iter().enumerate() {
+            let links: Vec<_> = hugr.linked_ports(h.node(), Port::new_incoming(0)).collect();
+            assert_eq!(links, [(inp, Port::new_outgoing(i + 1))]);
+        }
+        Ok(())
+    }
 }
@@ -1,19 +1,15 @@
 use super::{
-    build_traits::HugrBuilder,
+    build_traits::{purge_detached, HugrBuilder, SubContainer},
     dataflow::{DFGBuilder, FunctionBuilder},
     BuildError, Container,
 };
 
-use crate::{
-    hugr::{view::HugrView, ValidationError},
-    ops,
-    types::SimpleType,
-};
+use crate::{hugr::view::HugrView, ops, types::SimpleType};
 
-use crate::ops::handle::{AliasID, FuncID, NodeHandle};
+use crate::ops::handle::{AliasID, FuncID, ModuleRootID, NodeHandle};
 use crate::ops::OpType;
 
-use crate::types::Signature;
+use crate::types::{Signature, SignatureDescription};
 
 use crate::Node;
 use smol_str::SmolStr;
@@ -21,22 +17,40 @@ use smol_str::SmolStr;
 use crate::{hugr::HugrMut, Hugr};
 
 /// Builder for a HUGR module.
+///
+/// Function names must be unique within the module, unless shadowing is
+/// enabled with [`ModuleBuilder::set_allow_shadowing`].
 #[derive(Debug, Clone, PartialEq)]
-pub struct ModuleBuilder<T>(pub(super) T);
+pub struct ModuleBuilder<T> {
+    hugr: T,
+    allow_shadowing: bool,
+}
 
 impl<T: AsMut<Hugr> + AsRef<Hugr>> Container for ModuleBuilder<T> {
     #[inline]
     fn container_node(&self) -> Node {
-        self.0.as_ref().root()
+        self.hugr.as_ref().root()
     }
 
     #[inline]
     fn hugr_mut(&mut self) -> &mut Hugr {
-        self.0.as_mut()
+        self.hugr.as_mut()
     }
 
     fn hugr(&self) -> &Hugr {
-        self.0.as_ref()
+        self.hugr.as_ref()
+    }
+
+    /// Check that no function in the module has the given name, unless
+    /// shadowing is allowed.
+    fn check_function_name(&self, name: &str) -> Result<(), BuildError> {
+        match self.hugr().function_by_name(name) {
+            Some(existing) if !self.allow_shadowing => Err(BuildError::DuplicateFunctionName {
+                name: name.to_string(),
+                existing,
+            }),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -44,7 +58,10 @@ impl ModuleBuilder<Hugr> {
     /// Begin building a new module.
     #[must_use]
     pub fn new() -> Self {
-        Self(Default::default())
+        Self {
+            hugr: Default::default(),
+            allow_shadowing: false,
+        }
     }
 }
 
@@ -55,13 +72,56 @@ impl Default for ModuleBuilder<Hugr> {
 }
 
 impl HugrBuilder for ModuleBuilder<Hugr> {
-    fn finish_hugr(self) -> Result<Hugr, ValidationError> {
-        self.0.validate()?;
-        Ok(self.0)
+    fn finish_hugr(mut self) -> Result<Hugr, BuildError> {
+        purge_detached(&mut self.hugr);
+        self.hugr.validate()?;
+        Ok(self.hugr)
+    }
+}
+
+impl SubContainer for ModuleBuilder<&mut Hugr> {
+    type ContainerHandle = ModuleRootID;
+
+    /// Finish adding to an existing module, returning a handle to its root.
+    ///
+    /// The subtrees of abandoned sub-builders are removed from the HUGR.
+    fn finish_sub_container(self) -> Result<Self::ContainerHandle, BuildError> {
+        let root = self.container_node();
+        purge_detached(self.hugr);
+        Ok(root.into())
     }
 }
 
 impl<T: AsMut<Hugr> + AsRef<Hugr>> ModuleBuilder<T> {
+    /// Continue building an existing module, adding new children to its
+    /// root. Pass a `&mut Hugr` to build into a HUGR owned elsewhere; the
+    /// returned node handles are valid in that HUGR.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the root of the HUGR is not a
+    /// [`ops::Module`].
+    pub fn with_hugr(hugr: T) -> Result<Self, BuildError> {
+        let root = hugr.as_ref().root();
+        if !matches!(hugr.as_ref().get_optype(root), OpType::Module(_)) {
+            return Err(BuildError::UnexpectedType {
+                node: root,
+                op_desc: "OpType::Module",
+            });
+        }
+        Ok(Self {
+            hugr,
+            allow_shadowing: false,
+        })
+    }
+
+    /// Allow defining or declaring functions with the name of an existing
+    /// function in the module. This is disabled by default, as it makes
+    /// [`HugrView::function_by_name`] ambiguous.
+    pub fn set_allow_shadowing(&mut self, allow: bool) {
+        self.allow_shadowing = allow;
+    }
+
     /// Replace a [`ops::FuncDecl`] with [`ops::FuncDefn`] and return a builder for
     /// the defining graph.
     ///
@@ -74,10 +134,13 @@ impl<T: AsMut<Hugr> + AsRef<Hugr>> ModuleBuilder<T> {
         f_id: &FuncID<false>,
     ) -> Result<FunctionBuilder<&mut Hugr>, BuildError> {
         let f_node = f_id.node();
-        let (signature, name) = if let OpType::FuncDecl(ops::FuncDecl { signature, name }) =
-            self.hugr().get_optype(f_node)
+        let (signature, name, signature_desc) = if let OpType::FuncDecl(ops::FuncDecl {
+            signature,
+            name,
+            signature_desc,
+        }) = self.hugr().get_optype(f_node)
         {
-            (signature.clone(), name.clone())
+            (signature.clone(), name.clone(), signature_desc.clone())
         } else {
             return Err(BuildError::UnexpectedType {
                 node: f_node,
@@ -89,8 +152,45 @@ impl<T: AsMut<Hugr> + AsRef<Hugr>> ModuleBuilder<T> {
             ops::FuncDefn {
                 name,
                 signature: signature.clone(),
+                signature_desc,
             },
-        );
+        )?;
+
+        let db = DFGBuilder::create_with_io(self.hugr_mut(), f_node, signature)?;
+        Ok(FunctionBuilder::from_dfg_builder(db))
+    }
+
+    /// Add a [`ops::FuncDefn`] node with named parameters and results, and
+    /// returns a builder to define the function body graph.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the names in `signature_desc` do
+    /// not match the inputs and outputs of `signature`, if a function with the
+    /// same name already exists and shadowing is not allowed, or if there is
+    /// an error in adding the [`ops::FuncDefn`] node.
+    pub fn define_function_named(
+        &mut self,
+        name: impl Into<String>,
+        signature: Signature,
+        signature_desc: SignatureDescription,
+    ) -> Result<FunctionBuilder<&mut Hugr>, BuildError> {
+        if signature_desc.input.len() != signature.input.len()
+            || signature_desc.output.len() != signature.output.len()
+            || signature_desc.static_input.len() > signature.static_input.len()
+        {
+            return Err(BuildError::SignatureDescriptionMismatch {
+                signature,
+                signature_desc,
+            });
+        }
+        let name = name.into();
+        self.check_function_name(&name)?;
+        let f_node = self.add_child_op(ops::FuncDefn {
+            name,
+            signature: signature.clone(),
+            signature_desc,
+        })?;
 
         let db = DFGBuilder::create_with_io(self.hugr_mut(), f_node, signature)?;
         Ok(FunctionBuilder::from_dfg_builder(db))
@@ -100,17 +200,21 @@ impl<T: AsMut<Hugr> + AsRef<Hugr>> ModuleBuilder<T> {
     ///
     /// # Errors
     ///
-    /// This function will return an error if there is an error in adding the
-    /// [`OpType::FuncDecl`] node.
+    /// This function will return an error if a function with the same name
+    /// already exists and shadowing is not allowed, or if there is an error
+    /// in adding the [`OpType::FuncDecl`] node.
     pub fn declare(
         &mut self,
         name: impl Into<String>,
         signature: Signature,
     ) -> Result<FuncID<false>, BuildError> {
+        let name = name.into();
+        self.check_function_name(&name)?;
         // TODO add param names to metadata
         let declare_n = self.add_child_op(ops::FuncDecl {
             signature,
-            name: name.into(),
+            name,
+            signature_desc: Default::default(),
         })?;
 
         Ok(declare_n.into())
@@ -167,8 +271,9 @@ mod test {
     use crate::{
         builder::{
             test::{n_identity, NAT},
-            Dataflow, DataflowSubContainer,
+            Dataflow, DataflowHugr, DataflowSubContainer,
         },
+        ops::{ConstValue, OpTag, OpTrait},
         type_row,
     };
 
@@ -191,6 +296,34 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn named_function() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let sig = Signature::new_df(type_row![NAT, NAT], type_row![NAT]);
+        let desc = SignatureDescription::new_df(["lhs".into(), "rhs".into()], ["res".into()]);
+
+        let f_build = module_builder.define_function_named("add", sig.clone(), desc.clone())?;
+        let [lhs, _] = f_build.input_wires_arr();
+        let f_id = f_build.finish_with_outputs([lhs])?;
+        let hugr = module_builder.finish_hugr()?;
+
+        assert_eq!(hugr.get_optype(f_id.node()).signature_desc(), desc);
+        let dot = hugr.dot_string();
+        for name in ["lhs: I64", "rhs: I64", "res: I64"] {
+            assert!(dot.contains(name), "{name} not in dot string");
+        }
+
+        let mut module_builder = ModuleBuilder::new();
+        let bad_desc = SignatureDescription::new_df(["lhs".into()], ["res".into()]);
+        assert_matches!(
+            module_builder
+                .define_function_named("add", sig, bad_desc)
+                .map(|_| ()),
+            Err(BuildError::SignatureDescriptionMismatch { .. })
+        );
+        Ok(())
+    }
+
     #[test]
     fn simple_alias() -> Result<(), BuildError> {
         let build_result = {
@@ -232,4 +365,113 @@ mod test {
         assert_matches!(build_result, Ok(_));
         Ok(())
     }
+
+    #[test]
+    fn extend_existing_module() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        n_identity(module_builder.define_function("id", sig.clone())?)?;
+        let hugr = module_builder.finish_hugr()?;
+
+        let mut hugr: Hugr = serde_json::from_str(&serde_json::to_string(&hugr).unwrap()).unwrap();
+        let id_node = hugr
+            .children(hugr.root())
+            .find(|&n| hugr.get_optype(n).tag() == OpTag::FuncDefn)
+            .unwrap();
+        let id_func: FuncID<true> = id_node.into();
+
+        let mut module_builder = ModuleBuilder::with_hugr(&mut hugr)?;
+        let mut f_build = module_builder.define_function("main", sig)?;
+        let call = f_build.call(&id_func, f_build.input_wires())?;
+        let main = f_build.finish_with_outputs(call.outputs())?;
+        let root = module_builder.finish_sub_container()?;
+
+        assert_eq!(root.node(), hugr.root());
+        assert_eq!(hugr.get_parent(main.node()), Some(hugr.root()));
+        assert_eq!(hugr.input_neighbours(call.node()).last(), Some(id_node));
+        assert_eq!(hugr.validate(), Ok(()));
+
+        let dfg_builder = DFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let wires = dfg_builder.input_wires();
+        let mut dfg = dfg_builder.finish_hugr_with_outputs(wires)?;
+        assert_matches!(
+            ModuleBuilder::with_hugr(&mut dfg),
+            Err(BuildError::UnexpectedType { .. })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn abandon_in_existing_module() -> Result<(), BuildError> {
+        let mut hugr = ModuleBuilder::new().finish_hugr()?;
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+
+        let mut module_builder = ModuleBuilder::with_hugr(&mut hugr)?;
+        module_builder
+            .define_function("abandoned", sig.clone())?
+            .abandon();
+        n_identity(module_builder.define_function("id", sig)?)?;
+        module_builder.finish_sub_container()?;
+
+        assert_eq!(hugr.node_count(), 4);
+        assert_eq!(hugr.validate(), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_function_name() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let f_id = n_identity(module_builder.define_function("f", sig.clone())?)?;
+
+        let duplicate_error = BuildError::DuplicateFunctionName {
+            name: "f".to_string(),
+            existing: f_id.node(),
+        };
+        assert_eq!(
+            module_builder.define_function("f", sig.clone()).map(|_| ()),
+            Err(duplicate_error.clone())
+        );
+        assert_eq!(
+            module_builder.declare("f", sig.clone()).map(|_| ()),
+            Err(duplicate_error)
+        );
+
+        module_builder.set_allow_shadowing(true);
+        let decl = module_builder.declare("f", sig)?;
+        let hugr = module_builder.finish_hugr()?;
+        assert_eq!(hugr.function_by_name("f"), Some(f_id.node()));
+        assert_eq!(hugr.children(hugr.root()).next_back(), Some(decl.node()));
+        Ok(())
+    }
+
+    #[test]
+    fn shared_module_constant() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let c = module_builder.add_constant(ConstValue::i64(3))?;
+        let sig = Signature::new_df(type_row![], type_row![NAT, NAT]);
+
+        // Each load adds a new LoadConstant node.
+        let mut f_build = module_builder.define_function("f", sig.clone())?;
+        let w0 = f_build.load_const(&c)?;
+        let w1 = f_build.load_const(&c)?;
+        assert_ne!(w0.node(), w1.node());
+        let local = f_build.add_constant(ConstValue::i64(4))?;
+        f_build.finish_with_outputs([w0, w1])?;
+
+        let mut g_build = module_builder.define_function("g", sig)?;
+        let w0 = g_build.load_const_cached(&c)?;
+        let w1 = g_build.load_const_cached(&c)?;
+        assert_eq!(w0, w1);
+        assert_matches!(
+            g_build.load_const(&local),
+            Err(BuildError::ConstOutOfScope { constant, .. }) => assert_eq!(constant, local.node())
+        );
+        g_build.finish_with_outputs([w0, w1])?;
+
+        let hugr = module_builder.finish_hugr()?;
+        assert_eq!(hugr.nodes_named("LoadConstant").count(), 3);
+        assert_eq!(hugr.output_neighbours(c.node()).count(), 3);
+        Ok(())
+    }
 }
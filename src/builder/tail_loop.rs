@@ -1,7 +1,7 @@
-use crate::ops::{self, OpType};
+use crate::ops::{self, DataflowParent, OpType};
 
 use crate::hugr::view::HugrView;
-use crate::types::{Signature, TypeRow};
+use crate::types::TypeRow;
 use crate::{Hugr, Node};
 
 use super::build_traits::SubContainer;
@@ -20,8 +20,7 @@ impl<B: AsMut<Hugr> + AsRef<Hugr>> TailLoopBuilder<B> {
         loop_node: Node,
         tail_loop: &ops::TailLoop,
     ) -> Result<Self, BuildError> {
-        let signature = Signature::new_df(tail_loop.body_input_row(), tail_loop.body_output_row());
-        let dfg_build = DFGBuilder::create_with_io(base, loop_node, signature)?;
+        let dfg_build = DFGBuilder::create_with_io(base, loop_node, tail_loop.inner_signature())?;
 
         Ok(TailLoopBuilder::from_dfg_builder(dfg_build))
     }
@@ -96,8 +95,7 @@ mod test {
             test::{BIT, NAT},
             DataflowSubContainer, HugrBuilder, ModuleBuilder,
         },
-        hugr::ValidationError,
-        ops::ConstValue,
+        ops::{ConstValue, OpTrait},
         type_row,
         types::Signature,
         Hugr,
@@ -106,7 +104,7 @@ mod test {
     use super::*;
     #[test]
     fn basic_loop() -> Result<(), BuildError> {
-        let build_result: Result<Hugr, ValidationError> = {
+        let build_result: Result<Hugr, BuildError> = {
             let mut loop_b = TailLoopBuilder::new(vec![], vec![BIT], type_row![NAT])?;
             let [i1] = loop_b.input_wires_arr();
             let const_wire = loop_b.add_load_const(ConstValue::i64(1))?;
@@ -116,7 +114,21 @@ mod test {
             loop_b.finish_hugr()
         };
 
-        assert_matches!(build_result, Ok(_));
+        let hugr = build_result?;
+        let body_signature = hugr.tail_loop_body_signature(hugr.root()).unwrap();
+        let [input, output] = {
+            let mut children = hugr.children(hugr.root());
+            [children.next().unwrap(), children.next().unwrap()]
+        };
+        assert_eq!(
+            body_signature.input,
+            hugr.get_optype(input).signature().output
+        );
+        assert_eq!(
+            body_signature.output,
+            hugr.get_optype(output).signature().input
+        );
+        assert_eq!(hugr.tail_loop_body_signature(input), None);
         Ok(())
     }
 
@@ -0,0 +1,292 @@
+//! Typed wires for building circuits.
+//!
+//! [`QWire`] and [`CWire`] wrap a [`Wire`] carrying a qubit or a classical
+//! value respectively, so mixing them up in builder code is a compile-time
+//! error. The [`TypedDataflow`] methods add the built-in gates on typed wires,
+//! and the wrappers convert back into untyped wires for use with the rest of
+//! the builder API.
+//!
+//! ```compile_fail
+//! use hugr::builder::typed::{QWire, TypedDataflow};
+//! use hugr::builder::{BuildError, DFGBuilder};
+//!
+//! fn cx_on_bit(builder: &mut DFGBuilder<hugr::Hugr>, q: QWire) -> Result<(), BuildError> {
+//!     let (q, bit) = builder.add_measure(q)?;
+//!     // A measurement result cannot be used as a qubit.
+//!     builder.add_cx(q, bit)?;
+//!     Ok(())
+//! }
+//! ```
+
+use crate::ops::handle::DataflowOpID;
+use crate::ops::{LeafOp, OpTrait};
+use crate::types::{LinearType, SimpleType, TypeRow};
+use crate::Wire;
+
+use super::{BuildError, BuildHandle, Dataflow};
+
+/// A wire carrying a qubit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct QWire(Wire);
+
+/// A wire carrying a classical value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CWire(Wire);
+
+impl From<QWire> for Wire {
+    fn from(wire: QWire) -> Self {
+        wire.0
+    }
+}
+
+impl From<CWire> for Wire {
+    fn from(wire: CWire) -> Self {
+        wire.0
+    }
+}
+
+/// Typed operations for dataflow builders, covering the built-in gate set.
+pub trait TypedDataflow: Dataflow {
+    /// Check that `wire` carries a qubit.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the wire is not found or carries
+    /// another type.
+    fn qwire(&self, wire: Wire) -> Result<QWire, BuildError> {
+        match self.get_wire_type(wire)? {
+            SimpleType::Linear(LinearType::Qubit) => Ok(QWire(wire)),
+            typ => Err(BuildError::WireKindMismatch { wire, typ }),
+        }
+    }
+
+    /// Check that `wire` carries a classical value.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the wire is not found or carries
+    /// a linear value.
+    fn cwire(&self, wire: Wire) -> Result<CWire, BuildError> {
+        match self.get_wire_type(wire)? {
+            SimpleType::Classic(_) => Ok(CWire(wire)),
+            typ => Err(BuildError::WireKindMismatch { wire, typ }),
+        }
+    }
+
+    /// The input wires of the dataflow graph, split into qubit and classical
+    /// wires.
+    fn typed_input_wires(&self) -> (Vec<QWire>, Vec<CWire>) {
+        let (mut qubits, mut classical) = (Vec::new(), Vec::new());
+        for wire in self.input_wires() {
+            match self.get_wire_type(wire) {
+                Ok(SimpleType::Linear(LinearType::Qubit)) => qubits.push(QWire(wire)),
+                Ok(SimpleType::Classic(_)) => classical.push(CWire(wire)),
+                _ => {}
+            }
+        }
+        (qubits, classical)
+    }
+
+    /// Add a [`LeafOp::H`] gate.
+    fn add_h(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::H, q)
+    }
+
+    /// Add a [`LeafOp::T`] gate.
+    fn add_t(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::T, q)
+    }
+
+    /// Add a [`LeafOp::S`] gate.
+    fn add_s(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::S, q)
+    }
+
+    /// Add a [`LeafOp::X`] gate.
+    fn add_x(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::X, q)
+    }
+
+    /// Add a [`LeafOp::Y`] gate.
+    fn add_y(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::Y, q)
+    }
+
+    /// Add a [`LeafOp::Z`] gate.
+    fn add_z(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::Z, q)
+    }
+
+    /// Add a [`LeafOp::Tadj`] gate.
+    fn add_tadj(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::Tadj, q)
+    }
+
+    /// Add a [`LeafOp::Sadj`] gate.
+    fn add_sadj(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::Sadj, q)
+    }
+
+    /// Add a [`LeafOp::Reset`] operation.
+    fn add_reset(&mut self, q: QWire) -> Result<QWire, BuildError> {
+        add_1q_gate(self, LeafOp::Reset, q)
+    }
+
+    /// Add a [`LeafOp::CX`] gate, with `control` and `target` qubits.
+    fn add_cx(&mut self, control: QWire, target: QWire) -> Result<(QWire, QWire), BuildError> {
+        let [control, target] = self
+            .add_dataflow_op(LeafOp::CX, [control.0, target.0])?
+            .outputs_arr();
+        Ok((QWire(control), QWire(target)))
+    }
+
+    /// Add a [`LeafOp::ZZMax`] gate.
+    fn add_zzmax(&mut self, a: QWire, b: QWire) -> Result<(QWire, QWire), BuildError> {
+        let [a, b] = self
+            .add_dataflow_op(LeafOp::ZZMax, [a.0, b.0])?
+            .outputs_arr();
+        Ok((QWire(a), QWire(b)))
+    }
+
+    /// Add a [`LeafOp::RzF64`] rotation by a float `angle`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `angle` is not a float, or if
+    /// there is an error when adding the node.
+    fn add_rz_f64(&mut self, q: QWire, angle: CWire) -> Result<QWire, BuildError> {
+        let [q] = add_classical_op(self, LeafOp::RzF64, [q.0, angle.0])?.outputs_arr();
+        Ok(QWire(q))
+    }
+
+    /// Add a [`LeafOp::Rz`] rotation by an `angle`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `angle` is not an angle, or if
+    /// there is an error when adding the node.
+    fn add_rz(&mut self, q: QWire, angle: CWire) -> Result<QWire, BuildError> {
+        let [q] = add_classical_op(self, LeafOp::Rz, [q.0, angle.0])?.outputs_arr();
+        Ok(QWire(q))
+    }
+
+    /// Add a [`LeafOp::Measure`] operation, returning the qubit and the
+    /// measurement result.
+    fn add_measure(&mut self, q: QWire) -> Result<(QWire, CWire), BuildError> {
+        let [q, bit] = self.add_dataflow_op(LeafOp::Measure, [q.0])?.outputs_arr();
+        Ok((QWire(q), CWire(bit)))
+    }
+
+    /// Add a [`LeafOp::MeasureDestructive`] operation, consuming the qubit and
+    /// returning the measurement result.
+    fn add_measure_destructive(&mut self, q: QWire) -> Result<CWire, BuildError> {
+        let [bit] = self
+            .add_dataflow_op(LeafOp::MeasureDestructive, [q.0])?
+            .outputs_arr();
+        Ok(CWire(bit))
+    }
+
+    /// Add a [`LeafOp::Xor`] operation on two bits.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `a` or `b` is not a bit, or if
+    /// there is an error when adding the node.
+    fn add_xor(&mut self, a: CWire, b: CWire) -> Result<CWire, BuildError> {
+        let [out] = add_classical_op(self, LeafOp::Xor, [a.0, b.0])?.outputs_arr();
+        Ok(CWire(out))
+    }
+}
+
+impl<T: Dataflow + ?Sized> TypedDataflow for T {}
+
+/// Add a single-qubit gate of the built-in gate set.
+fn add_1q_gate<T: Dataflow + ?Sized>(
+    builder: &mut T,
+    op: LeafOp,
+    q: QWire,
+) -> Result<QWire, BuildError> {
+    let [q] = builder.add_dataflow_op(op, [q.0])?.outputs_arr();
+    Ok(QWire(q))
+}
+
+/// Add an operation with classical inputs, checking the types of the wires as
+/// a [`CWire`] may carry any classical value.
+fn add_classical_op<T: Dataflow + ?Sized>(
+    builder: &mut T,
+    op: LeafOp,
+    inputs: [Wire; 2],
+) -> Result<BuildHandle<DataflowOpID>, BuildError> {
+    let expected = op.signature().input;
+    let found: TypeRow = inputs
+        .iter()
+        .map(|&wire| builder.get_wire_type(wire))
+        .collect::<Result<Vec<_>, _>>()?
+        .into();
+    if found != expected {
+        return Err(BuildError::InputWiresMismatch { expected, found });
+    }
+    builder.add_dataflow_op(op, inputs)
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::test::{BIT, F64, QB};
+    use crate::builder::{DFGBuilder, DataflowHugr};
+    use crate::type_row;
+
+    #[test]
+    fn typed_circuit() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB, F64], type_row![QB, QB, BIT])?;
+        let (qubits, classical) = builder.typed_input_wires();
+        let ([q0, q1], [angle]) = (qubits.try_into().unwrap(), classical.try_into().unwrap());
+        let q0 = builder.add_h(q0)?;
+        let (q0, q1) = builder.add_cx(q0, q1)?;
+        let q1 = builder.add_rz_f64(q1, angle)?;
+        let (q0, b0) = builder.add_measure(q0)?;
+        let (q1, b1) = builder.add_measure(q1)?;
+        let bit = builder.add_xor(b0, b1)?;
+        let hugr = builder.finish_hugr_with_outputs([q0.into(), q1.into(), bit.into()]);
+        assert_matches!(hugr, Ok(_));
+        Ok(())
+    }
+
+    #[test]
+    fn check_wire_kind() -> Result<(), BuildError> {
+        let builder = DFGBuilder::new(type_row![QB, BIT], type_row![QB, BIT])?;
+        let [q, b] = builder.input_wires_arr();
+        assert_eq!(builder.qwire(q), Ok(QWire(q)));
+        assert_eq!(builder.cwire(b), Ok(CWire(b)));
+        assert_eq!(
+            builder.qwire(b),
+            Err(BuildError::WireKindMismatch { wire: b, typ: BIT })
+        );
+        assert_eq!(
+            builder.cwire(q),
+            Err(BuildError::WireKindMismatch { wire: q, typ: QB })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn classical_input_types() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, BIT, F64], type_row![QB, BIT, F64])?;
+        let (qubits, classical) = builder.typed_input_wires();
+        let ([q], [bit, angle]) = (qubits.try_into().unwrap(), classical.try_into().unwrap());
+        assert_matches!(
+            builder.add_rz_f64(q, bit),
+            Err(BuildError::InputWiresMismatch { .. })
+        );
+        assert_matches!(
+            builder.add_xor(bit, angle),
+            Err(BuildError::InputWiresMismatch { .. })
+        );
+        let q = builder.add_rz_f64(q, angle)?;
+        let hugr = builder.finish_hugr_with_outputs([q.into(), bit.into(), angle.into()]);
+        assert_matches!(hugr, Ok(_));
+        Ok(())
+    }
+}
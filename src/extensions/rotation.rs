@@ -1,21 +1,17 @@
 #![allow(missing_docs)]
 //! This is an experiment, it is probably already outdated.
 
-use std::ops::{Add, Div, Mul, Neg, Sub};
-
-use cgmath::num_traits::ToPrimitive;
-use num_rational::Rational64;
 use smol_str::SmolStr;
 use std::collections::HashMap;
 
-#[cfg(feature = "pyo3")]
-use pyo3::prelude::*;
-
 use crate::ops::constant::CustomConst;
+use crate::ops::ConstValue;
 use crate::resource::{CustomSignatureFunc, OpDef, ResourceSet, SignatureError, TypeDef};
-use crate::types::{type_param::TypeArg, ClassicType, CustomType, SimpleType, TypeRow};
+use crate::types::{angle, type_param::TypeArg, ClassicType, CustomType, SimpleType, TypeRow};
 use crate::Resource;
 
+pub use crate::types::angle::{AngleValue, Rational};
+
 pub const fn resource_id() -> SmolStr {
     SmolStr::new_inline("rotations")
 }
@@ -27,15 +23,20 @@ pub fn resource() -> Resource {
     resource.add_type(Type::Angle.type_def());
     resource.add_type(Type::Quaternion.type_def());
 
-    resource
-        .add_op(OpDef::new_with_custom_sig(
-            "AngleAdd".into(),
-            "".into(),
-            vec![],
-            HashMap::default(),
-            AngleAdd,
-        ))
-        .unwrap();
+    let mut angle_add = OpDef::new_with_custom_sig(
+        "AngleAdd".into(),
+        "".into(),
+        vec![],
+        HashMap::default(),
+        AngleAdd,
+    );
+    angle_add.set_constant_folder(|consts| match consts {
+        [ConstValue::Angle(a), ConstValue::Angle(b)] => {
+            Some(vec![ConstValue::Angle((a + b).canonicalize())])
+        }
+        _ => None,
+    });
+    resource.add_op(angle_add).unwrap();
     resource
 }
 
@@ -62,6 +63,7 @@ impl Type {
         TypeDef {
             name: self.name(),
             args: vec![],
+            linear: false,
         }
     }
 }
@@ -110,11 +112,12 @@ impl CustomSignatureFunc for AngleAdd {
         _arg_values: &[TypeArg],
         _misc: &HashMap<String, serde_yaml::Value>,
     ) -> Result<(TypeRow, TypeRow, ResourceSet), SignatureError> {
-        let t: TypeRow = vec![SimpleType::Classic(
-            Into::<CustomType>::into(Type::Angle).into(),
-        )]
-        .into();
-        Ok((t.clone(), t, ResourceSet::default()))
+        let t = SimpleType::Classic(angle::ANGLE);
+        Ok((
+            vec![t.clone(), t.clone()].into(),
+            vec![t].into(),
+            ResourceSet::singleton(&resource_id()),
+        ))
     }
 }
 
@@ -154,143 +157,3 @@ impl CustomSignatureFunc for AngleAdd {
 //                 ],
 //                 [Type::Quat64],
 //             ),
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
-#[cfg_attr(feature = "pyo3", pyclass(name = "Rational"))]
-pub struct Rational(pub Rational64);
-
-impl From<Rational64> for Rational {
-    fn from(r: Rational64) -> Self {
-        Self(r)
-    }
-}
-
-// angle is contained value * pi in radians
-#[derive(Clone, PartialEq, Debug, Copy, serde::Serialize, serde::Deserialize)]
-#[cfg_attr(feature = "pyo3", derive(FromPyObject))]
-pub enum AngleValue {
-    F64(f64),
-    Rational(Rational),
-}
-
-impl AngleValue {
-    fn binary_op<F: FnOnce(f64, f64) -> f64, G: FnOnce(Rational64, Rational64) -> Rational64>(
-        self,
-        rhs: Self,
-        opf: F,
-        opr: G,
-    ) -> Self {
-        match (self, rhs) {
-            (AngleValue::F64(x), AngleValue::F64(y)) => AngleValue::F64(opf(x, y)),
-            (AngleValue::F64(x), AngleValue::Rational(y))
-            | (AngleValue::Rational(y), AngleValue::F64(x)) => {
-                AngleValue::F64(opf(x, y.0.to_f64().unwrap()))
-            }
-            (AngleValue::Rational(x), AngleValue::Rational(y)) => {
-                AngleValue::Rational(Rational(opr(x.0, y.0)))
-            }
-        }
-    }
-
-    fn unary_op<F: FnOnce(f64) -> f64, G: FnOnce(Rational64) -> Rational64>(
-        self,
-        opf: F,
-        opr: G,
-    ) -> Self {
-        match self {
-            AngleValue::F64(x) => AngleValue::F64(opf(x)),
-            AngleValue::Rational(x) => AngleValue::Rational(Rational(opr(x.0))),
-        }
-    }
-
-    pub fn to_f64(&self) -> f64 {
-        match self {
-            AngleValue::F64(x) => *x,
-            AngleValue::Rational(x) => x.0.to_f64().expect("Floating point conversion error."),
-        }
-    }
-
-    pub fn radians(&self) -> f64 {
-        self.to_f64() * std::f64::consts::PI
-    }
-}
-
-impl Add for AngleValue {
-    type Output = AngleValue;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        self.binary_op(rhs, |x, y| x + y, |x, y| x + y)
-    }
-}
-
-impl Sub for AngleValue {
-    type Output = AngleValue;
-
-    fn sub(self, rhs: Self) -> Self::Output {
-        self.binary_op(rhs, |x, y| x - y, |x, y| x - y)
-    }
-}
-
-impl Mul for AngleValue {
-    type Output = AngleValue;
-
-    fn mul(self, rhs: Self) -> Self::Output {
-        self.binary_op(rhs, |x, y| x * y, |x, y| x * y)
-    }
-}
-
-impl Div for AngleValue {
-    type Output = AngleValue;
-
-    fn div(self, rhs: Self) -> Self::Output {
-        self.binary_op(rhs, |x, y| x / y, |x, y| x / y)
-    }
-}
-
-impl Neg for AngleValue {
-    type Output = AngleValue;
-
-    fn neg(self) -> Self::Output {
-        self.unary_op(|x| -x, |x| -x)
-    }
-}
-
-impl Add for &AngleValue {
-    type Output = AngleValue;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        self.binary_op(*rhs, |x, y| x + y, |x, y| x + y)
-    }
-}
-
-impl Sub for &AngleValue {
-    type Output = AngleValue;
-
-    fn sub(self, rhs: Self) -> Self::Output {
-        self.binary_op(*rhs, |x, y| x - y, |x, y| x - y)
-    }
-}
-
-impl Mul for &AngleValue {
-    type Output = AngleValue;
-
-    fn mul(self, rhs: Self) -> Self::Output {
-        self.binary_op(*rhs, |x, y| x * y, |x, y| x * y)
-    }
-}
-
-impl Div for &AngleValue {
-    type Output = AngleValue;
-
-    fn div(self, rhs: Self) -> Self::Output {
-        self.binary_op(*rhs, |x, y| x / y, |x, y| x / y)
-    }
-}
-
-impl Neg for &AngleValue {
-    type Output = AngleValue;
-
-    fn neg(self) -> Self::Output {
-        self.unary_op(|x| -x, |x| -x)
-    }
-}
@@ -2,18 +2,30 @@
 
 mod hugrmut;
 
+pub mod analysis;
+pub mod frozen;
+pub mod link;
+pub mod lint;
 pub mod region;
 pub mod rewrite;
 pub mod serialize;
+pub mod stage;
+pub mod summary;
 pub mod typecheck;
 pub mod validate;
 pub mod view;
+pub mod weighted;
 
 use std::collections::VecDeque;
 use std::iter;
 
+pub use self::frozen::FrozenHugr;
 pub(crate) use self::hugrmut::HugrMut;
-pub use self::validate::ValidationError;
+pub use self::stage::CompilationStage;
+pub use self::summary::HugrSummary;
+pub use self::validate::{
+    ErrorCategory, PortLayout, TypeValidationError, ValidationError, ValidationOptions,
+};
 
 use derive_more::From;
 pub use rewrite::{Rewrite, SimpleReplacement, SimpleReplacementError};
@@ -21,13 +33,19 @@ pub use rewrite::{Rewrite, SimpleReplacement, SimpleReplacementError};
 use portgraph::dot::{DotFormat, EdgeStyle, NodeStyle, PortStyle};
 use portgraph::multiportgraph::MultiPortGraph;
 use portgraph::{Hierarchy, LinkView, PortMut, PortView, UnmanagedDenseMap};
+use smol_str::SmolStr;
 use thiserror::Error;
 
-pub use self::view::HugrView;
-use crate::ops::{OpName, OpType};
+pub use self::view::{HugrEdge, HugrView};
+use crate::ops::{OpName, OpTag, OpTrait, OpType};
 use crate::types::EdgeKind;
 
 /// The Hugr data structure.
+///
+/// A `Hugr` is `Send + Sync`, so an immutable HUGR can be inspected from
+/// multiple threads at once. Any state added to it must preserve this, and
+/// user callbacks stored in operation definitions are required to be
+/// `Send + Sync`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Hugr {
     /// The graph encoding the adjacency structure of the HUGR.
@@ -84,7 +102,21 @@ pub struct Node {
 }
 
 /// A handle to a port for a node in the HUGR.
-#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash, Default, Debug, From)]
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    PartialOrd,
+    Eq,
+    Ord,
+    Hash,
+    Default,
+    Debug,
+    From,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(transparent)]
 pub struct Port {
     offset: portgraph::PortOffset,
 }
@@ -94,39 +126,144 @@ pub type Direction = portgraph::Direction;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// A DataFlow wire, defined by a Value-kind output port of a node
+///
+/// Wires are compared by their source port, so a wire through a
+/// [`LeafOp::Noop`](crate::ops::LeafOp::Noop) differs from the wire it copies.
+/// Use [`HugrView::resolve_wire`] to compare the values they carry. The wires
+/// handed out by the builders always refer to existing output ports.
 // Stores node and offset to output port
 pub struct Wire(Node, usize);
 
 /// Public API for HUGRs.
 impl Hugr {
+    /// The maximum number of ports of a node, counting both directions.
+    pub const MAX_PORTS_PER_NODE: usize = u16::MAX as usize - 1;
+
+    /// The maximum number of nodes in a HUGR.
+    pub const MAX_NODES: usize = (u32::MAX / 2) as usize - 1;
+
     /// Applies a rewrite to the graph.
     pub fn apply_rewrite<E>(&mut self, rw: impl Rewrite<Error = E>) -> Result<(), E> {
         rw.apply(self)
     }
 
+    /// Returns the number of nodes and ports the Hugr can hold without
+    /// reallocating.
+    pub fn capacity(&self) -> (usize, usize) {
+        (self.graph.node_capacity(), self.graph.port_capacity())
+    }
+
+    /// Reserves capacity for at least `nodes` more nodes and `ports` more
+    /// ports to be inserted in the Hugr.
+    pub fn reserve(&mut self, nodes: usize, ports: usize) {
+        self.graph.reserve(nodes, ports);
+        let capacity = self.graph.node_capacity();
+        self.hierarchy.ensure_capacity(capacity);
+        self.op_types.ensure_capacity(capacity);
+        self.metadata.ensure_capacity(capacity);
+    }
+
+    /// Removes the unconnected non-dataflow ports left at the end of every
+    /// node's port lists, beyond those required by its operation, such as
+    /// order ports left behind by removed edges. This may invalidate the
+    /// `PortIndex` of the nodes.
+    ///
+    /// Returns the total number of ports removed.
+    pub fn purge_all_dangling_other_ports(&mut self) -> usize {
+        let nodes: Vec<Node> = self.nodes().collect();
+        nodes
+            .into_iter()
+            .map(|node| self.purge_dangling_other_ports(node))
+            .sum()
+    }
+
+    /// Apply `f` to the operation of every node, giving mutable access to it.
+    ///
+    /// This is intended for edits of the operations' payloads which keep their
+    /// signatures, such as renaming custom operations. The edits must not
+    /// change the number of ports of the operations.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HugrError::PortCountChanged`] if `f` changes the number of
+    /// ports of an operation. That edit is kept, and the remaining nodes are
+    /// not visited.
+    pub fn map_ops(&mut self, f: impl FnMut(Node, &mut OpType)) -> Result<(), HugrError> {
+        let nodes: Vec<Node> = self.nodes().collect();
+        self.map_nodes_ops(nodes, f)
+    }
+
+    /// Apply `f` to the operation of every descendant of `parent`, like
+    /// [`Hugr::map_ops`]. The operation of `parent` itself is left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HugrError::PortCountChanged`] if `f` changes the number of
+    /// ports of an operation.
+    pub fn map_region_ops(
+        &mut self,
+        parent: Node,
+        f: impl FnMut(Node, &mut OpType),
+    ) -> Result<(), HugrError> {
+        let mut nodes = Vec::new();
+        let mut queue: VecDeque<Node> = self.children(parent).collect();
+        while let Some(node) = queue.pop_front() {
+            queue.extend(self.children(node));
+            nodes.push(node);
+        }
+        self.map_nodes_ops(nodes, f)
+    }
+
+    /// Releases the memory left unused by the growth strategy of the Hugr and
+    /// by removed nodes.
+    ///
+    /// The nodes are reordered canonically and compacted, so any [`Node`]
+    /// handle obtained before the call is invalidated.
+    pub fn shrink_to_fit(&mut self) {
+        self.canonicalize_nodes(|_, _| {});
+        self.purge_all_dangling_other_ports();
+        self.graph.compact_ports(|_, _| {});
+        self.graph.shrink_to_fit();
+        let node_count = self.node_count();
+        self.hierarchy.shrink_to(node_count);
+        self.op_types.shrink_to(node_count);
+        self.metadata.shrink_to(node_count);
+    }
+
     /// Return dot string showing underlying graph and hierarchy side by side.
     pub fn dot_string(&self) -> String {
-        self.graph
+        self.dot_string_with(&DotOptions::default())
+    }
+
+    /// Return dot string showing underlying graph and hierarchy side by side,
+    /// with the given formatting options.
+    pub fn dot_string_with(&self, options: &DotOptions) -> String {
+        let dot = self
+            .graph
             .dot_format()
             .with_hierarchy(&self.hierarchy)
             .with_node_style(|n| {
-                NodeStyle::Box(format!(
+                NodeStyle::Box(dot_label(&format!(
                     "({ni}) {name}",
                     ni = n.index(),
                     name = self.op_types[n].name()
-                ))
+                )))
             })
             .with_port_style(|port| {
                 let node = self.graph.port_node(port).unwrap();
                 let optype = self.op_types.get(node);
                 let offset = self.graph.port_offset(port).unwrap();
+                let label = |ty: &dyn std::fmt::Display| {
+                    let name = self.port_name(node.into(), offset.into());
+                    let label = match name.is_empty() {
+                        true => format!("{}", ty),
+                        false => format!("{}: {}", name, ty),
+                    };
+                    PortStyle::new(dot_label(&label))
+                };
                 match optype.port_kind(offset).unwrap() {
-                    EdgeKind::Static(ty) => {
-                        PortStyle::new(html_escape::encode_text(&format!("{}", ty)))
-                    }
-                    EdgeKind::Value(ty) => {
-                        PortStyle::new(html_escape::encode_text(&format!("{}", ty)))
-                    }
+                    EdgeKind::Static(ty) => label(&ty),
+                    EdgeKind::Value(ty) => label(&ty),
                     EdgeKind::StateOrder => match self.graph.port_links(port).count() > 0 {
                         true => PortStyle::text("", false),
                         false => PortStyle::Hidden,
@@ -143,20 +280,145 @@ impl Hugr {
                 if self.hierarchy.parent(src_node) != self.hierarchy.parent(tgt_node) {
                     EdgeStyle::Dashed
                 } else if src_optype.port_kind(src_offset) == Some(EdgeKind::StateOrder) {
-                    EdgeStyle::Dotted
+                    match options.circuit_layout {
+                        // The style is quoted by portgraph, so the extra
+                        // attribute is spliced in between the quotes.
+                        true => EdgeStyle::Custom("dotted\" constraint=\"false".into()),
+                        false => EdgeStyle::Dotted,
+                    }
                 } else {
                     EdgeStyle::Solid
                 }
             })
-            .finish()
+            .finish();
+        match options.circuit_layout {
+            true => self.add_circuit_layout(dot),
+            false => dot,
+        }
+    }
+
+    /// Add the layout hints of [`DotOptions::circuit_layout`] to a dot string.
+    fn add_circuit_layout(&self, dot: String) -> String {
+        let mut hints = String::from("rankdir=LR\n");
+        for node in self.nodes() {
+            let rank = match self.get_optype(node) {
+                OpType::Input(_) => "source",
+                OpType::Output(_) => "sink",
+                _ => continue,
+            };
+            hints.push_str(&format!("{{rank={rank}; {}}}\n", node.index.index()));
+            if rank == "sink" {
+                continue;
+            }
+            // Chain the first consumers of consecutive input wires, so the
+            // lanes are drawn in the declared order.
+            let targets: Vec<(Node, Port)> = self
+                .node_outputs(node)
+                .filter_map(|port| self.linked_ports(node, port).next())
+                .collect();
+            for pair in targets.windows(2) {
+                let [(a, a_port), (b, b_port)] = pair else {
+                    unreachable!()
+                };
+                if a != b {
+                    hints.push_str(&format!(
+                        "{}:in{} -> {}:in{} [style=\"invis\"]\n",
+                        a.index.index(),
+                        a_port.index(),
+                        b.index.index(),
+                        b_port.index()
+                    ));
+                }
+            }
+        }
+        let body = dot.strip_suffix("}\n").unwrap_or(&dot);
+        format!("{body}{hints}}}\n")
     }
 }
 
+/// Formatting options for [`Hugr::dot_string_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DotOptions {
+    /// Lay the graph out left to right like a circuit.
+    ///
+    /// The Input and Output nodes of each region are placed at the start and
+    /// end of the drawing, the wires leaving an Input node are kept in their
+    /// declared order, and state order edges do not affect the layout.
+    pub circuit_layout: bool,
+}
+
+/// Escape a label to be used as the content of a graphviz HTML-like label.
+///
+/// The result only contains printable ASCII characters, with markup
+/// characters and non-ASCII characters replaced by entities and newlines by
+/// line breaks. Embedding the dot string in a URL is left to the consumer.
+fn dot_label(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in html_escape::encode_double_quoted_attribute(label).chars() {
+        match c {
+            '\n' => escaped.push_str("<br/>"),
+            ' '..='~' => escaped.push(c),
+            c => escaped.push_str(&format!("&#{};", c as u32)),
+        }
+    }
+    escaped
+}
+
 /// Arbitrary metadata for a node.
 pub type NodeMetadata = serde_json::Value;
 
 /// Internal API for HUGRs, not intended for use by users.
 impl Hugr {
+    /// The name of a dataflow port, as given by the signature description of
+    /// the operation. The names of the ports of [`Input`] and [`Output`] nodes
+    /// are taken from their parent.
+    ///
+    /// Returns an empty string for unnamed ports.
+    ///
+    /// [`Input`]: crate::ops::Input
+    /// [`Output`]: crate::ops::Output
+    fn port_name(&self, node: Node, port: Port) -> SmolStr {
+        let optype = self.get_optype(node);
+        let parent_desc = || {
+            self.get_parent(node)
+                .map(|p| self.get_optype(p).signature_desc())
+                .unwrap_or_default()
+        };
+        let names = match (optype, port.direction()) {
+            (OpType::Input(_), Direction::Outgoing) => parent_desc().input,
+            (OpType::Output(_), Direction::Incoming) => parent_desc().output,
+            _ => return optype.signature_desc()[port].clone(),
+        };
+        names.get(port.index()).cloned().unwrap_or_default()
+    }
+
+    /// Apply `f` to the operations of `nodes`, checking that their number of
+    /// ports is unchanged.
+    fn map_nodes_ops(
+        &mut self,
+        nodes: Vec<Node>,
+        mut f: impl FnMut(Node, &mut OpType),
+    ) -> Result<(), HugrError> {
+        let port_counts = |op: &OpType| {
+            let [inputs, outputs] = Direction::BOTH.map(|dir| op.port_count(dir));
+            (inputs, outputs)
+        };
+        for node in nodes {
+            let op = self.op_types.get_mut(node.index);
+            let expected = port_counts(op);
+            f(node, op);
+            let actual = port_counts(op);
+            if actual != expected {
+                return Err(HugrError::PortCountChanged {
+                    node,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Create a new Hugr, with a single root node.
     pub(crate) fn new(root_op: impl Into<OpType>) -> Self {
         Self::with_capacity(root_op, 0, 0)
@@ -251,6 +513,15 @@ impl Wire {
     pub fn source(&self) -> Port {
         Port::new_outgoing(self.1)
     }
+
+    /// The output port that this wire is connected to, as paired with
+    /// [`Wire::node`] in the ports returned by [`HugrView`].
+    ///
+    /// Same as [`Wire::source`].
+    #[inline]
+    pub fn port(&self) -> Port {
+        self.source()
+    }
 }
 
 /// Errors that can occur while manipulating a Hugr.
@@ -265,17 +536,297 @@ pub enum HugrError {
     /// An error occurred while manipulating the hierarchy.
     #[error("An error occurred while manipulating the hierarchy.")]
     HierarchyError(#[from] portgraph::hierarchy::AttachError),
+    /// A child of a node is not valid for the operation replacing it.
+    #[error("Cannot replace node {node:?} with a {op_tag} operation, its child {child:?} with tag {child_tag} is not in {allowed}.")]
+    IncompatibleChildren {
+        /// The node being replaced.
+        node: Node,
+        /// The tag of the new operation.
+        op_tag: OpTag,
+        /// The incompatible child.
+        child: Node,
+        /// The tag of the incompatible child.
+        child_tag: OpTag,
+        /// The tags allowed by the new operation for that child.
+        allowed: OpTag,
+    },
+    /// A node cannot have the requested number of ports, see
+    /// [`Hugr::MAX_PORTS_PER_NODE`].
+    #[error("Cannot give {requested} ports to the node {node:?}, at most {max} are supported.")]
+    TooManyPorts {
+        /// The node, or `None` if it was being added.
+        node: Option<Node>,
+        /// The requested number of ports, counting both directions.
+        requested: usize,
+        /// The maximum number of ports of a node.
+        max: usize,
+    },
+    /// The children of a node cannot be moved under one of its descendants.
+    #[error("Cannot move the children of {src:?} under its descendant {dst:?}.")]
+    TransplantIntoDescendant {
+        /// The node whose children were being moved.
+        src: Node,
+        /// The descendant of `src` they were being moved to.
+        dst: Node,
+    },
+    /// A subtree cannot be copied under one of its own nodes.
+    #[error("Cannot copy the subtree of {src:?} under its node {new_parent:?}.")]
+    DuplicateIntoSubtree {
+        /// The root of the subtree being copied.
+        src: Node,
+        /// The node of the subtree the copy was being added to.
+        new_parent: Node,
+    },
+    /// A node of a weighted graph has no operation, see
+    /// [`Hugr::from_weighted_graph`].
+    #[error("The node {0:?} of the weighted graph has no operation.")]
+    MissingWeight(portgraph::NodeIndex),
+    /// A node of a weighted graph does not have the ports of its operation.
+    #[error("The node {node:?} of the weighted graph has {actual:?} (input, output) ports, but its operation {op} has {expected:?}.")]
+    WeightPortMismatch {
+        /// The node of the weighted graph.
+        node: portgraph::NodeIndex,
+        /// The name of the operation of the node.
+        op: SmolStr,
+        /// The number of input and output ports of the operation.
+        expected: (usize, usize),
+        /// The number of input and output ports of the node.
+        actual: (usize, usize),
+    },
+    /// The unlinked ports of a weighted graph do not match the signature of
+    /// the function wrapping it.
+    #[error(
+        "The weighted graph has {actual} unlinked {dir:?} ports, but the signature has {expected}."
+    )]
+    BoundaryMismatch {
+        /// The direction of the unlinked ports.
+        dir: Direction,
+        /// The length of the signature row.
+        expected: usize,
+        /// The number of unlinked ports.
+        actual: usize,
+    },
+    /// The HUGR cannot hold any more nodes, see [`Hugr::MAX_NODES`].
+    #[error("Cannot add a node to a HUGR, at most {max} are supported.")]
+    TooManyNodes {
+        /// The maximum number of nodes in a HUGR.
+        max: usize,
+    },
+    /// An edit of the operation of a node changed its number of ports.
+    #[error("The edit of the operation at {node:?} changed its (input, output) ports from {expected:?} to {actual:?}.")]
+    PortCountChanged {
+        /// The node whose operation was edited.
+        node: Node,
+        /// The number of input and output ports before the edit.
+        expected: (usize, usize),
+        /// The number of input and output ports after the edit.
+        actual: (usize, usize),
+    },
 }
 
 #[cfg(test)]
 mod test {
-    use super::Hugr;
+    use super::region::{FlatRegionView, RegionView};
+    use super::{DotOptions, Hugr, HugrMut, HugrView, Node, SimpleReplacement};
+    use crate::builder::{
+        BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+        ModuleBuilder,
+    };
+    use crate::ops::custom::{ExternalOp, OpaqueOp};
+    use crate::ops::{self, handle::NodeHandle, LeafOp};
+    use crate::resource::ResourceRegistry;
+    use crate::type_row;
+    use crate::types::{LinearType, Signature, SignatureDescription, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
 
     #[test]
     fn impls_send_and_sync() {
         // Send and Sync are automatically impl'd by the compiler, if possible.
         // This test will fail to compile if that wasn't possible.
-        trait Test: Send + Sync {}
-        impl Test for Hugr {}
+        fn assert_send<T: Send>() {}
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Hugr>();
+        assert_send_sync::<FlatRegionView<'_>>();
+        assert_send_sync::<ResourceRegistry>();
+        assert_send_sync::<SimpleReplacement>();
+        assert_send_sync::<DotOptions>();
+
+        // The underlying portgraph region caches descendant queries in a RefCell.
+        assert_send::<RegionView<'_, Hugr>>();
+    }
+
+    #[test]
+    fn concurrent_inspection() -> Result<(), BuildError> {
+        let mut dfg = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+        let [q0, q1] = dfg.input_wires_arr();
+        let h = dfg.add_dataflow_op(LeafOp::H, [q0])?;
+        let cx = dfg.add_dataflow_op(LeafOp::CX, [h.out_wire(0), q1])?;
+        let hugr = dfg.finish_hugr_with_outputs(cx.outputs())?;
+        let dot = hugr.dot_string();
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        assert_eq!(hugr.validate(), Ok(()));
+                        hugr.dot_string()
+                    })
+                })
+                .collect();
+            for handle in handles {
+                assert_eq!(handle.join().unwrap(), dot);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut hugr = Hugr::default();
+        let root = hugr.root();
+        let decls: Vec<Node> = (0..100)
+            .map(|i| {
+                let decl = ops::FuncDecl {
+                    name: format!("f{i}"),
+                    signature: Signature::default(),
+                    signature_desc: Default::default(),
+                };
+                hugr.add_op_with_parent(root, decl).unwrap()
+            })
+            .collect();
+        for (i, &decl) in decls.iter().enumerate() {
+            if i % 10 == 0 {
+                hugr.set_metadata(decl, i.into());
+            } else {
+                hugr.remove_node(decl).unwrap();
+            }
+        }
+        assert_eq!(hugr.validate(), Ok(()));
+
+        let (nodes, ports) = hugr.capacity();
+        hugr.shrink_to_fit();
+        let (new_nodes, new_ports) = hugr.capacity();
+        assert!(new_nodes < nodes);
+        assert!(new_ports < ports);
+        assert!(new_nodes >= hugr.node_count());
+        assert_eq!(hugr.node_count(), 11);
+        assert_eq!(hugr.validate(), Ok(()));
+
+        // Operations and metadata moved with the nodes.
+        let root = hugr.root();
+        for (i, decl) in hugr.children(root).enumerate() {
+            let ops::OpType::FuncDecl(op) = hugr.get_optype(decl) else {
+                panic!("expected a declaration");
+            };
+            assert_eq!(op.name, format!("f{}", i * 10));
+            assert_eq!(hugr.get_metadata(decl), &serde_json::json!(i * 10));
+        }
+
+        hugr.reserve(50, 100);
+        let (nodes, ports) = hugr.capacity();
+        assert!(nodes >= hugr.node_count() + 50);
+        assert!(ports >= 100);
+    }
+
+    #[test]
+    fn dot_circuit_layout() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB, QB, QB], type_row![QB, QB, QB])?;
+        let [input, output] = builder.io();
+        let [q0, q1, q2] = builder.input_wires_arr();
+        let h = builder.add_dataflow_op(LeafOp::H, [q0])?;
+        let cx = builder.add_dataflow_op(LeafOp::CX, h.outputs().chain([q1]))?;
+        let t = builder.add_dataflow_op(LeafOp::T, [q2])?;
+        builder.set_order(&h, &t)?;
+        let hugr = builder.finish_hugr_with_outputs(cx.outputs().chain(t.outputs()))?;
+
+        let [input, output, h, cx, t] =
+            [input, output, h.node(), cx.node(), t.node()].map(|n| n.index.index());
+        let dot = hugr.dot_string_with(&DotOptions {
+            circuit_layout: true,
+        });
+        for hint in [
+            "rankdir=LR\n".to_string(),
+            format!("{{rank=source; {input}}}\n"),
+            format!("{{rank=sink; {output}}}\n"),
+            format!("{h}:in0 -> {cx}:in1 [style=\"invis\"]\n"),
+            format!("{cx}:in1 -> {t}:in0 [style=\"invis\"]\n"),
+            format!("{h}:out1 -> {t}:in1 [style=\"dotted\" constraint=\"false\"]\n"),
+        ] {
+            assert!(dot.contains(&hint), "{hint:?} not in dot string");
+        }
+        assert!(dot.ends_with("}\n"));
+
+        // The hints are only added when requested.
+        let dot = hugr.dot_string();
+        assert!(!dot.contains("rank"));
+        assert!(!dot.contains("constraint"));
+        Ok(())
+    }
+
+    /// Check that the HTML-like labels of a dot string are well formed, and
+    /// that their text contains only printable ASCII characters and entities.
+    fn check_dot_labels(dot: &str) {
+        assert!(dot.chars().all(|c| c == '\n' || (' '..='~').contains(&c)));
+        for line in dot.lines() {
+            let Some((_, label)) = line.split_once("label=<") else {
+                continue;
+            };
+            let label = label.strip_suffix(">]").expect("unterminated label");
+            let mut chars = label.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    // Tags may contain quoted attributes, but no nested tags.
+                    '<' => loop {
+                        match chars.next() {
+                            Some('>') => break,
+                            Some('<') | None => panic!("malformed tag in {label:?}"),
+                            Some(_) => {}
+                        }
+                    },
+                    '&' => {
+                        let entity: String = chars.by_ref().take_while(|&c| c != ';').collect();
+                        assert!(!entity.is_empty(), "empty entity in {label:?}");
+                        assert!(entity
+                            .chars()
+                            .all(|c| c == '#' || c.is_ascii_alphanumeric()));
+                    }
+                    '>' | '"' => panic!("raw {c:?} in {label:?}"),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dot_escaped_labels() -> Result<(), BuildError> {
+        let names = ["a->b", "say \"hi\"", "<b>bold</b>", "\u{1F980}\nop"];
+        let mut module_builder = ModuleBuilder::new();
+        let sig = Signature::new_df(type_row![QB], type_row![QB]);
+        let desc = SignatureDescription::new_df(["<in>".into()], ["\"out\"".into()]);
+        let mut f_build = module_builder.define_function_named("main", sig.clone(), desc)?;
+        let mut wire = f_build.input_wires().next().unwrap();
+        for name in names {
+            let op = OpaqueOp::new("R".into(), name, String::new(), [], Some(sig.clone()));
+            let op: LeafOp = ExternalOp::Opaque(op).into();
+            [wire] = f_build.add_dataflow_op(op, [wire])?.outputs_arr();
+        }
+        f_build.finish_with_outputs([wire])?;
+
+        // The resources of the opaque operations are not lifted, so the HUGR
+        // is not validated.
+        let dot = module_builder.hugr().dot_string();
+        check_dot_labels(&dot);
+        for escaped in [
+            "R.a-&gt;b",
+            "R.say &quot;hi&quot;",
+            "R.&lt;b&gt;bold&lt;/b&gt;",
+            "R.&#129408;<br/>op",
+            "&lt;in&gt;: Q",
+            "&quot;out&quot;: Q",
+        ] {
+            assert!(dot.contains(escaped), "{escaped:?} not in dot string");
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,177 @@
+//! Caching of per-node analysis results.
+//!
+//! Results stored in a plain `HashMap<Node, T>` silently go stale when the
+//! HUGR is mutated. A [`RegionCache`] groups them by the region (the parent)
+//! of each node, so that after a rewrite only the results for the regions it
+//! touched need to be dropped. They are recomputed on the next access.
+
+use std::collections::HashMap;
+
+use crate::hugr::HugrView;
+use crate::Node;
+
+/// Per-node analysis results, grouped by region and computed lazily.
+///
+/// The cache is not notified of mutations of the HUGR. Whoever mutates it must
+/// invalidate the regions it changed, such as the
+/// [`parent`](crate::hugr::SimpleReplacement::parent) of a
+/// [`SimpleReplacement`](crate::hugr::SimpleReplacement). Results for nodes in
+/// other regions are kept, so they must not depend on the contents of the
+/// changed regions.
+#[derive(Debug, Clone)]
+pub struct RegionCache<T> {
+    /// The results for each region, keyed by the region parent. The root of
+    /// the HUGR is its own region.
+    regions: HashMap<Node, HashMap<Node, T>>,
+}
+
+impl<T> Default for RegionCache<T> {
+    fn default() -> Self {
+        Self {
+            regions: HashMap::new(),
+        }
+    }
+}
+
+impl<T> RegionCache<T> {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the result for `node`, computing it with `compute` if it is not
+    /// cached.
+    pub fn get_or_compute<H: HugrView>(
+        &mut self,
+        hugr: &H,
+        node: Node,
+        compute: impl FnOnce(&H, Node) -> T,
+    ) -> &T {
+        self.regions
+            .entry(region_of(hugr, node))
+            .or_default()
+            .entry(node)
+            .or_insert_with(|| compute(hugr, node))
+    }
+
+    /// Returns the cached result for `node`, if any.
+    pub fn get(&self, hugr: &impl HugrView, node: Node) -> Option<&T> {
+        self.regions.get(&region_of(hugr, node))?.get(&node)
+    }
+
+    /// Drop the cached results for the children of `region`.
+    ///
+    /// Results for nodes in nested regions are kept.
+    pub fn invalidate_region(&mut self, region: Node) {
+        self.regions.remove(&region);
+    }
+
+    /// Drop the cached results for the children of all the given regions.
+    pub fn invalidate_regions(&mut self, regions: impl IntoIterator<Item = Node>) {
+        for region in regions {
+            self.invalidate_region(region);
+        }
+    }
+
+    /// Drop all the cached results.
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Returns the number of cached results.
+    pub fn len(&self) -> usize {
+        self.regions.values().map(HashMap::len).sum()
+    }
+
+    /// Returns `true` if no result is cached.
+    pub fn is_empty(&self) -> bool {
+        self.regions.values().all(HashMap::is_empty)
+    }
+}
+
+/// The region grouping the cached results of a node.
+fn region_of(hugr: &impl HugrView, node: Node) -> Node {
+    hugr.get_parent(node).unwrap_or(node)
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer};
+    use crate::hugr::{Rewrite, SimpleReplacement};
+    use crate::ops::{handle::NodeHandle, LeafOp, OpName};
+    use crate::type_row;
+    use crate::types::{LinearType, Signature, SimpleType};
+    use crate::{Hugr, Port};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+
+    #[test]
+    fn invalidate_replaced_region() -> Result<(), BuildError> {
+        // Two nested DFGs with a single H gate each.
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+        let mut regions = Vec::new();
+        let mut gates = Vec::new();
+        let mut outputs = Vec::new();
+        for wire in builder.input_wires().collect::<Vec<_>>() {
+            let sig = Signature::new_df(type_row![QB], type_row![QB]);
+            let mut nested = builder.dfg_builder(sig, [wire])?;
+            let h = nested.add_dataflow_op(LeafOp::H, nested.input_wires())?;
+            let nested = nested.finish_with_outputs(h.outputs())?;
+            regions.push(nested.node());
+            gates.push(h.node());
+            outputs.extend(nested.outputs());
+        }
+        let mut hugr = builder.finish_hugr_with_outputs(outputs)?;
+
+        let computed = Cell::new(0);
+        let name = |hugr: &Hugr, node| {
+            computed.set(computed.get() + 1);
+            hugr.get_optype(node).name()
+        };
+        let mut cache = RegionCache::new();
+        for &gate in &gates {
+            assert_eq!(cache.get_or_compute(&hugr, gate, name), "H");
+        }
+        assert_eq!(cache.get_or_compute(&hugr, gates[0], name), "H");
+        assert_eq!(computed.get(), 2);
+        assert_eq!(cache.len(), 2);
+
+        // Replace the H gate in the first region by a T gate.
+        let mut replacement = DFGBuilder::new(type_row![QB], type_row![QB])?;
+        let t = replacement.add_dataflow_op(LeafOp::T, replacement.input_wires())?;
+        let replacement = replacement.finish_hugr_with_outputs(t.outputs())?;
+        let region_output = hugr.children(regions[0]).nth(1).unwrap();
+        let rewrite = SimpleReplacement::try_new(
+            regions[0],
+            [gates[0]],
+            replacement,
+            HashMap::from([(
+                (t.node(), Port::new_incoming(0)),
+                (gates[0], Port::new_incoming(0)),
+            )]),
+            HashMap::from([(
+                (region_output, Port::new_incoming(0)),
+                Port::new_incoming(0),
+            )]),
+        )
+        .unwrap();
+        cache.invalidate_region(rewrite.parent);
+        rewrite.apply(&mut hugr).unwrap();
+
+        // The untouched region keeps its result, the other one is recomputed.
+        assert_eq!(cache.get(&hugr, gates[0]), None);
+        assert_eq!(cache.get_or_compute(&hugr, gates[1], name), "H");
+        assert_eq!(computed.get(), 2);
+        let new_gate = hugr.children(regions[0]).nth(2).unwrap();
+        assert_eq!(cache.get_or_compute(&hugr, new_gate, name), "T");
+        assert_eq!(computed.get(), 3);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        Ok(())
+    }
+}
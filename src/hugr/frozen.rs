@@ -0,0 +1,159 @@
+//! Read-only snapshots of a HUGR.
+//!
+//! A [`FrozenHugr`] can be shared between threads answering analysis queries
+//! without locking, as it statically prevents any mutation. Cloning it only
+//! clones a reference to the shared data.
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::Hugr;
+
+/// An immutable HUGR, cheap to clone and share between threads.
+///
+/// Created with [`Hugr::freeze`]. It implements [`HugrView`] and dereferences
+/// to [`Hugr`] for the read-only methods, such as [`Hugr::validate`] and
+/// [`Hugr::dot_string`], but gives no mutable access. The HUGR can be mutated
+/// again after a [`FrozenHugr::thaw`].
+///
+/// [`HugrView`]: super::HugrView
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrozenHugr {
+    hugr: Arc<Hugr>,
+}
+
+impl Hugr {
+    /// Make the HUGR immutable, so that it can be shared without copies.
+    pub fn freeze(self) -> FrozenHugr {
+        FrozenHugr {
+            hugr: Arc::new(self),
+        }
+    }
+}
+
+impl FrozenHugr {
+    /// Recover a mutable HUGR.
+    ///
+    /// The data is only copied if other clones of the snapshot are alive.
+    pub fn thaw(self) -> Hugr {
+        Arc::try_unwrap(self.hugr).unwrap_or_else(|hugr| (*hugr).clone())
+    }
+
+    /// Returns `true` if both snapshots share the same data.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.hugr, &other.hugr)
+    }
+}
+
+impl From<Hugr> for FrozenHugr {
+    fn from(hugr: Hugr) -> Self {
+        hugr.freeze()
+    }
+}
+
+impl From<FrozenHugr> for Hugr {
+    fn from(frozen: FrozenHugr) -> Self {
+        frozen.thaw()
+    }
+}
+
+impl AsRef<Hugr> for FrozenHugr {
+    fn as_ref(&self) -> &Hugr {
+        &self.hugr
+    }
+}
+
+impl Deref for FrozenHugr {
+    type Target = Hugr;
+
+    fn deref(&self) -> &Hugr {
+        &self.hugr
+    }
+}
+
+impl Serialize for FrozenHugr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.hugr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FrozenHugr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Hugr::deserialize(deserializer).map(Hugr::freeze)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::hugr::{HugrMut, HugrView};
+    use crate::ops::{LeafOp, OpName, OpTag};
+    use crate::type_row;
+    use crate::types::{LinearType, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+
+    #[test]
+    fn impls_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<FrozenHugr>();
+    }
+
+    #[test]
+    fn parallel_analyses() -> Result<(), BuildError> {
+        let mut dfg = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+        let [q0, q1] = dfg.input_wires_arr();
+        let h = dfg.add_dataflow_op(LeafOp::H, [q0])?;
+        let cx = dfg.add_dataflow_op(LeafOp::CX, [h.out_wire(0), q1])?;
+        let hugr = dfg.finish_hugr_with_outputs(cx.outputs())?;
+        let dot = hugr.dot_string();
+        let json = serde_json::to_string(&hugr).unwrap();
+
+        let frozen = Arc::new(hugr.freeze());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let frozen = Arc::clone(&frozen);
+                thread::spawn(move || match i {
+                    0 => {
+                        assert_eq!(frozen.validate(), Ok(()));
+                        frozen.dot_string()
+                    }
+                    1 => serde_json::to_string(&*frozen).unwrap(),
+                    2 => frozen
+                        .nodes_with_tag(OpTag::Leaf)
+                        .map(|n| frozen.get_optype(n).name())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    _ => frozen.node_count().to_string(),
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, [dot, json.clone(), "H,CX".into(), "5".into()]);
+
+        // Thawing copies the data only while it is shared.
+        let frozen = Arc::try_unwrap(frozen).unwrap();
+        let snapshot = frozen.clone();
+        assert!(snapshot.ptr_eq(&frozen));
+        let mut hugr = frozen.thaw();
+        hugr.set_metadata(hugr.root(), "changed".into());
+        assert_eq!(serde_json::to_string(&snapshot).unwrap(), json);
+        assert_ne!(serde_json::to_string(&hugr).unwrap(), json);
+
+        let reloaded: FrozenHugr = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.validate(), Ok(()));
+        Ok(())
+    }
+}
@@ -6,7 +6,8 @@ use std::ops::Range;
 use portgraph::{LinkMut, NodeIndex, PortMut, PortView, SecondaryMap};
 
 use crate::hugr::{Direction, HugrError, HugrView, Node};
-use crate::ops::OpType;
+use crate::ops::{OpTag, OpTrait, OpType, ValidateOp};
+use crate::types::EdgeKind;
 use crate::{Hugr, Port};
 
 use super::NodeMetadata;
@@ -14,7 +15,12 @@ use super::NodeMetadata;
 /// Functions for low-level building of a HUGR. (Or, in the future, a subregion thereof)
 pub(crate) trait HugrMut {
     /// Add a node to the graph.
-    fn add_op(&mut self, op: impl Into<OpType>) -> Node;
+    ///
+    /// # Errors
+    ///
+    /// If the operation has more ports than [`Hugr::MAX_PORTS_PER_NODE`], or
+    /// the graph already holds [`Hugr::MAX_NODES`] nodes.
+    fn add_op(&mut self, op: impl Into<OpType>) -> Result<Node, HugrError>;
 
     /// Remove a node from the graph.
     ///
@@ -23,6 +29,24 @@ pub(crate) trait HugrMut {
     /// Panics if the node is the root node.
     fn remove_node(&mut self, node: Node) -> Result<(), HugrError>;
 
+    /// Remove a node from the graph, together with all its descendants.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is the root node.
+    fn remove_subtree(&mut self, node: Node) -> Result<(), HugrError>;
+
+    /// Detach a node from its parent and remove all the edges of the node and
+    /// its descendants.
+    ///
+    /// The nodes are kept in the graph, so their indices are not reused until
+    /// they are removed with [`HugrMut::remove_subtree`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is the root node.
+    fn detach_subtree(&mut self, node: Node) -> Result<(), HugrError>;
+
     /// Returns the metadata associated with a node.
     fn get_metadata_mut(&mut self, node: Node) -> &mut NodeMetadata;
 
@@ -61,14 +85,42 @@ pub(crate) trait HugrMut {
     fn add_other_edge(&mut self, src: Node, dst: Node) -> Result<(Port, Port), HugrError>;
 
     /// Set the number of ports on a node. This may invalidate the node's `PortIndex`.
-    fn set_num_ports(&mut self, node: Node, incoming: usize, outgoing: usize);
+    ///
+    /// # Errors
+    ///
+    /// If the node would have more ports than [`Hugr::MAX_PORTS_PER_NODE`].
+    fn set_num_ports(
+        &mut self,
+        node: Node,
+        incoming: usize,
+        outgoing: usize,
+    ) -> Result<(), HugrError>;
 
     /// Alter the number of ports on a node and returns a range with the new
     /// port offsets, if any. This may invalidate the node's `PortIndex`.
     ///
     /// The `direction` parameter specifies whether to add ports to the incoming
     /// or outgoing list.
-    fn add_ports(&mut self, node: Node, direction: Direction, amount: isize) -> Range<usize>;
+    ///
+    /// # Errors
+    ///
+    /// If the node would have more ports than [`Hugr::MAX_PORTS_PER_NODE`].
+    fn add_ports(
+        &mut self,
+        node: Node,
+        direction: Direction,
+        amount: isize,
+    ) -> Result<Range<usize>, HugrError>;
+
+    /// Remove the unconnected ports at the end of the port lists of a node
+    /// that are not expected by its operation, such as order ports left
+    /// behind by removed edges.
+    ///
+    /// The ports counted by [`OpTrait::port_count`] are always kept. This may
+    /// invalidate the node's `PortIndex`.
+    ///
+    /// Returns the number of ports removed.
+    fn purge_dangling_other_ports(&mut self, node: Node) -> usize;
 
     /// Sets the parent of a node.
     ///
@@ -90,6 +142,17 @@ pub(crate) trait HugrMut {
     /// The node becomes the parent's last child.
     fn move_before_sibling(&mut self, node: Node, before: Node) -> Result<(), HugrError>;
 
+    /// Move all the children of `src` to the end of the children of `dst`,
+    /// preserving their order.
+    ///
+    /// Moving the children of a node to itself does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HugrError::TransplantIntoDescendant`] if `dst` is a
+    /// descendant of `src`.
+    fn transplant_children(&mut self, src: Node, dst: Node) -> Result<(), HugrError>;
+
     /// Add a node to the graph with a parent in the hierarchy.
     ///
     /// The node becomes the parent's last child.
@@ -122,7 +185,21 @@ pub(crate) trait HugrMut {
     /// Replace the OpType at node and return the old OpType.
     /// In general this invalidates the ports, which may need to be resized to
     /// match the OpType signature.
-    fn replace_op(&mut self, node: Node, op: impl Into<OpType>) -> OpType;
+    ///
+    /// # Errors
+    ///
+    /// If the existing children of the node are not valid children of the new
+    /// operation, as given by its [`OpValidityFlags`]. The HUGR is left
+    /// unchanged in that case.
+    ///
+    /// [`OpValidityFlags`]: crate::ops::validate::OpValidityFlags
+    fn replace_op(&mut self, node: Node, op: impl Into<OpType>) -> Result<OpType, HugrError>;
+
+    /// Replace the OpType at node and return the old OpType, without checking
+    /// that the existing children of the node are valid for the new operation.
+    ///
+    /// See [`HugrMut::replace_op`].
+    fn replace_op_unchecked(&mut self, node: Node, op: impl Into<OpType>) -> OpType;
 
     /// Insert another hugr into this one, under a given root node.
     ///
@@ -134,6 +211,37 @@ pub(crate) trait HugrMut {
     /// Returns the root node of the inserted hugr.
     fn insert_from_view(&mut self, root: Node, other: &impl HugrView) -> Result<Node, HugrError>;
 
+    /// Copy a node and all its descendants as the last child of `new_parent`,
+    /// along with the edges between them. Returns a map from the copied nodes
+    /// to their copies.
+    ///
+    /// The copies keep the order of their siblings and of the links of each
+    /// port. Edges between the subtree and the rest of the HUGR are not
+    /// copied. See [`HugrMut::duplicate_subtree_with_external_edges`] to also
+    /// copy the inter-graph static edges coming into the subtree.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HugrError::DuplicateIntoSubtree`] if `new_parent` is `src` or
+    /// one of its descendants.
+    fn duplicate_subtree(
+        &mut self,
+        src: Node,
+        new_parent: Node,
+    ) -> Result<HashMap<Node, Node>, HugrError>;
+
+    /// Copy a node and all its descendants as the last child of `new_parent`,
+    /// like [`HugrMut::duplicate_subtree`].
+    ///
+    /// The static edges coming into the subtree from a node outside of it,
+    /// such as the loads of a module-level constant, are copied too and
+    /// connect the same source node to the copies.
+    fn duplicate_subtree_with_external_edges(
+        &mut self,
+        src: Node,
+        new_parent: Node,
+    ) -> Result<HashMap<Node, Node>, HugrError>;
+
     /// Compact the nodes indices of the hugr to be contiguous, and order them as a breadth-first
     /// traversal of the hierarchy.
     ///
@@ -148,14 +256,20 @@ impl<T> HugrMut for T
 where
     T: AsRef<Hugr> + AsMut<Hugr>,
 {
-    fn add_op(&mut self, op: impl Into<OpType>) -> Node {
+    fn add_op(&mut self, op: impl Into<OpType>) -> Result<Node, HugrError> {
         let op: OpType = op.into();
+        check_num_ports(None, op.input_count(), op.output_count())?;
+        if self.as_ref().graph.node_count() >= Hugr::MAX_NODES {
+            return Err(HugrError::TooManyNodes {
+                max: Hugr::MAX_NODES,
+            });
+        }
         let node = self
             .as_mut()
             .graph
             .add_node(op.input_count(), op.output_count());
         self.as_mut().op_types[node] = op;
-        node.into()
+        Ok(node.into())
     }
 
     fn remove_node(&mut self, node: Node) -> Result<(), HugrError> {
@@ -169,6 +283,30 @@ where
         Ok(())
     }
 
+    fn remove_subtree(&mut self, node: Node) -> Result<(), HugrError> {
+        let children: Vec<Node> = self.as_ref().children(node).collect();
+        for child in children {
+            self.remove_subtree(child)?;
+        }
+        self.remove_node(node)
+    }
+
+    fn detach_subtree(&mut self, node: Node) -> Result<(), HugrError> {
+        if node.index == self.as_ref().root {
+            panic!("cannot detach root node");
+        }
+        self.as_mut().hierarchy.detach(node.index);
+        let mut nodes = vec![node];
+        while let Some(node) = nodes.pop() {
+            nodes.extend(self.as_ref().children(node));
+            let ports: Vec<_> = self.as_ref().graph.all_ports(node.index).collect();
+            for port in ports {
+                self.as_mut().graph.unlink_port(port);
+            }
+        }
+        Ok(())
+    }
+
     fn get_metadata_mut(&mut self, node: Node) -> &mut NodeMetadata {
         self.as_mut().metadata.get_mut(node.index)
     }
@@ -212,14 +350,26 @@ where
     }
 
     #[inline]
-    fn set_num_ports(&mut self, node: Node, incoming: usize, outgoing: usize) {
+    fn set_num_ports(
+        &mut self,
+        node: Node,
+        incoming: usize,
+        outgoing: usize,
+    ) -> Result<(), HugrError> {
+        check_num_ports(Some(node), incoming, outgoing)?;
         self.as_mut()
             .graph
-            .set_num_ports(node.index, incoming, outgoing, |_, _| {})
+            .set_num_ports(node.index, incoming, outgoing, |_, _| {});
+        Ok(())
     }
 
     #[inline]
-    fn add_ports(&mut self, node: Node, direction: Direction, amount: isize) -> Range<usize> {
+    fn add_ports(
+        &mut self,
+        node: Node,
+        direction: Direction,
+        amount: isize,
+    ) -> Result<Range<usize>, HugrError> {
         let mut incoming = self.as_mut().graph.num_inputs(node.index);
         let mut outgoing = self.as_mut().graph.num_outputs(node.index);
         let increment = |num: &mut usize| {
@@ -232,10 +382,27 @@ where
             Direction::Incoming => increment(&mut incoming),
             Direction::Outgoing => increment(&mut outgoing),
         };
-        self.as_mut()
-            .graph
-            .set_num_ports(node.index, incoming, outgoing, |_, _| {});
-        range
+        self.set_num_ports(node, incoming, outgoing)?;
+        Ok(range)
+    }
+
+    fn purge_dangling_other_ports(&mut self, node: Node) -> usize {
+        let optype = self.get_optype(node);
+        let [incoming, outgoing] = Direction::BOTH.map(|dir| {
+            let expected = optype.port_count(dir);
+            let mut count = self.num_ports(node, dir);
+            while count > expected && !self.is_linked(node, Port::new(dir, count - 1)) {
+                count -= 1;
+            }
+            count
+        });
+        let removed = self.num_inputs(node) + self.num_outputs(node) - incoming - outgoing;
+        if removed > 0 {
+            self.as_mut()
+                .graph
+                .set_num_ports(node.index, incoming, outgoing, |_, _| {});
+        }
+        removed
     }
 
     fn set_parent(&mut self, node: Node, parent: Node) -> Result<(), HugrError> {
@@ -262,12 +429,27 @@ where
         Ok(())
     }
 
+    fn transplant_children(&mut self, src: Node, dst: Node) -> Result<(), HugrError> {
+        if src == dst {
+            return Ok(());
+        }
+        if self.is_ancestor_of(src, dst) {
+            return Err(HugrError::TransplantIntoDescendant { src, dst });
+        }
+        let hierarchy = &mut self.as_mut().hierarchy;
+        while let Some(child) = hierarchy.first(src.index) {
+            hierarchy.detach(child);
+            hierarchy.push_child(child, dst.index)?;
+        }
+        Ok(())
+    }
+
     fn add_op_with_parent(
         &mut self,
         parent: Node,
         op: impl Into<OpType>,
     ) -> Result<Node, HugrError> {
-        let node = self.add_op(op.into());
+        let node = self.add_op(op.into())?;
         self.as_mut()
             .hierarchy
             .push_child(node.index, parent.index)?;
@@ -275,7 +457,7 @@ where
     }
 
     fn add_op_before(&mut self, sibling: Node, op: impl Into<OpType>) -> Result<Node, HugrError> {
-        let node = self.add_op(op.into());
+        let node = self.add_op(op.into())?;
         self.as_mut()
             .hierarchy
             .insert_before(node.index, sibling.index)?;
@@ -283,14 +465,39 @@ where
     }
 
     fn add_op_after(&mut self, sibling: Node, op: impl Into<OpType>) -> Result<Node, HugrError> {
-        let node = self.add_op(op.into());
+        let node = self.add_op(op.into())?;
         self.as_mut()
             .hierarchy
             .insert_after(node.index, sibling.index)?;
         Ok(node)
     }
 
-    fn replace_op(&mut self, node: Node, op: impl Into<OpType>) -> OpType {
+    fn replace_op(&mut self, node: Node, op: impl Into<OpType>) -> Result<OpType, HugrError> {
+        let op = op.into();
+        let flags = op.validity_flags();
+        for (i, child) in self.children(node).enumerate() {
+            let child_tag = self.get_optype(child).tag();
+            let allowed = match i {
+                0 => flags.allowed_first_child,
+                1 => flags.allowed_second_child,
+                _ => OpTag::Any,
+            };
+            for allowed in [flags.allowed_children, allowed] {
+                if !allowed.is_superset(child_tag) {
+                    return Err(HugrError::IncompatibleChildren {
+                        node,
+                        op_tag: op.tag(),
+                        child,
+                        child_tag,
+                        allowed,
+                    });
+                }
+            }
+        }
+        Ok(self.replace_op_unchecked(node, op))
+    }
+
+    fn replace_op_unchecked(&mut self, node: Node, op: impl Into<OpType>) -> OpType {
         let cur = self.as_mut().op_types.get_mut(node.index);
         std::mem::replace(cur, op.into())
     }
@@ -302,7 +509,7 @@ where
             let optype = other.op_types.take(node);
             self.as_mut().op_types.set(new_node, optype);
             let meta = other.metadata.take(node);
-            self.as_mut().set_metadata(node.into(), meta);
+            self.as_mut().set_metadata(new_node.into(), meta);
         }
         Ok(other_root)
     }
@@ -314,11 +521,27 @@ where
             let optype = other.get_optype(node.into());
             self.as_mut().op_types.set(new_node, optype.clone());
             let meta = other.get_metadata(node.into());
-            self.as_mut().set_metadata(node.into(), meta.clone());
+            self.as_mut().set_metadata(new_node.into(), meta.clone());
         }
         Ok(other_root)
     }
 
+    fn duplicate_subtree(
+        &mut self,
+        src: Node,
+        new_parent: Node,
+    ) -> Result<HashMap<Node, Node>, HugrError> {
+        duplicate_subtree_internal(self.as_mut(), src, new_parent, false)
+    }
+
+    fn duplicate_subtree_with_external_edges(
+        &mut self,
+        src: Node,
+        new_parent: Node,
+    ) -> Result<HashMap<Node, Node>, HugrError> {
+        duplicate_subtree_internal(self.as_mut(), src, new_parent, true)
+    }
+
     fn canonicalize_nodes(&mut self, mut rekey: impl FnMut(Node, Node)) {
         // Generate the ordered list of nodes
         let mut ordered = Vec::with_capacity(self.node_count());
@@ -340,6 +563,7 @@ where
                 let hugr = self.as_mut();
                 hugr.graph.swap_nodes(target.index, source.index);
                 hugr.op_types.swap(target.index, source.index);
+                hugr.metadata.swap(target.index, source.index);
                 hugr.hierarchy.swap_nodes(target.index, source.index);
                 rekey(source, target);
             }
@@ -366,6 +590,7 @@ fn insert_hugr_internal(
     root: Node,
     other: &impl HugrView,
 ) -> Result<(Node, HashMap<NodeIndex, NodeIndex>), HugrError> {
+    hugr.reserve(other.node_count(), other.portgraph().port_count());
     let node_map = hugr.graph.insert_graph(other.portgraph())?;
     let other_root = node_map[&other.root().index];
 
@@ -387,17 +612,102 @@ fn insert_hugr_internal(
         other_root.into(),
         root_optype.input_count(),
         root_optype.output_count(),
-    );
+    )?;
 
     Ok((other_root.into(), node_map))
 }
 
+/// Internal implementation of the `duplicate_subtree` methods.
+#[allow(dead_code)]
+fn duplicate_subtree_internal(
+    hugr: &mut Hugr,
+    src: Node,
+    new_parent: Node,
+    external_edges: bool,
+) -> Result<HashMap<Node, Node>, HugrError> {
+    if src == new_parent || hugr.is_ancestor_of(src, new_parent) {
+        return Err(HugrError::DuplicateIntoSubtree { src, new_parent });
+    }
+
+    // List the nodes in breadth-first hierarchy order before adding any, so
+    // parents are copied before their children and siblings stay in order.
+    let mut nodes = vec![src];
+    let mut next = 0;
+    while let Some(&node) = nodes.get(next) {
+        nodes.extend(hugr.children(node));
+        next += 1;
+    }
+
+    let mut node_map = HashMap::with_capacity(nodes.len());
+    for &node in &nodes {
+        let parent = match hugr.get_parent(node) {
+            Some(parent) if node != src => node_map[&parent],
+            _ => new_parent,
+        };
+        let new_node = hugr.add_op_with_parent(parent, hugr.get_optype(node).clone())?;
+        hugr.set_num_ports(new_node, hugr.num_inputs(node), hugr.num_outputs(node))?;
+        hugr.set_metadata(new_node, hugr.get_metadata(node).clone());
+        node_map.insert(node, new_node);
+    }
+
+    // Collect the edges in the same order, so the links of each port are
+    // added in their original order.
+    let mut edges = Vec::new();
+    for &node in &nodes {
+        let new_node = node_map[&node];
+        for ((_, src_port), (tgt, tgt_port)) in hugr.all_linked_ports(node, Direction::Outgoing) {
+            if let Some(&new_tgt) = node_map.get(&tgt) {
+                edges.push((new_node, src_port, new_tgt, tgt_port));
+            }
+        }
+        if !external_edges {
+            continue;
+        }
+        let optype = hugr.get_optype(node);
+        for port in hugr.node_ports(node, Direction::Incoming) {
+            if !matches!(optype.port_kind(port), Some(EdgeKind::Static(_))) {
+                continue;
+            }
+            for (other, other_port) in hugr.linked_ports(node, port) {
+                if !node_map.contains_key(&other) {
+                    edges.push((other, other_port, new_node, port));
+                }
+            }
+        }
+    }
+    for (src, src_port, tgt, tgt_port) in edges {
+        hugr.connect(src, src_port.index(), tgt, tgt_port.index())?;
+    }
+    Ok(node_map)
+}
+
+/// Check that a node can have the given number of ports.
+fn check_num_ports(node: Option<Node>, incoming: usize, outgoing: usize) -> Result<(), HugrError> {
+    let requested = incoming.saturating_add(outgoing);
+    if requested > Hugr::MAX_PORTS_PER_NODE {
+        return Err(HugrError::TooManyPorts {
+            node,
+            requested,
+            max: Hugr::MAX_PORTS_PER_NODE,
+        });
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
+    use cool_asserts::assert_matches;
+
     use crate::{
-        hugr::HugrView,
+        builder::{
+            BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+            HugrBuilder, ModuleBuilder, SubContainer,
+        },
+        hugr::{HugrView, ValidationError},
         macros::type_row,
-        ops::{self, dataflow::IOTrait, LeafOp},
+        ops::custom::{ExternalOp, OpaqueOp},
+        ops::{self, dataflow::IOTrait, handle::NodeHandle, ConstValue, LeafOp},
+        resource::{ResourceId, ResourceSet},
         types::{ClassicType, Signature, SimpleType},
     };
 
@@ -422,6 +732,7 @@ mod test {
                 ops::FuncDefn {
                     name: "main".into(),
                     signature: Signature::new_df(type_row![NAT], type_row![NAT, NAT]),
+                    signature_desc: Default::default(),
                 },
             )
             .expect("Failed to add function definition node");
@@ -450,4 +761,386 @@ mod test {
         // Finish the construction and create the HUGR
         builder.validate().unwrap();
     }
+
+    #[test]
+    fn replace_container_op() {
+        let builder = DFGBuilder::new(type_row![NAT], type_row![NAT]).unwrap();
+        let inputs = builder.input_wires();
+        let mut hugr = builder.finish_hugr_with_outputs(inputs).unwrap();
+        let root = hugr.root();
+        let input = hugr.nth_child(root, 0).unwrap();
+
+        // A `Case` has the same `Input` and `Output` children as a `DFG`.
+        let case = ops::Case {
+            tag: 0,
+            signature: Signature::new_df(type_row![NAT], type_row![NAT]),
+        };
+        assert_matches!(hugr.replace_op(root, case), Ok(OpType::DFG(_)));
+        assert_eq!(hugr.validate(), Ok(()));
+
+        // The children of a `Conditional` must be `Case`s.
+        let conditional = ops::Conditional {
+            predicate_inputs: vec![type_row![]],
+            other_inputs: type_row![NAT],
+            outputs: type_row![NAT],
+        };
+        assert_eq!(
+            hugr.replace_op(root, conditional.clone()),
+            Err(HugrError::IncompatibleChildren {
+                node: root,
+                op_tag: OpTag::Conditional,
+                child: input,
+                child_tag: OpTag::Input,
+                allowed: OpTag::Case,
+            })
+        );
+        assert_matches!(hugr.get_optype(root), OpType::Case(_));
+
+        // Leaf operations cannot have children.
+        assert_matches!(
+            hugr.replace_op(root, LeafOp::Noop { ty: NAT }),
+            Err(HugrError::IncompatibleChildren {
+                allowed: OpTag::None,
+                ..
+            })
+        );
+
+        // The check can be skipped, leaving an invalid HUGR.
+        assert_matches!(
+            hugr.replace_op_unchecked(root, conditional),
+            OpType::Case(_)
+        );
+        assert!(hugr.validate().is_err());
+    }
+
+    /// A module whose `main` function applies an operation from the `rs_id`
+    /// resource, and an `other` function with no operations.
+    fn resource_module(rs_id: &ResourceId) -> Result<Hugr, BuildError> {
+        let ext_op: LeafOp = ExternalOp::from(OpaqueOp::new(
+            rs_id.clone(),
+            "op",
+            "".into(),
+            [],
+            Some(Signature::new_df(type_row![NAT], type_row![NAT])),
+        ))
+        .into();
+        let mut module_builder = ModuleBuilder::new();
+        let mut main_sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        main_sig.output_resources = ResourceSet::singleton(rs_id);
+        let mut main = module_builder.define_function("main", main_sig)?;
+        let [wire] = main.input_wires_arr();
+        let lift_op = LeafOp::Lift {
+            type_row: type_row![NAT],
+            input_resources: ResourceSet::new(),
+            new_resource: rs_id.clone(),
+        };
+        let [wire] = main.add_dataflow_op(lift_op, [wire])?.outputs_arr();
+        let [wire] = main.add_dataflow_op(ext_op, [wire])?.outputs_arr();
+        main.finish_with_outputs([wire])?;
+        let other = module_builder
+            .define_function("other", Signature::new_df(type_row![NAT], type_row![NAT]))?;
+        let wires = other.input_wires();
+        other.finish_with_outputs(wires)?;
+        module_builder.finish_hugr()
+    }
+
+    /// A control flow graph built with [`HugrMut::add_op_with_parent`] only,
+    /// without adjusting the ports of any node.
+    #[test]
+    fn container_port_counts() {
+        let mut hugr = Hugr::default();
+        let module = hugr.root();
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let f = hugr
+            .add_op_with_parent(
+                module,
+                ops::FuncDefn {
+                    name: "main".into(),
+                    signature: sig.clone(),
+                    signature_desc: Default::default(),
+                },
+            )
+            .unwrap();
+        let f_in = hugr
+            .add_op_with_parent(f, ops::Input::new(type_row![NAT]))
+            .unwrap();
+        let f_out = hugr
+            .add_op_with_parent(f, ops::Output::new(type_row![NAT]))
+            .unwrap();
+        let cfg = hugr
+            .add_op_with_parent(
+                f,
+                ops::CFG {
+                    inputs: type_row![NAT],
+                    outputs: type_row![NAT],
+                },
+            )
+            .unwrap();
+        hugr.connect(f_in, 0, cfg, 0).unwrap();
+        hugr.connect(cfg, 0, f_out, 0).unwrap();
+
+        let entry = hugr
+            .add_op_with_parent(
+                cfg,
+                ops::BasicBlock::DFB {
+                    inputs: type_row![NAT],
+                    other_outputs: type_row![NAT],
+                    predicate_variants: vec![type_row![]; 2],
+                },
+            )
+            .unwrap();
+        let exit = hugr
+            .add_op_with_parent(
+                cfg,
+                ops::BasicBlock::Exit {
+                    cfg_outputs: type_row![NAT],
+                },
+            )
+            .unwrap();
+        let predicate = ConstValue::simple_predicate(1, 2);
+        let predicate_type = SimpleType::new_simple_predicate(2);
+        let entry_in = hugr
+            .add_op_with_parent(entry, ops::Input::new(type_row![NAT]))
+            .unwrap();
+        let entry_out = hugr
+            .add_op_with_parent(entry, ops::Output::new(vec![predicate_type, NAT]))
+            .unwrap();
+        let datatype = predicate.const_type();
+        let row = hugr
+            .add_op_with_parent(entry, ops::ConstRow(vec![predicate, ConstValue::i64(0)]))
+            .unwrap();
+        let load = hugr
+            .add_op_with_parent(entry, ops::LoadConstant { datatype })
+            .unwrap();
+        hugr.connect(row, 0, load, 0).unwrap();
+        hugr.connect(entry_in, 1, load, 1).unwrap();
+        hugr.connect(load, 0, entry_out, 0).unwrap();
+        hugr.connect(entry_in, 0, entry_out, 1).unwrap();
+        // Both successors are the exit block.
+        hugr.connect(entry, 0, exit, 0).unwrap();
+        hugr.connect(entry, 1, exit, 0).unwrap();
+
+        assert_eq!(hugr.num_outputs(entry), 2);
+        assert_eq!(hugr.num_outputs(row), 2);
+        assert_eq!(hugr.num_outputs(exit), 0);
+        for node in hugr.nodes().filter(|&n| n != module) {
+            let op = hugr.get_optype(node);
+            for dir in Direction::BOTH {
+                assert_eq!(hugr.num_ports(node, dir), op.port_count(dir));
+            }
+        }
+        assert_eq!(
+            hugr.get_optype(entry).other_port_index(Direction::Outgoing),
+            None
+        );
+        assert_eq!(hugr.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rename_resource() -> Result<(), BuildError> {
+        let old: ResourceId = "old".into();
+        let new: ResourceId = "new".into();
+        let mut hugr = resource_module(&old)?;
+        let port_counts = |h: &Hugr| -> Vec<(usize, usize)> {
+            h.nodes()
+                .map(|n| {
+                    let op = h.get_optype(n);
+                    (op.input_count(), op.port_count(Direction::Outgoing))
+                })
+                .collect()
+        };
+        let before = port_counts(&hugr);
+
+        let rename_set = |rs: &mut ResourceSet| {
+            *rs = rs
+                .iter()
+                .map(|r| if r == &old { new.clone() } else { r.clone() })
+                .collect();
+        };
+        let mut renamed = 0;
+        hugr.map_ops(|_, op| match op {
+            OpType::LeafOp(LeafOp::CustomOp(ExternalOp::Opaque(opaque)))
+                if opaque.resource() == &old =>
+            {
+                opaque.set_resource(new.clone());
+                renamed += 1;
+            }
+            OpType::LeafOp(LeafOp::Lift { new_resource, .. }) => *new_resource = new.clone(),
+            OpType::FuncDefn(ops::FuncDefn { signature, .. }) => {
+                rename_set(&mut signature.input_resources);
+                rename_set(&mut signature.output_resources);
+            }
+            OpType::Output(output) => rename_set(&mut output.resources),
+            _ => {}
+        })?;
+        assert_eq!(renamed, 1);
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(port_counts(&hugr), before);
+        Ok(())
+    }
+
+    #[test]
+    fn map_region() -> Result<(), BuildError> {
+        let mut hugr = resource_module(&"A".into())?;
+        let other = hugr.children(hugr.root()).nth(1).unwrap();
+        let mut visited = Vec::new();
+        hugr.map_region_ops(other, |node, _| visited.push(node))?;
+        assert_eq!(visited, hugr.children(other).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn transplant_children() {
+        let mut hugr = Hugr::default();
+        let root = hugr.root();
+        let dfg = || ops::DFG {
+            signature: Signature::new_df(type_row![NAT], type_row![NAT]),
+            resource_delta: None,
+        };
+        let noop = || LeafOp::Noop { ty: NAT };
+        let src = hugr.add_op_with_parent(root, dfg()).unwrap();
+        let dst = hugr.add_op_with_parent(root, dfg()).unwrap();
+        let first = hugr.add_op_with_parent(dst, noop()).unwrap();
+        let moved: Vec<Node> = (0..4)
+            .map(|_| hugr.add_op_with_parent(src, noop()).unwrap())
+            .collect();
+
+        hugr.transplant_children(src, dst).unwrap();
+        assert_eq!(hugr.children(src).next(), None);
+        let expected: Vec<Node> = [first].into_iter().chain(moved).collect();
+        assert_eq!(hugr.children(dst).collect::<Vec<_>>(), expected);
+
+        // Moving to the same node does nothing.
+        hugr.transplant_children(dst, dst).unwrap();
+        assert_eq!(hugr.children(dst).collect::<Vec<_>>(), expected);
+
+        // The root cannot be moved under its own descendants.
+        assert_eq!(
+            hugr.transplant_children(root, dst),
+            Err(HugrError::TransplantIntoDescendant { src: root, dst })
+        );
+        assert_eq!(hugr.children(root).collect::<Vec<_>>(), [src, dst]);
+        assert_eq!(hugr.children(dst).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn duplicate_function() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let three = module_builder.add_constant(ConstValue::i64(3))?;
+        let mut main = module_builder
+            .define_function("main", Signature::new_df(type_row![NAT], type_row![NAT]))?;
+        let [x] = main.input_wires_arr();
+        let predicate = main.make_predicate(0, [type_row![], type_row![]], [])?;
+        let mut conditional = main.conditional_builder(
+            (vec![type_row![]; 2], predicate),
+            [(NAT, x)],
+            type_row![NAT],
+        )?;
+        let case = conditional.case_builder(0)?;
+        let [x] = case.input_wires_arr();
+        case.finish_with_outputs([x])?;
+        let mut case = conditional.case_builder(1)?;
+        let three = case.load_const(&three)?;
+        case.finish_with_outputs([three])?;
+        let conditional = conditional.finish_sub_container()?;
+        let main = main.finish_with_outputs(conditional.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+        let root = hugr.root();
+        let descendants = hugr.node_count() - 2;
+
+        // Without the edge from the constant, the copy is not valid.
+        let mut copy = hugr.clone();
+        let node_map = copy.duplicate_subtree(main.node(), root).unwrap();
+        assert_eq!(node_map.len(), descendants);
+        assert!(copy.validate().is_err());
+
+        let mut copy = hugr.clone();
+        let node_map = copy
+            .duplicate_subtree_with_external_edges(main.node(), root)
+            .unwrap();
+        assert_eq!(copy.validate(), Ok(()));
+        assert_eq!(copy.node_count(), hugr.node_count() + descendants);
+        assert_eq!(
+            copy.children(root).next_back(),
+            Some(node_map[&main.node()])
+        );
+        for (&node, &new_node) in &node_map {
+            assert_eq!(copy.get_optype(node), copy.get_optype(new_node));
+            assert_eq!(copy.num_inputs(node), copy.num_inputs(new_node));
+            assert_eq!(copy.num_outputs(node), copy.num_outputs(new_node));
+            // The children and the links stay in order.
+            let children: Vec<Node> = copy.children(node).map(|n| node_map[&n]).collect();
+            assert_eq!(copy.children(new_node).collect::<Vec<_>>(), children);
+            let links = |n| {
+                copy.all_linked_ports(n, Direction::Outgoing)
+                    .collect::<Vec<_>>()
+            };
+            let mapped: Vec<_> = links(node)
+                .into_iter()
+                .map(|((src, src_port), (tgt, tgt_port))| {
+                    ((node_map[&src], src_port), (node_map[&tgt], tgt_port))
+                })
+                .collect();
+            assert_eq!(links(new_node), mapped);
+        }
+
+        // The copy cannot be added inside the copied subtree.
+        let mut copy = hugr.clone();
+        for new_parent in [main.node(), conditional.node()] {
+            assert_eq!(
+                copy.duplicate_subtree(main.node(), new_parent),
+                Err(HugrError::DuplicateIntoSubtree {
+                    src: main.node(),
+                    new_parent
+                })
+            );
+        }
+        assert_eq!(copy.node_count(), hugr.node_count());
+        Ok(())
+    }
+
+    #[test]
+    fn purge_dangling_ports() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let [input] = builder.input_wires_arr();
+        let noop = builder.add_dataflow_op(LeafOp::Noop { ty: NAT }, [input])?;
+        let mut hugr = builder.finish_hugr_with_outputs(noop.outputs())?;
+        let noop = noop.node();
+
+        hugr.add_ports(noop, Direction::Incoming, 2)?;
+        hugr.add_ports(noop, Direction::Outgoing, 1)?;
+        assert_matches!(
+            hugr.validate(),
+            Err(ValidationError::WrongNumberOfPorts { node, .. }) => assert_eq!(node, noop)
+        );
+
+        assert_eq!(hugr.purge_all_dangling_other_ports(), 3);
+        let optype = hugr.get_optype(noop);
+        for dir in Direction::BOTH {
+            assert_eq!(hugr.num_ports(noop, dir), optype.port_count(dir));
+        }
+        hugr.validate().unwrap();
+
+        // Nothing is left to remove.
+        assert_eq!(hugr.purge_dangling_other_ports(noop), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn map_ops_port_change() {
+        let mut hugr = resource_module(&"A".into()).unwrap();
+        let custom = hugr
+            .nodes()
+            .find(|&n| matches!(hugr.get_optype(n), OpType::LeafOp(LeafOp::CustomOp(_))))
+            .unwrap();
+        let result = hugr.map_ops(|_, op| {
+            if let OpType::LeafOp(LeafOp::CustomOp(_)) = op {
+                *op = LeafOp::CX.into();
+            }
+        });
+        assert_matches!(
+            result,
+            Err(HugrError::PortCountChanged { node, .. }) => assert_eq!(node, custom)
+        );
+    }
 }
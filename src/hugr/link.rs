@@ -0,0 +1,403 @@
+//! Linking of separately built module HUGRs.
+//!
+//! [`Hugr::link`] moves the functions and aliases of a module into another
+//! one, resolving the declarations of each module against the definitions of
+//! the other.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use itertools::Itertools;
+use smol_str::SmolStr;
+use thiserror::Error;
+
+use crate::ops::{OpName, OpTag, OpTrait, OpType};
+use crate::types::Signature;
+use crate::{Hugr, HugrView, Node};
+
+use super::{HugrError, HugrMut};
+
+/// How [`Hugr::link`] treats a function or alias defined in both modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkPolicy {
+    /// Report a [`LinkError::DuplicateDefinition`].
+    #[default]
+    Error,
+    /// Keep the definition of the module being linked into, and use it in
+    /// place of the other one. Both definitions must have the same signature.
+    KeepFirst,
+    /// Keep both definitions, renaming the one of the module being linked in.
+    ///
+    /// Aliases are referred to by name in types, so they are never renamed:
+    /// identical alias definitions are merged, and differing ones are
+    /// reported as duplicates.
+    Rename,
+}
+
+/// Errors reported by [`Hugr::link`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[allow(missing_docs)]
+pub enum LinkError {
+    /// One of the HUGRs is not rooted at a module.
+    #[error("Only modules can be linked, found a {0} root.")]
+    NotAModule(SmolStr),
+    /// Both modules define the same function or alias. `first` is the node in
+    /// the module being linked into, `second` the node in the linked module.
+    #[error("{name} is defined by both {first:?} and {second:?}.")]
+    DuplicateDefinition {
+        name: SmolStr,
+        first: Node,
+        second: Node,
+    },
+    /// A function or alias is given incompatible types by the two modules.
+    #[error("{first:?} and {second:?} give different types to {name}.")]
+    Mismatch {
+        name: SmolStr,
+        first: Node,
+        second: Node,
+    },
+    /// Error while moving the nodes.
+    #[error("Error while linking the modules: {0}.")]
+    HugrError(#[from] HugrError),
+}
+
+impl Hugr {
+    /// Link the module `other` into this one.
+    ///
+    /// The children of the root of `other` are moved under the root of
+    /// `self`. Function declarations of either module are resolved against
+    /// the same-named definitions of the other one: their calls are rewired
+    /// to the definition and the declaration is removed. Declarations present
+    /// in both modules are merged, and so are alias declarations. Functions
+    /// and aliases defined in both modules are treated according to `policy`.
+    ///
+    /// Returns a map from the nodes of `other` to the nodes standing for them
+    /// in `self`. Resolved declarations map to the definitions replacing
+    /// them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, leaving `self` unchanged, if either HUGR is not a
+    /// module, if the two modules give different types to the same name, or
+    /// if they both define a name and `policy` is [`LinkPolicy::Error`].
+    pub fn link(
+        &mut self,
+        other: Hugr,
+        policy: LinkPolicy,
+    ) -> Result<HashMap<Node, Node>, LinkError> {
+        for hugr in [&*self, &other] {
+            if hugr.root_type().tag() != OpTag::ModuleRoot {
+                return Err(LinkError::NotAModule(hugr.root_type().name()));
+            }
+        }
+
+        // Plan the resolution before modifying `self`, so that errors leave
+        // it untouched. Nodes of `other` are translated once it is inserted.
+        let ours = symbols(self);
+        let theirs = symbols(&other);
+        let mut taken: HashSet<(Namespace, SmolStr)> = ours.keys().cloned().collect();
+        taken.extend(theirs.keys().cloned());
+        let mut resolutions = Vec::new();
+        for (key, &second) in theirs.iter().sorted_by_key(|(_, &node)| node) {
+            let Some(&first) = ours.get(key) else {
+                continue;
+            };
+            let (first_op, second_op) = (self.get_optype(first), other.get_optype(second));
+            let name = key.1.clone();
+            let duplicate = is_definition(first_op) && is_definition(second_op);
+            let resolution = match (duplicate, policy, key.0) {
+                (true, LinkPolicy::Error, _) => {
+                    return Err(LinkError::DuplicateDefinition {
+                        name,
+                        first,
+                        second,
+                    })
+                }
+                (true, LinkPolicy::Rename, Namespace::Function) => {
+                    let fresh = (1..)
+                        .map(|i| SmolStr::from(format!("{name}_{i}")))
+                        .find(|fresh| !taken.contains(&(Namespace::Function, fresh.clone())))
+                        .unwrap();
+                    taken.insert((Namespace::Function, fresh.clone()));
+                    Resolution::Rename(second, fresh)
+                }
+                (true, LinkPolicy::Rename, Namespace::Alias) if first_op != second_op => {
+                    return Err(LinkError::DuplicateDefinition {
+                        name,
+                        first,
+                        second,
+                    })
+                }
+                _ if !compatible(first_op, second_op) => {
+                    return Err(LinkError::Mismatch {
+                        name,
+                        first,
+                        second,
+                    })
+                }
+                _ if is_definition(second_op) && !is_definition(first_op) => {
+                    Resolution::ReplaceOurs(first, second)
+                }
+                _ => Resolution::ReplaceTheirs(second, first),
+            };
+            resolutions.push(resolution);
+        }
+
+        // Move the children of the other root under ours.
+        let other_root = other.root();
+        let other_nodes = descendants(&other, other_root);
+        let inserted = self.insert_hugr(self.root(), other)?;
+        let mut node_map: HashMap<Node, Node> = other_nodes
+            .into_iter()
+            .zip(descendants(self, inserted))
+            .collect();
+        self.transplant_children(inserted, self.root())?;
+        self.remove_node(inserted)?;
+        node_map.remove(&other_root);
+
+        for resolution in resolutions {
+            match resolution {
+                Resolution::Rename(node, name) => {
+                    match self.op_types.get_mut(node_map[&node].index) {
+                        OpType::FuncDefn(defn) => defn.name = name.to_string(),
+                        _ => unreachable!("Only functions are renamed."),
+                    }
+                }
+                Resolution::ReplaceOurs(ours, theirs) => {
+                    self.replace_symbol(ours, node_map[&theirs])?;
+                }
+                Resolution::ReplaceTheirs(theirs, ours) => {
+                    self.replace_symbol(node_map[&theirs], ours)?;
+                    node_map.insert(theirs, ours);
+                }
+            }
+        }
+        // Drop the bodies of the replaced definitions.
+        node_map.retain(|_, &mut node| self.contains_node(node));
+        Ok(node_map)
+    }
+
+    /// Rewire the static edges out of a function or alias node to another
+    /// one, and remove it with its descendants.
+    fn replace_symbol(&mut self, node: Node, replacement: Node) -> Result<(), HugrError> {
+        let links: Vec<_> = self
+            .node_outputs(node)
+            .flat_map(|port| {
+                self.linked_ports(node, port)
+                    .map(move |(target, target_port)| (port, target, target_port))
+            })
+            .collect();
+        for (port, target, target_port) in links {
+            self.disconnect(target, target_port)?;
+            self.connect(replacement, port.index(), target, target_port.index())?;
+        }
+        self.remove_subtree(node)
+    }
+}
+
+/// The namespaces of the names defined at the top level of a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Namespace {
+    Function,
+    Alias,
+}
+
+/// A modification of the linked module, planned before linking.
+enum Resolution {
+    /// Rename the function definition of the other module.
+    Rename(Node, SmolStr),
+    /// Replace our node with the node of the other module.
+    ReplaceOurs(Node, Node),
+    /// Replace the node of the other module with ours.
+    ReplaceTheirs(Node, Node),
+}
+
+/// The first function or alias with each name at the top level of a module.
+fn symbols(hugr: &Hugr) -> HashMap<(Namespace, SmolStr), Node> {
+    let mut symbols = HashMap::new();
+    for node in hugr.children(hugr.root()) {
+        let key = match hugr.get_optype(node) {
+            OpType::FuncDefn(f) => (Namespace::Function, f.name.as_str().into()),
+            OpType::FuncDecl(f) => (Namespace::Function, f.name.as_str().into()),
+            OpType::AliasDefn(a) => (Namespace::Alias, a.name.clone()),
+            OpType::AliasDecl(a) => (Namespace::Alias, a.name.clone()),
+            _ => continue,
+        };
+        symbols.entry(key).or_insert(node);
+    }
+    symbols
+}
+
+fn is_definition(op: &OpType) -> bool {
+    matches!(op, OpType::FuncDefn(_) | OpType::AliasDefn(_))
+}
+
+/// Whether two functions or aliases with the same name have the same type.
+fn compatible(first: &OpType, second: &OpType) -> bool {
+    match (first, second) {
+        (OpType::AliasDefn(a), OpType::AliasDefn(b)) => a.definition == b.definition,
+        (OpType::AliasDefn(defn), OpType::AliasDecl(decl))
+        | (OpType::AliasDecl(decl), OpType::AliasDefn(defn)) => {
+            defn.definition.is_linear() == decl.linear
+        }
+        (OpType::AliasDecl(a), OpType::AliasDecl(b)) => a.linear == b.linear,
+        _ => function_signature(first).is_some_and(|sig| Some(sig) == function_signature(second)),
+    }
+}
+
+fn function_signature(op: &OpType) -> Option<&Signature> {
+    match op {
+        OpType::FuncDefn(f) => Some(&f.signature),
+        OpType::FuncDecl(f) => Some(&f.signature),
+        _ => None,
+    }
+}
+
+/// The nodes of the subtree rooted at `root`, in breadth-first order.
+fn descendants(hugr: &Hugr, root: Node) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut queue = VecDeque::from([root]);
+    while let Some(node) = queue.pop_front() {
+        queue.extend(hugr.children(node));
+        nodes.push(node);
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+        HugrBuilder, ModuleBuilder,
+    };
+    use crate::ops::LeafOp;
+    use crate::type_row;
+    use crate::types::{ClassicType, SimpleType};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    fn bit_signature() -> Signature {
+        Signature::new_df(type_row![BIT], type_row![BIT])
+    }
+
+    /// A module defining `helper`, passing a bit through.
+    fn library() -> Result<Hugr, BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        module_builder.add_alias_def("Flag", BIT)?;
+        let mut helper = module_builder.define_function("helper", bit_signature())?;
+        let noop = helper.add_dataflow_op(LeafOp::Noop { ty: BIT }, helper.input_wires())?;
+        helper.finish_with_outputs(noop.outputs())?;
+        module_builder.finish_hugr()
+    }
+
+    /// A module declaring `helper` and calling it twice from `main`.
+    fn main_module() -> Result<Hugr, BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        module_builder.add_alias_declare("Flag", false)?;
+        let helper = module_builder.declare("helper", bit_signature())?;
+        let mut main = module_builder.define_function("main", bit_signature())?;
+        let call = main.call(&helper, main.input_wires())?;
+        let call = main.call(&helper, call.outputs())?;
+        main.finish_with_outputs(call.outputs())?;
+        module_builder.finish_hugr()
+    }
+
+    fn functions(hugr: &Hugr, name: &str) -> Vec<Node> {
+        hugr.children(hugr.root())
+            .filter(|&n| function_name(hugr.get_optype(n)) == Some(name))
+            .collect()
+    }
+
+    fn function_name(op: &OpType) -> Option<&str> {
+        match op {
+            OpType::FuncDefn(f) => Some(&f.name),
+            OpType::FuncDecl(f) => Some(&f.name),
+            _ => None,
+        }
+    }
+
+    fn assert_linked(hugr: &Hugr) {
+        assert_eq!(hugr.validate(), Ok(()));
+        let [helper] = functions(hugr, "helper")[..] else {
+            panic!("Expected a single helper function.")
+        };
+        assert_matches!(hugr.get_optype(helper), OpType::FuncDefn(_));
+        let calls: Vec<Node> = hugr
+            .nodes()
+            .filter(|&n| matches!(hugr.get_optype(n), OpType::Call(_)))
+            .collect();
+        assert_eq!(calls.len(), 2);
+        for call in calls {
+            assert_eq!(hugr.input_neighbours(call).last(), Some(helper));
+        }
+        let aliases = hugr
+            .children(hugr.root())
+            .filter(|&n| hugr.get_optype(n).tag() == OpTag::Alias)
+            .map(|n| hugr.get_optype(n))
+            .collect_vec();
+        assert_matches!(aliases.as_slice(), [OpType::AliasDefn(_)]);
+    }
+
+    #[test]
+    fn link_declaration() -> Result<(), BuildError> {
+        let mut hugr = main_module()?;
+        let lib = library()?;
+        let lib_helper = functions(&lib, "helper")[0];
+        let node_map = hugr.link(lib, LinkPolicy::Error).unwrap();
+        assert_linked(&hugr);
+        assert_eq!(node_map[&lib_helper], functions(&hugr, "helper")[0]);
+
+        // Linking the other way round resolves the declaration being linked in.
+        let mut hugr = library()?;
+        let main = main_module()?;
+        let main_helper = functions(&main, "helper")[0];
+        let node_map = hugr.link(main, LinkPolicy::Error).unwrap();
+        assert_linked(&hugr);
+        assert_eq!(node_map[&main_helper], functions(&hugr, "helper")[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn link_duplicate_definitions() -> Result<(), BuildError> {
+        let mut hugr = library()?;
+        let unchanged = hugr.clone();
+        assert_matches!(
+            hugr.link(library()?, LinkPolicy::Error),
+            Err(LinkError::DuplicateDefinition { name, .. }) => assert_eq!(name, "Flag")
+        );
+        assert_eq!(hugr, unchanged);
+
+        hugr.link(library()?, LinkPolicy::KeepFirst).unwrap();
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(functions(&hugr, "helper").len(), 1);
+        assert_eq!(hugr.node_count(), unchanged.node_count());
+
+        hugr.link(library()?, LinkPolicy::Rename).unwrap();
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(functions(&hugr, "helper").len(), 1);
+        assert_eq!(functions(&hugr, "helper_1").len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn link_mismatch() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        module_builder.declare("helper", Signature::new_df(type_row![BIT], type_row![]))?;
+        let mut hugr = module_builder.finish_hugr()?;
+        assert_matches!(
+            hugr.link(library()?, LinkPolicy::KeepFirst),
+            Err(LinkError::Mismatch { name, .. }) => assert_eq!(name, "helper")
+        );
+
+        let dfg = DFGBuilder::new(type_row![BIT], type_row![BIT])?;
+        let [b] = dfg.input_wires_arr();
+        let dfg = dfg.finish_hugr_with_outputs([b])?;
+        assert_matches!(
+            hugr.link(dfg, LinkPolicy::Error),
+            Err(LinkError::NotAModule(_))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+//! Non-fatal analyses reporting legal but suspicious HUGR constructs.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ops::custom::ExternalOp;
+use crate::ops::{LeafOp, OpTag, OpTrait, OpType};
+use crate::types::EdgeKind;
+use crate::{Direction, Hugr, HugrView, Node, Port};
+
+use super::view::function_name;
+use super::ValidationError;
+
+/// A suspicious construct in a HUGR.
+///
+/// Lints do not make the HUGR invalid, but are usually a sign of a bug in the
+/// code that generated it. Each lint records the path of ancestors (from the
+/// root) of the offending node.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[allow(missing_docs)]
+pub enum Lint {
+    /// A [`Const`] node that is never loaded or used.
+    ///
+    /// [`Const`]: crate::ops::Const
+    UnusedConst { node: Node, path: Vec<Node> },
+    /// A [`FuncDecl`] node that is never called.
+    ///
+    /// [`FuncDecl`]: crate::ops::FuncDecl
+    UnusedFunction { node: Node, path: Vec<Node> },
+    /// A dataflow node with no connections in one direction, whose order port
+    /// is also unconnected. The node is not ordered with respect to its
+    /// siblings on that side.
+    UnconnectedOrderPort {
+        node: Node,
+        port: Port,
+        path: Vec<Node>,
+    },
+    /// A function definition or declaration with the same name as a previous
+    /// sibling function. Lookups by name only find the first one.
+    DuplicateFunctionName {
+        node: Node,
+        name: String,
+        first: Node,
+        path: Vec<Node>,
+    },
+    /// An unresolved resource operation which does not record the version of
+    /// its resource, usually because it was saved before versions were
+    /// recorded. It is resolved against any version of the resource.
+    UnversionedOp { node: Node, path: Vec<Node> },
+}
+
+/// The kinds of [`Lint`], used to configure their [`LintLevel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum LintKind {
+    UnusedConst,
+    UnusedFunction,
+    UnconnectedOrderPort,
+    DuplicateFunctionName,
+    UnversionedOp,
+}
+
+/// How a [`Lint`] is treated by [`Hugr::validate_strict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LintLevel {
+    /// The lint is ignored.
+    Allow,
+    /// The lint is reported by [`Hugr::lints`], but is not an error.
+    #[default]
+    Warn,
+    /// The lint is a validation error.
+    Deny,
+}
+
+impl Lint {
+    /// The kind of the lint.
+    pub fn kind(&self) -> LintKind {
+        match self {
+            Lint::UnusedConst { .. } => LintKind::UnusedConst,
+            Lint::UnusedFunction { .. } => LintKind::UnusedFunction,
+            Lint::UnconnectedOrderPort { .. } => LintKind::UnconnectedOrderPort,
+            Lint::DuplicateFunctionName { .. } => LintKind::DuplicateFunctionName,
+            Lint::UnversionedOp { .. } => LintKind::UnversionedOp,
+        }
+    }
+
+    /// The node the lint refers to.
+    pub fn node(&self) -> Node {
+        match self {
+            Lint::UnusedConst { node, .. }
+            | Lint::UnusedFunction { node, .. }
+            | Lint::UnconnectedOrderPort { node, .. }
+            | Lint::DuplicateFunctionName { node, .. }
+            | Lint::UnversionedOp { node, .. } => *node,
+        }
+    }
+
+    /// The ancestors of the node, starting from the root.
+    pub fn path(&self) -> &[Node] {
+        match self {
+            Lint::UnusedConst { path, .. }
+            | Lint::UnusedFunction { path, .. }
+            | Lint::UnconnectedOrderPort { path, .. }
+            | Lint::DuplicateFunctionName { path, .. }
+            | Lint::UnversionedOp { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::UnusedConst { node, .. } => write!(f, "The constant {node:?} is never used")?,
+            Lint::UnusedFunction { node, .. } => {
+                write!(f, "The function declaration {node:?} is never called")?
+            }
+            Lint::UnconnectedOrderPort { node, port, .. } => write!(
+                f,
+                "The node {node:?} has no connections in direction {:?}, and its order port {port:?} is unconnected",
+                port.direction()
+            )?,
+            Lint::DuplicateFunctionName {
+                node, name, first, ..
+            } => write!(
+                f,
+                "The function {node:?} has the same name {name:?} as the function {first:?}"
+            )?,
+            Lint::UnversionedOp { node, .. } => write!(
+                f,
+                "The operation {node:?} does not record the version of its resource"
+            )?,
+        }
+        write!(f, ". In path {:?}.", self.path())
+    }
+}
+
+impl Hugr {
+    /// Report all the [`Lint`]s in the HUGR.
+    ///
+    /// This does not check the validity of the HUGR, see [`Hugr::validate`].
+    pub fn lints(&self) -> Vec<Lint> {
+        let path = |node: Node| {
+            let mut path: Vec<Node> = self.ancestors(node).collect();
+            path.reverse();
+            path
+        };
+
+        let mut lints = Vec::new();
+        for node in self.nodes() {
+            let optype = self.get_optype(node);
+            match optype {
+                OpType::Const(_) | OpType::ConstRow(_) if !self.has_static_out_link(node) => lints
+                    .push(Lint::UnusedConst {
+                        node,
+                        path: path(node),
+                    }),
+                OpType::LeafOp(LeafOp::CustomOp(ExternalOp::Opaque(op)))
+                    if op.version().is_unversioned() =>
+                {
+                    lints.push(Lint::UnversionedOp {
+                        node,
+                        path: path(node),
+                    })
+                }
+                OpType::FuncDecl(_) if !self.has_static_out_link(node) => {
+                    lints.push(Lint::UnusedFunction {
+                        node,
+                        path: path(node),
+                    })
+                }
+                OpType::Module(_) => {
+                    let mut names = HashMap::new();
+                    for child in self.children(node) {
+                        let Some(name) = function_name(self.get_optype(child)) else {
+                            continue;
+                        };
+                        if let Some(&first) = names.get(name) {
+                            lints.push(Lint::DuplicateFunctionName {
+                                node: child,
+                                name: name.to_string(),
+                                first,
+                                path: path(child),
+                            });
+                        } else {
+                            names.insert(name, child);
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            let tag = optype.tag();
+            if node == self.root()
+                || !OpTag::DataflowChild.is_superset(tag)
+                || tag == OpTag::Input
+                || tag == OpTag::Output
+            {
+                continue;
+            }
+            for dir in Direction::BOTH {
+                if optype.other_port(dir) != Some(EdgeKind::StateOrder) {
+                    continue;
+                }
+                let Some(port) = optype.other_port_index(dir) else {
+                    continue;
+                };
+                if !self.node_ports(node, dir).any(|p| self.is_linked(node, p)) {
+                    lints.push(Lint::UnconnectedOrderPort {
+                        node,
+                        port,
+                        path: path(node),
+                    });
+                }
+            }
+        }
+        lints
+    }
+
+    /// Check the validity of the HUGR, additionally treating the [`Lint`]s
+    /// configured with [`LintLevel::Deny`] as errors.
+    ///
+    /// Lints not present in `levels` use the default [`LintLevel::Warn`].
+    pub fn validate_strict(
+        &self,
+        levels: &HashMap<LintKind, LintLevel>,
+    ) -> Result<(), ValidationError> {
+        self.validate()?;
+        match self
+            .lints()
+            .into_iter()
+            .find(|lint| levels.get(&lint.kind()).copied().unwrap_or_default() == LintLevel::Deny)
+        {
+            Some(lint) => Err(ValidationError::DeniedLint(lint)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether any of the static output ports of a node is connected.
+    fn has_static_out_link(&self, node: Node) -> bool {
+        self.node_outputs(node)
+            .any(|port| self.is_linked(node, port))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+        HugrBuilder, ModuleBuilder,
+    };
+    use crate::ops::custom::OpaqueOp;
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::ConstValue;
+    use crate::resource::ResourceVersion;
+    use crate::type_row;
+    use crate::types::{ClassicType, Signature, SimpleType};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    fn deny(kind: LintKind) -> HashMap<LintKind, LintLevel> {
+        HashMap::from([(kind, LintLevel::Deny)])
+    }
+
+    #[test]
+    fn unused_const() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let const_id = builder.add_constant(ConstValue::i64(1))?;
+        let [w] = builder.input_wires_arr();
+        let hugr = builder.finish_hugr_with_outputs([w])?;
+
+        let lints = hugr.lints();
+        assert_eq!(
+            lints,
+            vec![Lint::UnusedConst {
+                node: const_id.node(),
+                path: vec![hugr.root()]
+            }]
+        );
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(hugr.validate_strict(&HashMap::new()), Ok(()));
+        assert_eq!(
+            hugr.validate_strict(&deny(LintKind::UnusedConst)),
+            Err(ValidationError::DeniedLint(lints[0].clone()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unused_function() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let used = module_builder.declare("used", sig.clone())?;
+        let unused = module_builder.declare("unused", sig.clone())?;
+        let mut f_build = module_builder.define_function("main", sig)?;
+        let call = f_build.call(&used, f_build.input_wires())?;
+        f_build.finish_with_outputs(call.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+
+        let lints = hugr.lints();
+        assert_matches!(
+            lints.as_slice(),
+            [Lint::UnusedFunction { node, .. }] => assert_eq!(*node, unused.node())
+        );
+        assert_eq!(hugr.validate(), Ok(()));
+        assert_eq!(
+            hugr.validate_strict(&deny(LintKind::UnusedFunction)),
+            Err(ValidationError::DeniedLint(lints[0].clone()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unconnected_order_port() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let [w] = builder.input_wires_arr();
+        let mut nested =
+            builder.dfg_builder(Signature::new_df(type_row![NAT], type_row![]), [w])?;
+        let [nested_in, nested_out] = nested.io();
+        nested.set_order(&nested_in, &nested_out)?;
+        let nested = nested.finish_with_outputs([])?;
+        let hugr = builder.finish_hugr_with_outputs([w])?;
+
+        let lints = hugr.lints();
+        assert_matches!(
+            lints.as_slice(),
+            [Lint::UnconnectedOrderPort { node, port, path }] => {
+                assert_eq!(*node, nested.node());
+                assert_eq!(port.direction(), Direction::Outgoing);
+                assert_eq!(path, &vec![hugr.root()]);
+            }
+        );
+        assert_eq!(hugr.validate(), Ok(()));
+        let mut levels = deny(LintKind::UnconnectedOrderPort);
+        assert_eq!(
+            hugr.validate_strict(&levels),
+            Err(ValidationError::DeniedLint(lints[0].clone()))
+        );
+        levels.insert(LintKind::UnconnectedOrderPort, LintLevel::Allow);
+        assert_eq!(hugr.validate_strict(&levels), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_function_name() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        module_builder.set_allow_shadowing(true);
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let first = module_builder.declare("f", sig.clone())?;
+        let mut f_build = module_builder.define_function("f", sig)?;
+        let call = f_build.call(&first, f_build.input_wires())?;
+        let second = f_build.finish_with_outputs(call.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+
+        let lints = hugr.lints();
+        assert_eq!(
+            lints,
+            vec![Lint::DuplicateFunctionName {
+                node: second.node(),
+                name: "f".to_string(),
+                first: first.node(),
+                path: vec![hugr.root()],
+            }]
+        );
+        assert_eq!(hugr.function_by_name("f"), Some(first.node()));
+        assert_eq!(
+            hugr.validate_strict(&deny(LintKind::DuplicateFunctionName)),
+            Err(ValidationError::DeniedLint(lints[0].clone()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unversioned_op() {
+        let op = |version| {
+            let sig = Signature::new_df(type_row![], type_row![]);
+            let op = OpaqueOp::new("ext".into(), "op", String::new(), vec![], Some(sig));
+            LeafOp::from(ExternalOp::from(op.with_version(version)))
+        };
+
+        let hugr = Hugr::new(op(ResourceVersion::default()));
+        assert_eq!(
+            hugr.lints(),
+            vec![Lint::UnversionedOp {
+                node: hugr.root(),
+                path: vec![]
+            }]
+        );
+        let hugr = Hugr::new(op(ResourceVersion(0, 1, 0)));
+        assert_eq!(hugr.lints(), vec![]);
+    }
+}
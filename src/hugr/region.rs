@@ -181,6 +181,10 @@ type RegionGraph<'g, Base> = portgraph::view::Region<'g, <Base as HugrInternals>
 /// For a view that includes only the direct children of the root, see
 /// [`FlatRegionView`]. Prefer using [`FlatRegionView`] over this type when
 /// possible, as it is more efficient.
+///
+/// The view caches which nodes are in the region, so unlike [`Hugr`] and
+/// [`FlatRegionView`] it is `Send` but not `Sync`. Create a view per thread to
+/// inspect a region concurrently.
 pub struct RegionView<'g, Base>
 where
     Base: HugrInternals,
@@ -195,6 +199,13 @@ where
     hugr: &'g Base,
 }
 
+/// A view of a HUGR rooted at one of its nodes, returned by
+/// [`HugrView::as_rooted`].
+///
+/// The root node has no parent in the view, so it can be inspected and
+/// validated as if it was the root of a HUGR.
+pub type RootedView<'g, Base = Hugr> = RegionView<'g, Base>;
+
 impl<'g, Base: Clone> Clone for RegionView<'g, Base>
 where
     Base: HugrInternals + HugrView,
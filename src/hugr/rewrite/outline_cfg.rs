@@ -110,10 +110,41 @@ impl Rewrite for OutlineCfg {
 
         // 3. new_block contains input node, sub-cfg, exit node all connected
         let wires_in = inputs.iter().cloned().zip(new_block.input_wires());
-        let cfg = new_block.cfg_builder(wires_in, outputs).unwrap();
+        let mut cfg = new_block.cfg_builder(wires_in, outputs).unwrap();
         let cfg_node = cfg.container_node();
-        let inner_exit = cfg.exit_block().node();
+        let inner_exit = cfg.exit_block().unwrap().node();
+        let h = cfg.hugr_mut();
+
+        // 4. Children of new CFG.
+        // Entry node must be first
+        h.hierarchy.detach(entry.index);
+        h.hierarchy
+            .insert_before(entry.index, inner_exit.index)
+            .unwrap();
+        // And remaining nodes
+        for &n in &self.blocks {
+            // Do not move the entry node, as we have already
+            if n != entry {
+                h.hierarchy.detach(n.index);
+                h.hierarchy.push_child(n.index, cfg_node.index).unwrap();
+            }
+        }
+
+        // 5. Exit edges.
+        // Retarget edge from exit_node (that used to target outside) to inner_exit
+        let exit_port = h
+            .node_outputs(exit)
+            .filter(|p| {
+                let (t, p2) = h.linked_ports(exit, *p).exactly_one().ok().unwrap();
+                assert!(p2.index() == 0);
+                t == outside
+            })
+            .exactly_one()
+            .unwrap();
+        h.disconnect(exit, exit_port).unwrap();
+        h.connect(exit, exit_port.index(), inner_exit, 0).unwrap();
         let cfg_outputs = cfg.finish_sub_container().unwrap().outputs();
+
         let predicate = new_block
             .add_constant(ConstValue::simple_predicate(0, 1))
             .unwrap();
@@ -123,7 +154,7 @@ impl Rewrite for OutlineCfg {
             .unwrap()
             .node();
 
-        // 4. Entry edges. Change any edges into entry_block from outside, to target new_block
+        // 6. Entry edges. Change any edges into entry_block from outside, to target new_block
         let h = existing_cfg.hugr_mut();
 
         let preds: Vec<_> = h
@@ -137,41 +168,13 @@ impl Rewrite for OutlineCfg {
         }
         if is_outer_entry {
             // new_block must be the entry node, i.e. first child, of the enclosing CFG
-            // (the current entry node will be reparented inside new_block below)
+            // (the current entry node has been reparented inside new_block above)
             let parent = h.hierarchy.detach(new_block.index).unwrap();
             h.hierarchy
                 .push_front_child(new_block.index, parent)
                 .unwrap();
         }
 
-        // 5. Children of new CFG.
-        // Entry node must be first
-        h.hierarchy.detach(entry.index);
-        h.hierarchy
-            .insert_before(entry.index, inner_exit.index)
-            .unwrap();
-        // And remaining nodes
-        for n in self.blocks {
-            // Do not move the entry node, as we have already
-            if n != entry {
-                h.hierarchy.detach(n.index);
-                h.hierarchy.push_child(n.index, cfg_node.index).unwrap();
-            }
-        }
-
-        // 6. Exit edges.
-        // Retarget edge from exit_node (that used to target outside) to inner_exit
-        let exit_port = h
-            .node_outputs(exit)
-            .filter(|p| {
-                let (t, p2) = h.linked_ports(exit, *p).exactly_one().ok().unwrap();
-                assert!(p2.index() == 0);
-                t == outside
-            })
-            .exactly_one()
-            .unwrap();
-        h.disconnect(exit, exit_port).unwrap();
-        h.connect(exit, exit_port.index(), inner_exit, 0).unwrap();
         // And connect new_block to outside instead
         h.connect(new_block, 0, outside, 0).unwrap();
 
@@ -5,20 +5,22 @@ use std::collections::{HashMap, HashSet};
 use itertools::Itertools;
 use portgraph::{LinkMut, LinkView, MultiMut, NodeIndex, PortView};
 
+use crate::algorithm::convex::{is_replaceable, ReplaceabilityError};
 use crate::hugr::{HugrMut, HugrView, NodeMetadata};
 use crate::{
     hugr::{Node, Rewrite},
-    ops::{OpTag, OpTrait, OpType},
-    Hugr, Port,
+    ops::{OpTag, OpTrait, OpType, PortClass},
+    Direction, Hugr, Port,
 };
 use thiserror::Error;
 
 /// Specification of a simple replacement operation.
 #[derive(Debug, Clone)]
 pub struct SimpleReplacement {
-    /// The common DFG parent of all nodes to be replaced.
+    /// The common dataflow parent of all nodes to be replaced.
     pub parent: Node,
-    /// The set of nodes to remove (a convex set of leaf children of `parent`).
+    /// The set of nodes to remove (a convex set of leaf children of `parent`,
+    /// see [`is_replaceable`]).
     pub removal: HashSet<Node>,
     /// A hugr with DFG root (consisting of replacement nodes).
     pub replacement: Hugr,
@@ -47,19 +49,36 @@ impl SimpleReplacement {
             nu_out,
         }
     }
+
+    /// Create a new [`SimpleReplacement`] specification, checking that no node
+    /// is listed twice for removal.
+    pub fn try_new(
+        parent: Node,
+        removal: impl IntoIterator<Item = Node>,
+        replacement: Hugr,
+        nu_inp: HashMap<(Node, Port), (Node, Port)>,
+        nu_out: HashMap<(Node, Port), Port>,
+    ) -> Result<Self, SimpleReplacementError> {
+        let mut removal_set = HashSet::new();
+        for node in removal {
+            if !removal_set.insert(node) {
+                return Err(SimpleReplacementError::DuplicateRemovedNode(node));
+            }
+        }
+        Ok(Self::new(parent, removal_set, replacement, nu_inp, nu_out))
+    }
 }
 
 impl Rewrite for SimpleReplacement {
     type Error = SimpleReplacementError;
     const UNCHANGED_ON_FAILURE: bool = true;
 
-    fn verify(&self, _h: &Hugr) -> Result<(), SimpleReplacementError> {
-        unimplemented!()
-    }
-
-    fn apply(self, h: &mut Hugr) -> Result<(), SimpleReplacementError> {
-        // 1. Check the parent node exists and is a DFG node.
-        if h.get_optype(self.parent).tag() != OpTag::Dfg {
+    /// Perform all the checks of [`Rewrite::apply`] without mutating the Hugr.
+    fn verify(&self, h: &Hugr) -> Result<(), SimpleReplacementError> {
+        // 1. Check the parent node exists and is a dataflow parent with Input and Output children.
+        if !OpTag::DataflowParent.is_superset(h.get_optype(self.parent).tag())
+            || h.get_io(self.parent).is_none()
+        {
             return Err(SimpleReplacementError::InvalidParentNode());
         }
         // 2. Check that all the to-be-removed nodes are children of it and are leaves.
@@ -70,18 +89,16 @@ impl Rewrite for SimpleReplacement {
                 return Err(SimpleReplacementError::InvalidRemovedNode());
             }
         }
-        // 3. Do the replacement.
-        // 3.1. Add copies of all replacement nodes and edges to h. Exclude Input/Output nodes.
-        // Create map from old NodeIndex (in self.replacement) to new NodeIndex (in self).
-        let mut index_map: HashMap<NodeIndex, NodeIndex> = HashMap::new();
-        let replacement_nodes = self
-            .replacement
-            .children(self.replacement.root())
-            .collect::<Vec<Node>>();
-        // slice of nodes omitting Input and Output:
-        let replacement_inner_nodes = &replacement_nodes[2..];
-        for &node in replacement_inner_nodes {
-            // Check there are no const inputs.
+        is_replaceable(h, self.parent, &self.removal)?;
+        // 3. Check the replacement is a DFG whose nodes have no const inputs.
+        let replacement_root = self.replacement.root();
+        let (replacement_input, replacement_output) =
+            match self.replacement.get_optype(replacement_root).tag() {
+                OpTag::Dfg => self.replacement.get_io(replacement_root),
+                _ => None,
+            }
+            .ok_or(SimpleReplacementError::InvalidReplacementNode())?;
+        for node in self.replacement.children(replacement_root).skip(2) {
             if !self
                 .replacement
                 .get_optype(node)
@@ -92,8 +109,103 @@ impl Rewrite for SimpleReplacement {
                 return Err(SimpleReplacementError::InvalidReplacementNode());
             }
         }
-        let self_output_node_index = h.children(self.parent).nth(1).unwrap();
-        let replacement_output_node = *replacement_nodes.get(1).unwrap();
+        // 4. Check the boundary maps refer to linked input ports of matching kinds.
+        for (&(rep_node, rep_port), &(rem_node, rem_port)) in self.nu_inp.iter().sorted() {
+            linked_input(&self.replacement, rep_node, rep_port)
+                .filter(|&pred| pred == replacement_input)
+                .ok_or(SimpleReplacementError::InvalidReplacementPort(
+                    rep_node, rep_port,
+                ))?;
+            linked_input(h, rem_node, rem_port)
+                .filter(|pred| self.removal.contains(&rem_node) && !self.removal.contains(pred))
+                .ok_or(SimpleReplacementError::InvalidBoundaryPort(
+                    rem_node, rem_port,
+                ))?;
+            if h.get_optype(rem_node).port_kind(rem_port)
+                != self.replacement.get_optype(rep_node).port_kind(rep_port)
+            {
+                return Err(SimpleReplacementError::BoundaryKindMismatch(
+                    rem_node, rem_port,
+                ));
+            }
+        }
+        for (&(rem_out_node, rem_out_port), &rep_out_port) in self.nu_out.iter().sorted() {
+            linked_input(h, rem_out_node, rem_out_port)
+                .filter(|pred| !self.removal.contains(&rem_out_node) && self.removal.contains(pred))
+                .ok_or(SimpleReplacementError::InvalidBoundaryPort(
+                    rem_out_node,
+                    rem_out_port,
+                ))?;
+            linked_input(&self.replacement, replacement_output, rep_out_port).ok_or(
+                SimpleReplacementError::InvalidReplacementPort(replacement_output, rep_out_port),
+            )?;
+            if h.get_optype(rem_out_node).port_kind(rem_out_port)
+                != self
+                    .replacement
+                    .get_optype(replacement_output)
+                    .port_kind(rep_out_port)
+            {
+                return Err(SimpleReplacementError::BoundaryKindMismatch(
+                    rem_out_node,
+                    rem_out_port,
+                ));
+            }
+        }
+        // 5. Check every dataflow edge crossing the boundary of the removed
+        // nodes is covered by the boundary maps.
+        let covered_inputs: HashSet<(Node, Port)> = self.nu_inp.values().copied().collect();
+        for &node in self.removal.iter().sorted() {
+            let optype = h.get_optype(node);
+            let value_ports = h
+                .all_node_ports(node)
+                .filter(|&p| matches!(optype.port_row_index(p), Some(PortClass::Value(_))));
+            for port in value_ports {
+                if port.direction() == Direction::Incoming {
+                    let external = h
+                        .linked_ports(node, port)
+                        .any(|(pred, _)| !self.removal.contains(&pred));
+                    if external && !covered_inputs.contains(&(node, port)) {
+                        return Err(SimpleReplacementError::UncoveredBoundaryPort(node, port));
+                    }
+                    continue;
+                }
+                for (succ, succ_port) in h.linked_ports(node, port) {
+                    if !self.removal.contains(&succ)
+                        && !self.nu_out.contains_key(&(succ, succ_port))
+                    {
+                        return Err(SimpleReplacementError::UncoveredBoundaryPort(
+                            succ, succ_port,
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the replacement.
+    ///
+    /// All the checks are performed by [`Rewrite::verify`] before
+    /// the Hugr is modified, so it is left untouched if an error is returned.
+    fn apply(self, h: &mut Hugr) -> Result<(), SimpleReplacementError> {
+        self.verify(h)?;
+        // 3. Do the replacement.
+        // 3.1. Add copies of all replacement nodes and edges to h. Exclude Input/Output nodes.
+        // Create map from old NodeIndex (in self.replacement) to new NodeIndex (in self).
+        let mut index_map: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let replacement_nodes = self
+            .replacement
+            .children(self.replacement.root())
+            .collect::<Vec<Node>>();
+        // slice of nodes omitting Input and Output:
+        let replacement_inner_nodes = &replacement_nodes[2..];
+        h.reserve(
+            replacement_inner_nodes.len(),
+            self.replacement.graph.port_count(),
+        );
+        let (_, self_output_node_index) = h.get_io(self.parent).unwrap();
+        let (_, replacement_output_node) =
+            self.replacement.get_io(self.replacement.root()).unwrap();
         for &node in replacement_inner_nodes {
             // Add the nodes.
             let op: &OpType = self.replacement.get_optype(node);
@@ -108,41 +220,27 @@ impl Rewrite for SimpleReplacement {
         // TODO This will probably change when implicit copies are implemented.
         for &node in replacement_inner_nodes {
             let new_node_index = index_map.get(&node.index).unwrap();
-            for node_successor in self.replacement.output_neighbours(node).unique() {
+            for ((_, src_port), (node_successor, tgt_port)) in
+                self.replacement.all_linked_ports(node, Direction::Outgoing)
+            {
                 if self.replacement.get_optype(node_successor).tag() != OpTag::Output {
                     let new_node_successor_index = index_map.get(&node_successor.index).unwrap();
-                    for connection in self
-                        .replacement
-                        .graph
-                        .get_connections(node.index, node_successor.index)
-                    {
-                        let src_offset = self
-                            .replacement
-                            .graph
-                            .port_offset(connection.0)
-                            .unwrap()
-                            .index();
-                        let tgt_offset = self
-                            .replacement
-                            .graph
-                            .port_offset(connection.1)
-                            .unwrap()
-                            .index();
-                        h.graph
-                            .link_nodes(
-                                *new_node_index,
-                                src_offset,
-                                *new_node_successor_index,
-                                tgt_offset,
-                            )
-                            .unwrap();
-                    }
+                    h.graph
+                        .link_nodes(
+                            *new_node_index,
+                            src_port.index(),
+                            *new_node_successor_index,
+                            tgt_port.index(),
+                        )
+                        .unwrap();
                 }
             }
         }
         // 3.2. For each p = self.nu_inp[q] such that q is not an Output port, add an edge from the
         // predecessor of p to (the new copy of) q.
-        for ((rep_inp_node, rep_inp_port), (rem_inp_node, rem_inp_port)) in &self.nu_inp {
+        for ((rep_inp_node, rep_inp_port), (rem_inp_node, rem_inp_port)) in
+            self.nu_inp.iter().sorted()
+        {
             if self.replacement.get_optype(*rep_inp_node).tag() != OpTag::Output {
                 let new_inp_node_index = index_map.get(&rep_inp_node.index).unwrap();
                 // add edge from predecessor of (s_inp_node, s_inp_port) to (new_inp_node, n_inp_port)
@@ -164,7 +262,7 @@ impl Rewrite for SimpleReplacement {
         }
         // 3.3. For each q = self.nu_out[p] such that the predecessor of q is not an Input port, add an
         // edge from (the new copy of) the predecessor of q to p.
-        for ((rem_out_node, rem_out_port), rep_out_port) in &self.nu_out {
+        for ((rem_out_node, rem_out_port), rep_out_port) in self.nu_out.iter().sorted() {
             let rem_out_port_index = h
                 .graph
                 .port_index(rem_out_node.index, rem_out_port.offset)
@@ -208,7 +306,7 @@ impl Rewrite for SimpleReplacement {
         }
         // 3.4. For each q = self.nu_out[p1], p0 = self.nu_inp[q], add an edge from the predecessor of p0
         // to p1.
-        for ((rem_out_node, rem_out_port), &rep_out_port) in &self.nu_out {
+        for ((rem_out_node, rem_out_port), &rep_out_port) in self.nu_out.iter().sorted() {
             let rem_inp_nodeport = self.nu_inp.get(&(replacement_output_node, rep_out_port));
             if let Some((rem_inp_node, rem_inp_port)) = rem_inp_nodeport {
                 // add edge from predecessor of (rem_inp_node, rem_inp_port) to (rem_out_node, rem_out_port):
@@ -229,14 +327,23 @@ impl Rewrite for SimpleReplacement {
                     .unwrap();
             }
         }
-        // 3.5. Remove all nodes in self.removal and edges between them.
-        for node in &self.removal {
+        // 3.5. Remove all nodes in self.removal and edges between them, in a
+        // deterministic order so the freed node indices are reused predictably.
+        for node in self.removal.iter().sorted() {
             h.remove_node(*node).unwrap();
         }
         Ok(())
     }
 }
 
+/// Returns the node linked to an existing input port, if any.
+fn linked_input(h: &Hugr, node: Node, port: Port) -> Option<Node> {
+    if port.direction() != Direction::Incoming || port.index() >= h.num_inputs(node) {
+        return None;
+    }
+    h.linked_ports(node, port).next().map(|(pred, _)| pred)
+}
+
 /// Error from a [`SimpleReplacement`] operation.
 #[derive(Debug, Clone, Error, PartialEq, Eq)]
 pub enum SimpleReplacementError {
@@ -249,27 +356,51 @@ pub enum SimpleReplacementError {
     /// Node in replacement graph is invalid.
     #[error("A node in the replacement graph is invalid.")]
     InvalidReplacementNode(),
+    /// The nodes requested for removal cannot be replaced.
+    #[error("The nodes requested for removal cannot be replaced: {0}")]
+    NotReplaceable(#[from] ReplaceabilityError),
+    /// A port of the HUGR in one of the boundary maps is not an input port
+    /// on the boundary of the removed nodes.
+    #[error("The boundary port {1:?} of node {0:?} is invalid.")]
+    InvalidBoundaryPort(Node, Port),
+    /// A port of the replacement in one of the boundary maps is not an input
+    /// port linked to its Input node, or an input port of its Output node.
+    #[error("The replacement port {1:?} of node {0:?} is invalid.")]
+    InvalidReplacementPort(Node, Port),
+    /// The ports matched by one of the boundary maps have different kinds.
+    #[error("The boundary port {1:?} of node {0:?} has a different kind in the replacement.")]
+    BoundaryKindMismatch(Node, Port),
+    /// A dataflow edge crossing the boundary of the removed nodes is not
+    /// covered by the boundary maps.
+    #[error("The boundary port {1:?} of node {0:?} is missing from the boundary maps.")]
+    UncoveredBoundaryPort(Node, Port),
+    /// A node was listed more than once for removal.
+    #[error("The node {0:?} is listed more than once for removal.")]
+    DuplicateRemovedNode(Node),
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::{HashMap, HashSet};
 
+    use cool_asserts::assert_matches;
     use itertools::Itertools;
     use portgraph::Direction;
 
     use crate::builder::{
-        BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
-        HugrBuilder, ModuleBuilder,
+        BuildError, CFGBuilder, ConditionalBuilder, Container, DFGBuilder, Dataflow, DataflowHugr,
+        DataflowSubContainer, HugrBuilder, ModuleBuilder,
     };
     use crate::hugr::view::HugrView;
-    use crate::hugr::{Hugr, Node};
+    use crate::hugr::{Hugr, Node, Rewrite};
+    use crate::ops::handle::NodeHandle;
     use crate::ops::OpTag;
-    use crate::ops::{LeafOp, OpTrait, OpType};
+    use crate::ops::{ConstValue, LeafOp, OpTrait, OpType};
     use crate::types::{ClassicType, LinearType, Signature, SimpleType};
     use crate::{type_row, Port};
 
-    use super::SimpleReplacement;
+    use super::{SimpleReplacement, SimpleReplacementError};
+    use crate::algorithm::convex::ReplaceabilityError;
 
     const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
 
@@ -312,7 +443,7 @@ mod test {
 
             func_builder.finish_with_outputs(inner_graph.outputs().chain(q_out.outputs()))?
         };
-        Ok(module_builder.finish_hugr()?)
+        module_builder.finish_hugr()
     }
 
     /// Creates a hugr with a DFG root like the following:
@@ -367,10 +498,7 @@ mod test {
     fn test_simple_replacement() {
         let mut h: Hugr = make_hugr().unwrap();
         // 1. Find the DFG node for the inner circuit
-        let p: Node = h
-            .nodes()
-            .find(|node: &Node| h.get_optype(*node).tag() == OpTag::Dfg)
-            .unwrap();
+        let p: Node = h.nodes_with_tag(OpTag::Dfg).next().unwrap();
         // 2. Locate the CX and its successor H's in h
         let h_node_cx: Node = h
             .nodes()
@@ -453,10 +581,7 @@ mod test {
     fn test_simple_replacement_with_empty_wires() {
         let mut h: Hugr = make_hugr().unwrap();
         // 1. Find the DFG node for the inner circuit
-        let p: Node = h
-            .nodes()
-            .find(|node: &Node| h.get_optype(*node).tag() == OpTag::Dfg)
-            .unwrap();
+        let p: Node = h.nodes_with_tag(OpTag::Dfg).next().unwrap();
         // 2. Locate the CX in h
         let h_node_cx: Node = h
             .nodes()
@@ -509,6 +634,86 @@ mod test {
         assert_eq!(h.validate(), Ok(()));
     }
 
+    /// Replaces the two consecutive `H` gates in `parent` with an identity wire.
+    fn cancel_hadamards(h: &mut Hugr, parent: Node) -> Result<(), SimpleReplacementError> {
+        let [h0, h1]: [Node; 2] = h
+            .children(parent)
+            .filter(|&n| *h.get_optype(n) == OpType::LeafOp(LeafOp::H))
+            .collect_vec()
+            .try_into()
+            .unwrap();
+        let builder = DFGBuilder::new(type_row![QB], type_row![QB]).unwrap();
+        let wires = builder.input_wires();
+        let identity = builder.finish_hugr_with_outputs(wires).unwrap();
+        let identity_output = identity.nth_child(identity.root(), 1).unwrap();
+
+        let in_port = Port::new_incoming(0);
+        let successor = h.linked_ports(h1, Port::new_outgoing(0)).next().unwrap();
+        let nu_inp = HashMap::from([((identity_output, in_port), (h0, in_port))]);
+        let nu_out = HashMap::from([(successor, in_port)]);
+        h.apply_rewrite(SimpleReplacement::new(
+            parent,
+            HashSet::from([h0, h1]),
+            identity,
+            nu_inp,
+            nu_out,
+        ))
+    }
+
+    fn count_hadamards(h: &Hugr) -> usize {
+        h.nodes()
+            .filter(|&n| *h.get_optype(n) == OpType::LeafOp(LeafOp::H))
+            .count()
+    }
+
+    #[test]
+    fn test_replace_in_case() -> Result<(), BuildError> {
+        let mut builder = ConditionalBuilder::new([type_row![]], type_row![QB], type_row![QB])?;
+        let mut case_builder = builder.case_builder(0)?;
+        let [q] = case_builder.input_wires_arr();
+        let q = case_builder.add_dataflow_op(LeafOp::H, [q])?.out_wire(0);
+        let q = case_builder.add_dataflow_op(LeafOp::H, [q])?.out_wire(0);
+        let case = case_builder.finish_with_outputs([q])?;
+        let mut h = builder.finish_hugr()?;
+
+        assert_eq!(cancel_hadamards(&mut h, case.node()), Ok(()));
+        assert_eq!(count_hadamards(&h), 0);
+        assert_eq!(h.validate(), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_in_basic_block() -> Result<(), BuildError> {
+        let mut builder = CFGBuilder::new(type_row![QB], type_row![QB])?;
+        let mut entry_builder = builder.simple_entry_builder(type_row![QB], 1)?;
+        let [q] = entry_builder.input_wires_arr();
+        let q = entry_builder.add_dataflow_op(LeafOp::H, [q])?.out_wire(0);
+        let q = entry_builder.add_dataflow_op(LeafOp::H, [q])?.out_wire(0);
+        let predicate = entry_builder.add_load_const(ConstValue::simple_unary_predicate())?;
+        let entry = entry_builder.finish_with_outputs(predicate, [q])?;
+        let exit = builder.exit_block()?;
+        builder.branch(&entry, 0, &exit)?;
+        let mut h = builder.finish_hugr()?;
+
+        assert_eq!(cancel_hadamards(&mut h, entry.node()), Ok(()));
+        assert_eq!(count_hadamards(&h), 0);
+        assert_eq!(h.validate(), Ok(()));
+
+        // The exit block has no dataflow graph to rewrite.
+        let r = SimpleReplacement::new(
+            exit.node(),
+            HashSet::new(),
+            h.clone(),
+            HashMap::new(),
+            HashMap::new(),
+        );
+        assert_eq!(
+            r.verify(&h),
+            Err(SimpleReplacementError::InvalidParentNode())
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_replace_cx_cross() {
         let q_row: Vec<SimpleType> = vec![LinearType::Qubit.into(), LinearType::Qubit.into()];
@@ -523,10 +728,7 @@ mod test {
         let orig = h.clone();
 
         let parent = h.root();
-        let removal = h
-            .nodes()
-            .filter(|&n| h.get_optype(n).tag() == OpTag::Leaf)
-            .collect();
+        let removal = h.nodes_with_tag(OpTag::Leaf).collect();
         let inputs = h
             .node_outputs(input)
             .filter(|&p| h.get_optype(input).signature().get(p).is_some())
@@ -553,6 +755,160 @@ mod test {
         assert_eq!(h.edge_count(), orig.edge_count());
     }
 
+    #[test]
+    fn test_replace_non_convex() {
+        let q_row: Vec<SimpleType> = vec![LinearType::Qubit.into(), LinearType::Qubit.into()];
+        let mut builder = DFGBuilder::new(q_row.clone(), q_row).unwrap();
+        let mut circ = builder.as_circuit(builder.input_wires().collect());
+        circ.append(LeafOp::CX, [0, 1]).unwrap();
+        circ.append(LeafOp::CX, [1, 0]).unwrap();
+        circ.append(LeafOp::CX, [0, 1]).unwrap();
+        let wires = circ.finish();
+        let mut h = builder.finish_hugr_with_outputs(wires).unwrap();
+        let replacement = h.clone();
+        let orig = h.clone();
+
+        let parent = h.root();
+        let cxs: Vec<Node> = h
+            .children(parent)
+            .filter(|&n| h.get_optype(n).tag() == OpTag::Leaf)
+            .collect();
+        let removal = HashSet::from([cxs[0], cxs[2]]);
+        let r =
+            SimpleReplacement::new(parent, removal, replacement, HashMap::new(), HashMap::new());
+        assert_matches!(
+            h.apply_rewrite(r),
+            Err(SimpleReplacementError::NotReplaceable(
+                ReplaceabilityError::LinearWireReenters { .. }
+            ))
+        );
+        assert_eq!(h.node_count(), orig.node_count());
+        assert_eq!(h.edge_count(), orig.edge_count());
+    }
+
+    #[test]
+    fn test_verify_failures() {
+        let q_row: Vec<SimpleType> = vec![LinearType::Qubit.into(), LinearType::Qubit.into()];
+        let mut builder = DFGBuilder::new(q_row.clone(), q_row).unwrap();
+        let mut circ = builder.as_circuit(builder.input_wires().collect());
+        circ.append(LeafOp::H, [0]).unwrap();
+        circ.append(LeafOp::CX, [0, 1]).unwrap();
+        let wires = circ.finish();
+        let [input, output] = builder.io();
+        let h = builder.finish_hugr_with_outputs(wires).unwrap();
+
+        let removal: HashSet<Node> = h
+            .nodes()
+            .filter(|&n| h.get_optype(n).tag() == OpTag::Leaf)
+            .collect();
+        let inputs: HashMap<_, _> = h
+            .node_outputs(input)
+            .filter_map(|p| h.linked_ports(input, p).next())
+            .map(|link| (link, link))
+            .collect();
+        let outputs: HashMap<_, _> = h
+            .node_inputs(output)
+            .filter(|&p| h.is_linked(output, p))
+            .map(|p| ((output, p), p))
+            .collect();
+        let valid = SimpleReplacement::new(h.root(), removal, h.clone(), inputs, outputs);
+        assert_eq!(valid.verify(&h), Ok(()));
+
+        let check = |r: SimpleReplacement, err: SimpleReplacementError| {
+            let mut h2 = h.clone();
+            assert_eq!(r.verify(&h2), Err(err.clone()));
+            assert_eq!(h2.apply_rewrite(r), Err(err));
+            assert_eq!(h2, h);
+        };
+
+        let mut r = valid.clone();
+        r.parent = input;
+        check(r, SimpleReplacementError::InvalidParentNode());
+
+        let mut r = valid.clone();
+        r.removal.insert(h.root());
+        check(r, SimpleReplacementError::InvalidRemovedNode());
+
+        let mut r = valid.clone();
+        r.removal.insert(input);
+        check(
+            r,
+            ReplaceabilityError::InvalidNode {
+                node: input,
+                tag: OpTag::Input,
+            }
+            .into(),
+        );
+
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB]).unwrap();
+        builder.add_load_const(ConstValue::i64(1)).unwrap();
+        let wires = builder.input_wires();
+        let mut r = valid.clone();
+        r.replacement = builder.finish_hugr_with_outputs(wires).unwrap();
+        r.nu_inp.clear();
+        r.nu_out.clear();
+        check(r, SimpleReplacementError::InvalidReplacementNode());
+
+        let (&(node, port), _) = valid.nu_inp.iter().next().unwrap();
+        let out_port = Port::new(Direction::Outgoing, port.index());
+        let mut r = valid.clone();
+        r.nu_inp.insert((node, out_port), (node, port));
+        check(
+            r,
+            SimpleReplacementError::InvalidReplacementPort(node, out_port),
+        );
+
+        let mut r = valid.clone();
+        let missing = Port::new(Direction::Incoming, 10);
+        r.nu_out.insert((output, missing), missing);
+        check(
+            r,
+            SimpleReplacementError::InvalidBoundaryPort(output, missing),
+        );
+
+        // A replacement input of a different type than the replaced wire.
+        let nat: SimpleType = ClassicType::i64().into();
+        let builder = DFGBuilder::new(vec![QB, nat.clone()], vec![QB, nat]).unwrap();
+        let wires = builder.input_wires();
+        let mut r = valid.clone();
+        r.replacement = builder.finish_hugr_with_outputs(wires).unwrap();
+        let rep_output = r.replacement.nth_child(r.replacement.root(), 1).unwrap();
+        let q1_target = h.linked_ports(input, Port::new_outgoing(1)).next().unwrap();
+        r.nu_inp = HashMap::from([((rep_output, Port::new_incoming(1)), q1_target)]);
+        r.nu_out.clear();
+        check(
+            r,
+            SimpleReplacementError::BoundaryKindMismatch(q1_target.0, q1_target.1),
+        );
+
+        // Every boundary edge must be covered by the maps.
+        let mut r = valid.clone();
+        r.nu_inp.remove(&q1_target);
+        check(
+            r,
+            SimpleReplacementError::UncoveredBoundaryPort(q1_target.0, q1_target.1),
+        );
+        let mut r = valid.clone();
+        r.nu_out.remove(&(output, Port::new_incoming(1)));
+        check(
+            r,
+            SimpleReplacementError::UncoveredBoundaryPort(output, Port::new_incoming(1)),
+        );
+
+        // Nodes can only be removed once.
+        let r = SimpleReplacement::try_new(
+            h.root(),
+            [q1_target.0, q1_target.0],
+            h.clone(),
+            valid.nu_inp.clone(),
+            valid.nu_out.clone(),
+        );
+        assert_eq!(
+            r.err(),
+            Some(SimpleReplacementError::DuplicateRemovedNode(q1_target.0))
+        );
+    }
+
     #[test]
     fn test_replace_after_copy() {
         let one_bit: Vec<SimpleType> = vec![ClassicType::bit().into()];
@@ -576,10 +932,7 @@ mod test {
         let orig = h.clone();
 
         let parent = h.root();
-        let removal = h
-            .nodes()
-            .filter(|&n| h.get_optype(n).tag() == OpTag::Leaf)
-            .collect();
+        let removal = h.nodes_with_tag(OpTag::Leaf).collect();
 
         let first_out_p = h.node_outputs(input).next().unwrap();
         let embedded_inputs = h.linked_ports(input, first_out_p);
@@ -1,14 +1,15 @@
 //! Serialization definition for [`Hugr`]
 //! [`Hugr`]: crate::hugr::Hugr
 
+pub mod tabular;
+
 use serde_json::json;
 use std::collections::HashMap;
 use thiserror::Error;
 
 use crate::hugr::{Hugr, HugrMut};
-use crate::ops::OpTrait;
 use crate::ops::OpType;
-use crate::Node;
+use crate::{Node, Port};
 use portgraph::hierarchy::AttachError;
 use portgraph::{Direction, LinkError, NodeIndex, PortView};
 
@@ -37,14 +38,112 @@ enum Versioned {
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 struct NodeSer {
     parent: Node,
-    #[serde(flatten)]
+    #[serde(flatten, with = "op_serde")]
     op: OpType,
 }
 
+/// Serialization of the operations of the nodes, which keeps operations of
+/// kinds unknown to this version as an [`UnknownOp`].
+///
+/// Operations are flat maps with their kind in the `"op"` field, next to their
+/// fields.
+mod op_serde {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::{Map, Value};
+
+    use crate::ops::{OpType, UnknownOp};
+
+    pub fn serialize<S: Serializer>(op: &OpType, serializer: S) -> Result<S::Ok, S::Error> {
+        match op {
+            OpType::UnknownOp(op) => op.serialized().serialize(serializer),
+            op => op.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OpType, D::Error> {
+        let serialized = Map::<String, Value>::deserialize(deserializer)?;
+        if has_unknown_tag(&serialized) {
+            return Ok(UnknownOp::new(serialized).into());
+        }
+        OpType::deserialize(Value::Object(serialized)).map_err(D::Error::custom)
+    }
+
+    /// The `"op"` tags of the [`OpType`] variants.
+    pub(super) const OP_TAGS: &[&str] = &[
+        "Module",
+        "FuncDefn",
+        "FuncDecl",
+        "AliasDecl",
+        "AliasDefn",
+        "Const",
+        "ConstRow",
+        "Input",
+        "Output",
+        "Call",
+        "CallIndirect",
+        "LoadConstant",
+        "DFG",
+        "LeafOp",
+        "BasicBlock",
+        "TailLoop",
+        "CFG",
+        "Conditional",
+        "Case",
+    ];
+
+    /// The `"lop"` tags of the [`LeafOp`] variants.
+    ///
+    /// [`LeafOp`]: crate::ops::LeafOp
+    pub(super) const LEAF_OP_TAGS: &[&str] = &[
+        "CustomOp",
+        "H",
+        "T",
+        "S",
+        "X",
+        "Y",
+        "Z",
+        "Tadj",
+        "Sadj",
+        "CX",
+        "ZZMax",
+        "Swap",
+        "Reset",
+        "Noop",
+        "Copy",
+        "Measure",
+        "MeasureDestructive",
+        "RzF64",
+        "Rz",
+        "Xor",
+        "BitToPredicate",
+        "PredicateToBit",
+        "MakeTuple",
+        "UnpackTuple",
+        "Tag",
+        "Lift",
+    ];
+
+    /// Whether the operation, or the leaf operation it wraps, is a variant
+    /// added in a newer version.
+    ///
+    /// Unknown variants of the enums nested in operations are errors, as they
+    /// cannot be told apart from typos.
+    fn has_unknown_tag(serialized: &Map<String, Value>) -> bool {
+        let tag = |key: &str| serialized.get(key).and_then(Value::as_str);
+        match tag("op") {
+            Some("LeafOp") => tag("lop").is_some_and(|lop| !LEAF_OP_TAGS.contains(&lop)),
+            Some(op) => !OP_TAGS.contains(&op),
+            None => false,
+        }
+    }
+}
+
 /// Version 0 of the HUGR serialization format.
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 struct SerHugrV0 {
     /// For each node: (parent, node_operation)
+    #[serde(deserialize_with = "deserialize_nodes")]
     nodes: Vec<NodeSer>,
     /// for each edge: (src, src_offset, tgt, tgt_offset)
     edges: Vec<[(Node, Option<u16>); 2]>,
@@ -53,6 +152,32 @@ struct SerHugrV0 {
     metadata: Vec<serde_json::Value>,
 }
 
+/// Deserialize the nodes of a HUGR, tagging the cases serialized before they
+/// were tagged with their position among their siblings.
+fn deserialize_nodes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<NodeSer>, D::Error> {
+    let nodes = Vec::<serde_json::Map<String, serde_json::Value>>::deserialize(deserializer)?;
+    let mut n_children: HashMap<u64, usize> = HashMap::new();
+    nodes
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut node)| {
+            // The root is its own parent, but not one of its children.
+            let position = match node.get("parent").and_then(serde_json::Value::as_u64) {
+                Some(parent) if index > 0 => {
+                    let count = n_children.entry(parent).or_default();
+                    *count += 1;
+                    *count - 1
+                }
+                _ => 0,
+            };
+            if node.get("op") == Some(&json!("Case")) && !node.contains_key("tag") {
+                node.insert("tag".to_string(), json!(position));
+            }
+            NodeSer::deserialize(serde_json::Value::Object(node)).map_err(serde::de::Error::custom)
+        })
+        .collect()
+}
+
 /// Errors that can occur while serializing a HUGR.
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum HUGRSerializationError {
@@ -139,31 +264,27 @@ impl TryFrom<&Hugr> for SerHugrV0 {
             .expect("Could not reach one of the nodes");
 
         let find_offset = |node: Node, offset: usize, dir: Direction, hugr: &Hugr| {
-            let sig = hugr.get_optype(node).signature();
-            let offset = match offset < sig.port_count(dir) {
-                true => Some(offset as u16),
-                false => None,
+            let op = hugr.get_optype(node);
+            // Only a single non-dataflow port can be identified without its
+            // offset. The ports of unknown operations are only known by their
+            // offsets.
+            let offset = match op.other_port_index(dir) == Some(Port::new(dir, offset)) {
+                true => None,
+                false => Some(offset as u16),
             };
             (node_rekey[&node], offset)
         };
 
+        // The edges between unknown operations have no known kind, so the
+        // links are listed directly.
         let edges: Vec<_> = hugr
             .nodes()
-            .flat_map(|node| {
-                hugr.node_ports(node, Direction::Outgoing)
-                    .enumerate()
-                    .flat_map(move |(src_offset, port)| {
-                        let src = find_offset(node, src_offset, Direction::Outgoing, hugr);
-                        hugr.linked_ports(node, port).map(move |(tgt_node, tgt)| {
-                            let tgt = find_offset(
-                                tgt_node,
-                                tgt.offset.index(),
-                                Direction::Incoming,
-                                hugr,
-                            );
-                            [src, tgt]
-                        })
-                    })
+            .flat_map(|n| hugr.all_linked_ports(n, Direction::Outgoing))
+            .map(|((src, src_port), (tgt, tgt_port))| {
+                [
+                    find_offset(src, src_port.index(), Direction::Outgoing, hugr),
+                    find_offset(tgt, tgt_port.index(), Direction::Incoming, hugr),
+                ]
             })
             .collect();
 
@@ -228,6 +349,15 @@ impl TryFrom<SerHugrV0> for Hugr {
         for [(src, from_offset), (dst, to_offset)] in edges {
             let src_port = unwrap_offset(src, from_offset, Direction::Outgoing, &hugr)?;
             let dst_port = unwrap_offset(dst, to_offset, Direction::Incoming, &hugr)?;
+            for (node, port, dir) in [
+                (src, src_port, Direction::Outgoing),
+                (dst, dst_port, Direction::Incoming),
+            ] {
+                let num_ports = hugr.num_ports(node, dir);
+                if matches!(hugr.get_optype(node), OpType::UnknownOp(_)) && port >= num_ports {
+                    hugr.add_ports(node, dir, (port + 1 - num_ports) as isize)?;
+                }
+            }
 
             hugr.connect(src, src_port, dst, dst_port)?;
         }
@@ -242,13 +372,16 @@ pub mod test {
     use super::*;
     use crate::{
         builder::{
-            Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, HugrBuilder,
-            ModuleBuilder,
+            BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+            HugrBuilder, ModuleBuilder,
         },
-        ops::{dataflow::IOTrait, Input, LeafOp, Module, Output, DFG},
+        hugr::ValidationError,
+        ops::{self, dataflow::IOTrait, ConstValue, Input, LeafOp, Module, OpName, Output, DFG},
+        type_row,
         types::{ClassicType, LinearType, Signature, SimpleType},
         Port,
     };
+    use cool_asserts::assert_matches;
     use itertools::Itertools;
     use portgraph::{
         multiportgraph::MultiPortGraph, Hierarchy, LinkMut, PortMut, PortView, UnmanagedDenseMap,
@@ -278,6 +411,7 @@ pub mod test {
                     vec![ClassicType::bit().into(); inputs - 1],
                     vec![ClassicType::bit().into(); outputs - 1],
                 ),
+                resource_delta: None,
             }
             .into(),
             (true, false) => Input::new(vec![ClassicType::bit().into(); outputs - 1]).into(),
@@ -395,6 +529,41 @@ pub mod test {
         assert_eq!(ser_roundtrip(&ser_hugr), ser_hugr);
     }
 
+    #[test]
+    fn resources_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let hugr = crate::hugr::validate::test::resource_mismatch_hugr()?;
+
+        let ser = serde_json::to_string(&hugr)?;
+        let h_deser: Hugr = serde_json::from_str(&ser)?;
+        let ser_hugr: SerHugrV0 = (&hugr).try_into()?;
+        let ser_deser: SerHugrV0 = (&h_deser).try_into()?;
+        assert_eq!(ser_deser, ser_hugr);
+
+        let resource_sets = |h: &Hugr| {
+            h.nodes()
+                .filter_map(|n| match h.get_optype(n) {
+                    OpType::DFG(DFG { signature, .. }) => Some((
+                        signature.input_resources.clone(),
+                        signature.output_resources.clone(),
+                    )),
+                    _ => None,
+                })
+                .collect_vec()
+        };
+        assert!(resource_sets(&hugr)
+            .iter()
+            .any(|(inp, out)| !inp.is_empty() && !out.is_empty()));
+        assert_eq!(resource_sets(&h_deser), resource_sets(&hugr));
+
+        // Empty resource sets are omitted, and default to empty when missing.
+        let sig = Signature::new_df(vec![NAT], vec![NAT]);
+        let ser = serde_json::to_value(&sig)?;
+        assert_eq!(ser.get("input_resources"), None);
+        assert_eq!(serde_json::from_value::<Signature>(ser)?, sig);
+
+        Ok(())
+    }
+
     #[test]
     fn dfg_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
         let tp: Vec<SimpleType> = vec![ClassicType::bit().into(); 2];
@@ -423,6 +592,30 @@ pub mod test {
         Ok(())
     }
 
+    #[test]
+    fn const_row_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut module_builder = ModuleBuilder::new();
+        let row = [
+            ConstValue::true_val(),
+            ConstValue::F64(0.5),
+            ConstValue::unit(),
+        ];
+        let cids = module_builder.add_constant_row(row)?;
+        let mut f_build = module_builder.define_function(
+            "main",
+            Signature::new_df(type_row![], vec![ClassicType::F64.into()]),
+        )?;
+        let loaded = f_build.load_const(&cids[1])?;
+        f_build.finish_with_outputs([loaded])?;
+        let hugr = module_builder.finish_hugr()?;
+
+        let ser = serde_json::to_string(&hugr)?;
+        let deser: Hugr = serde_json::from_str(&ser)?;
+        assert_eq!(deser, hugr);
+        assert_eq!(deser.validate(), Ok(()));
+        Ok(())
+    }
+
     #[test]
     fn hierarchy_order() {
         let qb: SimpleType = LinearType::Qubit.into();
@@ -432,7 +625,7 @@ pub mod test {
         let mut hugr = dfg.finish_hugr_with_outputs(w).unwrap();
 
         // Now add a new input
-        let new_in = hugr.add_op(Input::new([qb].to_vec()));
+        let new_in = hugr.add_op(Input::new([qb].to_vec())).unwrap();
         hugr.disconnect(old_in, Port::new_outgoing(0)).unwrap();
         hugr.connect(new_in, 0, out, 0).unwrap();
         hugr.move_before_sibling(new_in, old_in).unwrap();
@@ -452,4 +645,246 @@ pub mod test {
             assert_eq!(new_hugr.get_parent(node), h_canon.get_parent(node));
         }
     }
+
+    #[test]
+    fn legacy_const_input() {
+        let sig = Signature::new(type_row![NAT], type_row![NAT], type_row![NAT, NAT]);
+        assert_eq!(ser_roundtrip(&sig), sig);
+
+        // Signatures with the old optional `const_input` field are still read.
+        let legacy = |const_input: serde_json::Value| {
+            serde_json::from_value::<Signature>(json!({
+                "input": [],
+                "output": [],
+                "const_input": const_input,
+            }))
+            .unwrap()
+        };
+        let nat_json = serde_json::to_value(&NAT).unwrap();
+        assert_eq!(
+            legacy(json!(null)),
+            Signature::new_df(type_row![], type_row![])
+        );
+        assert_eq!(
+            legacy(nat_json),
+            Signature::new(type_row![], type_row![], type_row![NAT])
+        );
+    }
+
+    #[test]
+    fn canonical_signatures() {
+        // The edge kinds of the non-dataflow ports are derived from the
+        // operations, so they are not part of the serialized signatures.
+        let row = type_row![QB, NAT];
+        let signatures = [
+            Signature::new_df(row.clone(), row.clone()),
+            Signature::new_linear(row.clone()),
+            Signature {
+                input: row.clone(),
+                output: row.clone(),
+                ..Default::default()
+            },
+        ];
+        let serialized = signatures.clone().map(|signature| {
+            let mut hugr = Hugr::new(DFG {
+                signature,
+                resource_delta: None,
+            });
+            let root = hugr.root();
+            let input = hugr.add_op_with_parent(root, Input::new(row.clone()));
+            let output = hugr.add_op_with_parent(root, Output::new(row.clone()));
+            let [input, output] = [input.unwrap(), output.unwrap()];
+            for i in 0..row.len() {
+                hugr.connect(input, i, output, i).unwrap();
+            }
+            assert_eq!(hugr.validate(), Ok(()));
+            serde_json::to_string(&hugr).unwrap()
+        });
+        assert!(serialized.iter().all_equal());
+
+        let sig_json = serde_json::to_value(&signatures[0]).unwrap();
+        assert_eq!(
+            sig_json.as_object().unwrap().keys().collect_vec(),
+            ["input", "output", "static_input"]
+        );
+
+        // Signatures with explicit edge kinds for the other ports are still read.
+        let legacy: Signature = serde_json::from_value(json!({
+            "input": [],
+            "output": [],
+            "static_input": [],
+            "other_inputs": "StateOrder",
+            "other_outputs": null,
+        }))
+        .unwrap();
+        assert_eq!(legacy, Signature::new_df(type_row![], type_row![]));
+    }
+
+    #[test]
+    fn unknown_ops() -> Result<(), BuildError> {
+        let mut dfg = DFGBuilder::new(type_row![QB], type_row![QB])?;
+        let h = dfg.add_dataflow_op(LeafOp::H, dfg.input_wires())?;
+        let x = dfg.add_dataflow_op(LeafOp::X, h.outputs())?;
+        let hugr = dfg.finish_hugr_with_outputs(x.outputs())?;
+
+        // Replace the gates with operations from a newer version.
+        let mut json = serde_json::to_value(&hugr).unwrap();
+        for node in json["nodes"].as_array_mut().unwrap() {
+            match node["lop"].as_str() {
+                Some("H") => {
+                    *node = json!({"parent": node["parent"], "op": "Teleport", "fidelity": 0.5})
+                }
+                Some("X") => node["lop"] = json!("FutureGate"),
+                _ => {}
+            }
+        }
+
+        let hugr: Hugr = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&hugr).unwrap(), json);
+        let saved = serde_json::to_string(&hugr).unwrap();
+        let reloaded: Hugr = serde_json::from_str(&saved).unwrap();
+        assert_eq!(serde_json::to_string(&reloaded).unwrap(), saved);
+
+        let names: Vec<_> = hugr
+            .nodes()
+            .filter_map(|n| match hugr.get_optype(n) {
+                OpType::UnknownOp(op) => Some(op.name()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, ["Teleport", "LeafOp"]);
+        let err = hugr.validate().unwrap_err();
+        assert_matches!(err, ValidationError::UnknownOp { .. });
+        assert!(err.to_string().contains("produced by a newer version"));
+
+        // Other errors are still reported, including unknown variants of the
+        // enums nested in known operations.
+        let mut typo = json.clone();
+        typo["nodes"][1] = json!({"parent": 0, "op": "BasicBlock", "block": "Typo"});
+        assert!(serde_json::from_value::<Hugr>(typo).is_err());
+        json["nodes"][0]["signature"] = json!(null);
+        assert!(serde_json::from_value::<Hugr>(json).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn known_op_tags() {
+        // The variants listed by the error for an unknown tag.
+        let variants = |json: serde_json::Value| -> Vec<String> {
+            let err = serde_json::from_value::<OpType>(json)
+                .unwrap_err()
+                .to_string();
+            let (_, variants) = err.split_once("expected one of ").unwrap();
+            variants
+                .split(", ")
+                .map(|v| v.trim_matches('`').to_string())
+                .collect()
+        };
+        assert_eq!(variants(json!({"op": "?"})), op_serde::OP_TAGS);
+        assert_eq!(
+            variants(json!({"op": "LeafOp", "lop": "?"})),
+            op_serde::LEAF_OP_TAGS
+        );
+    }
+
+    /// An operation of each kind.
+    fn all_ops() -> Vec<OpType> {
+        let sig = Signature::new_df(type_row![QB], type_row![QB]);
+        let bit_row = type_row![SimpleType::Classic(ClassicType::bit())];
+        vec![
+            Module.into(),
+            ops::FuncDefn {
+                name: "main".into(),
+                signature: sig.clone(),
+                signature_desc: Default::default(),
+            }
+            .into(),
+            ops::FuncDecl {
+                name: "ext".into(),
+                signature: sig.clone(),
+                signature_desc: Default::default(),
+            }
+            .into(),
+            ops::AliasDecl {
+                name: "opaque".into(),
+                linear: true,
+            }
+            .into(),
+            ops::AliasDefn {
+                name: "qubit".into(),
+                definition: QB,
+            }
+            .into(),
+            ops::Const(ConstValue::F64(0.5)).into(),
+            ops::ConstRow(vec![ConstValue::true_val(), ConstValue::F64(1.5)]).into(),
+            Input::new(type_row![QB]).into(),
+            Output::new(type_row![QB]).into(),
+            ops::Call {
+                signature: sig.clone(),
+            }
+            .into(),
+            ops::CallIndirect {
+                signature: sig.clone(),
+            }
+            .into(),
+            ops::LoadConstant {
+                datatype: ClassicType::F64,
+            }
+            .into(),
+            DFG {
+                signature: sig.clone(),
+                resource_delta: None,
+            }
+            .into(),
+            LeafOp::H.into(),
+            LeafOp::Noop { ty: QB }.into(),
+            ops::BasicBlock::DFB {
+                inputs: type_row![QB],
+                other_outputs: type_row![QB],
+                predicate_variants: vec![type_row![]],
+            }
+            .into(),
+            ops::BasicBlock::Exit {
+                cfg_outputs: type_row![QB],
+            }
+            .into(),
+            ops::TailLoop {
+                just_inputs: bit_row.clone(),
+                just_outputs: type_row![],
+                rest: type_row![QB],
+            }
+            .into(),
+            ops::CFG {
+                inputs: type_row![QB],
+                outputs: type_row![QB],
+            }
+            .into(),
+            ops::Conditional {
+                predicate_inputs: vec![type_row![], bit_row],
+                other_inputs: type_row![QB],
+                outputs: type_row![QB],
+            }
+            .into(),
+            ops::Case {
+                tag: 0,
+                signature: sig,
+            }
+            .into(),
+        ]
+    }
+
+    #[test]
+    fn op_roundtrip() {
+        for op in all_ops() {
+            let node = NodeSer {
+                parent: NodeIndex::new(0).into(),
+                op,
+            };
+            let json = serde_json::to_value(&node).unwrap();
+            // The operations are flat maps tagged by their kind.
+            assert!(json.as_object().unwrap().contains_key("op"), "{json}");
+            assert_eq!(serde_json::from_value::<NodeSer>(json).unwrap(), node);
+            assert_eq!(ser_roundtrip(&node), node);
+        }
+    }
 }
@@ -0,0 +1,317 @@
+//! Flat tabular representation of a [`Hugr`], for external tools.
+//!
+//! A HUGR is encoded as a table of nodes and a table of edges. The hierarchy is
+//! given by the parent column of the node table, and the order of the children
+//! of a node by the order of their rows. This is lower level than the serde
+//! encoding of [`Hugr`], but it is lossless.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::hugr::{Hugr, HugrError, HugrMut, HugrView, NodeMetadata};
+use crate::ops::{OpName, OpType};
+use crate::types::EdgeKind;
+use crate::{Direction, Node, Port};
+
+/// The node and edge tables of a HUGR.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HugrTables {
+    /// One row per node. The relative order of the rows of sibling nodes gives
+    /// the order of the children of their parent.
+    pub nodes: Vec<NodeRow>,
+    /// One row per edge.
+    pub edges: Vec<EdgeRow>,
+}
+
+/// A row of the node table.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeRow {
+    /// Identifier of the node, unique in the table.
+    pub id: usize,
+    /// Identifier of the parent node, or `None` for the root.
+    pub parent: Option<usize>,
+    /// Name of the operation, for convenience. Ignored by [`from_tables`].
+    pub op_name: String,
+    /// The operation, serialized as JSON.
+    pub op: String,
+    /// The metadata of the node.
+    #[serde(default)]
+    pub metadata: NodeMetadata,
+}
+
+/// A row of the edge table.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EdgeRow {
+    /// Identifier of the source node.
+    pub src: usize,
+    /// Offset of the outgoing port of the source node.
+    pub src_port: usize,
+    /// Identifier of the target node.
+    pub dst: usize,
+    /// Offset of the incoming port of the target node.
+    pub dst_port: usize,
+    /// The kind of the edge.
+    pub kind: EdgeKind,
+}
+
+/// Errors that can occur while reconstructing a HUGR from its tables.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[allow(missing_docs)]
+pub enum TabularError {
+    /// The node table has no row without a parent.
+    #[error("The node table has no root.")]
+    MissingRoot,
+    /// The node table has more than one row without a parent.
+    #[error("The node table has multiple roots: {0} and {1}.")]
+    MultipleRoots(usize, usize),
+    /// Two rows of the node table have the same identifier.
+    #[error("The node identifier {0} is duplicated.")]
+    DuplicateNode(usize),
+    /// A row refers to a node missing from the node table.
+    #[error("The node identifier {0} is not in the node table.")]
+    UnknownNode(usize),
+    /// The serialized operation of a node could not be read.
+    #[error("Invalid operation for node {id}: {msg}")]
+    InvalidOp { id: usize, msg: String },
+    /// An edge refers to a port missing from its node, or its kind does not
+    /// match the ports.
+    #[error("Invalid edge {edge:?}.")]
+    InvalidEdge { edge: EdgeRow },
+    /// Error building the HUGR.
+    #[error("HugrError: {0:?}")]
+    HugrError(#[from] HugrError),
+}
+
+/// Encode a HUGR as node and edge tables.
+///
+/// The node identifiers are the positions of the nodes in a breadth-first
+/// traversal of the hierarchy.
+pub fn to_tables(hugr: &Hugr) -> HugrTables {
+    let order: Vec<Node> = hugr.canonical_order().collect();
+    let ids: HashMap<Node, usize> = order.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+
+    let nodes = order
+        .iter()
+        .enumerate()
+        .map(|(id, &node)| {
+            let op = hugr.get_optype(node);
+            NodeRow {
+                id,
+                parent: hugr.get_parent(node).map(|p| ids[&p]),
+                op_name: op.name().to_string(),
+                op: serde_json::to_string(op).expect("Operations are serializable"),
+                metadata: hugr.get_metadata(node).clone(),
+            }
+        })
+        .collect();
+
+    let ids = &ids;
+    let edges = order
+        .iter()
+        .flat_map(|&node| {
+            let op = hugr.get_optype(node);
+            hugr.node_outputs(node).flat_map(move |port| {
+                let kind = op.port_kind(port).unwrap();
+                hugr.linked_ports(node, port)
+                    .map(move |(dst, dst_port)| EdgeRow {
+                        src: ids[&node],
+                        src_port: port.index(),
+                        dst: ids[&dst],
+                        dst_port: dst_port.index(),
+                        kind: kind.clone(),
+                    })
+            })
+        })
+        .collect();
+
+    HugrTables { nodes, edges }
+}
+
+/// Reconstruct a HUGR from its node and edge tables.
+///
+/// The rows of the node table may be in any order, as long as the relative
+/// order of sibling nodes is preserved.
+pub fn from_tables(tables: HugrTables) -> Result<Hugr, TabularError> {
+    let mut root_row = None;
+    for row in &tables.nodes {
+        if row.parent.is_none() {
+            if let Some(root) = root_row.replace(row) {
+                return Err(TabularError::MultipleRoots(root.id, row.id));
+            }
+        }
+    }
+    let root_row = root_row.ok_or(TabularError::MissingRoot)?;
+    let read_op = |row: &NodeRow| -> Result<OpType, TabularError> {
+        serde_json::from_str(&row.op).map_err(|e| TabularError::InvalidOp {
+            id: row.id,
+            msg: e.to_string(),
+        })
+    };
+
+    // Add all the nodes before attaching them, as rows may precede their parents.
+    let mut hugr = Hugr::with_capacity(
+        read_op(root_row)?,
+        tables.nodes.len(),
+        tables.edges.len() * 2,
+    );
+    let mut nodes: HashMap<usize, Node> = HashMap::with_capacity(tables.nodes.len());
+    for row in &tables.nodes {
+        let node = match row.parent {
+            None => hugr.root(),
+            Some(_) => hugr.add_op(read_op(row)?)?,
+        };
+        hugr.set_metadata(node, row.metadata.clone());
+        if nodes.insert(row.id, node).is_some() {
+            return Err(TabularError::DuplicateNode(row.id));
+        }
+    }
+
+    let get_node = |id: usize| nodes.get(&id).copied().ok_or(TabularError::UnknownNode(id));
+    for row in &tables.nodes {
+        if let Some(parent) = row.parent {
+            hugr.set_parent(get_node(row.id)?, get_node(parent)?)?;
+        }
+    }
+
+    for edge in tables.edges {
+        let src = get_node(edge.src)?;
+        let dst = get_node(edge.dst)?;
+        let src_port = Port::new(Direction::Outgoing, edge.src_port);
+        let dst_port = Port::new(Direction::Incoming, edge.dst_port);
+        let valid_port = |node: Node, port: Port| {
+            port.index() < hugr.num_ports(node, port.direction())
+                && hugr.get_optype(node).port_kind(port).as_ref() == Some(&edge.kind)
+        };
+        if !valid_port(src, src_port) || !valid_port(dst, dst_port) {
+            return Err(TabularError::InvalidEdge { edge });
+        }
+        hugr.connect(src, edge.src_port, dst, edge.dst_port)?;
+    }
+
+    Ok(hugr)
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+    use serde_json::json;
+
+    use super::*;
+    use crate::builder::{
+        Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, HugrBuilder,
+        ModuleBuilder,
+    };
+    use crate::hugr::serialize::SerHugrV0;
+    use crate::ops::{ConstValue, LeafOp};
+    use crate::type_row;
+    use crate::types::{ClassicType, LinearType, Signature, SimpleType};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+
+    fn module_hugr() -> Hugr {
+        let mut module_builder = ModuleBuilder::new();
+        module_builder.set_metadata(json!({"name": "test"}));
+        let decl = module_builder
+            .declare("decl", Signature::new_df(type_row![NAT], type_row![NAT]))
+            .unwrap();
+        let mut f_build = module_builder
+            .define_function(
+                "main",
+                Signature::new_df(type_row![NAT, QB], type_row![NAT, QB]),
+            )
+            .unwrap();
+        let [n, q] = f_build.input_wires_arr();
+        let call = f_build.call(&decl, [n]).unwrap();
+        let h = f_build.add_dataflow_op(LeafOp::H, [q]).unwrap();
+        f_build.set_order(&call, &h).unwrap();
+        f_build.set_metadata(json!(42));
+        f_build
+            .finish_with_outputs(call.outputs().chain(h.outputs()))
+            .unwrap();
+        module_builder.finish_hugr().unwrap()
+    }
+
+    fn dfg_hugr() -> Hugr {
+        let mut dfg = DFGBuilder::new(type_row![NAT], type_row![NAT]).unwrap();
+        let [w] = dfg.input_wires_arr();
+        dfg.add_load_const(ConstValue::F64(1.5)).unwrap();
+        let nested = dfg
+            .dfg_builder(Signature::new_df(type_row![NAT], type_row![NAT]), [w])
+            .unwrap();
+        let [nested_w] = nested.input_wires_arr();
+        let nested = nested.finish_with_outputs([nested_w]).unwrap();
+        dfg.finish_hugr_with_outputs(nested.outputs()).unwrap()
+    }
+
+    fn fixtures() -> Vec<Hugr> {
+        vec![
+            Hugr::default(),
+            module_hugr(),
+            dfg_hugr(),
+            crate::hugr::validate::test::resource_mismatch_hugr().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn tables_roundtrip() {
+        for hugr in fixtures() {
+            let tables = to_tables(&hugr);
+            assert_eq!(tables.nodes.len(), hugr.node_count());
+            assert_eq!(tables.edges.len(), hugr.edge_count());
+
+            let ser_tables: HugrTables =
+                serde_json::from_str(&serde_json::to_string(&tables).unwrap()).unwrap();
+            assert_eq!(ser_tables, tables);
+
+            let new_hugr = from_tables(tables.clone()).unwrap();
+            assert_eq!(new_hugr.validate().is_ok(), hugr.validate().is_ok());
+            let ser: SerHugrV0 = (&hugr).try_into().unwrap();
+            let new_ser: SerHugrV0 = (&new_hugr).try_into().unwrap();
+            assert_eq!(new_ser, ser);
+        }
+    }
+
+    #[test]
+    fn reordered_rows() {
+        let hugr = module_hugr();
+        let tables = to_tables(&hugr);
+
+        // Move the rows of the children before their parents, keeping the
+        // order of the siblings.
+        let mut reordered = tables.clone();
+        reordered
+            .nodes
+            .sort_by_key(|row| std::cmp::Reverse(row.parent));
+        reordered.edges.reverse();
+        assert_ne!(reordered, tables);
+        let new_hugr = from_tables(reordered).unwrap();
+        assert_eq!(to_tables(&new_hugr), tables);
+
+        // Swapping sibling rows changes the hugr.
+        let mut swapped = tables.clone();
+        swapped.nodes.swap(1, 2);
+        assert_eq!(swapped.nodes[1].parent, swapped.nodes[2].parent);
+        let new_hugr = from_tables(swapped).unwrap();
+        assert_ne!(to_tables(&new_hugr), tables);
+    }
+
+    #[test]
+    fn invalid_tables() {
+        let tables = to_tables(&module_hugr());
+
+        let mut no_root = tables.clone();
+        no_root.nodes.remove(0);
+        assert_eq!(from_tables(no_root), Err(TabularError::MissingRoot));
+
+        let mut unknown = tables.clone();
+        unknown.nodes[1].parent = Some(100);
+        assert_eq!(from_tables(unknown), Err(TabularError::UnknownNode(100)));
+
+        let mut bad_edge = tables.clone();
+        bad_edge.edges[0].kind = EdgeKind::StateOrder;
+        assert_matches!(from_tables(bad_edge), Err(TabularError::InvalidEdge { .. }));
+    }
+}
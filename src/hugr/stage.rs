@@ -0,0 +1,150 @@
+//! Markers for the compilation stage a HUGR has reached.
+//!
+//! Some checks of the validation assume that earlier passes already ran over
+//! the HUGR, so a failure does not always mean that it is broken. The stage is
+//! recorded in the metadata of the root node by the passes advancing it, and
+//! is serialized with the HUGR.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{Hugr, HugrMut, HugrView, ValidationError, ValidationOptions};
+
+/// The metadata key of the root node storing the [`CompilationStage`].
+const STAGE_KEY: &str = "stage";
+
+/// The passes a HUGR has gone through.
+///
+/// Stages are ordered, each one implying the previous ones.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum CompilationStage {
+    /// No pass has run. The resource requirements may not be solved yet, so
+    /// only [`structural validation`](CompilationStage::validation_options)
+    /// is meaningful.
+    #[default]
+    Raw,
+    /// The resource deltas of the containers have been inferred, see
+    /// [`infer_resource_deltas`](crate::algorithm::resource_delta::infer_resource_deltas).
+    ResourcesInferred,
+    /// The HUGR passed the full validation, see [`Hugr::mark_validated`].
+    Validated,
+}
+
+impl CompilationStage {
+    /// The validation options checking what is expected to hold at this stage.
+    pub fn validation_options(self) -> ValidationOptions {
+        ValidationOptions {
+            assume_resources_solved: self >= CompilationStage::ResourcesInferred,
+            ..Default::default()
+        }
+    }
+}
+
+impl Hugr {
+    /// The compilation stage recorded in the HUGR.
+    ///
+    /// HUGRs without a recorded stage are [`CompilationStage::Raw`].
+    pub fn stage(&self) -> CompilationStage {
+        self.get_metadata(self.root())
+            .get(STAGE_KEY)
+            .and_then(|stage| CompilationStage::deserialize(stage).ok())
+            .unwrap_or_default()
+    }
+
+    /// Record the compilation stage in the metadata of the root node.
+    ///
+    /// The stage is not recorded if the root metadata is set to something
+    /// other than a map.
+    pub fn set_stage(&mut self, stage: CompilationStage) {
+        let root = self.root();
+        let metadata = self.get_metadata_mut(root);
+        if metadata.is_null() {
+            *metadata = Value::Object(Map::new());
+        }
+        if let Value::Object(map) = metadata {
+            map.insert(STAGE_KEY.to_string(), serde_json::to_value(stage).unwrap());
+        }
+    }
+
+    /// Check the full validity of the HUGR and, if it is valid, advance its
+    /// stage to [`CompilationStage::Validated`].
+    pub fn mark_validated(&mut self) -> Result<(), ValidationError> {
+        self.validate()?;
+        self.set_stage(CompilationStage::Validated);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::algorithm::resource_delta::infer_resource_deltas;
+    use crate::builder::{
+        BuildError, Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder,
+    };
+    use crate::ops::{handle::NodeHandle, LeafOp, DFG};
+    use crate::resource::{ResourceId, ResourceSet};
+    use crate::type_row;
+    use crate::types::{ClassicType, Signature, SimpleType};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    #[test]
+    fn stage_pipeline() -> Result<(), BuildError> {
+        let a: ResourceId = "A".into();
+        let mut sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        sig.output_resources = ResourceSet::singleton(&a);
+
+        // A DFG introducing the resource A, but declaring no delta yet.
+        let mut module_builder = ModuleBuilder::new();
+        let mut main = module_builder.define_function("main", sig.clone())?;
+        let mut dfg = main.dfg_builder(sig.clone(), main.input_wires())?;
+        let lift = LeafOp::Lift {
+            type_row: type_row![NAT],
+            input_resources: ResourceSet::new(),
+            new_resource: a,
+        };
+        let lifted = dfg.add_dataflow_op(lift, dfg.input_wires())?;
+        let dfg = dfg.finish_with_outputs(lifted.outputs())?;
+        main.finish_with_outputs(dfg.outputs())?;
+        let mut hugr = module_builder.finish_hugr()?;
+        let declared = DFG {
+            signature: sig,
+            resource_delta: Some(ResourceSet::new()),
+        };
+        hugr.replace_op(dfg.node(), declared).unwrap();
+
+        assert_eq!(hugr.stage(), CompilationStage::Raw);
+        assert_matches!(
+            hugr.validate(),
+            Err(ValidationError::RegionExceedsResourceDelta { .. })
+        );
+        assert_eq!(
+            hugr.validate_with_options(&hugr.stage().validation_options()),
+            Ok(())
+        );
+        assert_matches!(
+            hugr.mark_validated(),
+            Err(ValidationError::RegionExceedsResourceDelta { .. })
+        );
+        assert_eq!(hugr.stage(), CompilationStage::Raw);
+
+        assert_eq!(infer_resource_deltas(&mut hugr), 1);
+        assert_eq!(hugr.stage(), CompilationStage::ResourcesInferred);
+        assert_eq!(hugr.mark_validated(), Ok(()));
+        assert_eq!(hugr.stage(), CompilationStage::Validated);
+
+        // Inference with nothing left to update keeps the stage.
+        assert_eq!(infer_resource_deltas(&mut hugr), 0);
+        assert_eq!(hugr.stage(), CompilationStage::Validated);
+
+        // The stage is kept by serialization.
+        let hugr: Hugr = serde_json::from_str(&serde_json::to_string(&hugr).unwrap()).unwrap();
+        assert_eq!(hugr.stage(), CompilationStage::Validated);
+        Ok(())
+    }
+}
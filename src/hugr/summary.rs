@@ -0,0 +1,260 @@
+//! Coarse statistics of a HUGR, for logging and telemetry.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use crate::ops::{LeafOp, OpName, OpTag, OpTrait, OpType};
+use crate::{Direction, Hugr, HugrView, Node};
+
+/// Summary statistics of a HUGR, see [`Hugr::summary`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HugrSummary {
+    /// The number of nodes.
+    pub nodes: usize,
+    /// The number of nodes of each kind.
+    pub ops: OpCounts,
+    /// The most frequent leaf operation names with their number of nodes, by
+    /// decreasing count. At most [`HugrSummary::TOP_LEAF_OPS`] are kept.
+    pub top_leaf_ops: Vec<(SmolStr, usize)>,
+    /// The number of nodes with children.
+    pub regions: usize,
+    /// The largest number of children of a node.
+    pub max_region_size: usize,
+    /// The mean number of children of the nodes with children.
+    pub mean_region_size: f64,
+    /// The largest depth of a node in the hierarchy, the root having depth 0.
+    pub max_depth: usize,
+    /// The number of edges between nodes with different parents.
+    pub inter_graph_edges: usize,
+}
+
+/// The number of nodes of a HUGR in coarse buckets of [`OpTag`]s.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OpCounts {
+    /// Function definitions and declarations.
+    pub functions: usize,
+    /// Constants and type aliases.
+    pub consts: usize,
+    /// Dataflow containers: DFG, CFG, TailLoop, Conditional and Case nodes.
+    pub containers: usize,
+    /// Basic blocks, including the exit blocks.
+    pub basic_blocks: usize,
+    /// Input and Output nodes.
+    pub io: usize,
+    /// Direct and indirect calls.
+    pub calls: usize,
+    /// Constant loads.
+    pub load_consts: usize,
+    /// Leaf operations, including the custom ones.
+    pub leaf_ops: usize,
+    /// Leaf operations defined by resources.
+    pub custom_ops: usize,
+    /// Other operations, such as module roots.
+    pub other: usize,
+}
+
+impl OpCounts {
+    /// Count a node of the given type.
+    fn add(&mut self, op: &OpType) {
+        let bucket = match op.tag() {
+            OpTag::Function | OpTag::FuncDefn => &mut self.functions,
+            OpTag::Const | OpTag::Alias => &mut self.consts,
+            OpTag::Dfg | OpTag::Cfg | OpTag::TailLoop | OpTag::Conditional | OpTag::Case => {
+                &mut self.containers
+            }
+            OpTag::BasicBlock | OpTag::BasicBlockExit => &mut self.basic_blocks,
+            OpTag::Input | OpTag::Output => &mut self.io,
+            OpTag::FnCall => &mut self.calls,
+            OpTag::LoadConst => &mut self.load_consts,
+            OpTag::Leaf => &mut self.leaf_ops,
+            _ => &mut self.other,
+        };
+        *bucket += 1;
+        if let OpType::LeafOp(LeafOp::CustomOp(_)) = op {
+            self.custom_ops += 1;
+        }
+    }
+}
+
+impl HugrSummary {
+    /// The number of leaf operation names kept in
+    /// [`HugrSummary::top_leaf_ops`].
+    pub const TOP_LEAF_OPS: usize = 5;
+}
+
+impl Hugr {
+    /// Compute summary statistics of the HUGR, in a single traversal.
+    pub fn summary(&self) -> HugrSummary {
+        self.summary_visiting(|_| {})
+    }
+
+    /// Compute the summary, calling `visit` on each node as it is traversed.
+    pub(crate) fn summary_visiting(&self, mut visit: impl FnMut(Node)) -> HugrSummary {
+        let mut summary = HugrSummary::default();
+        let mut leaf_ops: HashMap<SmolStr, usize> = HashMap::new();
+        let mut region_children = 0;
+
+        let mut stack = vec![(self.root(), 0)];
+        while let Some((node, depth)) = stack.pop() {
+            visit(node);
+            summary.nodes += 1;
+            summary.max_depth = summary.max_depth.max(depth);
+
+            let optype = self.get_optype(node);
+            summary.ops.add(optype);
+            if let OpType::LeafOp(leaf) = optype {
+                *leaf_ops.entry(leaf.name()).or_default() += 1;
+            }
+
+            let parent = self.get_parent(node);
+            summary.inter_graph_edges += self
+                .all_linked_ports(node, Direction::Outgoing)
+                .filter(|&(_, (target, _))| self.get_parent(target) != parent)
+                .count();
+
+            let len = stack.len();
+            stack.extend(self.children(node).map(|child| (child, depth + 1)));
+            let size = stack.len() - len;
+            if size > 0 {
+                summary.regions += 1;
+                summary.max_region_size = summary.max_region_size.max(size);
+                region_children += size;
+            }
+        }
+
+        if summary.regions > 0 {
+            summary.mean_region_size = region_children as f64 / summary.regions as f64;
+        }
+        let mut leaf_ops: Vec<_> = leaf_ops.into_iter().collect();
+        leaf_ops.sort_unstable_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+        leaf_ops.truncate(HugrSummary::TOP_LEAF_OPS);
+        summary.top_leaf_ops = leaf_ops;
+        summary
+    }
+}
+
+impl fmt::Display for HugrSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "nodes={} ({}) regions={} max_region={} mean_region={:.1} depth={} inter_graph_edges={} top_leaf_ops=[",
+            self.nodes,
+            self.ops,
+            self.regions,
+            self.max_region_size,
+            self.mean_region_size,
+            self.max_depth,
+            self.inter_graph_edges
+        )?;
+        for (i, (name, count)) in self.top_leaf_ops.iter().enumerate() {
+            let sep = if i == 0 { "" } else { " " };
+            write!(f, "{sep}{name}:{count}")?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl fmt::Display for OpCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let buckets = [
+            ("functions", self.functions),
+            ("consts", self.consts),
+            ("containers", self.containers),
+            ("blocks", self.basic_blocks),
+            ("io", self.io),
+            ("calls", self.calls),
+            ("loads", self.load_consts),
+            ("leaf", self.leaf_ops),
+            ("custom", self.custom_ops),
+            ("other", self.other),
+        ];
+        let mut first = true;
+        for (name, count) in buckets.into_iter().filter(|&(_, count)| count > 0) {
+            if !first {
+                f.write_str(" ")?;
+            }
+            write!(f, "{name}={count}")?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder,
+    };
+    use crate::ops::ConstValue;
+    use crate::type_row;
+    use crate::types::{ClassicType, LinearType, Signature, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    /// A function loading a module constant, with a gate in a nested DFG.
+    fn nested_hugr() -> Result<Hugr, BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let c = module_builder.add_constant(ConstValue::i64(3))?;
+        let sig = Signature::new_df(type_row![QB], type_row![QB, NAT]);
+        let mut main = module_builder.define_function("main", sig)?;
+        let loaded = main.load_const(&c)?;
+        let h = main.add_dataflow_op(LeafOp::H, main.input_wires())?;
+        let sig = Signature::new_df(type_row![QB], type_row![QB]);
+        let mut dfg = main.dfg_builder(sig, h.outputs())?;
+        let h = dfg.add_dataflow_op(LeafOp::H, dfg.input_wires())?;
+        let dfg = dfg.finish_with_outputs(h.outputs())?;
+        main.finish_with_outputs([dfg.out_wire(0), loaded])?;
+        module_builder.finish_hugr()
+    }
+
+    #[test]
+    fn nested_summary() -> Result<(), BuildError> {
+        let summary = nested_hugr()?.summary();
+        let ops = OpCounts {
+            functions: 1,
+            consts: 1,
+            containers: 1,
+            io: 4,
+            load_consts: 1,
+            leaf_ops: 2,
+            other: 1,
+            ..Default::default()
+        };
+        assert_eq!(summary.nodes, 11);
+        assert_eq!(summary.ops, ops);
+        assert_eq!(summary.top_leaf_ops, [("H".into(), 2)]);
+        assert_eq!(summary.regions, 3);
+        assert_eq!(summary.max_region_size, 5);
+        assert_eq!(summary.max_depth, 3);
+        // The static edge from the constant to its load.
+        assert_eq!(summary.inter_graph_edges, 1);
+        assert_eq!(
+            summary.to_string(),
+            "nodes=11 (functions=1 consts=1 containers=1 io=4 loads=1 leaf=2 other=1) \
+             regions=3 max_region=5 mean_region=3.3 depth=3 inter_graph_edges=1 top_leaf_ops=[H:2]"
+        );
+
+        let json = serde_json::to_string(&summary).unwrap();
+        assert_eq!(serde_json::from_str::<HugrSummary>(&json).unwrap(), summary);
+        Ok(())
+    }
+
+    #[test]
+    fn visits_each_node_once() -> Result<(), BuildError> {
+        let hugr = nested_hugr()?;
+        let mut visited = Vec::new();
+        let summary = hugr.summary_visiting(|node| visited.push(node));
+        assert_eq!(visited.len(), hugr.node_count());
+        let unique: HashSet<_> = visited.into_iter().collect();
+        assert_eq!(unique.len(), hugr.node_count());
+        assert_eq!(summary.nodes, hugr.node_count());
+        Ok(())
+    }
+}
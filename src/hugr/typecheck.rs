@@ -10,12 +10,12 @@ use crate::types::{SimpleType, TypeRow};
 
 // For static typechecking
 use crate::ops::ConstValue;
-use crate::types::{ClassicType, Container};
+use crate::types::{angle, ClassicType, Container};
 
 use crate::ops::constant::{HugrIntValueStore, HugrIntWidthStore, HUGR_MAX_INT_WIDTH};
 
 /// Errors that arise from typechecking constants
-#[derive(Clone, Debug, Eq, PartialEq, Error)]
+#[derive(Clone, Debug, Eq, PartialEq, Error, serde::Serialize)]
 pub enum ConstTypeError {
     /// This case hasn't been implemented. Possibly because we don't have value
     /// constructors to check against it
@@ -146,6 +146,7 @@ pub fn typecheck_const(typ: &ClassicType, val: &ConstValue) -> Result<(), ConstT
             }
             Ok(())
         }
+        (ClassicType::Opaque(_), ConstValue::Angle(_)) if typ == &angle::ANGLE => Ok(()),
         (ty, _) => Err(ConstTypeError::Failed(ty.clone())),
     }
 }
@@ -154,6 +155,8 @@ pub fn typecheck_const(typ: &ClassicType, val: &ConstValue) -> Result<(), ConstT
 mod test {
     use cool_asserts::assert_matches;
 
+    use crate::types::angle::AngleValue;
+    use crate::types::CustomType;
     use crate::{type_row, types::ClassicType};
 
     use super::*;
@@ -199,4 +202,25 @@ mod test {
             Err(ConstTypeError::TupleWrongLength)
         );
     }
+
+    #[test]
+    fn test_typecheck_angle() {
+        let half_turn = ConstValue::Angle(AngleValue::rational(1, 1).unwrap());
+        typecheck_const(&angle::ANGLE, &half_turn).unwrap();
+        typecheck_const(&angle::ANGLE, &ConstValue::Angle(AngleValue::F64(0.25))).unwrap();
+        assert_eq!(half_turn.const_type(), angle::ANGLE);
+        assert_eq!(
+            typecheck_const(&ClassicType::F64, &half_turn),
+            Err(ConstTypeError::Failed(ClassicType::F64))
+        );
+        assert_eq!(
+            typecheck_const(&angle::ANGLE, &ConstValue::F64(0.25)),
+            Err(ConstTypeError::Failed(angle::ANGLE))
+        );
+        let quat = CustomType::new_simple("quat".into()).classic_type();
+        assert_eq!(
+            typecheck_const(&quat, &half_turn),
+            Err(ConstTypeError::Failed(quat))
+        );
+    }
 }
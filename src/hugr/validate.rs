@@ -1,72 +1,179 @@
 //! HUGR invariant checks.
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::iter;
+use std::fmt::{self, Display};
 
 use itertools::Itertools;
 use petgraph::algo::dominators::{self, Dominators};
 use petgraph::visit::{DfsPostOrder, Walker};
-use portgraph::{LinkView, PortView};
+use smol_str::SmolStr;
 use thiserror::Error;
 
 use crate::hugr::typecheck::{typecheck_const, ConstTypeError};
 use crate::ops::validate::{ChildrenEdgeData, ChildrenValidationError, EdgeValidationError};
 use crate::ops::OpTag;
-use crate::ops::{self, OpTrait, OpType, ValidateOp};
-use crate::resource::ResourceSet;
-use crate::types::ClassicType;
+use crate::ops::{self, OpName, OpTrait, OpType, PortClass, ValidateOp};
+use crate::resource::{ResourceId, ResourceSet};
+use crate::types::{ClassicType, Container, Signature, TypeRow};
 use crate::types::{EdgeKind, SimpleType};
 use crate::{Direction, Hugr, Node, Port};
 
-use super::region::{FlatRegionView, Region};
+use super::lint::Lint;
+use super::region::{FlatRegionView, Region, RegionView};
+use super::view::sealed::HugrInternals;
 use super::view::HugrView;
 
+mod types;
+pub use types::TypeValidationError;
+
 /// Structure keeping track of pre-computed information used in the validation
 /// process.
 ///
 /// TODO: Consider implementing updatable dominator trees and storing it in the
 /// Hugr to avoid recomputing it every time.
-struct ValidationContext<'a> {
-    hugr: &'a Hugr,
+struct ValidationContext<'a, H: HugrView> {
+    hugr: &'a H,
     /// Dominator tree for each CFG region, using the container node as index.
     dominators: HashMap<Node, Dominators<Node>>,
     /// Resource requirements associated with each edge
     resources: HashMap<(Node, Direction), ResourceSet>,
+    /// Options for the validation.
+    options: ValidationOptions,
+}
+
+/// Options for [`Hugr::validate_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationOptions {
+    /// Compute additional information about the causes of the errors, which
+    /// may require further traversals of the HUGR.
+    ///
+    /// Currently this fills the `provenance` of
+    /// [`ValidationError::SrcExceedsTgtResources`].
+    pub detailed_errors: bool,
+    /// Ignore the ports of a view which are only connected to nodes outside
+    /// of it, such as the uses of a function validated with
+    /// [`HugrView::as_rooted`].
+    ///
+    /// Otherwise they are reported as [`ValidationError::BoundaryEdge`].
+    pub ignore_boundary_edges: bool,
+    /// Check the resource requirements of the edges and the declared resource
+    /// deltas, assuming the resource variables have already been solved.
+    ///
+    /// Disable it to only check the structure of a HUGR at an earlier
+    /// [`CompilationStage`](super::CompilationStage).
+    pub assume_resources_solved: bool,
+    /// Compare the types of connected ports structurally, ignoring the names
+    /// of new types, see [`SimpleType::structurally_eq`].
+    ///
+    /// Otherwise new types are only compatible with themselves.
+    pub structural_types: bool,
+    /// Forbid implicit fan-out: report every Value output connected more than
+    /// once as [`ValidationError::TooManyConnections`], classical ones
+    /// included.
+    ///
+    /// Use [`materialise_copies`] to insert explicit copies instead.
+    ///
+    /// [`materialise_copies`]: crate::algorithm::materialise_copies
+    pub forbid_implicit_copies: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        Self {
+            detailed_errors: false,
+            ignore_boundary_edges: false,
+            assume_resources_solved: true,
+            structural_types: false,
+            forbid_implicit_copies: false,
+        }
+    }
 }
 
 impl Hugr {
     /// Check the validity of the HUGR.
     pub fn validate(&self) -> Result<(), ValidationError> {
-        let mut validator = ValidationContext::new(self);
+        self.validate_with_options(&ValidationOptions::default())
+    }
+
+    /// Check the validity of the HUGR, with the given options.
+    pub fn validate_with_options(
+        &self,
+        options: &ValidationOptions,
+    ) -> Result<(), ValidationError> {
+        let mut validator = ValidationContext::new(self, *options);
+        validator.validate()
+    }
+
+    /// Check the validity of the HUGR, reporting an error as a JSON document.
+    ///
+    /// The document is an object with the fields:
+    /// - `version`: the version of the document format, currently
+    ///   [`VALIDATION_JSON_VERSION`].
+    /// - `category`: the [`ErrorCategory`] of the error.
+    /// - `message`: the human-readable description of the error.
+    /// - `error`: the serialized [`ValidationError`], tagged by variant name.
+    pub fn validate_json(&self) -> Result<(), String> {
+        self.validate().map_err(|err| {
+            serde_json::json!({
+                "version": VALIDATION_JSON_VERSION,
+                "category": err.category(),
+                "message": err.to_string(),
+                "error": err,
+            })
+            .to_string()
+        })
+    }
+}
+
+/// The version of the JSON documents produced by [`Hugr::validate_json`].
+pub const VALIDATION_JSON_VERSION: u32 = 1;
+
+impl<'g, Base> RegionView<'g, Base>
+where
+    Base: HugrInternals + HugrView,
+{
+    /// Check the validity of the region, as if its root was the root of a
+    /// HUGR.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_with_options(&ValidationOptions::default())
+    }
+
+    /// Check the validity of the region, with the given options.
+    pub fn validate_with_options(
+        &self,
+        options: &ValidationOptions,
+    ) -> Result<(), ValidationError> {
+        let mut validator = ValidationContext::new(self, *options);
         validator.validate()
     }
 }
 
-impl<'a> ValidationContext<'a> {
+impl<'a, H: HugrView> ValidationContext<'a, H> {
     /// Create a new validation context.
-    pub fn new(hugr: &'a Hugr) -> Self {
+    pub fn new(hugr: &'a H, options: ValidationOptions) -> Self {
         Self {
             hugr,
             dominators: HashMap::new(),
             resources: HashMap::new(),
+            options,
         }
     }
 
     /// Check the validity of the HUGR.
     pub fn validate(&mut self) -> Result<(), ValidationError> {
         // Root node must be a root in the hierarchy.
-        if !self.hugr.hierarchy.is_root(self.hugr.root) {
-            return Err(ValidationError::RootNotRoot {
-                node: self.hugr.root(),
-            });
+        let hugr = self.hugr;
+        if hugr.get_parent(hugr.root()).is_some() {
+            return Err(ValidationError::RootNotRoot { node: hugr.root() });
         }
 
-        for node in self.hugr.graph.nodes_iter().map_into() {
+        for node in hugr.nodes() {
             self.gather_resources(&node)?;
         }
 
         // Node-specific checks
-        for node in self.hugr.graph.nodes_iter().map_into() {
+        for node in hugr.nodes() {
             self.validate_node(node)?;
         }
 
@@ -77,14 +184,13 @@ impl<'a> ValidationContext<'a> {
     /// resource requirements for all of its input and output edges, then put
     /// those requirements in the ValidationContext
     fn gather_resources(&mut self, node: &Node) -> Result<(), ValidationError> {
-        let op = self.hugr.op_types.get(node.index);
-        let sig = op.signature();
-
-        for dir in Direction::BOTH {
-            assert!(self
-                .resources
-                .insert((*node, dir), sig.get_resources(&dir).clone())
-                .is_none());
+        let sig = self.hugr.effective_signature(*node);
+
+        for (dir, resources) in [
+            (Direction::Incoming, sig.input_resources),
+            (Direction::Outgoing, sig.output_resources),
+        ] {
+            assert!(self.resources.insert((*node, dir), resources).is_none());
         }
 
         Ok(())
@@ -107,13 +213,44 @@ impl<'a> ValidationContext<'a> {
     /// - Matching the number of ports with the signature
     /// - Dataflow ports are correct. See `validate_df_port`
     fn validate_node(&mut self, node: Node) -> Result<(), ValidationError> {
-        let optype = self.hugr.get_optype(node);
+        let hugr = self.hugr;
+        let optype = hugr.get_optype(node);
+
+        if let OpType::UnknownOp(op) = optype {
+            return Err(ValidationError::UnknownOp {
+                node,
+                name: op.name(),
+            });
+        }
+
+        // A declared resource delta must match the resources introduced by
+        // the children. It is checked before the edges, whose resources
+        // derive from it.
+        if let OpType::DFG(ops::DFG {
+            resource_delta: Some(declared),
+            ..
+        }) = optype
+        {
+            if self.options.assume_resources_solved {
+                self.validate_resource_delta(node, declared)?;
+            }
+        }
+
+        // The Input and Output nodes of a dataflow region carry the resources
+        // of the container's inner signature, from which the resources of the
+        // region's edges derive.
+        if let Some(inner) = optype.inner_signature() {
+            if self.options.assume_resources_solved {
+                self.validate_io_resources(node, &inner)?;
+            }
+        }
 
         // The Hugr can have only one root node.
         if node == self.hugr.root() {
-            // The root node has no edges.
-            if self.hugr.graph.num_outputs(node.index) + self.hugr.graph.num_inputs(node.index) != 0
-            {
+            // The root node has no edges. The root of a view may be any node
+            // of the underlying HUGR, in which case its ports are not checked.
+            let num_ports = hugr.num_inputs(node) + hugr.num_outputs(node);
+            if node == hugr.base_hugr().root() && num_ports != 0 {
                 return Err(ValidationError::RootWithEdges { node });
             }
         } else {
@@ -133,23 +270,92 @@ impl<'a> ValidationContext<'a> {
                 });
             }
 
+            // Static inputs can only carry classical values.
+            if let Some(typ) = optype
+                .signature()
+                .static_input
+                .iter()
+                .find(|typ| !typ.is_classical())
+            {
+                return Err(ValidationError::NonClassicalStaticInput {
+                    node,
+                    optype: optype.clone(),
+                    typ: typ.clone(),
+                });
+            }
+
+            // The graph argument of an indirect call must be a classical value.
+            if let OpType::CallIndirect(call) = optype {
+                let sig = &call.signature;
+                if let Some((typ, linear)) = sig
+                    .input
+                    .iter()
+                    .chain(sig.output.iter())
+                    .filter_map(|typ| match typ {
+                        SimpleType::Classic(typ) => Some(typ),
+                        SimpleType::Linear(_) => None,
+                    })
+                    .find_map(|typ| Some((typ, linear_in_classic(typ)?)))
+                {
+                    return Err(ValidationError::LinearInClassicType {
+                        node,
+                        typ: typ.clone(),
+                        linear,
+                    });
+                }
+            }
+
+            // Input and Output nodes have no ports on their outer side,
+            // whatever their type rows say. In particular, no edges may come
+            // into an Input node, not even order edges from outside the region.
+            let num_inputs = hugr.num_inputs(node);
+            let num_outputs = hugr.num_outputs(node);
+            match optype {
+                OpType::Input(_) if num_inputs != 0 => {
+                    return Err(ValidationError::InputWithIncomingPorts {
+                        node,
+                        count: num_inputs,
+                    });
+                }
+                OpType::Output(_) if num_outputs != 0 => {
+                    return Err(ValidationError::OutputWithOutgoingPorts {
+                        node,
+                        count: num_outputs,
+                    });
+                }
+                _ => {}
+            }
+
+            // Basic blocks have one outgoing control flow port per successor.
+            if let OpType::BasicBlock(block) = optype {
+                let expected = block.num_successors();
+                if num_outputs != expected {
+                    return Err(ValidationError::WrongNumberOfSuccessors {
+                        node,
+                        actual: num_outputs,
+                        expected,
+                    });
+                }
+            }
+
             for dir in Direction::BOTH {
                 // Check that we have the correct amount of ports and edges.
-                let num_ports = self.hugr.graph.num_ports(node.index, dir);
+                let num_ports = hugr.num_ports(node, dir);
                 if num_ports != optype.port_count(dir) {
                     return Err(ValidationError::WrongNumberOfPorts {
                         node,
                         optype: optype.clone(),
                         actual: num_ports,
                         expected: optype.port_count(dir),
+                        actual_layout: PortLayout::of_node(hugr, node, dir),
+                        expected_layout: PortLayout::of_op(optype, dir),
                         dir,
                     });
                 }
 
                 // Check port connections
-                for (i, port_index) in self.hugr.graph.ports(node.index, dir).enumerate() {
-                    let port = Port::new(dir, i);
-                    self.validate_port(node, port, port_index, optype)?;
+                for port in hugr.node_ports(node, dir) {
+                    self.validate_port(node, port, optype)?;
                 }
             }
         }
@@ -167,7 +373,8 @@ impl<'a> ValidationContext<'a> {
     ///   (e.g. implicit lifting of `A -> B` to `[R]A -> [R]B`)
     /// and adding of lift nodes
     ///   (i.e. those which transform an edge from `A` to `[R]A`)
-    /// has already been done.
+    /// has already been done. It is skipped unless
+    /// [`ValidationOptions::assume_resources_solved`] is set.
     fn check_resources_compatible(
         &self,
         src: &(Node, Port),
@@ -190,6 +397,15 @@ impl<'a> ValidationContext<'a> {
                 to_resources: rs_tgt.clone(),
             })
         } else {
+            let provenance = if self.options.detailed_errors {
+                rs_src
+                    .iter()
+                    .filter(|r| !rs_tgt.contains(r))
+                    .map(|r| (r.clone(), self.resource_provenance(src.0, r)))
+                    .collect()
+            } else {
+                Vec::new()
+            };
             Err(ValidationError::SrcExceedsTgtResources {
                 from: src.0,
                 from_offset: src.1,
@@ -197,10 +413,95 @@ impl<'a> ValidationContext<'a> {
                 to: tgt.0,
                 to_offset: tgt.1,
                 to_resources: rs_tgt.clone(),
+                provenance,
             })
         }
     }
 
+    /// Check that the resources of the Input and Output children of a
+    /// dataflow container match its inner signature.
+    ///
+    /// Containers without Input and Output children are ignored, they are
+    /// reported by the structural checks of the container.
+    fn validate_io_resources(
+        &self,
+        parent: Node,
+        inner: &Signature,
+    ) -> Result<(), ValidationError> {
+        let Some((input, output)) = self.hugr.get_io(parent) else {
+            return Ok(());
+        };
+        let checks = [
+            (input, &inner.input_resources),
+            (output, &inner.output_resources),
+        ];
+        for (child, expected) in checks {
+            let actual = match self.hugr.get_optype(child) {
+                OpType::Input(ops::Input { resources, .. })
+                | OpType::Output(ops::Output { resources, .. }) => resources,
+                _ => continue,
+            };
+            if actual != expected {
+                return Err(ValidationError::IOResourcesMismatch {
+                    parent,
+                    child,
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the resources introduced by the children of a container
+    /// are the ones declared by it.
+    fn validate_resource_delta(
+        &self,
+        node: Node,
+        declared: &ResourceSet,
+    ) -> Result<(), ValidationError> {
+        let actual = self.hugr.region_resource_delta(node);
+        if !actual.is_subset(declared) {
+            return Err(ValidationError::RegionExceedsResourceDelta {
+                node,
+                declared: declared.clone(),
+                actual,
+            });
+        }
+        if actual != *declared {
+            return Err(ValidationError::UnusedResourceDelta {
+                node,
+                declared: declared.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// The path of nodes through which a resource requirement reaches the
+    /// outputs of `node`.
+    ///
+    /// Walks backwards from `node` through predecessors requiring the resource
+    /// on their outputs, until reaching a node that does not require it on its
+    /// inputs and hence introduces it. The path starts at that node and ends
+    /// at `node`.
+    fn resource_provenance(&self, node: Node, resource: &ResourceId) -> Vec<Node> {
+        let mut path = vec![node];
+        let mut node = node;
+        while self.resources[&(node, Direction::Incoming)].contains(resource) {
+            let Some(pred) = self.hugr.input_neighbours(node).find(|pred| {
+                !path.contains(pred)
+                    && self.resources[&(*pred, Direction::Outgoing)].contains(resource)
+            }) else {
+                break;
+            };
+            path.push(pred);
+            node = pred;
+        }
+        path.reverse();
+        path
+    }
+
     /// Check whether a port is valid.
     /// - Input ports and output linear ports must be connected
     /// - The linked port must have a compatible type.
@@ -208,13 +509,20 @@ impl<'a> ValidationContext<'a> {
         &mut self,
         node: Node,
         port: Port,
-        port_index: portgraph::PortIndex,
         optype: &OpType,
     ) -> Result<(), ValidationError> {
         let port_kind = optype.port_kind(port).unwrap();
         let dir = port.direction();
 
-        let mut links = self.hugr.graph.port_links(port_index).peekable();
+        let hugr = self.hugr;
+        let mut links = hugr.linked_ports(node, port).peekable();
+        // The port may be connected to nodes outside of the validated view.
+        if links.peek().is_none() && hugr.base_hugr().is_linked(node, port) {
+            return match self.options.ignore_boundary_edges {
+                true => Ok(()),
+                false => Err(ValidationError::BoundaryEdge { node, port }),
+            };
+        }
         let must_be_connected = match dir {
             // Incoming ports must be connected, except for state order ports, branch case nodes,
             // and CFG nodes.
@@ -226,6 +534,13 @@ impl<'a> ValidationContext<'a> {
             // Linear dataflow values must be connected.
             Direction::Outgoing => port_kind.is_linear(),
         };
+        // Control flow cannot dangle out of a basic block.
+        if dir == Direction::Outgoing
+            && port_kind == EdgeKind::ControlFlow
+            && links.peek().is_none()
+        {
+            return Err(ValidationError::UnconnectedSuccessor { node, port });
+        }
         if must_be_connected && links.peek().is_none() {
             return Err(ValidationError::UnconnectedPort {
                 node,
@@ -239,10 +554,12 @@ impl<'a> ValidationContext<'a> {
             return Ok(());
         }
 
+        let single_link = port_kind.is_linear()
+            || (self.options.forbid_implicit_copies && matches!(port_kind, EdgeKind::Value(_)));
         let mut link_cnt = 0;
-        for (_, link) in links {
+        for (other_node, other_offset) in links {
             link_cnt += 1;
-            if port_kind.is_linear() && link_cnt > 1 {
+            if single_link && link_cnt > 1 {
                 return Err(ValidationError::TooManyConnections {
                     node,
                     port,
@@ -250,10 +567,11 @@ impl<'a> ValidationContext<'a> {
                 });
             }
 
-            let other_node: Node = self.hugr.graph.port_node(link).unwrap().into();
-            let other_offset = self.hugr.graph.port_offset(link).unwrap().into();
-
-            self.check_resources_compatible(&(node, port), &(other_node, other_offset))?;
+            // Static values are not computed at runtime, so they carry no
+            // resource requirements.
+            if self.options.assume_resources_solved && !matches!(port_kind, EdgeKind::Static(_)) {
+                self.check_resources_compatible(&(node, port), &(other_node, other_offset))?;
+            }
 
             let other_op = self.hugr.get_optype(other_node);
             let Some(other_kind) = other_op.port_kind(other_offset) else {
@@ -262,7 +580,17 @@ impl<'a> ValidationContext<'a> {
                 return Err(self.validate_node(other_node).unwrap_err());
             };
             // TODO: We will require some "unifiable" comparison instead of strict equality, to allow for pre-type inference hugrs.
-            if other_kind != port_kind {
+            let compatible = match self.options.structural_types {
+                true => other_kind.structurally_eq(&port_kind),
+                false => other_kind == port_kind,
+            };
+            if !compatible {
+                if let Some(err) = self
+                    .call_indirect_mismatch(node, port, &other_kind)
+                    .or_else(|| self.call_indirect_mismatch(other_node, other_offset, &port_kind))
+                {
+                    return Err(err);
+                }
                 return Err(ValidationError::IncompatiblePorts {
                     from: node,
                     from_port: port,
@@ -279,13 +607,39 @@ impl<'a> ValidationContext<'a> {
         Ok(())
     }
 
+    /// Report a graph of the wrong signature connected to the graph argument
+    /// of a [`CallIndirect`] node, given the kind of the other end of the edge.
+    ///
+    /// Returns `None` if `(node, port)` is not the graph argument of a
+    /// [`CallIndirect`], or if the other end is not a graph.
+    ///
+    /// [`CallIndirect`]: crate::ops::CallIndirect
+    fn call_indirect_mismatch(
+        &self,
+        node: Node,
+        port: Port,
+        graph_kind: &EdgeKind,
+    ) -> Option<ValidationError> {
+        let OpType::CallIndirect(call) = self.hugr.get_optype(node) else {
+            return None;
+        };
+        let EdgeKind::Value(SimpleType::Classic(ClassicType::Graph(graph))) = graph_kind else {
+            return None;
+        };
+        (port == Port::new_incoming(0)).then(|| ValidationError::CallIndirectMismatch {
+            node,
+            expected: call.signature.clone(),
+            found: graph.1.clone(),
+        })
+    }
+
     /// Check operation-specific constraints.
     ///
     /// These are flags defined for each operation type as an [`OpValidityFlags`] object.
     fn validate_operation(&self, node: Node, optype: &OpType) -> Result<(), ValidationError> {
         let flags = optype.validity_flags();
 
-        if self.hugr.hierarchy.child_count(node.index) > 0 {
+        if self.hugr.children(node).next().is_some() {
             if flags.allowed_children.is_empty() {
                 return Err(ValidationError::NonContainerWithChildren {
                     node,
@@ -293,9 +647,7 @@ impl<'a> ValidationContext<'a> {
                 });
             }
 
-            let all_children = self.hugr.children(node);
-            let mut first_two_children = all_children.clone().take(2);
-            let first_child = self.hugr.get_optype(first_two_children.next().unwrap());
+            let first_child = self.hugr.get_optype(self.hugr.nth_child(node, 0).unwrap());
             if !flags.allowed_first_child.is_superset(first_child.tag()) {
                 return Err(ValidationError::InvalidInitialChild {
                     parent: node,
@@ -306,8 +658,9 @@ impl<'a> ValidationContext<'a> {
                 });
             }
 
-            if let Some(second_child) = first_two_children
-                .next()
+            if let Some(second_child) = self
+                .hugr
+                .nth_child(node, 1)
                 .map(|child| self.hugr.get_optype(child))
             {
                 if !flags.allowed_second_child.is_superset(second_child.tag()) {
@@ -321,7 +674,10 @@ impl<'a> ValidationContext<'a> {
                 }
             }
             // Additional validations running over the full list of children optypes
-            let children_optypes = all_children.map(|c| (c.index, self.hugr.get_optype(c)));
+            let children_optypes = self
+                .hugr
+                .children(node)
+                .map(|c| (c.index, self.hugr.get_optype(c)));
             if let Err(source) = optype.validate_children(children_optypes) {
                 return Err(ValidationError::InvalidChildren {
                     parent: node,
@@ -332,31 +688,26 @@ impl<'a> ValidationContext<'a> {
 
             // Additional validations running over the edges of the contained graph
             if let Some(edge_check) = flags.edge_check {
-                for source in self.hugr.hierarchy.children(node.index) {
-                    for target in self.hugr.graph.output_neighbours(source) {
-                        if self.hugr.hierarchy.parent(target) != Some(node.index) {
-                            continue;
-                        }
-                        let source_op = self.hugr.get_optype(source.into());
-                        let target_op = self.hugr.get_optype(target.into());
-                        for (source_port, target_port) in
-                            self.hugr.graph.get_connections(source, target)
-                        {
-                            let edge_data = ChildrenEdgeData {
+                for source in self.hugr.children(node) {
+                    let links = self
+                        .hugr
+                        .all_linked_ports(source, Direction::Outgoing)
+                        .filter(|&(_, (target, _))| self.hugr.get_parent(target) == Some(node));
+                    for ((_, source_port), (target, target_port)) in links {
+                        let edge_data = ChildrenEdgeData {
+                            source: source.index,
+                            target: target.index,
+                            source_port: source_port.offset,
+                            target_port: target_port.offset,
+                            source_op: self.hugr.get_optype(source).clone(),
+                            target_op: self.hugr.get_optype(target).clone(),
+                        };
+                        if let Err(source) = edge_check(edge_data) {
+                            return Err(ValidationError::InvalidEdges {
+                                parent: node,
+                                parent_optype: optype.clone(),
                                 source,
-                                target,
-                                source_port: self.hugr.graph.port_offset(source_port).unwrap(),
-                                target_port: self.hugr.graph.port_offset(target_port).unwrap(),
-                                source_op: source_op.clone(),
-                                target_op: target_op.clone(),
-                            };
-                            if let Err(source) = edge_check(edge_data) {
-                                return Err(ValidationError::InvalidEdges {
-                                    parent: node,
-                                    parent_optype: optype.clone(),
-                                    source,
-                                });
-                            }
+                            });
                         }
                     }
                 }
@@ -382,7 +733,7 @@ impl<'a> ValidationContext<'a> {
     /// Inter-graph edges are ignored. Only internal dataflow, constant, or
     /// state order edges are considered.
     fn validate_children_dag(&self, parent: Node, optype: &OpType) -> Result<(), ValidationError> {
-        if !self.hugr.hierarchy.has_children(parent.index) {
+        if self.hugr.children(parent).next().is_none() {
             // No children, nothing to do
             return Ok(());
         };
@@ -440,6 +791,9 @@ impl<'a> ValidationContext<'a> {
             EdgeKind::Static(typ) => {
                 if let OpType::Const(ops::Const(val)) = from_optype {
                     return typecheck_const(&typ, val).map_err(ValidationError::from);
+                } else if let OpType::ConstRow(ops::ConstRow(vals)) = from_optype {
+                    let val = &vals[from_offset.index()];
+                    return typecheck_const(&typ, val).map_err(ValidationError::from);
                 } else {
                     // If const edges aren't coming from const nodes, they're graph
                     // edges coming from FuncDecl or FuncDefn
@@ -475,17 +829,13 @@ impl<'a> ValidationContext<'a> {
         // This search could be sped-up with a pre-computed LCA structure, but
         // for valid Hugrs this search should be very short.
         let from_parent_parent = self.hugr.get_parent(from_parent);
-        for (ancestor, ancestor_parent) in
-            iter::successors(to_parent, |&p| self.hugr.get_parent(p)).tuple_windows()
-        {
+        for (ancestor, ancestor_parent) in self.hugr.ancestors(to).tuple_windows() {
             if ancestor_parent == from_parent {
                 // External edge. Must have an order edge.
                 self.hugr
-                    .graph
-                    .get_connections(from.index, ancestor.index)
-                    .find(|&(p, _)| {
-                        let offset = self.hugr.graph.port_offset(p).unwrap();
-                        from_optype.port_kind(offset) == Some(EdgeKind::StateOrder)
+                    .all_linked_ports(from, Direction::Outgoing)
+                    .find(|&((_, p), (n, _))| {
+                        n == ancestor && from_optype.port_kind(p) == Some(EdgeKind::StateOrder)
                     })
                     .ok_or(InterGraphEdgeError::MissingOrderEdge {
                         from,
@@ -548,7 +898,7 @@ impl<'a> ValidationContext<'a> {
 }
 
 /// Errors that can occur while validating a Hugr.
-#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error, serde::Serialize)]
 #[allow(missing_docs)]
 pub enum ValidationError {
     /// The root node of the Hugr is not a root in the hierarchy.
@@ -557,15 +907,32 @@ pub enum ValidationError {
     /// The root node of the Hugr should not have any edges.
     #[error("The root node of the Hugr {node:?} has edges when it should not.")]
     RootWithEdges { node: Node },
+    /// A port of a view is only connected to nodes outside of it.
+    #[error("The node {node:?} has a port {port:?} only connected to nodes outside of the view.")]
+    BoundaryEdge { node: Node, port: Port },
+    /// The operation of the node is of a kind unknown to this version.
+    #[error("The node {node:?} has an unknown operation {name}, produced by a newer version of the library.")]
+    UnknownOp { node: Node, name: SmolStr },
     /// The node ports do not match the operation signature.
-    #[error("The node {node:?} has an invalid number of ports. The operation {optype:?} cannot have {actual:?} {dir:?} ports. Expected {expected:?}.")]
+    ///
+    /// The layouts classify the ports to tell which kinds are missing or extra.
+    #[error("The node {node:?} has an invalid number of ports. The operation {optype:?} cannot have {actual:?} {dir:?} ports. Expected {expected:?}: {}.", display_layouts(expected_layout, actual_layout))]
     WrongNumberOfPorts {
         node: Node,
         optype: OpType,
         actual: usize,
         expected: usize,
+        actual_layout: PortLayout,
+        expected_layout: PortLayout,
+        #[serde(serialize_with = "serialize_direction")]
         dir: Direction,
     },
+    /// An Input node has incoming ports.
+    #[error("The Input node {node:?} cannot have incoming ports, but has {count}.")]
+    InputWithIncomingPorts { node: Node, count: usize },
+    /// An Output node has outgoing ports.
+    #[error("The Output node {node:?} cannot have outgoing ports, but has {count}.")]
+    OutputWithOutgoingPorts { node: Node, count: usize },
     /// A dataflow port is not connected.
     #[error("The node {node:?} has an unconnected port {port:?} of type {port_kind:?}.")]
     UnconnectedPort {
@@ -573,7 +940,19 @@ pub enum ValidationError {
         port: Port,
         port_kind: EdgeKind,
     },
-    /// A linear port is connected to more than one thing.
+    /// The number of successor ports of a basic block does not match the
+    /// variants of its predicate.
+    #[error("The basic block {node:?} has {actual} successor ports, but its predicate has {expected} variants.")]
+    WrongNumberOfSuccessors {
+        node: Node,
+        actual: usize,
+        expected: usize,
+    },
+    /// A successor port of a basic block is not connected.
+    #[error("The successor port {port:?} of the basic block {node:?} is not connected.")]
+    UnconnectedSuccessor { node: Node, port: Port },
+    /// A linear port is connected to more than one thing, or any Value output
+    /// with [`ValidationOptions::forbid_implicit_copies`].
     #[error("The node {node:?} has a port {port:?} of type {port_kind:?} with more than one connection.")]
     TooManyConnections {
         node: Node,
@@ -581,7 +960,7 @@ pub enum ValidationError {
         port_kind: EdgeKind,
     },
     /// Connected ports have different types, or non-unifiable types.
-    #[error("Connected ports {from_port:?} in node {from:?} and {to_port:?} in node {to:?} have incompatible kinds. Cannot connect {from_kind:?} to {to_kind:?}.")]
+    #[error("Connected ports {from_port:?} in node {from:?} and {to_port:?} in node {to:?} have incompatible kinds. Cannot connect {}.", display_kinds(from_kind, to_kind))]
     IncompatiblePorts {
         from: Node,
         from_port: Port,
@@ -590,6 +969,33 @@ pub enum ValidationError {
         to_port: Port,
         to_kind: EdgeKind,
     },
+    /// The graph argument of a [`CallIndirect`] node has a different signature
+    /// than the call.
+    ///
+    /// [`CallIndirect`]: crate::ops::CallIndirect
+    #[error("The CallIndirect node {node:?} calls a graph with signature {found}, but its other ports have signature {expected}.")]
+    CallIndirectMismatch {
+        node: Node,
+        expected: Signature,
+        found: Signature,
+    },
+    /// A classical type in the signature of a [`CallIndirect`] node contains
+    /// linear data, so its graph argument is not a classical value.
+    ///
+    /// [`CallIndirect`]: crate::ops::CallIndirect
+    #[error("The CallIndirect node {node:?} has a classical type {typ} containing the linear type {linear}.")]
+    LinearInClassicType {
+        node: Node,
+        typ: ClassicType,
+        linear: SimpleType,
+    },
+    /// A static input of the operation signature is not a classical type.
+    #[error("The operation {optype:?} has a non-classical static input of type {typ:?}. In node {node:?}.")]
+    NonClassicalStaticInput {
+        node: Node,
+        optype: OpType,
+        typ: SimpleType,
+    },
     /// The non-root node has no parent.
     #[error("The node {node:?} has no parent.")]
     NoParent { node: Node },
@@ -668,11 +1074,270 @@ pub enum ValidationError {
         to: Node,
         to_offset: Port,
         to_resources: ResourceSet,
+        /// For each resource missing at the target, the path of nodes from
+        /// the node introducing it to the source node.
+        ///
+        /// Only computed with [`ValidationOptions::detailed_errors`], empty
+        /// otherwise.
+        provenance: Vec<(ResourceId, Vec<Node>)>,
+    },
+    /// The children of a container introduce resources missing from its
+    /// declared resource delta.
+    #[error("The resources introduced by the children of {node:?} ({actual}) exceed its declared resource delta ({declared}).")]
+    RegionExceedsResourceDelta {
+        node: Node,
+        declared: ResourceSet,
+        actual: ResourceSet,
+    },
+    /// The declared resource delta of a container includes resources not
+    /// introduced by its children.
+    #[error("The resource delta of {node:?} ({declared}) includes resources not introduced by its children ({actual}).")]
+    UnusedResourceDelta {
+        node: Node,
+        declared: ResourceSet,
+        actual: ResourceSet,
+    },
+    /// The resources of an Input or Output node differ from those of the
+    /// inner signature of its container.
+    #[error("The resources of {child:?} ({actual}) differ from those declared by its parent {parent:?} ({expected}).")]
+    IOResourcesMismatch {
+        parent: Node,
+        child: Node,
+        expected: ResourceSet,
+        actual: ResourceSet,
     },
+    /// A lint configured as an error in [`Hugr::validate_strict`].
+    #[error("Denied lint: {0}")]
+    DeniedLint(Lint),
+}
+
+/// The number of ports of each class in one direction of a node, reported
+/// by [`ValidationError::WrongNumberOfPorts`]. See [`PortClass`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize)]
+pub struct PortLayout {
+    /// The number of dataflow value ports.
+    pub value: usize,
+    /// The number of static (constant) ports.
+    pub constant: usize,
+    /// The number of state order ports.
+    pub order: usize,
+    /// The number of control flow ports.
+    pub control_flow: usize,
+}
+
+impl PortLayout {
+    /// The layout of the ports an operation expects in a direction.
+    pub fn of_op(optype: &OpType, dir: Direction) -> Self {
+        let mut layout = Self::default();
+        for index in 0..optype.port_count(dir) {
+            if let Some(class) = optype.port_row_index(Port::new(dir, index)) {
+                layout.add(class);
+            }
+        }
+        layout
+    }
+
+    /// The layout of the ports allocated for a node in a direction.
+    ///
+    /// A connected port is classified by the kind of the port at its other
+    /// end, and an unconnected one by its position in the node's operation.
+    /// Unconnected ports past those of the operation count as its other
+    /// ports, or as values if it has none.
+    pub fn of_node(hugr: &impl HugrView, node: Node, dir: Direction) -> Self {
+        let optype = hugr.get_optype(node);
+        let mut layout = Self::default();
+        for port in hugr.node_ports(node, dir) {
+            let linked_kind = hugr
+                .linked_ports(node, port)
+                .next()
+                .and_then(|(other, other_port)| hugr.get_optype(other).port_kind(other_port));
+            let class = match linked_kind {
+                None if port.index() < optype.port_count(dir) => optype.port_row_index(port),
+                None => optype.other_port(dir).map(|kind| kind_class(&kind)),
+                Some(kind) => Some(kind_class(&kind)),
+            };
+            layout.add(class.unwrap_or(PortClass::Value(0)));
+        }
+        layout
+    }
+
+    fn add(&mut self, class: PortClass) {
+        match class {
+            PortClass::Value(_) => self.value += 1,
+            PortClass::Static(_) => self.constant += 1,
+            PortClass::Other(_) => self.order += 1,
+            PortClass::ControlFlow(_) => self.control_flow += 1,
+        }
+    }
+
+    fn counts(&self) -> [(usize, &'static str); 4] {
+        [
+            (self.value, "value"),
+            (self.constant, "const"),
+            (self.order, "order"),
+            (self.control_flow, "control flow"),
+        ]
+    }
+}
+
+impl Display for PortLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = self
+            .counts()
+            .into_iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, name)| format!("{count} {name}"))
+            .collect_vec();
+        if parts.is_empty() {
+            f.write_str("no")
+        } else {
+            f.write_str(&parts.join(" + "))
+        }
+    }
+}
+
+/// Broad categories of [`ValidationError`]s, see [`ValidationError::category`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// The graph or its hierarchy is malformed.
+    Structural,
+    /// The types of connected ports or constant values do not match.
+    Typing,
+    /// The resource requirements of connected ports are incompatible.
+    ///
+    /// These may be fixed by adding lift nodes.
+    Resources,
+    /// A lint was configured as an error.
+    Lint,
+}
+
+impl ValidationError {
+    /// Returns the category of the error.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ValidationError::RootNotRoot { .. }
+            | ValidationError::RootWithEdges { .. }
+            | ValidationError::BoundaryEdge { .. }
+            | ValidationError::UnknownOp { .. }
+            | ValidationError::WrongNumberOfPorts { .. }
+            | ValidationError::InputWithIncomingPorts { .. }
+            | ValidationError::OutputWithOutgoingPorts { .. }
+            | ValidationError::UnconnectedPort { .. }
+            | ValidationError::WrongNumberOfSuccessors { .. }
+            | ValidationError::UnconnectedSuccessor { .. }
+            | ValidationError::TooManyConnections { .. }
+            | ValidationError::NoParent { .. }
+            | ValidationError::InvalidParentOp { .. }
+            | ValidationError::InvalidInitialChild { .. }
+            | ValidationError::NonContainerWithChildren { .. }
+            | ValidationError::ContainerWithoutChildren { .. }
+            | ValidationError::NotABoundedDag { .. }
+            | ValidationError::InterGraphEdgeError(_) => ErrorCategory::Structural,
+            ValidationError::InvalidChildren { source, .. } => match source {
+                ChildrenValidationError::InternalExitChildren { .. }
+                | ChildrenValidationError::InternalIOChildren { .. }
+                | ChildrenValidationError::ConditionalCaseTag { .. } => ErrorCategory::Structural,
+                ChildrenValidationError::IOSignatureMismatch { .. }
+                | ChildrenValidationError::ConditionalCaseSignature { .. }
+                | ChildrenValidationError::InvalidConditionalPredicate { .. } => {
+                    ErrorCategory::Typing
+                }
+            },
+            ValidationError::IncompatiblePorts { .. }
+            | ValidationError::CallIndirectMismatch { .. }
+            | ValidationError::LinearInClassicType { .. }
+            | ValidationError::NonClassicalStaticInput { .. }
+            | ValidationError::InvalidEdges { .. }
+            | ValidationError::ConstTypeError(_) => ErrorCategory::Typing,
+            ValidationError::TgtExceedsSrcResources { .. }
+            | ValidationError::SrcExceedsTgtResources { .. }
+            | ValidationError::RegionExceedsResourceDelta { .. }
+            | ValidationError::UnusedResourceDelta { .. }
+            | ValidationError::IOResourcesMismatch { .. } => ErrorCategory::Resources,
+            ValidationError::DeniedLint(_) => ErrorCategory::Lint,
+        }
+    }
+}
+
+/// Serialize a [`Direction`] by its name.
+fn serialize_direction<S: serde::Serializer>(
+    dir: &Direction,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(match dir {
+        Direction::Incoming => "Incoming",
+        Direction::Outgoing => "Outgoing",
+    })
+}
+
+/// Display the kinds of incompatible ports, followed by their structure if
+/// they involve new types.
+fn display_kinds(from: &EdgeKind, to: &EdgeKind) -> String {
+    let (from_resolved, to_resolved) = (from.resolve_alias(), to.resolve_alias());
+    if &from_resolved == from && &to_resolved == to {
+        format!("{from} to {to}")
+    } else {
+        format!("{from} to {to} (resolved: {from_resolved} to {to_resolved})")
+    }
+}
+
+/// The class of a port of the given kind, with a placeholder index.
+fn kind_class(kind: &EdgeKind) -> PortClass {
+    match kind {
+        EdgeKind::Value(_) => PortClass::Value(0),
+        EdgeKind::Static(_) => PortClass::Static(0),
+        EdgeKind::StateOrder => PortClass::Other(0),
+        EdgeKind::ControlFlow => PortClass::ControlFlow(0),
+    }
+}
+
+/// Display the expected and found port layouts of a node, followed by the
+/// classes of ports that are missing or extra.
+fn display_layouts(expected: &PortLayout, actual: &PortLayout) -> String {
+    let diff = expected
+        .counts()
+        .into_iter()
+        .zip(actual.counts())
+        .filter_map(
+            |((expected, name), (actual, _))| match actual.cmp(&expected) {
+                Ordering::Less => Some(format!("missing {} {name}", expected - actual)),
+                Ordering::Greater => Some(format!("extra {} {name}", actual - expected)),
+                Ordering::Equal => None,
+            },
+        )
+        .join(", ");
+    format!("expected {expected} ports, found {actual} ({diff})")
+}
+
+/// Find a linear type nested in a classical container type.
+///
+/// The signatures of graph types are not searched, as graphs are classical
+/// values whatever their inputs and outputs.
+fn linear_in_classic(typ: &ClassicType) -> Option<SimpleType> {
+    let in_row = |row: &TypeRow| {
+        row.iter().find_map(|typ| match typ {
+            SimpleType::Classic(typ) => linear_in_classic(typ),
+            SimpleType::Linear(_) => Some(typ.clone()),
+        })
+    };
+    match typ {
+        ClassicType::Container(container) => match container {
+            Container::List(typ) | Container::Array(typ, _) | Container::NewType(_, typ) => {
+                linear_in_classic(typ)
+            }
+            Container::Map(types) => {
+                linear_in_classic(&types.0).or_else(|| linear_in_classic(&types.1))
+            }
+            Container::Tuple(row) | Container::Sum(row) => in_row(row),
+            Container::Alias(_) => None,
+        },
+        _ => None,
+    }
 }
 
 /// Errors related to the inter-graph edge validations.
-#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error, serde::Serialize)]
 #[allow(missing_docs)]
 pub enum InterGraphEdgeError {
     /// Inter-Graph edges can only carry classical data.
@@ -731,16 +1396,20 @@ pub enum InterGraphEdgeError {
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use cool_asserts::assert_matches;
 
     use super::*;
-    use crate::builder::{BuildError, ModuleBuilder};
-    use crate::builder::{Container, Dataflow, DataflowSubContainer, HugrBuilder};
+    use crate::algorithm::resource_delta::infer_resource_deltas;
+    use crate::builder::{BuildError, DFGBuilder, DataflowHugr, ModuleBuilder};
+    use crate::builder::{Container, Dataflow, DataflowSubContainer, HugrBuilder, SubContainer};
     use crate::hugr::{HugrError, HugrMut};
+    use crate::ops::custom::{ExternalOp, OpaqueOp};
     use crate::ops::dataflow::IOTrait;
+    use crate::ops::handle::NodeHandle;
     use crate::ops::{self, ConstValue, LeafOp, OpType};
-    use crate::types::{ClassicType, LinearType, Signature};
+    use crate::resource::ResourceId;
+    use crate::types::{ClassicType, LinearType, Signature, TypeRow};
     use crate::Direction;
     use crate::{type_row, Node};
 
@@ -755,6 +1424,7 @@ mod test {
         let def_op: OpType = ops::FuncDefn {
             name: "main".into(),
             signature: Signature::new_df(type_row![B], vec![B; copies]),
+            signature_desc: Default::default(),
         }
         .into();
 
@@ -836,6 +1506,7 @@ mod test {
         let declare_op: OpType = ops::FuncDecl {
             name: "main".into(),
             signature: Default::default(),
+            signature_desc: Default::default(),
         }
         .into();
 
@@ -844,14 +1515,14 @@ mod test {
         assert_eq!(b.validate(), Ok(()));
 
         // Add another hierarchy root
-        let other = b.add_op(ops::Module);
+        let other = b.add_op(ops::Module).unwrap();
         assert_matches!(
             b.validate(),
             Err(ValidationError::NoParent { node }) => assert_eq!(node, other)
         );
         b.set_parent(other, root).unwrap();
-        b.replace_op(other, declare_op);
-        b.add_ports(other, Direction::Outgoing, 1);
+        b.replace_op(other, declare_op).unwrap();
+        b.add_ports(other, Direction::Outgoing, 1).unwrap();
         assert_eq!(b.validate(), Ok(()));
 
         // Make the hugr root not a hierarchy root
@@ -880,6 +1551,7 @@ mod test {
     fn dfg_root() {
         let dfg_op: OpType = ops::DFG {
             signature: Signature::new_linear(type_row![B]),
+            resource_delta: None,
         }
         .into();
 
@@ -915,6 +1587,7 @@ mod test {
                 ops::FuncDefn {
                     signature: def_sig,
                     name: "main".into(),
+                    signature_desc: Default::default(),
                 },
             )
             .unwrap();
@@ -947,15 +1620,11 @@ mod test {
     /// Validation errors in a dataflow subgraph.
     fn df_children_restrictions() {
         let (mut b, def) = make_simple_hugr(2);
-        let (_input, output, copy) = b
-            .hierarchy
-            .children(def.index)
-            .map_into()
-            .collect_tuple()
-            .unwrap();
+        let (_input, output) = b.get_io(def).unwrap();
+        let copy = b.nth_child(def, 2).unwrap();
 
         // Replace the output operation of the df subgraph with a copy
-        b.replace_op(
+        b.replace_op_unchecked(
             output,
             LeafOp::Noop {
                 ty: ClassicType::bit().into(),
@@ -967,35 +1636,40 @@ mod test {
         );
 
         // Revert it back to an output, but with the wrong number of ports
-        b.replace_op(output, ops::Output::new(type_row![B]));
+        b.replace_op_unchecked(output, ops::Output::new(type_row![B]));
         assert_matches!(
             b.validate(),
             Err(ValidationError::InvalidChildren { parent, source: ChildrenValidationError::IOSignatureMismatch { child, .. }, .. })
                 => {assert_eq!(parent, def); assert_eq!(child, output.index)}
         );
-        b.replace_op(output, ops::Output::new(type_row![B, B]));
+        let err = b.validate().unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Typing);
+        assert_matches!(
+            std::error::Error::source(&err).and_then(|e| e.downcast_ref()),
+            Some(ChildrenValidationError::IOSignatureMismatch { .. })
+        );
+        b.replace_op_unchecked(output, ops::Output::new(type_row![B, B]));
 
         // After fixing the output back, replace the copy with an output op
-        b.replace_op(copy, ops::Output::new(type_row![B, B]));
+        b.replace_op_unchecked(copy, ops::Output::new(type_row![B, B]));
         assert_matches!(
             b.validate(),
             Err(ValidationError::InvalidChildren { parent, source: ChildrenValidationError::InternalIOChildren { child, .. }, .. })
                 => {assert_eq!(parent, def); assert_eq!(child, copy.index)}
         );
+        assert_eq!(
+            b.validate().unwrap_err().category(),
+            ErrorCategory::Structural
+        );
     }
 
     #[test]
     /// Validation errors in a dataflow subgraph.
     fn cfg_children_restrictions() {
         let (mut b, def) = make_simple_hugr(1);
-        let (_input, _output, copy) = b
-            .hierarchy
-            .children(def.index)
-            .map_into()
-            .collect_tuple()
-            .unwrap();
+        let copy = b.nth_child(def, 2).unwrap();
 
-        b.replace_op(
+        b.replace_op_unchecked(
             copy,
             ops::CFG {
                 inputs: type_row![B],
@@ -1050,7 +1724,7 @@ mod test {
         b.remove_node(exit2).unwrap();
 
         // Change the types in the BasicBlock node to work on qubits instead of bits
-        b.replace_op(
+        b.replace_op_unchecked(
             block,
             ops::BasicBlock::DFB {
                 inputs: type_row![Q],
@@ -1058,11 +1732,9 @@ mod test {
                 other_outputs: type_row![Q],
             },
         );
-        let mut block_children = b.hierarchy.children(block.index);
-        let block_input = block_children.next().unwrap().into();
-        let block_output = block_children.next_back().unwrap().into();
-        b.replace_op(block_input, ops::Input::new(type_row![Q]));
-        b.replace_op(
+        let (block_input, block_output) = b.get_io(block).unwrap();
+        b.replace_op_unchecked(block_input, ops::Input::new(type_row![Q]));
+        b.replace_op_unchecked(
             block_output,
             ops::Output::new(vec![SimpleType::new_simple_predicate(1), Q]),
         );
@@ -1073,10 +1745,193 @@ mod test {
         );
     }
 
+    #[test]
+    fn cfg_successor_ports() {
+        let (mut b, def) = make_simple_hugr(1);
+        let copy = b.nth_child(def, 2).unwrap();
+        b.replace_op(
+            copy,
+            ops::CFG {
+                inputs: type_row![B],
+                outputs: type_row![B],
+            },
+        )
+        .unwrap();
+        let cfg = copy;
+        let block = b
+            .add_op_with_parent(
+                cfg,
+                ops::BasicBlock::DFB {
+                    inputs: type_row![B],
+                    predicate_variants: vec![type_row![]],
+                    other_outputs: type_row![B],
+                },
+            )
+            .unwrap();
+        add_block_children(&mut b, block, 1);
+        let exit = b
+            .add_op_with_parent(
+                cfg,
+                ops::BasicBlock::Exit {
+                    cfg_outputs: type_row![B],
+                },
+            )
+            .unwrap();
+        b.add_other_edge(block, exit).unwrap();
+        assert_eq!(b.validate(), Ok(()));
+
+        // The block has more predicate variants than successor ports
+        let mut h = b.clone();
+        h.replace_op_unchecked(
+            block,
+            ops::BasicBlock::DFB {
+                inputs: type_row![B],
+                predicate_variants: vec![type_row![]; 3],
+                other_outputs: type_row![B],
+            },
+        );
+        assert_eq!(
+            h.validate(),
+            Err(ValidationError::WrongNumberOfSuccessors {
+                node: block,
+                actual: 1,
+                expected: 3,
+            })
+        );
+
+        // The exit block has a successor port
+        let mut h = b.clone();
+        h.add_ports(exit, Direction::Outgoing, 1).unwrap();
+        assert_eq!(
+            h.validate(),
+            Err(ValidationError::WrongNumberOfSuccessors {
+                node: exit,
+                actual: 1,
+                expected: 0,
+            })
+        );
+
+        // The successor of the block is not connected
+        let mut h = b.clone();
+        h.disconnect(block, Port::new_outgoing(0)).unwrap();
+        assert_eq!(
+            h.validate(),
+            Err(ValidationError::UnconnectedSuccessor {
+                node: block,
+                port: Port::new_outgoing(0),
+            })
+        );
+    }
+
+    #[test]
+    /// A function declared at module level can be called from deep inside
+    /// nested regions, without order edges.
+    fn call_nested_declaration() -> Result<(), BuildError> {
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+
+        // FuncDefn > CFG > BasicBlock > Conditional > Case > Call
+        let mut module_builder = ModuleBuilder::new();
+        let decl = module_builder.declare("ext", sig.clone())?;
+        let mut main = module_builder.define_function("main", sig.clone())?;
+        let [x] = main.input_wires_arr();
+        let mut cfg = main.cfg_builder([(NAT, x)], type_row![NAT])?;
+        let mut entry = cfg.simple_entry_builder(type_row![NAT], 1)?;
+        let [x] = entry.input_wires_arr();
+        let predicate = entry.make_predicate(0, [type_row![]], [])?;
+        let mut conditional =
+            entry.conditional_builder(([type_row![]], predicate), [(NAT, x)], type_row![NAT])?;
+        let mut case = conditional.case_builder(0)?;
+        let [x] = case.input_wires_arr();
+        let call = case.call(&decl, [x])?;
+        case.finish_with_outputs(call.outputs())?;
+        let conditional = conditional.finish_sub_container()?;
+        let branch = entry.make_predicate(0, [type_row![]], [])?;
+        let entry = entry.finish_with_outputs(branch, conditional.outputs())?;
+        let exit = cfg.exit_block()?;
+        cfg.branch(&entry, 0, &exit)?;
+        let cfg = cfg.finish_sub_container()?;
+        main.finish_with_outputs(cfg.outputs())?;
+        assert_matches!(module_builder.finish_hugr(), Ok(_));
+
+        // FuncDefn > DFG > DFG > DFG > Call
+        let mut module_builder = ModuleBuilder::new();
+        let decl = module_builder.declare("ext", sig.clone())?;
+        let mut main = module_builder.define_function("main", sig.clone())?;
+        let [x] = main.input_wires_arr();
+        let mut outer = main.dfg_builder(sig.clone(), [x])?;
+        let [x] = outer.input_wires_arr();
+        let mut middle = outer.dfg_builder(sig.clone(), [x])?;
+        let [x] = middle.input_wires_arr();
+        let mut inner = middle.dfg_builder(sig, [x])?;
+        let [x] = inner.input_wires_arr();
+        let call = inner.call(&decl, [x])?;
+        let inner = inner.finish_with_outputs(call.outputs())?;
+        let middle = middle.finish_with_outputs(inner.outputs())?;
+        let outer = outer.finish_with_outputs(middle.outputs())?;
+        main.finish_with_outputs(outer.outputs())?;
+        assert_matches!(module_builder.finish_hugr(), Ok(_));
+        Ok(())
+    }
+
+    #[test]
+    /// A single function of a module can be validated through a rooted view.
+    fn rooted_function() -> Result<(), BuildError> {
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let mut module_builder = ModuleBuilder::new();
+        let helper = module_builder.define_function("helper", sig.clone())?;
+        let [x] = helper.input_wires_arr();
+        let helper = helper.finish_with_outputs([x])?;
+        let mut main = module_builder.define_function("main", sig)?;
+        let [x] = main.input_wires_arr();
+        let call = main.call(helper.handle(), [x])?;
+        let main = main.finish_with_outputs(call.outputs())?;
+        let mut hugr = module_builder.finish_hugr()?;
+
+        // A function with an unconnected output breaks the module.
+        let broken = hugr
+            .add_op_with_parent(
+                hugr.root(),
+                ops::FuncDefn {
+                    name: "broken".into(),
+                    signature: Signature::new_df(type_row![], type_row![B]),
+                    signature_desc: Default::default(),
+                },
+            )
+            .unwrap();
+        hugr.add_op_with_parent(broken, ops::Input::new(type_row![]))
+            .unwrap();
+        hugr.add_op_with_parent(broken, ops::Output::new(type_row![B]))
+            .unwrap();
+        assert!(hugr.validate().is_err());
+
+        let view = hugr.as_rooted(helper.node());
+        assert_eq!(view.root(), helper.node());
+        assert_eq!(view.get_parent(view.root()), None);
+        assert_eq!(view.validate(), Ok(()));
+
+        // The call in `main` is connected to a function outside of the view.
+        let view = hugr.as_rooted(main.node());
+        assert_eq!(
+            view.validate(),
+            Err(ValidationError::BoundaryEdge {
+                node: call.node(),
+                port: Port::new_incoming(1),
+            })
+        );
+        let options = ValidationOptions {
+            ignore_boundary_edges: true,
+            ..Default::default()
+        };
+        assert_eq!(view.validate_with_options(&options), Ok(()));
+        assert!(hugr.as_rooted(broken).validate().is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_ext_edge() -> Result<(), HugrError> {
         let mut h = Hugr::new(ops::DFG {
             signature: Signature::new_df(type_row![B, B], type_row![B]),
+            resource_delta: None,
         });
         let input = h.add_op_with_parent(h.root(), ops::Input::new(type_row![B, B]))?;
         let output = h.add_op_with_parent(h.root(), ops::Output::new(type_row![B]))?;
@@ -1085,6 +1940,7 @@ mod test {
             h.root(),
             ops::DFG {
                 signature: Signature::new_linear(type_row![B]),
+                resource_delta: None,
             },
         )?;
         // this Xor has its 2nd input unconnected
@@ -1115,10 +1971,105 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn io_outer_ports() -> Result<(), HugrError> {
+        let (mut h, def) = make_simple_hugr(1);
+        let [input, output, copy] = h.children(def).collect_vec().try_into().unwrap();
+
+        // An order edge into the Input node.
+        h.add_ports(input, Direction::Incoming, 1).unwrap();
+        h.connect(copy, 1, input, 0)?;
+        assert_eq!(
+            h.validate(),
+            Err(ValidationError::InputWithIncomingPorts {
+                node: input,
+                count: 1
+            })
+        );
+        h.set_num_ports(input, 0, 2).unwrap();
+        h.validate().unwrap();
+
+        // An edge out of the Output node.
+        h.add_ports(output, Direction::Outgoing, 1).unwrap();
+        h.connect(output, 0, copy, 1)?;
+        assert_eq!(
+            h.validate(),
+            Err(ValidationError::OutputWithOutgoingPorts {
+                node: output,
+                count: 1
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn other_port_kinds() -> Result<(), HugrError> {
+        let mut h = Hugr::new(ops::DFG {
+            signature: Signature::new_df(type_row![B], type_row![B]),
+            resource_delta: None,
+        });
+        let input = h.add_op_with_parent(h.root(), ops::Input::new(type_row![B]))?;
+        let output = h.add_op_with_parent(h.root(), ops::Output::new(type_row![B]))?;
+        let xor = h.add_op_with_parent(h.root(), LeafOp::Xor)?;
+        h.connect(input, 0, xor, 0)?;
+        h.connect(xor, 0, output, 0)?;
+
+        // An order port connected to a value port
+        h.connect(input, 1, xor, 1)?;
+        assert_eq!(
+            h.validate(),
+            Err(ValidationError::IncompatiblePorts {
+                from: input,
+                from_port: Port::new_outgoing(1),
+                from_kind: EdgeKind::StateOrder,
+                to: xor,
+                to_port: Port::new_incoming(1),
+                to_kind: EdgeKind::Value(B),
+            })
+        );
+        h.disconnect(xor, Port::new_incoming(1))?;
+        h.connect(input, 0, xor, 1)?;
+        h.validate().unwrap();
+
+        // A value port connected to an order port
+        let cst = h.add_op_with_parent(h.root(), ops::Const(ConstValue::i64(1)))?;
+        let lcst = h.add_op_with_parent(
+            h.root(),
+            ops::LoadConstant {
+                datatype: ClassicType::i64(),
+            },
+        )?;
+        h.connect(cst, 0, lcst, 0)?;
+        h.add_other_edge(input, lcst)?;
+        h.connect(lcst, 0, xor, 2)?;
+        assert_matches!(
+            h.validate(),
+            Err(ValidationError::IncompatiblePorts { from, from_kind: EdgeKind::Value(_), to_kind: EdgeKind::StateOrder, .. })
+                => assert_eq!(from, lcst)
+        );
+        h.disconnect(lcst, Port::new_outgoing(0))?;
+        h.validate().unwrap();
+
+        // Static edges require the same constant type on both ends
+        h.replace_op_unchecked(
+            lcst,
+            ops::LoadConstant {
+                datatype: ClassicType::bit(),
+            },
+        );
+        assert_matches!(
+            h.validate(),
+            Err(ValidationError::IncompatiblePorts { from, from_kind: EdgeKind::Static(_), to_kind: EdgeKind::Static(_), .. })
+                => assert_eq!(from, cst)
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_local_const() -> Result<(), HugrError> {
         let mut h = Hugr::new(ops::DFG {
             signature: Signature::new_df(type_row![B], type_row![B]),
+            resource_delta: None,
         });
         let input = h.add_op_with_parent(h.root(), ops::Input::new(type_row![B]))?;
         let output = h.add_op_with_parent(h.root(), ops::Output::new(type_row![B]))?;
@@ -1152,6 +2103,83 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn wrong_port_layouts() -> Result<(), HugrError> {
+        let mut h = Hugr::new(ops::DFG {
+            signature: Signature::new_df(type_row![B, B], type_row![B]),
+            resource_delta: None,
+        });
+        // The nodes are created first so they are validated before their
+        // neighbours, which would report the mismatched edges instead.
+        let noop = h.add_op(LeafOp::Noop { ty: B })?;
+        let lcst = h.add_op(ops::LoadConstant {
+            datatype: ClassicType::bit(),
+        })?;
+        let input = h.add_op_with_parent(h.root(), ops::Input::new(type_row![B, B]))?;
+        let output = h.add_op_with_parent(h.root(), ops::Output::new(type_row![B]))?;
+        h.set_parent(noop, h.root())?;
+
+        // A Noop with an extra value input.
+        h.set_num_ports(noop, 3, 2)?;
+        h.connect(input, 0, noop, 0)?;
+        h.connect(input, 1, noop, 1)?;
+        h.connect(noop, 0, output, 0)?;
+        let err = h.validate().unwrap_err();
+        assert_matches!(
+            &err,
+            ValidationError::WrongNumberOfPorts { node, actual_layout, expected_layout, .. } => {
+                assert_eq!(node, &noop);
+                assert_eq!(expected_layout, &PortLayout { value: 1, order: 1, ..Default::default() });
+                assert_eq!(actual_layout, &PortLayout { value: 2, order: 1, ..Default::default() });
+            }
+        );
+        assert!(err.to_string().ends_with(
+            "expected 1 value + 1 order ports, found 2 value + 1 order (extra 1 value)."
+        ));
+
+        // A LoadConstant missing its static input, with only an order edge.
+        h.set_num_ports(noop, 2, 2)?;
+        h.disconnect(input, Port::new_outgoing(1))?;
+        h.set_parent(lcst, h.root())?;
+        h.set_num_ports(lcst, 1, 2)?;
+        h.connect(input, 2, lcst, 0)?;
+        let err = h.validate().unwrap_err();
+        assert_matches!(
+            &err,
+            ValidationError::WrongNumberOfPorts { node, actual_layout, expected_layout, .. } => {
+                assert_eq!(node, &lcst);
+                assert_eq!(expected_layout, &PortLayout { constant: 1, order: 1, ..Default::default() });
+                assert_eq!(actual_layout, &PortLayout { order: 1, ..Default::default() });
+            }
+        );
+        assert!(err
+            .to_string()
+            .ends_with("expected 1 const + 1 order ports, found 1 order (missing 1 const)."));
+        Ok(())
+    }
+
+    #[test]
+    /// A long chain of nested DFGs, all requiring the same resources.
+    fn many_resource_sets() -> Result<(), BuildError> {
+        let rs = ResourceSet::from_iter(["A".into(), "B".into(), "C".into()]);
+        let mut sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        sig.input_resources = rs.clone();
+        sig.output_resources = rs;
+
+        let mut module_builder = ModuleBuilder::new();
+        let mut main = module_builder.define_function("main", sig.clone())?;
+        let [mut wire] = main.input_wires_arr();
+        for _ in 0..1000 {
+            let nested = main.dfg_builder(sig.clone(), [wire])?;
+            let nested_inputs = nested.input_wires();
+            [wire] = nested.finish_with_outputs(nested_inputs)?.outputs_arr();
+        }
+        main.finish_with_outputs([wire])?;
+        let hugr = module_builder.finish_hugr()?;
+        assert_eq!(hugr.node_count(), 3 + 3 * 1000 + 1);
+        Ok(())
+    }
+
     #[test]
     /// A wire with no resource requirements is wired into a node which has
     /// [A,B] resources required on its inputs and outputs. This could be fixed
@@ -1176,7 +2204,229 @@ mod test {
         main.finish_with_outputs([f_output])?;
         let handle = module_builder.finish_hugr();
 
-        assert_matches!(handle, Err(ValidationError::TgtExceedsSrcResources { .. }));
+        // The error is reported at the boundary of the inner DFG.
+        assert_matches!(
+            handle,
+            Err(BuildError::InvalidHUGR(ValidationError::TgtExceedsSrcResources { to, .. })) => assert_eq!(to, f_handle.node())
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// A function declaring resources on its inputs, whose Input node does not
+    /// carry them.
+    fn unannotated_input_resources() -> Result<(), BuildError> {
+        let rs = ResourceSet::singleton(&"A".into());
+        let mut sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        sig.input_resources = rs.clone();
+        sig.output_resources = rs.clone();
+
+        let mut module_builder = ModuleBuilder::new();
+        let main = module_builder.define_function("main", sig)?;
+        let [input, _] = main.io();
+        let main_inputs = main.input_wires();
+        let main = main.finish_with_outputs(main_inputs)?;
+        let mut hugr = module_builder.finish_hugr()?;
+        assert_matches!(hugr.get_optype(input), OpType::Input(ops::Input { resources, .. }) => assert_eq!(resources, &rs));
+
+        hugr.replace_op(input, ops::Input::new(type_row![NAT]))
+            .unwrap();
+        let err = hugr.validate().unwrap_err();
+        assert_eq!(
+            err,
+            ValidationError::IOResourcesMismatch {
+                parent: main.node(),
+                child: input,
+                expected: rs,
+                actual: ResourceSet::new(),
+            }
+        );
+        assert_eq!(err.category(), ErrorCategory::Resources);
+        let structural = ValidationOptions {
+            assume_resources_solved: false,
+            ..Default::default()
+        };
+        assert_eq!(hugr.validate_with_options(&structural), Ok(()));
+
+        // The resources of the Input node are fixed by the inference pass.
+        assert_eq!(infer_resource_deltas(&mut hugr), 1);
+        assert_eq!(hugr.validate(), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    /// An operation from an extension requires its resource on its inputs, so
+    /// wiring it directly to a region input with no resources requires a lift.
+    fn extension_op_requires_lift() -> Result<(), BuildError> {
+        let rs_id: ResourceId = "quantinuum.hardware".into();
+        let ext_op: LeafOp = ExternalOp::from(OpaqueOp::new(
+            rs_id.clone(),
+            "op",
+            "".into(),
+            [],
+            Some(Signature::new_df(type_row![NAT], type_row![NAT])),
+        ))
+        .into();
+        let ext_sig = ext_op.signature();
+        assert!(ext_sig.input_resources.contains(&rs_id));
+        assert!(ext_sig.output_resources.contains(&rs_id));
+
+        let build = |lift: bool| -> Result<Hugr, BuildError> {
+            let mut module_builder = ModuleBuilder::new();
+            let mut main_sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+            main_sig.output_resources = ResourceSet::singleton(&rs_id);
+            let mut main = module_builder.define_function("main", main_sig)?;
+            let [mut wire] = main.input_wires_arr();
+            if lift {
+                let lift_op = LeafOp::Lift {
+                    type_row: type_row![NAT],
+                    input_resources: ResourceSet::new(),
+                    new_resource: rs_id.clone(),
+                };
+                [wire] = main.add_dataflow_op(lift_op, [wire])?.outputs_arr();
+            }
+            let [out] = main.add_dataflow_op(ext_op.clone(), [wire])?.outputs_arr();
+            main.finish_with_outputs([out])?;
+            module_builder.finish_hugr()
+        };
+
+        let err = build(false).unwrap_err();
+        assert_matches!(err, BuildError::InvalidHUGR(err) => {
+            assert_matches!(err, ValidationError::TgtExceedsSrcResources { .. });
+            assert_eq!(err.category(), ErrorCategory::Resources);
+        });
+        assert_matches!(build(true), Ok(_));
+        Ok(())
+    }
+
+    #[test]
+    /// A function requiring a resource on its inputs can only be called from a
+    /// region providing it.
+    fn call_requires_callee_resources() -> Result<(), BuildError> {
+        let rs_id: ResourceId = "A".into();
+        let mut callee_sig = Signature::new_df(type_row![NAT], type_row![NAT, B]);
+        callee_sig.input_resources = ResourceSet::singleton(&rs_id);
+        callee_sig.output_resources = ResourceSet::singleton(&rs_id);
+
+        let build = |lift: bool| -> Result<(Hugr, Node), BuildError> {
+            let mut module_builder = ModuleBuilder::new();
+            let callee = module_builder.declare("callee", callee_sig.clone())?;
+            let mut main_sig = Signature::new_df(type_row![NAT], type_row![NAT, B]);
+            main_sig.output_resources = ResourceSet::singleton(&rs_id);
+            let mut main = module_builder.define_function("main", main_sig)?;
+            let [mut wire] = main.input_wires_arr();
+            if lift {
+                let lift_op = LeafOp::Lift {
+                    type_row: type_row![NAT],
+                    input_resources: ResourceSet::new(),
+                    new_resource: rs_id.clone(),
+                };
+                [wire] = main.add_dataflow_op(lift_op, [wire])?.outputs_arr();
+            }
+            let call = main.call(&callee, [wire])?;
+            main.finish_with_outputs(call.outputs())?;
+            Ok((module_builder.hugr().clone(), call.node()))
+        };
+
+        let (mut hugr, call) = build(false)?;
+        let OpType::Call(ops::Call { signature }) = hugr.get_optype(call) else {
+            panic!("Not a Call node");
+        };
+        assert_eq!(signature, &callee_sig);
+        assert_matches!(
+            hugr.validate(),
+            Err(ValidationError::TgtExceedsSrcResources { to, .. }) => assert_eq!(to, call)
+        );
+
+        // The resources of the Call are taken from the callee, not from the
+        // signature stored in the node.
+        hugr.replace_op_unchecked(
+            call,
+            ops::Call {
+                signature: Signature::new_df(type_row![NAT], type_row![NAT, B]),
+            },
+        );
+        let effective = hugr.effective_signature(call);
+        assert_eq!(effective.input_resources, callee_sig.input_resources);
+        assert_eq!(effective.output_resources, callee_sig.output_resources);
+
+        let (hugr, _) = build(true)?;
+        assert_eq!(hugr.validate(), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    /// An operation with two static inputs, each fed by a constant.
+    fn multiple_static_inputs() -> Result<(), BuildError> {
+        const BIT: SimpleType = SimpleType::Classic(ClassicType::Int(1));
+        let rs_id: ResourceId = "quantinuum.hardware".into();
+        let ext_op = |static_input: TypeRow| -> LeafOp {
+            ExternalOp::from(OpaqueOp::new(
+                rs_id.clone(),
+                "op",
+                "".into(),
+                [],
+                Some(Signature::new(type_row![NAT], type_row![NAT], static_input)),
+            ))
+            .into()
+        };
+
+        let build = |static_input: TypeRow| -> Result<Hugr, BuildError> {
+            let mut module_builder = ModuleBuilder::new();
+            let mut main_sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+            main_sig.output_resources = ResourceSet::singleton(&rs_id);
+            let mut main = module_builder.define_function("main", main_sig)?;
+            let c_int = main.add_constant(ConstValue::i64(3))?;
+            let c_bit = main.add_constant(ConstValue::Int { width: 1, value: 1 })?;
+            let lift_op = LeafOp::Lift {
+                type_row: type_row![NAT],
+                input_resources: ResourceSet::new(),
+                new_resource: rs_id.clone(),
+            };
+            let [wire] = main
+                .add_dataflow_op(lift_op, main.input_wires())?
+                .outputs_arr();
+            let op = main.add_dataflow_op(ext_op(static_input), [wire])?;
+            main.hugr_mut().connect(c_int.node(), 0, op.node(), 1)?;
+            main.hugr_mut().connect(c_bit.node(), 0, op.node(), 2)?;
+            main.finish_with_outputs(op.outputs())?;
+            Ok(module_builder.hugr().clone())
+        };
+
+        let hugr = build(type_row![NAT, BIT])?;
+        assert_eq!(hugr.validate(), Ok(()));
+        let op = hugr
+            .nodes()
+            .find(|&n| matches!(hugr.get_optype(n), OpType::LeafOp(LeafOp::CustomOp(_))))
+            .unwrap();
+        assert_eq!(
+            hugr.get_optype(op).port_kind(Port::new_incoming(2)),
+            Some(EdgeKind::Static(ClassicType::Int(1)))
+        );
+        assert_eq!(hugr.input_neighbours(op).count(), 3);
+
+        // The static inputs must match the types of the constants.
+        assert_matches!(
+            build(type_row![BIT, NAT])?.validate(),
+            Err(ValidationError::IncompatiblePorts {
+                from_kind: EdgeKind::Static(_),
+                ..
+            })
+        );
+
+        // Static inputs must be classical.
+        const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+        let mut hugr = build(type_row![NAT, BIT])?;
+        hugr.replace_op_unchecked(op, ext_op(type_row![NAT, QB]));
+        let err = hugr.validate().unwrap_err();
+        assert_matches!(
+            &err,
+            ValidationError::NonClassicalStaticInput { node, typ, .. } => {
+                assert_eq!(*node, op);
+                assert_eq!(typ, &QB);
+            }
+        );
+        assert_eq!(err.category(), ErrorCategory::Typing);
         Ok(())
     }
 
@@ -1202,7 +2452,79 @@ mod test {
         let [f_output] = f_handle.outputs_arr();
         main.finish_with_outputs([f_output])?;
         let handle = module_builder.finish_hugr();
-        assert_matches!(handle, Err(ValidationError::SrcExceedsTgtResources { .. }));
+        assert_matches!(
+            handle,
+            Err(BuildError::InvalidHUGR(
+                ValidationError::SrcExceedsTgtResources { .. }
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// A chain of three DFGs where the first introduces a resource `[A]` with
+    /// a lift node and the others pass it on, wired into an output with no
+    /// resource req.
+    fn too_many_resources_provenance() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let main_sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let mut main = module_builder.define_function("main", main_sig)?;
+        let [wire] = main.input_wires_arr();
+
+        let rs = ResourceSet::singleton(&"A".into());
+        let mut chain = Vec::new();
+        let mut wire = wire;
+        for input_resources in [ResourceSet::new(), rs.clone(), rs.clone()] {
+            let mut sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+            sig.input_resources = input_resources.clone();
+            sig.output_resources = rs.clone();
+            let mut dfg = main.dfg_builder(sig, [wire])?;
+            let [mut inner] = dfg.input_wires_arr();
+            if input_resources.is_empty() {
+                let lift = LeafOp::Lift {
+                    type_row: type_row![NAT],
+                    input_resources: ResourceSet::new(),
+                    new_resource: "A".into(),
+                };
+                [inner] = dfg.add_dataflow_op(lift, [inner])?.outputs_arr();
+            }
+            let dfg = dfg.finish_with_outputs([inner])?;
+            chain.push(dfg.node());
+            [wire] = dfg.outputs_arr();
+        }
+        let main = main.finish_with_outputs([wire])?;
+        let output = module_builder.hugr().nth_child(main.node(), 1).unwrap();
+
+        let hugr = module_builder.hugr();
+        assert_matches!(
+            hugr.validate(),
+            Err(ValidationError::SrcExceedsTgtResources { provenance, .. }) => {
+                assert!(provenance.is_empty())
+            }
+        );
+        let options = ValidationOptions {
+            detailed_errors: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            hugr.validate_with_options(&options),
+            Err(ValidationError::SrcExceedsTgtResources {
+                from: chain[2],
+                from_offset: Port::new_outgoing(0),
+                from_resources: rs.clone(),
+                to: output,
+                to_offset: Port::new_incoming(0),
+                to_resources: ResourceSet::new(),
+                provenance: vec![("A".into(), chain)],
+            })
+        );
+
+        // The structure is valid if the resources are not solved yet.
+        let options = ValidationOptions {
+            assume_resources_solved: false,
+            ..Default::default()
+        };
+        assert_eq!(hugr.validate_with_options(&options), Ok(()));
         Ok(())
     }
 
@@ -1212,6 +2534,31 @@ mod test {
     /// requirements `[A,B]`. A slightly more complex test of the error from
     /// `missing_lift_node`.
     fn resource_mismatch() -> Result<(), BuildError> {
+        let handle = resource_mismatch_hugr()?.validate();
+        assert_matches!(handle, Err(ValidationError::TgtExceedsSrcResources { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn exposed_port_resources() -> Result<(), BuildError> {
+        let hugr = resource_mismatch_hugr()?;
+        let mut validator = ValidationContext::new(&hugr, ValidationOptions::default());
+        for node in hugr.nodes() {
+            validator.gather_resources(&node).unwrap();
+        }
+        for node in hugr.nodes() {
+            for port in hugr.all_node_ports(node) {
+                assert_eq!(
+                    hugr.port_resources(node, port),
+                    validator.resources[&(node, port.direction())]
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the hugr for [`resource_mismatch`], without validating it.
+    pub(crate) fn resource_mismatch_hugr() -> Result<Hugr, BuildError> {
         let mut module_builder = ModuleBuilder::new();
 
         let all_rs = ResourceSet::from_iter(["A".into(), "B".into()]);
@@ -1246,8 +2593,120 @@ mod test {
         let [output] = builder.finish_with_outputs([])?.outputs_arr();
 
         main.finish_with_outputs([output])?;
-        let handle = module_builder.finish_hugr();
-        assert_matches!(handle, Err(ValidationError::TgtExceedsSrcResources { .. }));
+        Ok(module_builder.hugr().clone())
+    }
+
+    #[test]
+    fn call_indirect_signature() -> Result<(), BuildError> {
+        // Call a graph argument with signature `graph_row -> graph_row` as if
+        // it had signature `call_row -> call_row`.
+        let build = |graph_row: TypeRow, call_row: TypeRow| -> Result<Hugr, BuildError> {
+            let graph_sig = Signature::new_df(graph_row.clone(), graph_row);
+            let graph: SimpleType = ClassicType::graph_from_sig(graph_sig).into();
+            let inputs: Vec<SimpleType> = std::iter::once(graph)
+                .chain(call_row.iter().cloned())
+                .collect();
+            let mut dfg = DFGBuilder::new(inputs, call_row.clone())?;
+            let call = ops::CallIndirect {
+                signature: Signature::new_df(call_row.clone(), call_row),
+            };
+            let call = dfg.add_dataflow_op(call, dfg.input_wires())?;
+            dfg.finish_hugr_with_outputs(call.outputs())
+        };
+
+        assert_matches!(build(type_row![B], type_row![B]), Ok(_));
+        assert_matches!(build(type_row![Q, NAT], type_row![Q, NAT]), Ok(_));
+
+        let err = build(type_row![B], type_row![Q]).err().unwrap();
+        assert_matches!(
+            &err,
+            BuildError::InvalidHUGR(ValidationError::CallIndirectMismatch { expected, found, .. }) => {
+                assert_eq!(expected, &Signature::new_df(type_row![Q], type_row![Q]));
+                assert_eq!(found, &Signature::new_df(type_row![B], type_row![B]));
+            }
+        );
+        assert!(err.to_string().contains("[I1] -> [I1]"));
+
+        // A classical tuple smuggling a qubit.
+        let smuggled =
+            ClassicType::Container(crate::types::Container::Tuple(Box::new(type_row![Q])));
+        let row: TypeRow = vec![smuggled.clone().into()].into();
+        assert_matches!(
+            build(row.clone(), row).err(),
+            Some(BuildError::InvalidHUGR(ValidationError::LinearInClassicType { typ, linear, .. }))
+                => { assert_eq!(typ, smuggled); assert_eq!(linear, Q) }
+        );
         Ok(())
     }
+
+    #[test]
+    fn new_type_ports() -> Result<(), BuildError> {
+        let length = NAT.into_new_type("Length");
+        let mut dfg = DFGBuilder::new(vec![length.clone()], type_row![NAT])?;
+        let [w] = dfg.input_wires_arr();
+        dfg.set_outputs([w])?;
+        let h = std::mem::take(dfg.hugr_mut());
+
+        let err = h.validate().unwrap_err();
+        assert_matches!(
+            &err,
+            ValidationError::IncompatiblePorts { from_kind: EdgeKind::Value(from), to_kind: EdgeKind::Value(to), .. } => {
+                assert_eq!(from, &length);
+                assert_eq!(to, &NAT);
+            }
+        );
+        assert!(err.to_string().ends_with(
+            "Cannot connect Value(Length) to Value(I64) (resolved: Value(I64) to Value(I64))."
+        ));
+
+        let structural = ValidationOptions {
+            structural_types: true,
+            ..Default::default()
+        };
+        assert_eq!(h.validate_with_options(&structural), Ok(()));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_json() {
+        let (mut b, def) = make_simple_hugr(2);
+        assert_eq!(b.validate_json(), Ok(()));
+
+        // An unconnected dataflow port.
+        let output = b.children(def).nth(1).unwrap();
+        b.disconnect(output, Port::new_incoming(1)).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&b.validate_json().unwrap_err()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": 1,
+                "category": "Structural",
+                "message": b.validate().unwrap_err().to_string(),
+                "error": {"UnconnectedPort": {
+                    "node": output.index.index(),
+                    "port": {"Incoming": 1},
+                    "port_kind": {"Value": {"t": "I", "width": 1}},
+                }},
+            })
+        );
+
+        // A second hierarchy root.
+        let mut b = Hugr::default();
+        let other = b.add_op(ops::Module).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&b.validate_json().unwrap_err()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": 1,
+                "category": "Structural",
+                "message": format!("The node {other:?} has no parent."),
+                "error": {"NoParent": {"node": other.index.index()}},
+            })
+        );
+        for field in ["version", "category", "message", "error"] {
+            assert!(json.get(field).is_some(), "missing field {field}");
+        }
+    }
 }
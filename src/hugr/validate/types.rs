@@ -0,0 +1,389 @@
+//! Validation of the custom types used in a HUGR against their definitions.
+
+use thiserror::Error;
+
+use smol_str::SmolStr;
+
+use crate::ops::{LeafOp, OpName, OpType};
+use crate::resource::{ResourceId, ResourceRegistry, TypeDef};
+use crate::types::simple::PrimType;
+use crate::types::type_param::{check_type_arg, TypeArg, TypeArgError};
+use crate::types::{ClassicType, Container, CustomType, EdgeKind, LinearType, SimpleType, TypeRow};
+use crate::{Hugr, HugrView, Node, Port};
+
+impl Hugr {
+    /// Check that every custom type on the ports of the HUGR is defined by a
+    /// resource in `registry`, with matching type arguments and linearity,
+    /// and that the type arguments of custom operations fit their
+    /// definitions.
+    ///
+    /// Custom types may be identified by the name of their [`TypeDef`] qualified
+    /// with the resource defining it, or by the name alone if a single
+    /// resource defines a type with that name.
+    pub fn validate_types(&self, registry: &ResourceRegistry) -> Result<(), TypeValidationError> {
+        for node in self.nodes() {
+            let optype = self.get_optype(node);
+            if let OpType::LeafOp(LeafOp::CustomOp(op)) = optype {
+                if let Some(def) = op.op_def(registry) {
+                    def.check_args(op.args()).map_err(|source| {
+                        TypeValidationError::InvalidOpArgs {
+                            node,
+                            op: op.name(),
+                            source,
+                        }
+                    })?;
+                }
+            }
+            for port in self.all_node_ports(node) {
+                let checker = TypeChecker {
+                    registry,
+                    node,
+                    port,
+                };
+                match optype.port_kind(port) {
+                    Some(EdgeKind::Value(typ)) => checker.check_simple(&typ)?,
+                    Some(EdgeKind::Static(typ)) => checker.check_classic(&typ)?,
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks the types on a port of a node.
+struct TypeChecker<'a> {
+    registry: &'a ResourceRegistry,
+    node: Node,
+    port: Port,
+}
+
+impl<'a> TypeChecker<'a> {
+    fn check_simple(&self, typ: &SimpleType) -> Result<(), TypeValidationError> {
+        match typ {
+            SimpleType::Classic(typ) => self.check_classic(typ),
+            SimpleType::Linear(typ) => self.check_linear(typ),
+        }
+    }
+
+    fn check_classic(&self, typ: &ClassicType) -> Result<(), TypeValidationError> {
+        match typ {
+            ClassicType::Graph(graph) => {
+                let (_, sig) = graph.as_ref();
+                self.check_row(&sig.input)?;
+                self.check_row(&sig.output)?;
+                self.check_row(&sig.static_input)
+            }
+            ClassicType::Container(container) => {
+                self.check_container(container, Self::check_classic)
+            }
+            ClassicType::Opaque(custom) => self.check_custom(custom, false),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_linear(&self, typ: &LinearType) -> Result<(), TypeValidationError> {
+        match typ {
+            LinearType::Qubit => Ok(()),
+            LinearType::Qpaque(custom) => self.check_custom(custom, true),
+            LinearType::Container(container) => self.check_container(container, Self::check_linear),
+        }
+    }
+
+    fn check_row(&self, row: &TypeRow) -> Result<(), TypeValidationError> {
+        row.iter().try_for_each(|typ| self.check_simple(typ))
+    }
+
+    fn check_container<T: PrimType>(
+        &self,
+        container: &Container<T>,
+        check_elem: impl Fn(&Self, &T) -> Result<(), TypeValidationError>,
+    ) -> Result<(), TypeValidationError> {
+        match container {
+            Container::List(elem) | Container::Array(elem, _) | Container::NewType(_, elem) => {
+                check_elem(self, elem)
+            }
+            Container::Map(map) => {
+                let (key, value) = map.as_ref();
+                self.check_classic(key)?;
+                check_elem(self, value)
+            }
+            Container::Tuple(row) | Container::Sum(row) => self.check_row(row),
+            Container::Alias(_) => Ok(()),
+        }
+    }
+
+    fn check_custom(&self, custom: &CustomType, linear: bool) -> Result<(), TypeValidationError> {
+        let def = self.type_def(custom)?;
+        if def.linear != linear {
+            return Err(TypeValidationError::LinearityMismatch {
+                node: self.node,
+                port: self.port,
+                typ: custom.clone(),
+                linear: def.linear,
+            });
+        }
+        let invalid_args = |source| TypeValidationError::InvalidTypeArgs {
+            node: self.node,
+            port: self.port,
+            typ: custom.clone(),
+            source,
+        };
+        if custom.params().len() != def.args.len() {
+            return Err(invalid_args(TypeArgError::WrongNumber(
+                custom.params().len(),
+                def.args.len(),
+            )));
+        }
+        for (arg, param) in custom.params().iter().zip(&def.args) {
+            check_type_arg(arg, param).map_err(invalid_args)?;
+            self.check_type_arg(arg)?;
+        }
+        Ok(())
+    }
+
+    /// Check the types nested in a type argument.
+    fn check_type_arg(&self, arg: &TypeArg) -> Result<(), TypeValidationError> {
+        match arg {
+            TypeArg::Type(typ) => self.check_simple(typ),
+            TypeArg::ClassicType(typ) => self.check_classic(typ),
+            TypeArg::Int(_) => Ok(()),
+            TypeArg::List(args) => args.iter().try_for_each(|arg| self.check_type_arg(arg)),
+        }
+    }
+
+    /// Find the definition of a custom type, from its name qualified with the
+    /// resource defining it, or from its name alone if it is defined by a
+    /// single resource.
+    fn type_def(&self, custom: &CustomType) -> Result<&'a TypeDef, TypeValidationError> {
+        let id = custom.id();
+        let qualified = id
+            .rsplit_once('.')
+            .and_then(|(resource, name)| self.registry.get(resource)?.types().get(name));
+        if let Some(def) = qualified {
+            return Ok(def);
+        }
+        let mut defs: Vec<(&ResourceId, &TypeDef)> = self
+            .registry
+            .iter()
+            .filter_map(|(resource, r)| Some((resource, r.types().get(id)?)))
+            .collect();
+        match defs.len() {
+            0 => Err(TypeValidationError::UnknownType {
+                node: self.node,
+                port: self.port,
+                typ: custom.clone(),
+            }),
+            1 => Ok(defs[0].1),
+            _ => {
+                defs.sort_by_key(|(resource, _)| *resource);
+                Err(TypeValidationError::AmbiguousType {
+                    node: self.node,
+                    port: self.port,
+                    typ: custom.clone(),
+                    resources: defs.into_iter().map(|(r, _)| r.clone()).collect(),
+                })
+            }
+        }
+    }
+}
+
+/// Errors from checking the custom types of a HUGR against their definitions.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[allow(missing_docs)]
+pub enum TypeValidationError {
+    /// No resource in the registry defines the type.
+    #[error("Unknown custom type {typ} on port {port:?} of node {node:?}.")]
+    UnknownType {
+        node: Node,
+        port: Port,
+        typ: CustomType,
+    },
+    /// The type is not qualified with a resource, and several resources in
+    /// the registry define a type with its name.
+    #[error("Custom type {typ} on port {port:?} of node {node:?} is defined by several resources: {resources:?}.")]
+    AmbiguousType {
+        node: Node,
+        port: Port,
+        typ: CustomType,
+        resources: Vec<ResourceId>,
+    },
+    /// The type arguments do not fit the parameters of the type definition.
+    #[error("Invalid arguments for custom type {typ} on port {port:?} of node {node:?}: {source}")]
+    InvalidTypeArgs {
+        node: Node,
+        port: Port,
+        typ: CustomType,
+        source: TypeArgError,
+    },
+    /// The type is used as a linear type but its definition is classical, or
+    /// vice versa.
+    #[error("Custom type {typ} on port {port:?} of node {node:?} has the wrong linearity, its definition is {}.", if *linear { "linear" } else { "classical" })]
+    LinearityMismatch {
+        node: Node,
+        port: Port,
+        typ: CustomType,
+        linear: bool,
+    },
+    /// The type arguments of a custom operation do not fit the parameters of
+    /// its definition.
+    #[error("Invalid arguments for operation {op} at node {node:?}: {source}")]
+    InvalidOpArgs {
+        node: Node,
+        op: SmolStr,
+        source: TypeArgError,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::extensions::rotation;
+    use crate::ops::custom::{ExternalOp, OpaqueOp};
+    use crate::resource::Resource;
+    use crate::types::type_param::TypeParam;
+    use crate::types::Signature;
+
+    fn registry() -> ResourceRegistry {
+        let mut resource = Resource::new("my_ext".into());
+        resource.add_type(TypeDef {
+            name: "angle".into(),
+            args: vec![],
+            linear: false,
+        });
+        resource.add_type(TypeDef {
+            name: "array".into(),
+            args: vec![TypeParam::Int],
+            linear: true,
+        });
+        ResourceRegistry::from([("my_ext".into(), resource)])
+    }
+
+    /// A HUGR passing a value of type `typ` through a no-op.
+    fn noop_hugr(typ: SimpleType) -> Result<Hugr, BuildError> {
+        let mut builder = DFGBuilder::new(vec![typ.clone()], vec![typ.clone()])?;
+        let noop = builder.add_dataflow_op(LeafOp::Noop { ty: typ }, builder.input_wires())?;
+        builder.finish_hugr_with_outputs(noop.outputs())
+    }
+
+    fn angle() -> CustomType {
+        CustomType::new("my_ext.angle", [])
+    }
+
+    #[test]
+    fn declared_types() -> Result<(), BuildError> {
+        let array = LinearType::Qpaque(CustomType::new("array", [TypeArg::Int(4)]));
+        let tuple = SimpleType::new_tuple(vec![angle().classic_type().into()]);
+        for typ in [array.into(), tuple] {
+            assert_eq!(noop_hugr(typ)?.validate_types(&registry()), Ok(()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn undeclared_type() -> Result<(), BuildError> {
+        let hugr = noop_hugr(angle().classic_type().into())?;
+        assert_matches!(
+            hugr.validate_types(&ResourceRegistry::new()),
+            Err(TypeValidationError::UnknownType { typ, .. }) => assert_eq!(typ, angle())
+        );
+
+        let graph = ClassicType::graph_from_sig(Signature::new_df(
+            vec![CustomType::new("my_ext.other", []).classic_type().into()],
+            vec![],
+        ));
+        let hugr = noop_hugr(graph.into())?;
+        assert_matches!(
+            hugr.validate_types(&registry()),
+            Err(TypeValidationError::UnknownType { .. })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_type_args() -> Result<(), BuildError> {
+        let array = CustomType::new("my_ext.array", []);
+        let hugr = noop_hugr(LinearType::Qpaque(array).into())?;
+        assert_matches!(
+            hugr.validate_types(&registry()),
+            Err(TypeValidationError::InvalidTypeArgs {
+                source: TypeArgError::WrongNumber(0, 1),
+                ..
+            })
+        );
+
+        let array = CustomType::new("my_ext.array", [TypeArg::ClassicType(ClassicType::F64)]);
+        let hugr = noop_hugr(LinearType::Qpaque(array).into())?;
+        assert_matches!(
+            hugr.validate_types(&registry()),
+            Err(TypeValidationError::InvalidTypeArgs {
+                source: TypeArgError::TypeMismatch(..),
+                ..
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ambiguous_type() -> Result<(), BuildError> {
+        let mut registry = registry();
+        let mut other = Resource::new("other_ext".into());
+        other.add_type(TypeDef {
+            name: "angle".into(),
+            args: vec![],
+            linear: false,
+        });
+        registry.insert("other_ext".into(), other);
+
+        let unqualified = CustomType::new("angle", []);
+        let hugr = noop_hugr(unqualified.classic_type().into())?;
+        assert_matches!(
+            hugr.validate_types(&registry),
+            Err(TypeValidationError::AmbiguousType { resources, .. }) => {
+                assert_eq!(resources, ["my_ext", "other_ext"]);
+            }
+        );
+        for id in ["my_ext.angle", "other_ext.angle"] {
+            let hugr = noop_hugr(CustomType::new(id, []).classic_type().into())?;
+            assert_eq!(hugr.validate_types(&registry), Ok(()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_op_args() {
+        let sig = Signature::new_df(vec![], vec![]);
+        let op = |args: Vec<TypeArg>| {
+            let op = OpaqueOp::new(
+                rotation::resource_id(),
+                "AngleAdd",
+                String::new(),
+                args,
+                Some(sig.clone()),
+            );
+            Hugr::new(LeafOp::from(ExternalOp::from(op)))
+        };
+        let registry = ResourceRegistry::from([(rotation::resource_id(), rotation::resource())]);
+        assert_eq!(op(vec![]).validate_types(&registry), Ok(()));
+        assert_matches!(
+            op(vec![TypeArg::Int(1)]).validate_types(&registry),
+            Err(TypeValidationError::InvalidOpArgs {
+                source: TypeArgError::WrongNumber(1, 0),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn linearity_mismatch() -> Result<(), BuildError> {
+        let hugr = noop_hugr(LinearType::Qpaque(angle()).into())?;
+        assert_matches!(
+            hugr.validate_types(&registry()),
+            Err(TypeValidationError::LinearityMismatch { linear: false, .. })
+        );
+        Ok(())
+    }
+}
@@ -1,18 +1,27 @@
 #![allow(unused)]
 //! A Trait for "read-only" HUGRs.
 
-use std::iter::FusedIterator;
+use std::iter::{self, Enumerate, FusedIterator, Take};
 use std::ops::Deref;
 
 use context_iterators::{ContextIterator, IntoContextIterator, MapCtx, MapWithCtx, WithCtx};
 use itertools::{Itertools, MapInto};
 use portgraph::{multiportgraph, LinkView, MultiPortGraph, PortView};
+use smol_str::SmolStr;
 
 use super::{Hugr, NodeMetadata};
-use super::{Node, Port};
-use crate::ops::OpType;
+use super::{Node, Port, Wire};
+use crate::ops::{self, DataflowParent, OpName, OpTag, OpTrait, OpType};
+use crate::resource::ResourceSet;
+use crate::types::{EdgeKind, Signature};
 use crate::Direction;
 
+pub mod dynamic;
+mod filter;
+pub use filter::FilteredView;
+
+use super::region::{Region, RootedView};
+
 /// A trait for inspecting HUGRs.
 /// For end users we intend this to be superseded by region-specific APIs.
 pub trait HugrView: sealed::HugrInternals {
@@ -27,7 +36,7 @@ pub trait HugrView: sealed::HugrInternals {
         Self: 'a;
 
     /// An Iterator over the children of a node
-    type Children<'a>: Iterator<Item = Node>
+    type Children<'a>: DoubleEndedIterator<Item = Node>
     where
         Self: 'a;
 
@@ -52,12 +61,52 @@ pub trait HugrView: sealed::HugrInternals {
     /// Returns the parent of a node.
     fn get_parent(&self, node: Node) -> Option<Node>;
 
+    /// Iterates over the ancestors of a node, from its parent up to the root.
+    #[inline]
+    fn ancestors(&self, node: Node) -> Ancestors<'_, Self> {
+        Ancestors {
+            hugr: self,
+            next: self.get_parent(node),
+        }
+    }
+
+    /// Returns the number of ancestors of a node, zero for the root.
+    #[inline]
+    fn depth(&self, node: Node) -> usize {
+        self.ancestors(node).count()
+    }
+
+    /// Returns whether `ancestor` is a strict ancestor of `node`.
+    #[inline]
+    fn is_ancestor_of(&self, ancestor: Node, node: Node) -> bool {
+        self.ancestors(node).any(|n| n == ancestor)
+    }
+
+    /// Returns the deepest node that is either equal to or an ancestor of
+    /// both `a` and `b`, or `None` if they are not in the same hierarchy.
+    fn lowest_common_ancestor(&self, a: Node, b: Node) -> Option<Node> {
+        // Walk up from the same depth until the paths meet.
+        let (depth_a, depth_b) = (self.depth(a), self.depth(b));
+        let path_a = iter::once(a).chain(self.ancestors(a));
+        let path_b = iter::once(b).chain(self.ancestors(b));
+        path_a
+            .skip(depth_a.saturating_sub(depth_b))
+            .zip(path_b.skip(depth_b.saturating_sub(depth_a)))
+            .find_map(|(a, b)| (a == b).then_some(a))
+    }
+
     /// Returns the operation type of a node.
     fn get_optype(&self, node: Node) -> &OpType;
 
     /// Returns the metadata associated with a node.
     fn get_metadata(&self, node: Node) -> &NodeMetadata;
 
+    /// Returns whether the node exists in the hugr.
+    #[inline]
+    fn contains_node(&self, node: Node) -> bool {
+        self.portgraph().contains_node(node.index)
+    }
+
     /// Returns the number of nodes in the hugr.
     fn node_count(&self) -> usize;
 
@@ -95,6 +144,31 @@ pub trait HugrView: sealed::HugrInternals {
         self.linked_ports(node, port).next().is_some()
     }
 
+    /// Returns the canonical form of a wire, following it back through
+    /// [`LeafOp::Noop`] nodes to the operation producing the value.
+    ///
+    /// Two wires carry the same value if their canonical forms are equal.
+    /// [`Wire`] equality is only meaningful for canonical wires.
+    ///
+    /// [`LeafOp::Noop`]: crate::ops::LeafOp::Noop
+    fn resolve_wire(&self, wire: Wire) -> Wire {
+        let mut wire = wire;
+        // Bound the walk, in case of a cycle of Noops in an invalid HUGR.
+        for _ in 0..self.node_count() {
+            if !matches!(
+                self.get_optype(wire.node()),
+                OpType::LeafOp(ops::LeafOp::Noop { .. })
+            ) {
+                break;
+            }
+            match self.linked_ports(wire.node(), Port::new_incoming(0)).next() {
+                Some((src, src_port)) => wire = Wire::new(src, src_port),
+                None => break,
+            }
+        }
+        wire
+    }
+
     /// Number of ports in node for a given direction.
     fn num_ports(&self, node: Node, dir: Direction) -> usize;
 
@@ -115,6 +189,37 @@ pub trait HugrView: sealed::HugrInternals {
     /// Return iterator over children of node.
     fn children(&self, node: Node) -> Self::Children<'_>;
 
+    /// Return the `n`-th child of a node, counting from zero.
+    ///
+    /// Returns `None` if the node has `n` or fewer children.
+    #[inline]
+    fn nth_child(&self, parent: Node, n: usize) -> Option<Node> {
+        self.children(parent).nth(n)
+    }
+
+    /// Return the last child of a node, or `None` if it has no children.
+    #[inline]
+    fn last_child(&self, parent: Node) -> Option<Node> {
+        self.children(parent).next_back()
+    }
+
+    /// Return the [`Input`] and [`Output`] children of a dataflow container.
+    ///
+    /// These are the first two children of the node. Returns `None` if the
+    /// node has no such children, as for a [`Conditional`] or the root module.
+    ///
+    /// [`Input`]: crate::ops::Input
+    /// [`Output`]: crate::ops::Output
+    /// [`Conditional`]: crate::ops::Conditional
+    fn get_io(&self, parent: Node) -> Option<(Node, Node)> {
+        let mut children = self.children(parent);
+        let input = children.next()?;
+        let output = children.next()?;
+        (OpTag::Input.is_superset(self.get_optype(input).tag())
+            && OpTag::Output.is_superset(self.get_optype(output).tag()))
+        .then_some((input, output))
+    }
+
     /// Iterates over neighbour nodes in the given direction.
     /// May contain duplicates if the graph has multiple links between nodes.
     fn neighbours(&self, node: Node, dir: Direction) -> Self::Neighbours<'_>;
@@ -135,6 +240,248 @@ pub trait HugrView: sealed::HugrInternals {
 
     /// Iterates over the input and output neighbours of the `node` in sequence.
     fn all_neighbours(&self, node: Node) -> Self::Neighbours<'_>;
+
+    /// Iterates over neighbour nodes in the given direction, yielding each
+    /// node only once even if it is connected by multiple links.
+    #[inline]
+    fn unique_neighbours(
+        &self,
+        node: Node,
+        dir: Direction,
+    ) -> UniqueNeighbours<Self::Neighbours<'_>> {
+        UniqueNeighbours::new(self.neighbours(node, dir))
+    }
+
+    /// Iterates over all the links of the ports of `node` in the given
+    /// direction, as pairs of `(node, port)` endpoints. The first element of
+    /// each pair is the endpoint at `node`.
+    #[inline]
+    fn all_linked_ports(&self, node: Node, dir: Direction) -> AllLinkedPorts<'_, Self> {
+        AllLinkedPorts {
+            hugr: self,
+            node,
+            ports: self.node_ports(node, dir),
+            links: None,
+        }
+    }
+
+    /// Iterates over all the links of `node`, incoming then outgoing, as
+    /// `(port, other_node, other_port)` triples where `port` is the port at
+    /// `node`.
+    ///
+    /// A port with several links, such as a copied value, yields one triple
+    /// per link. Use [`HugrView::edges`] to visit each link of the HUGR once.
+    #[inline]
+    fn node_connections(&self, node: Node) -> NodeConnections<'_, Self> {
+        NodeConnections(AllLinkedPorts {
+            hugr: self,
+            node,
+            ports: self.all_node_ports(node),
+            links: None,
+        })
+    }
+
+    /// Iterates over all the edges of the HUGR, with their kinds.
+    ///
+    /// Each link appears once, from its source to its target.
+    #[inline]
+    fn edges(&self) -> Edges<'_, Self, Self::Nodes<'_>> {
+        Edges::new(self, self.nodes())
+    }
+
+    /// Iterates over the edges leaving the children of `parent`, with their
+    /// kinds.
+    ///
+    /// This includes the external edges from the children to nodes in other
+    /// regions.
+    #[inline]
+    fn region_edges(&self, parent: Node) -> Edges<'_, Self, Self::Children<'_>> {
+        Edges::new(self, self.children(parent))
+    }
+
+    /// Iterates over the `(tag, case)` pairs of a [`Conditional`] node, where
+    /// `case` is the [`Case`] child executed for the predicate tag `tag`.
+    ///
+    /// Returns an empty iterator if the node is not a [`Conditional`].
+    ///
+    /// [`Conditional`]: crate::ops::Conditional
+    /// [`Case`]: crate::ops::Case
+    #[inline]
+    fn conditional_cases(&self, node: Node) -> Enumerate<Take<Self::Children<'_>>> {
+        let n_cases = match self.get_optype(node) {
+            OpType::Conditional(cond) => cond.predicate_inputs.len(),
+            _ => 0,
+        };
+        self.children(node).take(n_cases).enumerate()
+    }
+
+    /// The signature of the dataflow graph contained in a [`Case`] node.
+    ///
+    /// Returns `None` if the node is not a [`Case`].
+    ///
+    /// [`Case`]: crate::ops::Case
+    #[inline]
+    fn case_signature(&self, case: Node) -> Option<&Signature> {
+        match self.get_optype(case) {
+            OpType::Case(case) => Some(&case.signature),
+            _ => None,
+        }
+    }
+
+    /// The signature of the dataflow graph contained in a [`TailLoop`] node.
+    ///
+    /// Returns `None` if the node is not a [`TailLoop`].
+    ///
+    /// [`TailLoop`]: crate::ops::TailLoop
+    #[inline]
+    fn tail_loop_body_signature(&self, node: Node) -> Option<Signature> {
+        match self.get_optype(node) {
+            OpType::TailLoop(tail_loop) => Some(tail_loop.inner_signature()),
+            _ => None,
+        }
+    }
+
+    /// The signature of a node as seen by its neighbours, including the
+    /// resource requirements of its incoming and outgoing edges.
+    ///
+    /// The resource requirements of a [`Call`] are those of the called
+    /// function's signature, if the Call is connected to it, rather than the
+    /// ones stored in the Call node.
+    ///
+    /// Validation checks the resources of connected ports against this
+    /// signature.
+    ///
+    /// [`Call`]: crate::ops::Call
+    fn effective_signature(&self, node: Node) -> Signature {
+        let optype = self.get_optype(node);
+        let mut sig = optype.signature();
+        if let OpType::Call(call) = optype {
+            let static_port = Port::new_incoming(call.signature.input.len());
+            if let Some((callee, _)) = self.linked_ports(node, static_port).next() {
+                if let OpType::FuncDefn(ops::FuncDefn { signature, .. })
+                | OpType::FuncDecl(ops::FuncDecl { signature, .. }) = self.get_optype(callee)
+                {
+                    sig.input_resources = signature.input_resources.clone();
+                    sig.output_resources = signature.output_resources.clone();
+                }
+            }
+        }
+        sig
+    }
+
+    /// The resource requirements of the edges connected to a port of a node,
+    /// as given by [`HugrView::effective_signature`].
+    fn port_resources(&self, node: Node, port: Port) -> ResourceSet {
+        let sig = self.effective_signature(node);
+        match port.direction() {
+            Direction::Incoming => sig.input_resources,
+            Direction::Outgoing => sig.output_resources,
+        }
+    }
+
+    /// The resources introduced by the children of a container node, required
+    /// by their outputs but not by their inputs, as given by
+    /// [`HugrView::effective_signature`]. The Input and Output nodes of the
+    /// region are ignored.
+    fn region_resource_delta(&self, node: Node) -> ResourceSet {
+        self.children(node)
+            .filter(|&child| {
+                !matches!(self.get_optype(child), OpType::Input(_) | OpType::Output(_))
+            })
+            .fold(ResourceSet::new(), |delta, child| {
+                let sig = self.effective_signature(child);
+                delta.union(&sig.input_resources.missing_from(&sig.output_resources))
+            })
+    }
+
+    /// Returns the first [`FuncDefn`] or [`FuncDecl`] child of the root with
+    /// the given name.
+    ///
+    /// [`FuncDefn`]: crate::ops::FuncDefn
+    /// [`FuncDecl`]: crate::ops::FuncDecl
+    fn function_by_name(&self, name: &str) -> Option<Node> {
+        self.children(self.root())
+            .find(|&n| function_name(self.get_optype(n)) == Some(name))
+    }
+
+    /// Returns the first [`AliasDefn`] or [`AliasDecl`] child of the root with
+    /// the given name.
+    ///
+    /// [`AliasDefn`]: crate::ops::AliasDefn
+    /// [`AliasDecl`]: crate::ops::AliasDecl
+    fn alias_by_name(&self, name: &str) -> Option<Node> {
+        self.children(self.root())
+            .find(|&n| match self.get_optype(n) {
+                OpType::AliasDefn(alias) => alias.name == name,
+                OpType::AliasDecl(alias) => alias.name == name,
+                _ => false,
+            })
+    }
+
+    /// Iterates over the nodes whose operation tag is contained in `tag`.
+    ///
+    /// For example, [`OpTag::DataflowParent`] matches all the nodes with
+    /// dataflow children, and [`OpTag::Const`] only the constant nodes.
+    #[inline]
+    fn nodes_with_tag(&self, tag: OpTag) -> MatchingNodes<'_, Self, Self::Nodes<'_>> {
+        MatchingNodes::new(self, self.nodes(), NodeCriterion::Tag(tag))
+    }
+
+    /// Iterates over the nodes whose operation has the given
+    /// [name](OpName::name).
+    #[inline]
+    fn nodes_named(&self, name: &str) -> MatchingNodes<'_, Self, Self::Nodes<'_>> {
+        MatchingNodes::new(self, self.nodes(), NodeCriterion::Name(name.into()))
+    }
+
+    /// Iterates over the children of `parent` whose operation tag is
+    /// contained in `tag`.
+    #[inline]
+    fn region_nodes_with_tag(
+        &self,
+        parent: Node,
+        tag: OpTag,
+    ) -> MatchingNodes<'_, Self, Self::Children<'_>> {
+        MatchingNodes::new(self, self.children(parent), NodeCriterion::Tag(tag))
+    }
+
+    /// Iterates over the children of `parent` whose operation has the given
+    /// [name](OpName::name).
+    #[inline]
+    fn region_nodes_named(
+        &self,
+        parent: Node,
+        name: &str,
+    ) -> MatchingNodes<'_, Self, Self::Children<'_>> {
+        MatchingNodes::new(
+            self,
+            self.children(parent),
+            NodeCriterion::Name(name.into()),
+        )
+    }
+
+    /// Returns a view of the HUGR including only the nodes for which `filter`
+    /// returns `true`. See [`FilteredView`].
+    #[inline]
+    fn filter<F: Fn(Node) -> bool>(&self, filter: F) -> FilteredView<'_, F, Self>
+    where
+        Self: Sized,
+    {
+        FilteredView::new(self, filter)
+    }
+
+    /// Returns a view of the HUGR rooted at `root`, including only its
+    /// descendants. See [`RootedView`].
+    ///
+    /// The view can be validated on its own, e.g. to check a single function
+    /// definition of a module.
+    #[inline]
+    fn as_rooted(&self, root: Node) -> RootedView<'_, Self>
+    where
+        Self: Sized,
+    {
+        RootedView::new(self, root)
+    }
 }
 
 impl<T> HugrView for T
@@ -239,6 +586,211 @@ where
     }
 }
 
+/// The name of a function definition or declaration.
+pub(crate) fn function_name(op: &OpType) -> Option<&str> {
+    match op {
+        OpType::FuncDefn(f) => Some(&f.name),
+        OpType::FuncDecl(f) => Some(&f.name),
+        _ => None,
+    }
+}
+
+/// Iterator over the distinct neighbours of a node, returned by
+/// [`HugrView::unique_neighbours`].
+///
+/// Nodes are yielded in the order of their first link. The yielded nodes are
+/// tracked in a vector, as nodes usually have few neighbours.
+#[derive(Debug, Clone)]
+pub struct UniqueNeighbours<I> {
+    neighbours: I,
+    seen: Vec<Node>,
+}
+
+impl<I> UniqueNeighbours<I> {
+    fn new(neighbours: I) -> Self {
+        Self {
+            neighbours,
+            seen: Vec::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Node>> Iterator for UniqueNeighbours<I> {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Parallel links to the same node are usually consecutive.
+        let node = self
+            .neighbours
+            .find(|n| self.seen.last() != Some(n) && !self.seen.contains(n))?;
+        self.seen.push(node);
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.neighbours.size_hint().1)
+    }
+}
+
+impl<I: FusedIterator<Item = Node>> FusedIterator for UniqueNeighbours<I> {}
+
+/// Iterator over the ancestors of a node, returned by
+/// [`HugrView::ancestors`].
+pub struct Ancestors<'a, H: HugrView + ?Sized + 'a> {
+    hugr: &'a H,
+    next: Option<Node>,
+}
+
+impl<'a, H: HugrView + ?Sized> Iterator for Ancestors<'a, H> {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Node> {
+        let node = self.next?;
+        self.next = self.hugr.get_parent(node);
+        Some(node)
+    }
+}
+
+impl<'a, H: HugrView + ?Sized> FusedIterator for Ancestors<'a, H> {}
+
+/// Iterator over the links of the ports of a node, returned by
+/// [`HugrView::all_linked_ports`].
+pub struct AllLinkedPorts<'a, H: HugrView + ?Sized + 'a> {
+    hugr: &'a H,
+    node: Node,
+    ports: H::NodePorts<'a>,
+    links: Option<(Port, H::PortLinks<'a>)>,
+}
+
+impl<'a, H: HugrView + ?Sized> Iterator for AllLinkedPorts<'a, H> {
+    type Item = ((Node, Port), (Node, Port));
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((port, links)) = &mut self.links {
+                if let Some(other) = links.next() {
+                    return Some(((self.node, *port), other));
+                }
+            }
+            let port = self.ports.next()?;
+            self.links = Some((port, self.hugr.linked_ports(self.node, port)));
+        }
+    }
+}
+
+/// Iterator over the links of all the ports of a node, returned by
+/// [`HugrView::node_connections`].
+pub struct NodeConnections<'a, H: HugrView + ?Sized + 'a>(AllLinkedPorts<'a, H>);
+
+impl<'a, H: HugrView + ?Sized> Iterator for NodeConnections<'a, H> {
+    type Item = (Port, Node, Port);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ((_, port), (other, other_port)) = self.0.next()?;
+        Some((port, other, other_port))
+    }
+}
+
+/// An edge of a HUGR, returned by [`HugrView::edges`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HugrEdge {
+    /// The source node and its outgoing port.
+    pub src: (Node, Port),
+    /// The target node and its incoming port.
+    pub dst: (Node, Port),
+    /// The kind of the edge, or `None` if neither operation gives a kind to
+    /// its port, as for an [`UnknownOp`] or a port beyond the signature of an
+    /// operation.
+    ///
+    /// [`UnknownOp`]: crate::ops::UnknownOp
+    pub kind: Option<EdgeKind>,
+}
+
+/// Iterator over the edges leaving a sequence of nodes, returned by
+/// [`HugrView::edges`] and [`HugrView::region_edges`].
+pub struct Edges<'a, H: HugrView + ?Sized + 'a, I> {
+    hugr: &'a H,
+    nodes: I,
+    links: Option<AllLinkedPorts<'a, H>>,
+}
+
+impl<'a, H: HugrView + ?Sized, I> Edges<'a, H, I> {
+    fn new(hugr: &'a H, nodes: I) -> Self {
+        Self {
+            hugr,
+            nodes,
+            links: None,
+        }
+    }
+}
+
+impl<'a, H: HugrView + ?Sized, I: Iterator<Item = Node>> Iterator for Edges<'a, H, I> {
+    type Item = HugrEdge;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((src, dst)) = self.links.as_mut().and_then(Iterator::next) {
+                let kind = self
+                    .hugr
+                    .get_optype(src.0)
+                    .port_kind(src.1)
+                    .or_else(|| self.hugr.get_optype(dst.0).port_kind(dst.1));
+                return Some(HugrEdge { src, dst, kind });
+            }
+            let node = self.nodes.next()?;
+            self.links = Some(self.hugr.all_linked_ports(node, Direction::Outgoing));
+        }
+    }
+}
+
+/// The operations matched by a [`MatchingNodes`] iterator.
+#[derive(Debug, Clone)]
+enum NodeCriterion {
+    /// Operations whose tag is contained in the given tag.
+    Tag(OpTag),
+    /// Operations with the given name.
+    Name(SmolStr),
+}
+
+/// Iterator over the nodes with matching operations, returned by
+/// [`HugrView::nodes_with_tag`], [`HugrView::nodes_named`] and their region
+/// variants.
+pub struct MatchingNodes<'a, H: HugrView + ?Sized + 'a, I> {
+    hugr: &'a H,
+    nodes: I,
+    criterion: NodeCriterion,
+}
+
+impl<'a, H: HugrView + ?Sized, I> MatchingNodes<'a, H, I> {
+    fn new(hugr: &'a H, nodes: I, criterion: NodeCriterion) -> Self {
+        Self {
+            hugr,
+            nodes,
+            criterion,
+        }
+    }
+}
+
+impl<'a, H: HugrView + ?Sized, I: Iterator<Item = Node>> Iterator for MatchingNodes<'a, H, I> {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hugr = self.hugr;
+        let criterion = &self.criterion;
+        self.nodes.find(|&n| {
+            let optype = hugr.get_optype(n);
+            match criterion {
+                NodeCriterion::Tag(tag) => tag.is_superset(optype.tag()),
+                NodeCriterion::Name(name) => optype.name() == *name,
+            }
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.nodes.size_hint().1)
+    }
+}
+
 pub(crate) mod sealed {
     use super::*;
 
@@ -274,3 +826,416 @@ pub(crate) mod sealed {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::algorithm::nest_cfgs::test::build_cond_then_loop_cfg;
+    use crate::builder::{
+        BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+        HugrBuilder, ModuleBuilder,
+    };
+    use crate::hugr::HugrMut;
+    use crate::ops::{self, dataflow::IOTrait, handle::NodeHandle, LeafOp};
+    use crate::ops::{OpTag, OpTrait};
+    use crate::type_row;
+    use crate::types::{ClassicType, LinearType, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    #[test]
+    fn hand_built_conditional_cases() {
+        let mut hugr = Hugr::new(ops::Conditional {
+            predicate_inputs: vec![type_row![]; 2],
+            other_inputs: type_row![NAT],
+            outputs: type_row![NAT],
+        });
+        let root = hugr.root();
+        let case_signature = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let cases: Vec<Node> = (0..2)
+            .map(|tag| {
+                let case = ops::Case {
+                    tag,
+                    signature: case_signature.clone(),
+                };
+                hugr.add_op_with_parent(root, case).unwrap()
+            })
+            .collect();
+        // Extra children beyond the predicate arity are not cases.
+        hugr.add_op_with_parent(root, LeafOp::Noop { ty: NAT })
+            .unwrap();
+
+        assert_eq!(
+            hugr.conditional_cases(root).collect::<Vec<_>>(),
+            [(0, cases[0]), (1, cases[1])]
+        );
+        assert_eq!(hugr.case_signature(cases[1]), Some(&case_signature));
+        assert_eq!(hugr.conditional_cases(cases[0]).next(), None);
+        assert_eq!(hugr.tail_loop_body_signature(root), None);
+    }
+
+    #[test]
+    fn nodes_by_tag_and_name() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        module_builder.add_constant(ops::ConstValue::i64(3))?;
+        let mut f_build = module_builder.define_function(
+            "main",
+            Signature::new_df(type_row![QB, QB], type_row![QB, QB]),
+        )?;
+        let [q0, q1] = f_build.input_wires_arr();
+        let h0 = f_build.add_dataflow_op(LeafOp::H, [q0])?;
+        let h1 = f_build.add_dataflow_op(LeafOp::H, [q1])?;
+        let mut nested = f_build.dfg_builder(
+            Signature::new_df(type_row![QB, QB], type_row![QB, QB]),
+            h0.outputs().chain(h1.outputs()),
+        )?;
+        let [q0, q1] = nested.input_wires_arr();
+        let cx = nested.add_dataflow_op(LeafOp::CX, [q0, q1])?;
+        let nested = nested.finish_with_outputs(cx.outputs())?;
+        let f = f_build.finish_with_outputs(nested.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+
+        assert_eq!(hugr.nodes_with_tag(OpTag::Const).count(), 1);
+        assert_eq!(hugr.nodes_with_tag(OpTag::Leaf).count(), 3);
+        assert_eq!(hugr.nodes_with_tag(OpTag::Input).count(), 2);
+        assert_eq!(
+            hugr.nodes_with_tag(OpTag::Dfg).collect::<Vec<_>>(),
+            [nested.node()]
+        );
+        // Both the function definition and the nested DFG have dataflow
+        // children.
+        assert_eq!(hugr.nodes_with_tag(OpTag::DataflowParent).count(), 2);
+        assert_eq!(hugr.nodes_with_tag(OpTag::Any).count(), hugr.node_count());
+        assert_eq!(hugr.nodes_with_tag(OpTag::Cfg).next(), None);
+
+        assert_eq!(
+            hugr.nodes_named("H").collect::<Vec<_>>(),
+            [h0.node(), h1.node()]
+        );
+        assert_eq!(hugr.nodes_named("CX").collect::<Vec<_>>(), [cx.node()]);
+        assert_eq!(hugr.nodes_named("Measure").next(), None);
+
+        assert_eq!(hugr.region_nodes_with_tag(f.node(), OpTag::Leaf).count(), 2);
+        assert_eq!(
+            hugr.region_nodes_with_tag(nested.node(), OpTag::Leaf)
+                .collect::<Vec<_>>(),
+            [cx.node()]
+        );
+        assert_eq!(hugr.region_nodes_named(f.node(), "H").count(), 2);
+        assert_eq!(hugr.region_nodes_named(nested.node(), "H").next(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn parallel_links() {
+        let mut hugr = Hugr::new(ops::DFG {
+            signature: Signature::new_df(type_row![NAT, NAT, NAT], type_row![NAT, NAT, NAT]),
+            resource_delta: None,
+        });
+        let input = hugr
+            .add_op_with_parent(hugr.root(), ops::Input::new(type_row![NAT, NAT, NAT]))
+            .unwrap();
+        let output = hugr
+            .add_op_with_parent(hugr.root(), ops::Output::new(type_row![NAT, NAT, NAT]))
+            .unwrap();
+        for i in 0..3 {
+            hugr.connect(input, i, output, i).unwrap();
+        }
+        assert_eq!(hugr.validate(), Ok(()));
+
+        assert_eq!(hugr.output_neighbours(input).count(), 3);
+        assert_eq!(
+            hugr.unique_neighbours(input, Direction::Outgoing)
+                .collect::<Vec<_>>(),
+            [output]
+        );
+        assert_eq!(
+            hugr.unique_neighbours(output, Direction::Incoming)
+                .collect::<Vec<_>>(),
+            [input]
+        );
+        assert_eq!(
+            hugr.unique_neighbours(output, Direction::Outgoing).next(),
+            None
+        );
+
+        let links = hugr
+            .all_linked_ports(input, Direction::Outgoing)
+            .collect::<Vec<_>>();
+        let expected = (0..3)
+            .map(|i| {
+                (
+                    (input, Port::new_outgoing(i)),
+                    (output, Port::new_incoming(i)),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(links, expected);
+        assert_eq!(
+            hugr.all_linked_ports(output, Direction::Incoming).count(),
+            3
+        );
+    }
+
+    #[test]
+    fn indexed_children() {
+        let mut hugr = Hugr::new(ops::DFG {
+            signature: Signature::new_df(type_row![NAT], type_row![NAT]),
+            resource_delta: None,
+        });
+        let root = hugr.root();
+        assert_eq!(hugr.nth_child(root, 0), None);
+        assert_eq!(hugr.last_child(root), None);
+
+        let input = hugr
+            .add_op_with_parent(root, ops::Input::new(type_row![NAT]))
+            .unwrap();
+        assert_eq!(hugr.nth_child(root, 0), Some(input));
+        assert_eq!(hugr.nth_child(root, 1), None);
+        assert_eq!(hugr.last_child(root), Some(input));
+
+        let output = hugr
+            .add_op_with_parent(root, ops::Output::new(type_row![NAT]))
+            .unwrap();
+        let noop = hugr
+            .add_op_with_parent(root, LeafOp::Noop { ty: NAT })
+            .unwrap();
+        assert_eq!(hugr.nth_child(root, 1), Some(output));
+        assert_eq!(hugr.nth_child(root, 2), Some(noop));
+        assert_eq!(hugr.nth_child(root, 3), None);
+        assert_eq!(hugr.last_child(root), Some(noop));
+        assert_eq!(hugr.children(root).len(), 3);
+        assert_eq!(
+            hugr.children(root).rev().collect::<Vec<_>>(),
+            [noop, output, input]
+        );
+
+        // Leaf operations have no children.
+        assert_eq!(hugr.nth_child(noop, 0), None);
+        assert_eq!(hugr.last_child(noop), None);
+    }
+
+    #[test]
+    fn cfg_edges() -> Result<(), BuildError> {
+        let (hugr, _, _) = build_cond_then_loop_cfg(false)?;
+        let edges = hugr.edges().collect::<Vec<_>>();
+        assert_eq!(edges.len(), hugr.edge_count());
+        assert!(edges.iter().any(|e| e.kind == Some(EdgeKind::ControlFlow)));
+
+        // Each link is seen from both of its ends.
+        let connections: usize = hugr.nodes().map(|n| hugr.node_connections(n).count()).sum();
+        assert_eq!(connections, 2 * hugr.edge_count());
+        for edge in &edges {
+            let (src, src_port) = edge.src;
+            let (dst, dst_port) = edge.dst;
+            assert!(hugr
+                .node_connections(src)
+                .any(|c| c == (src_port, dst, dst_port)));
+            assert!(hugr
+                .node_connections(dst)
+                .any(|c| c == (dst_port, src, src_port)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dataflow_io() {
+        let mut hugr = Hugr::new(ops::Conditional {
+            predicate_inputs: vec![type_row![]],
+            other_inputs: type_row![NAT],
+            outputs: type_row![NAT],
+        });
+        let root = hugr.root();
+        let case = ops::Case {
+            tag: 0,
+            signature: Signature::new_df(type_row![NAT], type_row![NAT]),
+        };
+        let case = hugr.add_op_with_parent(root, case).unwrap();
+        assert_eq!(hugr.get_io(case), None);
+
+        let input = hugr
+            .add_op_with_parent(case, ops::Input::new(type_row![NAT]))
+            .unwrap();
+        let copy = hugr
+            .add_op_with_parent(case, LeafOp::Noop { ty: NAT })
+            .unwrap();
+        // The second child must be the output.
+        assert_eq!(hugr.get_io(case), None);
+
+        let output = hugr
+            .add_op_before(copy, ops::Output::new(type_row![NAT]))
+            .unwrap();
+        assert_eq!(hugr.get_io(case), Some((input, output)));
+        assert_eq!(hugr.get_io(root), None);
+        assert_eq!(hugr.get_io(copy), None);
+    }
+
+    #[test]
+    fn edges() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let mut f_build = module_builder.define_function(
+            "main",
+            Signature::new_df(type_row![NAT], type_row![NAT, NAT]),
+        )?;
+        let [w] = f_build.input_wires_arr();
+        let noop = f_build.add_dataflow_op(LeafOp::Noop { ty: NAT }, [w])?;
+        let [copied] = noop.outputs_arr();
+        let [input, output] = f_build.io();
+        f_build.set_order(&noop, &output)?;
+        let f_id = f_build.finish_with_outputs([copied, copied])?;
+        let hugr = module_builder.finish_hugr()?;
+
+        let mut expected = Vec::new();
+        for node in hugr.nodes() {
+            for port in hugr.node_outputs(node) {
+                for dst in hugr.linked_ports(node, port) {
+                    let kind = hugr.get_optype(node).port_kind(port);
+                    expected.push(HugrEdge {
+                        src: (node, port),
+                        dst,
+                        kind,
+                    });
+                }
+            }
+        }
+        let edges = hugr.edges().collect::<Vec<_>>();
+        assert_eq!(edges, expected);
+        assert_eq!(edges.len(), hugr.edge_count());
+        assert_eq!(edges.iter().filter(|e| e.src.0 == noop.node()).count(), 3);
+        assert!(edges.contains(&HugrEdge {
+            src: (noop.node(), Port::new_outgoing(1)),
+            dst: (output, Port::new_incoming(2)),
+            kind: Some(EdgeKind::StateOrder),
+        }));
+
+        assert_eq!(
+            hugr.node_connections(noop.node()).collect::<Vec<_>>(),
+            [
+                (Port::new_incoming(0), input, Port::new_outgoing(0)),
+                (Port::new_outgoing(0), output, Port::new_incoming(0)),
+                (Port::new_outgoing(0), output, Port::new_incoming(1)),
+                (Port::new_outgoing(1), output, Port::new_incoming(2)),
+            ]
+        );
+
+        // All the edges of this HUGR are in the function's region.
+        assert_eq!(hugr.region_edges(f_id.node()).collect::<Vec<_>>(), edges);
+        assert_eq!(hugr.region_edges(hugr.root()).count(), 0);
+
+        // The ports of unknown operations have no kind.
+        let mut hugr = hugr;
+        let unknown = || ops::UnknownOp::new(Default::default());
+        hugr.replace_op_unchecked(noop.node(), unknown());
+        hugr.replace_op_unchecked(output, unknown());
+        let kinds = hugr
+            .edges()
+            .filter(|e| e.src.0 == noop.node())
+            .map(|e| e.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, [None, None, None]);
+        assert_eq!(hugr.edges().count(), hugr.edge_count());
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_by_name() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let decl = module_builder.declare("decl", sig.clone())?;
+        let mut main = module_builder.define_function("main", sig)?;
+        let call = main.call(&decl, main.input_wires())?;
+        main.finish_with_outputs(call.outputs())?;
+        module_builder.add_alias_def("Nat", NAT)?;
+        let hugr = module_builder.finish_hugr()?;
+
+        // Node indices are not preserved by serialization.
+        let hugr: Hugr = serde_json::from_str(&serde_json::to_string(&hugr).unwrap()).unwrap();
+        let main = hugr.function_by_name("main").unwrap();
+        assert_eq!(hugr.get_optype(main).tag(), OpTag::FuncDefn);
+        let decl = hugr.function_by_name("decl").unwrap();
+        assert_matches!(
+            hugr.get_optype(decl),
+            OpType::FuncDecl(f) => assert_eq!(f.name, "decl")
+        );
+        assert_eq!(hugr.function_by_name("Nat"), None);
+        let alias = hugr.alias_by_name("Nat").unwrap();
+        assert_matches!(
+            hugr.get_optype(alias),
+            OpType::AliasDefn(a) => assert_eq!(a.definition, NAT)
+        );
+        assert_eq!(hugr.alias_by_name("main"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn ancestors_and_depth() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let sig = Signature::new_df(type_row![NAT], type_row![NAT]);
+        let mut f_build = module_builder.define_function("main", sig.clone())?;
+        let [f_input, _] = f_build.io();
+        let mut nested = f_build.dfg_builder(sig, f_build.input_wires())?;
+        let [nested_input, _] = nested.io();
+        let noop = nested.add_dataflow_op(LeafOp::Noop { ty: NAT }, nested.input_wires())?;
+        let nested = nested.finish_with_outputs(noop.outputs())?;
+        let f = f_build.finish_with_outputs(nested.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+        let root = hugr.root();
+        let noop = noop.node();
+
+        assert_eq!(hugr.ancestors(root).next(), None);
+        assert_eq!(
+            hugr.ancestors(noop).collect::<Vec<_>>(),
+            [nested.node(), f.node(), root]
+        );
+        assert_eq!(hugr.depth(root), 0);
+        assert_eq!(hugr.depth(f.node()), 1);
+        assert_eq!(hugr.depth(nested.node()), 2);
+        assert_eq!(hugr.depth(noop), 3);
+
+        assert!(hugr.is_ancestor_of(root, noop));
+        assert!(hugr.is_ancestor_of(f.node(), noop));
+        assert!(!hugr.is_ancestor_of(noop, noop));
+        assert!(!hugr.is_ancestor_of(noop, root));
+        assert!(!hugr.is_ancestor_of(nested_input, noop));
+
+        assert_eq!(hugr.lowest_common_ancestor(root, noop), Some(root));
+        assert_eq!(hugr.lowest_common_ancestor(noop, noop), Some(noop));
+        assert_eq!(
+            hugr.lowest_common_ancestor(noop, nested_input),
+            Some(nested.node())
+        );
+        assert_eq!(hugr.lowest_common_ancestor(f_input, noop), Some(f.node()));
+        assert_eq!(
+            hugr.lowest_common_ancestor(nested.node(), f.node()),
+            Some(f.node())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_wire_through_noops() -> Result<(), BuildError> {
+        let mut dfg = DFGBuilder::new(type_row![NAT], type_row![NAT, NAT])?;
+        let [w] = dfg.input_wires_arr();
+        let noop = |ty| LeafOp::Noop { ty };
+        let [copy] = dfg.add_dataflow_op(noop(NAT), [w])?.outputs_arr();
+        let [copy_of_copy] = dfg.add_dataflow_op(noop(NAT), [copy])?.outputs_arr();
+        let [other_copy] = dfg.add_dataflow_op(noop(NAT), [w])?.outputs_arr();
+        let hugr = dfg.finish_hugr_with_outputs([copy_of_copy, other_copy])?;
+
+        assert_ne!(copy_of_copy, other_copy);
+        assert_eq!(hugr.resolve_wire(copy_of_copy), w);
+        assert_eq!(
+            hugr.resolve_wire(copy_of_copy),
+            hugr.resolve_wire(other_copy)
+        );
+        assert_eq!(hugr.resolve_wire(w), w);
+        assert_eq!(
+            hugr.linked_ports(copy.node(), Port::new_incoming(0)).next(),
+            Some((w.node(), w.port()))
+        );
+        Ok(())
+    }
+}
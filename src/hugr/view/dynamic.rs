@@ -0,0 +1,186 @@
+//! An object-safe version of [`HugrView`].
+//!
+//! [`HugrViewDyn`] shares its method names with [`HugrView`], so it is not
+//! re-exported alongside it, to avoid ambiguous calls on concrete views.
+
+use crate::ops::OpType;
+use crate::{Direction, Node, Port};
+
+use super::{HugrView, NodeMetadata};
+
+/// An object-safe subset of [`HugrView`], for code that needs to inspect
+/// HUGRs through a `&dyn HugrViewDyn`.
+///
+/// The iterators are boxed, so this trait is implemented for every
+/// [`HugrView`] and code that does not need dynamic dispatch should keep
+/// using [`HugrView`] directly.
+pub trait HugrViewDyn {
+    /// Return the root node of this view.
+    fn root(&self) -> Node;
+
+    /// Returns the parent of a node.
+    fn get_parent(&self, node: Node) -> Option<Node>;
+
+    /// Returns the operation type of a node.
+    fn get_optype(&self, node: Node) -> &OpType;
+
+    /// Returns the metadata associated with a node.
+    fn get_metadata(&self, node: Node) -> &NodeMetadata;
+
+    /// Returns the number of nodes in the hugr.
+    fn node_count(&self) -> usize;
+
+    /// Returns the number of edges in the hugr.
+    fn edge_count(&self) -> usize;
+
+    /// Iterates over the nodes in the port graph.
+    fn nodes(&self) -> Box<dyn Iterator<Item = Node> + '_>;
+
+    /// Iterator over ports of node in a given direction.
+    fn node_ports(&self, node: Node, dir: Direction) -> Box<dyn Iterator<Item = Port> + '_>;
+
+    /// Iterator over both the input and output ports of node.
+    fn all_node_ports(&self, node: Node) -> Box<dyn Iterator<Item = Port> + '_>;
+
+    /// Iterator over the nodes and ports connected to a port.
+    fn linked_ports(&self, node: Node, port: Port) -> Box<dyn Iterator<Item = (Node, Port)> + '_>;
+
+    /// Number of ports in node for a given direction.
+    fn num_ports(&self, node: Node, dir: Direction) -> usize;
+
+    /// Return iterator over children of node.
+    fn children(&self, node: Node) -> Box<dyn DoubleEndedIterator<Item = Node> + '_>;
+
+    /// Iterates over neighbour nodes in the given direction.
+    /// May contain duplicates if the graph has multiple links between nodes.
+    fn neighbours(&self, node: Node, dir: Direction) -> Box<dyn Iterator<Item = Node> + '_>;
+
+    /// Iterates over the input and output neighbours of the `node` in sequence.
+    fn all_neighbours(&self, node: Node) -> Box<dyn Iterator<Item = Node> + '_>;
+}
+
+impl<T: HugrView> HugrViewDyn for T {
+    #[inline]
+    fn root(&self) -> Node {
+        HugrView::root(self)
+    }
+
+    #[inline]
+    fn get_parent(&self, node: Node) -> Option<Node> {
+        HugrView::get_parent(self, node)
+    }
+
+    #[inline]
+    fn get_optype(&self, node: Node) -> &OpType {
+        HugrView::get_optype(self, node)
+    }
+
+    #[inline]
+    fn get_metadata(&self, node: Node) -> &NodeMetadata {
+        HugrView::get_metadata(self, node)
+    }
+
+    #[inline]
+    fn node_count(&self) -> usize {
+        HugrView::node_count(self)
+    }
+
+    #[inline]
+    fn edge_count(&self) -> usize {
+        HugrView::edge_count(self)
+    }
+
+    #[inline]
+    fn nodes(&self) -> Box<dyn Iterator<Item = Node> + '_> {
+        Box::new(HugrView::nodes(self))
+    }
+
+    #[inline]
+    fn node_ports(&self, node: Node, dir: Direction) -> Box<dyn Iterator<Item = Port> + '_> {
+        Box::new(HugrView::node_ports(self, node, dir))
+    }
+
+    #[inline]
+    fn all_node_ports(&self, node: Node) -> Box<dyn Iterator<Item = Port> + '_> {
+        Box::new(HugrView::all_node_ports(self, node))
+    }
+
+    #[inline]
+    fn linked_ports(&self, node: Node, port: Port) -> Box<dyn Iterator<Item = (Node, Port)> + '_> {
+        Box::new(HugrView::linked_ports(self, node, port))
+    }
+
+    #[inline]
+    fn num_ports(&self, node: Node, dir: Direction) -> usize {
+        HugrView::num_ports(self, node, dir)
+    }
+
+    #[inline]
+    fn children(&self, node: Node) -> Box<dyn DoubleEndedIterator<Item = Node> + '_> {
+        Box::new(HugrView::children(self, node))
+    }
+
+    #[inline]
+    fn neighbours(&self, node: Node, dir: Direction) -> Box<dyn Iterator<Item = Node> + '_> {
+        Box::new(HugrView::neighbours(self, node, dir))
+    }
+
+    #[inline]
+    fn all_neighbours(&self, node: Node) -> Box<dyn Iterator<Item = Node> + '_> {
+        Box::new(HugrView::all_neighbours(self, node))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer};
+    use crate::hugr::region::{Region, RegionView};
+    use crate::ops::{handle::NodeHandle, LeafOp, OpTag, OpTrait};
+    use crate::type_row;
+    use crate::types::{LinearType, Signature, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+
+    /// Count the nodes, the descendants of the root, and the nodes linked to
+    /// the outputs of the root's Input node.
+    fn summary(view: &dyn HugrViewDyn) -> (usize, usize, usize) {
+        let mut descendants = 0;
+        let mut stack: Vec<Node> = view.children(view.root()).collect();
+        while let Some(node) = stack.pop() {
+            descendants += 1;
+            stack.extend(view.children(node));
+        }
+        let input = view.children(view.root()).next().unwrap();
+        let linked = view
+            .node_ports(input, Direction::Outgoing)
+            .flat_map(|port| view.linked_ports(input, port))
+            .count();
+        (view.node_count(), descendants, linked)
+    }
+
+    #[test]
+    fn dyn_views() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB], type_row![QB])?;
+        let [q] = builder.input_wires_arr();
+        let q = builder.add_dataflow_op(LeafOp::H, [q])?.out_wire(0);
+        let inner = {
+            let inner =
+                builder.dfg_builder(Signature::new_df(type_row![QB], type_row![QB]), [q])?;
+            let w = inner.input_wires();
+            inner.finish_with_outputs(w)?
+        };
+        let hugr = builder.finish_hugr_with_outputs(inner.outputs())?;
+
+        // Root, Input, Output, H, and the nested DFG with its Input and Output.
+        assert_eq!(summary(&hugr), (7, 6, 1));
+        let region = RegionView::new(&hugr, inner.handle().node());
+        assert_eq!(summary(&region), (3, 2, 1));
+
+        let views: [&dyn HugrViewDyn; 2] = [&hugr, &region];
+        assert!(views
+            .iter()
+            .all(|v| v.get_optype(v.root()).tag() == OpTag::Dfg));
+        Ok(())
+    }
+}
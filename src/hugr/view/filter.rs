@@ -0,0 +1,244 @@
+//! A view of a HUGR with some of its nodes hidden.
+
+use context_iterators::{ContextIterator, FilterWithCtx, IntoContextIterator, MapWithCtx};
+use itertools::{Itertools, MapInto};
+use portgraph::view::filter::{NodeFilter, NodeFiltered};
+use portgraph::{LinkView, PortIndex, PortView};
+
+use crate::{ops::OpType, Direction, Hugr, Node, Port};
+
+use super::sealed::HugrInternals;
+use super::{HugrView, NodeMetadata};
+
+type FilteredGraph<'g, F, Base> =
+    NodeFiltered<'g, <Base as HugrInternals>::Portgraph, NodeFilter<F>, F>;
+
+/// View of a HUGR including only the nodes accepted by a filter.
+///
+/// Nodes rejected by the filter do not appear in the iterators over nodes,
+/// children and neighbours, and links to them are ignored, so ports connected
+/// to hidden nodes appear unconnected.
+///
+/// The view does not preserve the HUGR invariants, so validating it is not
+/// meaningful.
+///
+/// Created with [`HugrView::filter`].
+pub struct FilteredView<'g, F, Base = Hugr>
+where
+    Base: HugrInternals,
+{
+    /// The filtered portgraph encoding the adjacency structure of the HUGR.
+    graph: FilteredGraph<'g, F, Base>,
+
+    /// The rest of the HUGR.
+    hugr: &'g Base,
+}
+
+impl<'g, F, Base> FilteredView<'g, F, Base>
+where
+    F: Fn(Node) -> bool,
+    Base: HugrInternals,
+{
+    /// Create a view of `hugr` including only the nodes for which `filter`
+    /// returns `true`.
+    pub fn new(hugr: &'g Base, filter: F) -> Self {
+        let node_filter: NodeFilter<F> = |node, filter| filter(node.into());
+        Self {
+            graph: NodeFiltered::new(hugr.portgraph(), node_filter, filter),
+            hugr,
+        }
+    }
+
+    /// Returns whether the node is included in the view.
+    #[inline]
+    pub fn contains_node(&self, node: Node) -> bool {
+        self.graph.contains_node(node.index)
+    }
+}
+
+impl<'g, F, Base> HugrView for FilteredView<'g, F, Base>
+where
+    F: Fn(Node) -> bool,
+    Base: HugrInternals + HugrView,
+{
+    type Nodes<'a> = MapInto<<FilteredGraph<'g, F, Base> as PortView>::Nodes<'a>, Node>
+    where
+        Self: 'a;
+
+    type NodePorts<'a> = MapInto<<FilteredGraph<'g, F, Base> as PortView>::NodePortOffsets<'a>, Port>
+    where
+        Self: 'a;
+
+    type Children<'a> = FilterWithCtx<MapInto<portgraph::hierarchy::Children<'a>, Node>, &'a Self>
+    where
+        Self: 'a;
+
+    type Neighbours<'a> = MapInto<<FilteredGraph<'g, F, Base> as LinkView>::Neighbours<'a>, Node>
+    where
+        Self: 'a;
+
+    type PortLinks<'a> = MapWithCtx<
+        <FilteredGraph<'g, F, Base> as LinkView>::PortLinks<'a>,
+        &'a Self,
+        (Node, Port),
+    > where
+        Self: 'a;
+
+    #[inline]
+    fn root(&self) -> Node {
+        self.hugr.root()
+    }
+
+    #[inline]
+    fn get_parent(&self, node: Node) -> Option<Node> {
+        self.hugr
+            .get_parent(node)
+            .filter(|&parent| self.contains_node(parent))
+    }
+
+    #[inline]
+    fn get_optype(&self, node: Node) -> &OpType {
+        self.hugr.get_optype(node)
+    }
+
+    #[inline]
+    fn get_metadata(&self, node: Node) -> &NodeMetadata {
+        self.hugr.get_metadata(node)
+    }
+
+    #[inline]
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    #[inline]
+    fn edge_count(&self) -> usize {
+        // The filtered portgraph counts each link from both of its endpoints.
+        self.edges().count()
+    }
+
+    #[inline]
+    fn nodes(&self) -> Self::Nodes<'_> {
+        self.graph.nodes_iter().map_into()
+    }
+
+    #[inline]
+    fn node_ports(&self, node: Node, dir: Direction) -> Self::NodePorts<'_> {
+        self.graph.port_offsets(node.index, dir).map_into()
+    }
+
+    #[inline]
+    fn all_node_ports(&self, node: Node) -> Self::NodePorts<'_> {
+        self.graph.all_port_offsets(node.index).map_into()
+    }
+
+    fn linked_ports(&self, node: Node, port: Port) -> Self::PortLinks<'_> {
+        let port = self.graph.port_index(node.index, port.offset).unwrap();
+        self.graph
+            .port_links(port)
+            .with_context(self)
+            .map_with_context(|(_, link), view| {
+                let port: PortIndex = link.into();
+                let node = view.graph.port_node(port).unwrap();
+                let offset = view.graph.port_offset(port).unwrap();
+                (node.into(), offset.into())
+            })
+    }
+
+    #[inline]
+    fn num_ports(&self, node: Node, dir: Direction) -> usize {
+        self.graph.num_ports(node.index, dir)
+    }
+
+    #[inline]
+    fn children(&self, node: Node) -> Self::Children<'_> {
+        self.base_hugr()
+            .hierarchy
+            .children(node.index)
+            .map_into()
+            .with_context(self)
+            .filter_with_context(|&child, view| view.contains_node(child))
+    }
+
+    #[inline]
+    fn neighbours(&self, node: Node, dir: Direction) -> Self::Neighbours<'_> {
+        self.graph.neighbours(node.index, dir).map_into()
+    }
+
+    #[inline]
+    fn all_neighbours(&self, node: Node) -> Self::Neighbours<'_> {
+        self.graph.all_neighbours(node.index).map_into()
+    }
+}
+
+impl<'g, F, Base> HugrInternals for FilteredView<'g, F, Base>
+where
+    Base: HugrInternals,
+{
+    type Portgraph = FilteredGraph<'g, F, Base>;
+
+    #[inline]
+    fn portgraph(&self) -> &Self::Portgraph {
+        &self.graph
+    }
+
+    #[inline]
+    fn base_hugr(&self) -> &Hugr {
+        self.hugr.base_hugr()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::{Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder},
+        ops::{handle::NodeHandle, LeafOp},
+        type_row,
+        types::{LinearType, Signature, SimpleType},
+    };
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+
+    #[test]
+    fn filtered_view() -> Result<(), crate::builder::BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let mut f_build = module_builder.define_function(
+            "main",
+            Signature::new_df(type_row![QB, QB], type_row![QB, QB]),
+        )?;
+        let [q0, q1] = f_build.input_wires_arr();
+        let h0 = f_build.add_dataflow_op(LeafOp::H, [q0])?;
+        let h1 = f_build.add_dataflow_op(LeafOp::H, [q1])?;
+        let cx = f_build.add_dataflow_op(LeafOp::CX, h0.outputs().chain(h1.outputs()))?;
+        let f_id = f_build.finish_with_outputs(cx.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+
+        let count_h = |view: &dyn Fn(Node) -> bool| {
+            let view = hugr.filter(view);
+            view.children(f_id.node())
+                .filter(|&n| view.get_optype(n) == &LeafOp::H.into())
+                .count()
+        };
+        assert_eq!(count_h(&|_| true), 2);
+        assert_eq!(count_h(&|n| n != h0.node()), 1);
+
+        let view = hugr.filter(|n| n != h0.node());
+        assert_eq!(view.node_count(), hugr.node_count() - 1);
+        assert_eq!(view.edge_count(), hugr.edge_count() - 2);
+        assert_eq!(view.children(f_id.node()).count(), 4);
+        assert_eq!(view.get_parent(cx.node()), Some(f_id.node()));
+        assert_eq!(
+            view.input_neighbours(cx.node()).collect::<Vec<_>>(),
+            [h1.node()]
+        );
+
+        // The ports linked to the hidden node appear unconnected.
+        let input = hugr.children(f_id.node()).next().unwrap();
+        let in_port = view.node_outputs(input).next().unwrap();
+        assert!(hugr.is_linked(input, in_port));
+        assert!(!view.is_linked(input, in_port));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+//! Conversion between flat dataflow regions and portgraphs weighted by leaf
+//! operations.
+//!
+//! Tools holding circuits as a [`PortGraph`] with a [`LeafOp`] per node can
+//! wrap them into a HUGR with [`Hugr::from_weighted_graph`], and extract them
+//! back with [`Hugr::as_weighted_graph`].
+
+use std::collections::HashMap;
+
+use portgraph::{LinkMut, LinkView, NodeIndex, PortGraph, PortMut, PortView, UnmanagedDenseMap};
+
+use super::{Hugr, HugrError, HugrMut, HugrView, Node};
+use crate::ops::dataflow::IOTrait;
+use crate::ops::{FuncDefn, Input, LeafOp, OpName, OpTrait, OpType, Output};
+use crate::types::Signature;
+use crate::{Direction, Port};
+
+/// The operation of each node of a weighted graph.
+pub type LeafOpWeights = UnmanagedDenseMap<NodeIndex, Option<LeafOp>>;
+
+impl Hugr {
+    /// Create a module HUGR with a single function named "main" with the
+    /// given signature, whose body contains the operations of `graph`.
+    ///
+    /// Each node of the graph must have a weight in `weights`, and exactly
+    /// the value ports of the signature of its operation. The unlinked input
+    /// ports of the graph, ordered by node and then port, are connected to
+    /// the Input node of the function, and the unlinked output ports to its
+    /// Output node.
+    pub fn from_weighted_graph(
+        graph: &PortGraph,
+        weights: &LeafOpWeights,
+        signature: Signature,
+    ) -> Result<Hugr, HugrError> {
+        let mut hugr = Hugr::default();
+        let func = hugr.add_op_with_parent(
+            hugr.root(),
+            FuncDefn {
+                name: "main".into(),
+                signature: signature.clone(),
+                signature_desc: Default::default(),
+            },
+        )?;
+        let input = hugr.add_op_with_parent(func, Input::new(signature.input.clone()))?;
+        let output = hugr.add_op_with_parent(func, Output::new(signature.output.clone()))?;
+
+        let mut nodes: HashMap<NodeIndex, Node> = HashMap::new();
+        for n in graph.nodes_iter() {
+            let op = weights.get(n).clone().ok_or(HugrError::MissingWeight(n))?;
+            let sig = op.signature();
+            let expected = (sig.input.len(), sig.output.len());
+            let actual = (graph.num_inputs(n), graph.num_outputs(n));
+            if expected != actual {
+                return Err(HugrError::WeightPortMismatch {
+                    node: n,
+                    op: op.name(),
+                    expected,
+                    actual,
+                });
+            }
+            nodes.insert(n, hugr.add_op_with_parent(func, op)?);
+        }
+
+        let mut boundary_inputs = Vec::new();
+        let mut boundary_outputs = Vec::new();
+        for n in graph.nodes_iter() {
+            for (offset, port) in graph.inputs(n).enumerate() {
+                match graph.port_link(port) {
+                    Some(link) => {
+                        let src = graph.port_node(link).unwrap();
+                        let src_offset = graph.port_offset(link).unwrap().index();
+                        hugr.connect(nodes[&src], src_offset, nodes[&n], offset)?;
+                    }
+                    None => boundary_inputs.push((nodes[&n], offset)),
+                }
+            }
+            for (offset, port) in graph.outputs(n).enumerate() {
+                if graph.port_link(port).is_none() {
+                    boundary_outputs.push((nodes[&n], offset));
+                }
+            }
+        }
+
+        for (dir, boundary, row) in [
+            (Direction::Incoming, &boundary_inputs, &signature.input),
+            (Direction::Outgoing, &boundary_outputs, &signature.output),
+        ] {
+            if boundary.len() != row.len() {
+                return Err(HugrError::BoundaryMismatch {
+                    dir,
+                    expected: row.len(),
+                    actual: boundary.len(),
+                });
+            }
+        }
+        for (i, (node, offset)) in boundary_inputs.into_iter().enumerate() {
+            hugr.connect(input, i, node, offset)?;
+        }
+        for (i, (node, offset)) in boundary_outputs.into_iter().enumerate() {
+            hugr.connect(node, offset, output, i)?;
+        }
+        Ok(hugr)
+    }
+
+    /// The leaf operations of a dataflow region as a weighted portgraph, with
+    /// the signature of the region.
+    ///
+    /// The ports linked to the Input and Output nodes are left unlinked, and
+    /// the order edges are dropped. [`Hugr::from_weighted_graph`] rebuilds
+    /// the region if the unlinked ports are in the order of the signature.
+    ///
+    /// Returns `None` if `parent` is not a dataflow container, if it has
+    /// children other than leaf operations, or if an output port is linked
+    /// to more than one input.
+    pub fn as_weighted_graph(&self, parent: Node) -> Option<(PortGraph, LeafOpWeights, Signature)> {
+        let signature = self.get_optype(parent).inner_signature()?;
+        let mut graph = PortGraph::new();
+        let mut weights = LeafOpWeights::new();
+        let mut nodes: HashMap<Node, NodeIndex> = HashMap::new();
+        for node in self.children(parent).skip(2) {
+            let OpType::LeafOp(op) = self.get_optype(node) else {
+                return None;
+            };
+            let sig = op.signature();
+            let n = graph.add_node(sig.input.len(), sig.output.len());
+            weights[n] = Some(op.clone());
+            nodes.insert(node, n);
+        }
+
+        for (&node, &n) in &nodes {
+            for offset in 0..graph.num_outputs(n) {
+                let links: Vec<(Node, Port)> = self
+                    .linked_ports(node, Port::new_outgoing(offset))
+                    .collect();
+                match links.as_slice() {
+                    [] => {}
+                    [(tgt, tgt_port)] => {
+                        if let Some(&tgt) = nodes.get(tgt) {
+                            graph.link_nodes(n, offset, tgt, tgt_port.index()).unwrap();
+                        }
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        Some((graph, weights, signature))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder,
+    };
+    use crate::ops::handle::NodeHandle;
+    use crate::type_row;
+    use crate::types::{LinearType, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+
+    #[test]
+    fn weighted_graph_roundtrip() -> Result<(), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let sig = Signature::new_linear(type_row![QB, QB]);
+        let mut main = module_builder.define_function("main", sig)?;
+        let [q0, q1] = main.input_wires_arr();
+        let [q0] = main.add_dataflow_op(LeafOp::H, [q0])?.outputs_arr();
+        let [q1] = main.add_dataflow_op(LeafOp::T, [q1])?.outputs_arr();
+        let cx = main.add_dataflow_op(LeafOp::CX, [q0, q1])?;
+        let main = main.finish_with_outputs(cx.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+
+        let (graph, weights, signature) = hugr.as_weighted_graph(main.node()).unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.link_count(), 2);
+        let rebuilt = Hugr::from_weighted_graph(&graph, &weights, signature).unwrap();
+        assert_eq!(rebuilt.validate(), Ok(()));
+        assert_eq!(
+            serde_json::to_value(&rebuilt).unwrap(),
+            serde_json::to_value(&hugr).unwrap()
+        );
+
+        // Only flat dataflow regions can be converted.
+        assert_eq!(hugr.as_weighted_graph(hugr.root()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_weighted_graph() {
+        let mut graph = PortGraph::new();
+        let h = graph.add_node(1, 1);
+        let mut weights = LeafOpWeights::new();
+        let sig = Signature::new_linear(type_row![QB]);
+
+        assert_eq!(
+            Hugr::from_weighted_graph(&graph, &weights, sig.clone()),
+            Err(HugrError::MissingWeight(h))
+        );
+        weights[h] = Some(LeafOp::CX);
+        assert_eq!(
+            Hugr::from_weighted_graph(&graph, &weights, sig.clone()),
+            Err(HugrError::WeightPortMismatch {
+                node: h,
+                op: "CX".into(),
+                expected: (2, 2),
+                actual: (1, 1),
+            })
+        );
+        weights[h] = Some(LeafOp::H);
+        assert_eq!(
+            Hugr::from_weighted_graph(&graph, &weights, Signature::new_linear(type_row![QB, QB])),
+            Err(HugrError::BoundaryMismatch {
+                dir: Direction::Incoming,
+                expected: 2,
+                actual: 1,
+            })
+        );
+        let hugr = Hugr::from_weighted_graph(&graph, &weights, sig).unwrap();
+        assert_eq!(hugr.validate(), Ok(()));
+    }
+}
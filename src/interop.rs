@@ -0,0 +1,4 @@
+//! Conversions between HUGRs and external program representations.
+
+#[cfg(feature = "circuit_json")]
+pub mod circuit_json;
@@ -0,0 +1,566 @@
+//! Conversion between HUGRs and tket-style JSON circuits.
+//!
+//! A circuit is a list of commands acting on named qubit and bit registers.
+//! It is encoded as a [`DFG`] whose inputs and outputs are the qubits and then
+//! the bits of the circuit, in the order they are declared. Each command is an
+//! operation of the fixed [`LeafOp`] gate set, or a parametric rotation
+//! (`Rx`, `Ry`, `Rz`) encoded as an opaque operation of the [`resource_id`]
+//! resource, with its parameters stored in the node metadata.
+//!
+//! The rotations carry a resource requirement that the surrounding gates do
+//! not, so HUGRs containing them do not pass validation without inserting
+//! [`LeafOp::Lift`] nodes.
+//!
+//! [`DFG`]: crate::ops::DFG
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use smol_str::SmolStr;
+use thiserror::Error;
+
+use crate::algorithm::lanes::{lane_assignment, LaneError};
+use crate::hugr::{HugrMut, NodeMetadata};
+use crate::ops::custom::{ExternalOp, OpaqueOp};
+use crate::ops::dataflow::IOTrait;
+use crate::ops::{self, LeafOp, OpName, OpTrait, OpType};
+use crate::types::{ClassicType, LinearType, Signature, SimpleType, TypeRow};
+use crate::{Direction, Hugr, HugrView, Node, Port, Wire};
+
+const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+/// The parametric rotations supported as opaque operations.
+const ROTATIONS: [&str; 3] = ["Rx", "Ry", "Rz"];
+
+/// The identifier of the resource of the parametric rotation operations.
+pub const fn resource_id() -> SmolStr {
+    SmolStr::new_inline("circuit_json")
+}
+
+/// A serialized circuit.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct SerialCircuit {
+    #[serde(default = "default_phase")]
+    phase: String,
+    commands: Vec<Command>,
+    qubits: Vec<Register>,
+    bits: Vec<Register>,
+    #[serde(default)]
+    implicit_permutation: Vec<(Register, Register)>,
+}
+
+fn default_phase() -> String {
+    "0.0".to_string()
+}
+
+/// A named qubit or bit register, e.g. `["q", [0]]`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Register(String, Vec<i64>);
+
+/// An operation applied to registers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Command {
+    op: Operation,
+    args: Vec<Register>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Operation {
+    #[serde(rename = "type")]
+    op_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    params: Vec<String>,
+}
+
+/// Errors converting between HUGRs and JSON circuits.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[allow(missing_docs)]
+pub enum CircuitJsonError {
+    /// The circuit could not be parsed.
+    #[error("Invalid circuit JSON: {0}")]
+    InvalidJson(String),
+    /// A command of the circuit has no corresponding operation.
+    #[error("Unsupported operation {op_type:?} in command {index}.")]
+    UnsupportedOperation { index: usize, op_type: String },
+    /// A command of the circuit has the wrong registers or parameters for its
+    /// operation.
+    #[error("Invalid arguments for command {index}.")]
+    InvalidArguments { index: usize },
+    /// The circuit has a non-trivial implicit qubit permutation.
+    #[error("Implicit qubit permutations are not supported.")]
+    ImplicitPermutation,
+    /// The node is not a dataflow region with matching rows of qubits and bits
+    /// as inputs and outputs.
+    #[error("The node {0:?} is not a circuit region.")]
+    InvalidRegion(Node),
+    /// The region contains nodes that have no corresponding circuit operation.
+    #[error("The nodes {0:?} cannot be converted to circuit operations.")]
+    UnsupportedNodes(Vec<Node>),
+    /// The result of a measurement is not connected to a single output of the
+    /// region, so it cannot be assigned a bit register.
+    #[error("The result of the measurement {0:?} is not a single output of the region.")]
+    UnmappedMeasurement(Node),
+    /// An output of the region does not carry the register of the input at the
+    /// same position.
+    #[error("The output {0:?} of the region does not carry its input register.")]
+    MismatchedOutput(Port),
+}
+
+impl Hugr {
+    /// Read a HUGR from a JSON-encoded circuit.
+    ///
+    /// The result is a [`DFG`] whose inputs and outputs are the qubits and
+    /// then the bits of the circuit, in the order they are declared.
+    ///
+    /// [`DFG`]: crate::ops::DFG
+    pub fn from_circuit_json(json: &str) -> Result<Hugr, CircuitJsonError> {
+        let circ: SerialCircuit =
+            serde_json::from_str(json).map_err(|e| CircuitJsonError::InvalidJson(e.to_string()))?;
+        if circ.implicit_permutation.iter().any(|(a, b)| a != b) {
+            return Err(CircuitJsonError::ImplicitPermutation);
+        }
+
+        let row: TypeRow = std::iter::repeat(QB)
+            .take(circ.qubits.len())
+            .chain(std::iter::repeat(BIT).take(circ.bits.len()))
+            .collect::<Vec<_>>()
+            .into();
+        let mut hugr = Hugr::new(ops::DFG {
+            signature: Signature::new_df(row.clone(), row.clone()),
+            resource_delta: None,
+        });
+        let root = hugr.root();
+        let input = hugr
+            .add_op_with_parent(root, ops::Input::new(row.clone()))
+            .unwrap();
+        let output = hugr
+            .add_op_with_parent(root, ops::Output::new(row))
+            .unwrap();
+
+        // The current wire of each register.
+        let mut wires: HashMap<&Register, (Node, usize)> = circ
+            .qubits
+            .iter()
+            .chain(&circ.bits)
+            .enumerate()
+            .map(|(i, reg)| (reg, (input, i)))
+            .collect();
+        let is_qubit = |reg: &Register| circ.qubits.contains(reg);
+
+        for (index, command) in circ.commands.iter().enumerate() {
+            let op =
+                command_op(&command.op).ok_or_else(|| CircuitJsonError::UnsupportedOperation {
+                    index,
+                    op_type: command.op.op_type.clone(),
+                })?;
+            let sig = op.signature();
+            let num_bits = sig.output.len() - sig.input.len();
+            let args_valid = command.args.len() == sig.input.len() + num_bits
+                && command.args.iter().all_unique()
+                && command.args.iter().enumerate().all(|(i, reg)| {
+                    wires.contains_key(reg) && is_qubit(reg) == (i < sig.input.len())
+                });
+            let params_valid = match &op {
+                LeafOp::CustomOp(_) => command.op.params.len() == 1,
+                _ => command.op.params.is_empty(),
+            };
+            if !args_valid || !params_valid {
+                return Err(CircuitJsonError::InvalidArguments { index });
+            }
+
+            let node = hugr.add_op_with_parent(root, op).unwrap();
+            if !command.op.params.is_empty() {
+                hugr.set_metadata(node, json!({ "params": command.op.params }));
+            }
+            for (port, reg) in command.args[..sig.input.len()].iter().enumerate() {
+                let (src, src_port) = wires[reg];
+                hugr.connect(src, src_port, node, port).unwrap();
+            }
+            // The qubits are passed through, and the bits overwritten.
+            for (port, reg) in command.args.iter().enumerate() {
+                wires.insert(reg, (node, port));
+            }
+        }
+
+        for (port, reg) in circ.qubits.iter().chain(&circ.bits).enumerate() {
+            let (src, src_port) = wires[reg];
+            hugr.connect(src, src_port, output, port).unwrap();
+        }
+        Ok(hugr)
+    }
+
+    /// Encode a dataflow region as a JSON circuit.
+    ///
+    /// The region must be a [`DFG`] or [`FuncDefn`] node with the same row of
+    /// qubits and bits as inputs and outputs. They become the qubit and bit
+    /// registers `q[i]` and `c[i]` of the circuit, in port order. Each output
+    /// must carry the register of its corresponding input, or for bits the
+    /// result of a measurement.
+    ///
+    /// [`DFG`]: crate::ops::DFG
+    /// [`FuncDefn`]: crate::ops::FuncDefn
+    pub fn to_circuit_json(&self, function: Node) -> Result<String, CircuitJsonError> {
+        let invalid_region = || CircuitJsonError::InvalidRegion(function);
+        let signature = match self.get_optype(function) {
+            OpType::DFG(dfg) => &dfg.signature,
+            OpType::FuncDefn(defn) => &defn.signature,
+            _ => return Err(invalid_region()),
+        };
+        if signature.input != signature.output
+            || !signature.input.iter().all(|t| t == &QB || t == &BIT)
+        {
+            return Err(invalid_region());
+        }
+        let mut children = self.children(function);
+        let (Some(input), Some(output)) = (children.next(), children.next()) else {
+            return Err(invalid_region());
+        };
+        let nodes: Vec<Node> = children.collect();
+        let unsupported: Vec<Node> = nodes
+            .iter()
+            .copied()
+            .filter(|&n| circuit_op(self, n).is_none())
+            .collect();
+        if !unsupported.is_empty() {
+            return Err(CircuitJsonError::UnsupportedNodes(unsupported));
+        }
+
+        // The registers of the region inputs, in port order.
+        let (mut num_qubits, mut num_bits) = (0, 0);
+        let io_registers: Vec<Register> = signature
+            .input
+            .iter()
+            .map(|t| {
+                let (name, count) = match t == &QB {
+                    true => ("q", &mut num_qubits),
+                    false => ("c", &mut num_bits),
+                };
+                *count += 1;
+                Register(name.to_string(), vec![*count - 1])
+            })
+            .collect();
+        let mut registers: HashMap<(Node, Port), Register> = io_registers
+            .iter()
+            .enumerate()
+            .map(|(i, reg)| ((input, Port::new_outgoing(i)), reg.clone()))
+            .collect();
+        let source_register = |registers: &HashMap<(Node, Port), Register>, node, port| {
+            let src = self
+                .linked_ports(node, port)
+                .next()
+                .ok_or_else(invalid_region)?;
+            registers.get(&src).cloned().ok_or_else(invalid_region)
+        };
+
+        // The qubit registers follow the lanes of the linear wires.
+        let lanes = lane_assignment(self, function).map_err(|e| match e {
+            LaneError::ArityMismatch { node, .. } => CircuitJsonError::UnsupportedNodes(vec![node]),
+            _ => invalid_region(),
+        })?;
+
+        let mut commands = Vec::new();
+        for node in topological_order(self, &nodes) {
+            let op = self.get_optype(node);
+            let sig = op.signature();
+            let mut args = Vec::with_capacity(sig.output.len());
+            for port in 0..sig.input.len() {
+                args.push(source_register(&registers, node, Port::new_incoming(port))?);
+            }
+            for port in sig.input.len()..sig.output.len() {
+                // Measurement results are written to the bit of the output
+                // they are connected to.
+                let port = Port::new_outgoing(port);
+                let mut links = self.linked_ports(node, port);
+                let reg = match (links.next(), links.next()) {
+                    (Some((tgt, tgt_port)), None) if tgt == output => {
+                        io_registers[tgt_port.index()].clone()
+                    }
+                    _ => return Err(CircuitJsonError::UnmappedMeasurement(node)),
+                };
+                args.push(reg);
+            }
+            for (port, reg) in args.iter().enumerate() {
+                let port = Port::new_outgoing(port);
+                let reg = match lanes.get(&Wire::new(node, port)) {
+                    Some(&lane) => Register("q".to_string(), vec![lane as i64]),
+                    None => reg.clone(),
+                };
+                registers.insert((node, port), reg);
+            }
+            // No-ops only pass their register through.
+            if let Some(command) = circuit_op(self, node).unwrap() {
+                commands.push(Command { op: command, args });
+            }
+        }
+
+        for (port, reg) in io_registers.iter().enumerate() {
+            let port = Port::new_incoming(port);
+            if &source_register(&registers, output, port)? != reg {
+                return Err(CircuitJsonError::MismatchedOutput(port));
+            }
+        }
+
+        let (qubits, bits) = io_registers.into_iter().partition(|reg| reg.0 == "q");
+        let circ = SerialCircuit {
+            phase: default_phase(),
+            commands,
+            qubits,
+            bits,
+            implicit_permutation: vec![],
+        };
+        Ok(serde_json::to_string(&circ).expect("Circuits are serializable"))
+    }
+}
+
+/// The operation corresponding to a circuit command.
+fn command_op(op: &Operation) -> Option<LeafOp> {
+    let op = match op.op_type.as_str() {
+        "H" => LeafOp::H,
+        "T" => LeafOp::T,
+        "S" => LeafOp::S,
+        "X" => LeafOp::X,
+        "Y" => LeafOp::Y,
+        "Z" => LeafOp::Z,
+        "Tdg" => LeafOp::Tadj,
+        "Sdg" => LeafOp::Sadj,
+        "CX" => LeafOp::CX,
+        "ZZMax" => LeafOp::ZZMax,
+        "Reset" => LeafOp::Reset,
+        "Measure" => LeafOp::Measure,
+        name if ROTATIONS.contains(&name) => ExternalOp::from(OpaqueOp::new(
+            resource_id(),
+            name,
+            format!("{name} rotation"),
+            vec![],
+            Some(Signature::new_linear(vec![QB])),
+        ))
+        .into(),
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// The circuit operation corresponding to a node, or `Some(None)` for no-ops
+/// which have no command. Returns `None` if the node cannot be converted.
+fn circuit_op(hugr: &Hugr, node: Node) -> Option<Option<Operation>> {
+    let OpType::LeafOp(op) = hugr.get_optype(node) else {
+        return None;
+    };
+    let op_type = match op {
+        LeafOp::Noop { ty } if ty == &QB || ty == &BIT => return Some(None),
+        // SWAPs only exchange the registers of their wires.
+        LeafOp::Swap => return Some(None),
+        LeafOp::H => "H",
+        LeafOp::T => "T",
+        LeafOp::S => "S",
+        LeafOp::X => "X",
+        LeafOp::Y => "Y",
+        LeafOp::Z => "Z",
+        LeafOp::Tadj => "Tdg",
+        LeafOp::Sadj => "Sdg",
+        LeafOp::CX => "CX",
+        LeafOp::ZZMax => "ZZMax",
+        LeafOp::Reset => "Reset",
+        LeafOp::Measure => "Measure",
+        // Circuit measurements do not consume their qubit.
+        LeafOp::MeasureDestructive => return None,
+        LeafOp::CustomOp(ext) => {
+            let name = ext.name();
+            let name = name.strip_prefix(&format!("{}.", resource_id()))?;
+            let rotation = ROTATIONS.iter().find(|&&r| r == name)?;
+            let params = rotation_params(hugr.get_metadata(node))?;
+            let valid_sig =
+                ext.signature().input.as_ref() == [QB] && ext.signature().output.as_ref() == [QB];
+            return valid_sig.then(|| {
+                Some(Operation {
+                    op_type: rotation.to_string(),
+                    params,
+                })
+            });
+        }
+        _ => return None,
+    };
+    Some(Some(Operation {
+        op_type: op_type.to_string(),
+        params: vec![],
+    }))
+}
+
+/// The single parameter of a rotation, stored in its metadata.
+fn rotation_params(metadata: &NodeMetadata) -> Option<Vec<String>> {
+    let params: Vec<String> = serde_json::from_value(metadata.get("params")?.clone()).ok()?;
+    (params.len() == 1).then_some(params)
+}
+
+/// Sort sibling nodes so that each node comes after its predecessors among
+/// them. Ties are broken by the order of `nodes`.
+fn topological_order(hugr: &Hugr, nodes: &[Node]) -> Vec<Node> {
+    let index: HashMap<Node, usize> = nodes.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+    let mut pending: Vec<usize> = nodes
+        .iter()
+        .map(|&n| {
+            hugr.all_linked_ports(n, Direction::Incoming)
+                .filter(|(_, (src, _))| index.contains_key(src))
+                .count()
+        })
+        .collect();
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..nodes.len())
+        .filter(|&i| pending[i] == 0)
+        .map(Reverse)
+        .collect();
+    let mut order = Vec::with_capacity(nodes.len());
+    while let Some(Reverse(i)) = ready.pop() {
+        order.push(nodes[i]);
+        for (_, (tgt, _)) in hugr.all_linked_ports(nodes[i], Direction::Outgoing) {
+            if let Some(&j) = index.get(&tgt) {
+                pending[j] -= 1;
+                if pending[j] == 0 {
+                    ready.push(Reverse(j));
+                }
+            }
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer};
+    use crate::ops::handle::NodeHandle;
+    use crate::type_row;
+
+    const CIRCUIT: &str = r#"{
+        "phase": "0.0",
+        "qubits": [["q", [0]], ["q", [1]]],
+        "bits": [["c", [0]], ["c", [1]]],
+        "commands": [
+            {"op": {"type": "H"}, "args": [["q", [0]]]},
+            {"op": {"type": "T"}, "args": [["q", [1]]]},
+            {"op": {"type": "S"}, "args": [["q", [0]]]},
+            {"op": {"type": "X"}, "args": [["q", [1]]]},
+            {"op": {"type": "Y"}, "args": [["q", [0]]]},
+            {"op": {"type": "Z"}, "args": [["q", [1]]]},
+            {"op": {"type": "Tdg"}, "args": [["q", [0]]]},
+            {"op": {"type": "Sdg"}, "args": [["q", [1]]]},
+            {"op": {"type": "CX"}, "args": [["q", [0]], ["q", [1]]]},
+            {"op": {"type": "ZZMax"}, "args": [["q", [1]], ["q", [0]]]},
+            {"op": {"type": "Rz", "params": ["0.5"]}, "args": [["q", [0]]]},
+            {"op": {"type": "Measure"}, "args": [["q", [1]], ["c", [1]]]},
+            {"op": {"type": "Reset"}, "args": [["q", [1]]]}
+        ],
+        "implicit_permutation": []
+    }"#;
+
+    #[test]
+    fn circuit_roundtrip() {
+        let hugr = Hugr::from_circuit_json(CIRCUIT).unwrap();
+        assert_eq!(hugr.node_count(), 16);
+        assert_eq!(
+            hugr.get_optype(hugr.root()).signature().input,
+            type_row![QB, QB, BIT, BIT]
+        );
+
+        let json = hugr.to_circuit_json(hugr.root()).unwrap();
+        let circ: SerialCircuit = serde_json::from_str(&json).unwrap();
+        let expected: SerialCircuit = serde_json::from_str(CIRCUIT).unwrap();
+        assert_eq!(circ, expected);
+        assert_eq!(Hugr::from_circuit_json(&json).unwrap(), hugr);
+    }
+
+    #[test]
+    fn hugr_to_circuit() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![BIT, QB, QB], type_row![BIT, QB, QB])?;
+        let [_, q0, q1] = builder.input_wires_arr();
+        let cx = builder.add_dataflow_op(LeafOp::CX, [q1, q0])?;
+        let [q1, q0] = cx.outputs_arr();
+        let noop = builder.add_dataflow_op(LeafOp::Noop { ty: QB }, [q0])?;
+        let measure = builder.add_dataflow_op(LeafOp::Measure, noop.outputs())?;
+        let [q0, c] = measure.outputs_arr();
+        let hugr = builder.finish_hugr_with_outputs([c, q0, q1])?;
+
+        let circ: SerialCircuit =
+            serde_json::from_str(&hugr.to_circuit_json(hugr.root()).unwrap()).unwrap();
+        let q = |i| Register("q".to_string(), vec![i]);
+        let c = Register("c".to_string(), vec![0]);
+        assert_eq!(circ.qubits, [q(0), q(1)]);
+        assert_eq!(circ.bits, [Register("c".to_string(), vec![0])]);
+        assert_eq!(
+            circ.commands
+                .iter()
+                .map(|cmd| (cmd.op.op_type.as_str(), cmd.args.clone()))
+                .collect::<Vec<_>>(),
+            [("CX", vec![q(1), q(0)]), ("Measure", vec![q(0), c])]
+        );
+
+        // Swapping the qubits at the outputs is not supported.
+        let builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+        let [q0, q1] = builder.input_wires_arr();
+        let hugr = builder.finish_hugr_with_outputs([q1, q0])?;
+        assert_eq!(
+            hugr.to_circuit_json(hugr.root()),
+            Err(CircuitJsonError::MismatchedOutput(Port::new_incoming(0)))
+        );
+
+        // SWAPs exchange the registers of the gates after them.
+        let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB])?;
+        let swap = builder.add_dataflow_op(LeafOp::Swap, builder.input_wires())?;
+        let [q1, q0] = swap.outputs_arr();
+        let h = builder.add_dataflow_op(LeafOp::H, [q1])?;
+        let swap = builder.add_dataflow_op(LeafOp::Swap, [h.out_wire(0), q0])?;
+        let hugr = builder.finish_hugr_with_outputs(swap.outputs())?;
+        let circ: SerialCircuit =
+            serde_json::from_str(&hugr.to_circuit_json(hugr.root()).unwrap()).unwrap();
+        assert_eq!(circ.commands.len(), 1);
+        assert_eq!(circ.commands[0].args, [q(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported() -> Result<(), BuildError> {
+        let mut builder = DFGBuilder::new(type_row![QB], type_row![QB])?;
+        let [q] = builder.input_wires_arr();
+        let nested = builder.dfg_builder(Signature::new_linear(type_row![QB]), [q])?;
+        let nested_inputs = nested.input_wires();
+        let nested = nested.finish_with_outputs(nested_inputs)?;
+        let hugr = builder.finish_hugr_with_outputs(nested.outputs())?;
+        assert_eq!(
+            hugr.to_circuit_json(hugr.root()),
+            Err(CircuitJsonError::UnsupportedNodes(vec![nested.node()]))
+        );
+        assert_eq!(
+            hugr.to_circuit_json(nested.node()),
+            Ok(r#"{"phase":"0.0","commands":[],"qubits":[["q",[0]]],"bits":[],"implicit_permutation":[]}"#.to_string())
+        );
+
+        assert_matches!(
+            Hugr::from_circuit_json(r#"{"commands": [], "qubits": [["q"]]}"#),
+            Err(CircuitJsonError::InvalidJson(_))
+        );
+        let circuit = |command: &str| {
+            format!(r#"{{"commands": [{command}], "qubits": [["q", [0]]], "bits": [["c", [0]]]}}"#)
+        };
+        assert_eq!(
+            Hugr::from_circuit_json(&circuit(r#"{"op": {"type": "CCX"}, "args": []}"#)),
+            Err(CircuitJsonError::UnsupportedOperation {
+                index: 0,
+                op_type: "CCX".to_string()
+            })
+        );
+        assert_eq!(
+            Hugr::from_circuit_json(&circuit(r#"{"op": {"type": "H"}, "args": [["c", [0]]]}"#)),
+            Err(CircuitJsonError::InvalidArguments { index: 0 })
+        );
+        assert_eq!(
+            Hugr::from_circuit_json(&circuit(r#"{"op": {"type": "Rz"}, "args": [["q", [0]]]}"#)),
+            Err(CircuitJsonError::InvalidArguments { index: 0 })
+        );
+        Ok(())
+    }
+}
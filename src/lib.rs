@@ -13,6 +13,7 @@ pub mod algorithm;
 pub mod builder;
 pub mod extensions;
 pub mod hugr;
+pub mod interop;
 pub mod macros;
 pub mod ops;
 pub mod resource;
@@ -8,21 +8,25 @@ pub mod handle;
 pub mod leaf;
 pub mod module;
 pub mod tag;
+pub mod unknown;
 pub mod validate;
 use crate::types::{EdgeKind, Signature, SignatureDescription};
 use crate::{Direction, Port};
 
+use std::mem::{self, Discriminant};
+
 use portgraph::NodeIndex;
 use smol_str::SmolStr;
 
 use enum_dispatch::enum_dispatch;
 
-pub use constant::{Const, ConstValue};
+pub use constant::{Const, ConstRow, ConstValue};
 pub use controlflow::{BasicBlock, Case, Conditional, TailLoop, CFG};
-pub use dataflow::{Call, CallIndirect, Input, LoadConstant, Output, DFG};
+pub use dataflow::{Call, CallIndirect, DataflowParent, Input, LoadConstant, Output, DFG};
 pub use leaf::LeafOp;
 pub use module::{AliasDecl, AliasDefn, FuncDecl, FuncDefn, Module};
 pub use tag::OpTag;
+pub use unknown::UnknownOp;
 
 #[enum_dispatch(OpTrait, OpName, ValidateOp)]
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -38,6 +42,7 @@ pub enum OpType {
     AliasDecl,
     AliasDefn,
     Const,
+    ConstRow,
     Input,
     Output,
     Call,
@@ -50,6 +55,8 @@ pub enum OpType {
     CFG,
     Conditional,
     Case,
+    #[serde(skip)]
+    UnknownOp,
 }
 
 impl Default for OpType {
@@ -62,23 +69,39 @@ impl OpType {
     /// The edge kind for the non-dataflow or constant-input ports of the
     /// operation, not described by the signature.
     ///
-    /// If not None, a single extra multiport of that kind will be present on
-    /// the given direction.
+    /// If not None, the ports following the dataflow ports in the given
+    /// direction are multiports of that kind. There is a single such port
+    /// unless [`OpTrait::non_df_port_count`] specifies a different count. A
+    /// StateOrder port may carry any number of edges.
     pub fn other_port(&self, dir: Direction) -> Option<EdgeKind> {
-        match dir {
+        let kind = match dir {
             Direction::Incoming => self.other_input(),
             Direction::Outgoing => self.other_output(),
-        }
+        };
+        debug_assert!(
+            !matches!(kind, Some(EdgeKind::Value(_))),
+            "Non-dataflow ports cannot carry values"
+        );
+        kind
     }
 
     /// Returns the edge kind for the given port.
+    ///
+    /// Ports past the dataflow ports of the signature all have the kind given
+    /// by [`OpType::other_port`], except for the outputs of a [`ConstRow`]
+    /// which have the type of their value. Connected ports must have the same
+    /// kind on both ends.
     pub fn port_kind(&self, port: impl Into<Port>) -> Option<EdgeKind> {
         let signature = self.signature();
         let port = port.into();
         let dir = port.direction();
-        match port.index() < signature.port_count(dir) {
-            true => signature.get(port),
-            false => self.other_port(dir),
+        let df_count = signature.port_count(dir);
+        match (self, dir) {
+            _ if port.index() < df_count => signature.get(port),
+            (OpType::ConstRow(row), Direction::Outgoing) => {
+                row.static_output(port.index() - df_count)
+            }
+            _ => self.other_port(dir),
         }
     }
 
@@ -87,36 +110,99 @@ impl OpType {
     ///
     /// Returns None if there is no such port, or if the operation defines multiple non-dataflow ports.
     pub fn other_port_index(&self, dir: Direction) -> Option<Port> {
-        let non_df_count = self.validity_flags().non_df_port_count(dir).unwrap_or(1);
-        if self.other_port(dir).is_some() && non_df_count == 1 {
+        if self.other_port(dir).is_some() && self.non_df_port_count(dir) == 1 {
             Some(Port::new(dir, self.signature().port_count(dir)))
         } else {
             None
         }
     }
 
-    /// Returns the number of ports for the given direction.
-    pub fn port_count(&self, dir: Direction) -> usize {
+    /// The port of the value at `row_index` in the dataflow row of the
+    /// signature in the given direction, or `None` if out of bounds.
+    pub fn value_port(&self, row_index: usize, dir: Direction) -> Option<Port> {
+        (row_index < self.signature().df_port_count(dir)).then(|| Port::new(dir, row_index))
+    }
+
+    /// The class of a port with its index among the ports of that class, in
+    /// the port's direction. Returns `None` if the operation has no such port.
+    ///
+    /// The value ports come first, then the static ports, then the other
+    /// ports of [`OpType::other_port`]. Static other ports, such as the output
+    /// of a [`Const`], are numbered after the static ports of the signature.
+    pub fn port_row_index(&self, port: impl Into<Port>) -> Option<PortClass> {
+        let port = port.into();
+        let dir = port.direction();
+        let index = port.index();
+        if index >= self.port_count(dir) {
+            return None;
+        }
         let signature = self.signature();
-        let has_other_ports = self.other_port(dir).is_some();
-        let non_df_count = self
-            .validity_flags()
-            .non_df_port_count(dir)
-            .unwrap_or(has_other_ports as usize);
-        signature.port_count(dir) + non_df_count
+        let values = signature.df_port_count(dir);
+        let others = self.port_count(dir) - self.non_df_port_count(dir);
+        Some(match self.port_kind(port)? {
+            EdgeKind::Value(_) => PortClass::Value(index),
+            EdgeKind::Static(_) => PortClass::Static(index - values),
+            EdgeKind::ControlFlow => PortClass::ControlFlow(index - others),
+            EdgeKind::StateOrder => PortClass::Other(index - others),
+        })
     }
 
-    /// Returns the number of inputs ports for the operation.
-    pub fn input_count(&self) -> usize {
-        self.port_count(Direction::Incoming)
+    /// The signature of the dataflow graph contained in the operation, if its
+    /// children form one. See [`DataflowParent`].
+    ///
+    /// Returns `None` for the exit block of a CFG, which has no children.
+    pub fn inner_signature(&self) -> Option<Signature> {
+        match self {
+            OpType::FuncDefn(op) => Some(op.inner_signature()),
+            OpType::DFG(op) => Some(op.inner_signature()),
+            OpType::Case(op) => Some(op.inner_signature()),
+            OpType::TailLoop(op) => Some(op.inner_signature()),
+            OpType::BasicBlock(op) => op.inner_signature(),
+            _ => None,
+        }
     }
 
-    /// Returns the number of outputs ports for the operation.
-    pub fn output_count(&self) -> usize {
-        self.port_count(Direction::Outgoing)
+    /// A compact identifier for the kind of the operation, see [`OpId`].
+    pub fn op_id(&self) -> OpId {
+        match self {
+            OpType::LeafOp(LeafOp::CustomOp(ext)) => ext.op_id(),
+            OpType::LeafOp(leaf) => OpId::Leaf(mem::discriminant(leaf)),
+            _ => OpId::Op(mem::discriminant(self)),
+        }
     }
 }
 
+/// The class of a port of an operation, with its index among the ports of
+/// that class in its direction. See [`OpType::port_row_index`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PortClass {
+    /// A dataflow value, indexing the row of the signature.
+    Value(usize),
+    /// A static input or output.
+    Static(usize),
+    /// A state order port.
+    Other(usize),
+    /// A control flow port of a basic block.
+    ControlFlow(usize),
+}
+
+/// A compact identifier for the kind of an operation, ignoring its
+/// parameters.
+///
+/// Cheap to copy, compare and hash, so it can be used as a key when
+/// dispatching on operations instead of their names. Custom operations are
+/// identified by their qualified name, with ids assigned on first use. The ids
+/// are stable within a process but must not be persisted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpId {
+    /// A built-in operation other than a leaf operation.
+    Op(Discriminant<OpType>),
+    /// A built-in leaf operation.
+    Leaf(Discriminant<LeafOp>),
+    /// An operation defined by a resource.
+    Custom(u32),
+}
+
 /// Macro used by operations that want their
 /// name to be the same as their type name
 macro_rules! impl_op_name {
@@ -173,8 +259,9 @@ pub trait OpTrait {
     /// The edge kind for the non-dataflow or constant inputs of the operation,
     /// not described by the signature.
     ///
-    /// If not None, a single extra output multiport of that kind will be
-    /// present.
+    /// If not None, a single extra input multiport of that kind will be
+    /// present, unless [`OpTrait::non_df_port_count`] specifies a different
+    /// count. This is never a [`EdgeKind::Value`].
     fn other_input(&self) -> Option<EdgeKind> {
         None
     }
@@ -183,10 +270,60 @@ pub trait OpTrait {
     /// described by the signature.
     ///
     /// If not None, a single extra output multiport of that kind will be
-    /// present.
+    /// present, unless [`OpTrait::non_df_port_count`] specifies a different
+    /// count. This is never a [`EdgeKind::Value`].
     fn other_output(&self) -> Option<EdgeKind> {
         None
     }
+
+    /// The number of non-dataflow ports of the operation in the given
+    /// direction, following the ports of its signature.
+    ///
+    /// Defaults to a single port if the operation has
+    /// [`OpTrait::other_input`] or [`OpTrait::other_output`] edges in that
+    /// direction, and none otherwise.
+    fn non_df_port_count(&self, dir: Direction) -> usize {
+        let other = match dir {
+            Direction::Incoming => self.other_input(),
+            Direction::Outgoing => self.other_output(),
+        };
+        other.is_some() as usize
+    }
+
+    /// Returns the number of ports for the given direction.
+    ///
+    /// This is the number of ports created for the operation when it is
+    /// added to a HUGR, and the number validation expects it to have.
+    fn port_count(&self, dir: Direction) -> usize {
+        self.signature().port_count(dir) + self.non_df_port_count(dir)
+    }
+
+    /// Returns the number of inputs ports for the operation.
+    fn input_count(&self) -> usize {
+        self.port_count(Direction::Incoming)
+    }
+
+    /// Returns the number of outputs ports for the operation.
+    fn output_count(&self) -> usize {
+        self.port_count(Direction::Outgoing)
+    }
+
+    /// Whether the operation may consume a different number of linear values
+    /// than it produces, e.g. by allocating or discarding qubits.
+    ///
+    /// Other operations are expected to conserve their linear values, see
+    /// [`crate::algorithm::linear_balance`].
+    fn creates_or_destroys_linear(&self) -> bool {
+        false
+    }
+
+    /// Compute the outputs of the operation when all its inputs are
+    /// constants, if it can be evaluated at compile time.
+    ///
+    /// Used by [`crate::algorithm::const_fold`].
+    fn const_fold(&self, _consts: &[ConstValue]) -> Option<Vec<ConstValue>> {
+        None
+    }
 }
 
 #[enum_dispatch]
@@ -216,3 +353,99 @@ macro_rules! impl_validate_op {
 }
 
 use impl_validate_op;
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::ops::dataflow::IOTrait;
+    use crate::type_row;
+    use crate::types::{ClassicType, LinearType, SimpleType};
+
+    const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    #[test]
+    fn builtin_op_ids() {
+        let ops: Vec<OpType> = vec![
+            Module.into(),
+            Input::new(type_row![QB]).into(),
+            Output::new(type_row![QB]).into(),
+            LeafOp::H.into(),
+            LeafOp::T.into(),
+            LeafOp::Noop { ty: QB }.into(),
+        ];
+        let ids: HashSet<OpId> = ops.iter().map(OpType::op_id).collect();
+        assert_eq!(ids.len(), ops.len());
+
+        // Parameters of the operation are ignored.
+        let other_input: OpType = Input::new(type_row![QB, QB]).into();
+        assert_eq!(other_input.op_id(), ops[1].op_id());
+    }
+
+    /// Check that the ports of `op` in direction `dir` have the given
+    /// classes, and that the value ports round-trip.
+    fn check_port_classes(op: &OpType, dir: Direction, classes: &[PortClass]) {
+        for (index, &class) in classes.iter().enumerate() {
+            let port = Port::new(dir, index);
+            assert_eq!(op.port_row_index(port), Some(class));
+            if let PortClass::Value(row_index) = class {
+                assert_eq!(op.value_port(row_index, dir), Some(port));
+            }
+        }
+        let values = classes
+            .iter()
+            .filter(|c| matches!(c, PortClass::Value(_)))
+            .count();
+        assert_eq!(op.value_port(values, dir), None);
+        assert_eq!(op.port_row_index(Port::new(dir, classes.len())), None);
+    }
+
+    #[test]
+    fn call_port_classes() {
+        let call: OpType = Call {
+            signature: Signature::new_df(type_row![QB, NAT], type_row![NAT]),
+        }
+        .into();
+        check_port_classes(
+            &call,
+            Direction::Incoming,
+            &[
+                PortClass::Value(0),
+                PortClass::Value(1),
+                PortClass::Static(0),
+                PortClass::Other(0),
+            ],
+        );
+        check_port_classes(
+            &call,
+            Direction::Outgoing,
+            &[PortClass::Value(0), PortClass::Other(0)],
+        );
+    }
+
+    #[test]
+    fn block_port_classes() {
+        let block: OpType = BasicBlock::DFB {
+            inputs: type_row![NAT],
+            other_outputs: type_row![NAT],
+            predicate_variants: vec![type_row![], type_row![], type_row![]],
+        }
+        .into();
+        check_port_classes(&block, Direction::Incoming, &[PortClass::ControlFlow(0)]);
+        check_port_classes(
+            &block,
+            Direction::Outgoing,
+            &[
+                PortClass::ControlFlow(0),
+                PortClass::ControlFlow(1),
+                PortClass::ControlFlow(2),
+            ],
+        );
+
+        // The static output of a constant follows its (absent) values.
+        let cst: OpType = Const(ConstValue::i64(3)).into();
+        check_port_classes(&cst, Direction::Outgoing, &[PortClass::Static(0)]);
+    }
+}
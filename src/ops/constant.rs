@@ -1,11 +1,16 @@
 //! Constant value definitions.
 
 use std::any::Any;
+use std::hash::{Hash, Hasher};
 
 use crate::{
     macros::impl_box_clone,
     type_row,
-    types::{ClassicType, Container, EdgeKind, SimpleType, TypeRow},
+    types::{
+        angle::{self, AngleValue},
+        ClassicType, Container, EdgeKind, SimpleType, TypeRow,
+    },
+    Direction,
 };
 
 use downcast_rs::{impl_downcast, Downcast};
@@ -39,6 +44,70 @@ impl OpTrait for Const {
     }
 }
 
+/// A row of constant values, with a static output port for each value.
+///
+/// Avoids loading and unpacking a [`ConstValue::Tuple`] when its elements are
+/// used separately, as each [`LoadConstant`] can be connected to the port of
+/// a single element.
+///
+/// [`LoadConstant`]: super::LoadConstant
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(from = "SerConstRow", into = "SerConstRow")]
+pub struct ConstRow(pub Vec<ConstValue>);
+
+/// Serialized form of [`ConstRow`], as operations are serialized as maps.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct SerConstRow {
+    values: Vec<ConstValue>,
+}
+
+impl From<SerConstRow> for ConstRow {
+    fn from(row: SerConstRow) -> Self {
+        Self(row.values)
+    }
+}
+
+impl From<ConstRow> for SerConstRow {
+    fn from(row: ConstRow) -> Self {
+        Self { values: row.0 }
+    }
+}
+
+impl ConstRow {
+    /// The kind of the static output port of the value at `offset`.
+    pub fn static_output(&self, offset: usize) -> Option<EdgeKind> {
+        let value = self.0.get(offset)?;
+        Some(EdgeKind::Static(value.const_type()))
+    }
+}
+
+impl OpName for ConstRow {
+    fn name(&self) -> SmolStr {
+        let names: Vec<_> = self.0.iter().map(|v| v.name()).collect();
+        format!("const_row:[{}]", names.join(", ")).into()
+    }
+}
+impl StaticTag for ConstRow {
+    const TAG: OpTag = OpTag::Const;
+}
+impl OpTrait for ConstRow {
+    fn description(&self) -> &str {
+        "Row of constant values"
+    }
+
+    fn tag(&self) -> OpTag {
+        <Self as StaticTag>::TAG
+    }
+
+    /// A static output port for each value.
+    fn non_df_port_count(&self, dir: Direction) -> usize {
+        match dir {
+            Direction::Incoming => 0,
+            Direction::Outgoing => self.0.len(),
+        }
+    }
+}
+
 pub(crate) type HugrIntValueStore = u128;
 pub(crate) type HugrIntWidthStore = u8;
 pub(crate) const HUGR_MAX_INT_WIDTH: HugrIntWidthStore =
@@ -46,6 +115,11 @@ pub(crate) const HUGR_MAX_INT_WIDTH: HugrIntWidthStore =
 
 /// Value constants
 ///
+/// Constants implement [`Eq`] and [`Hash`], so they can be used as keys when
+/// deduplicating or memoising. Floats are compared bitwise, so `NaN`s with
+/// different payloads (and `0.0` and `-0.0`) are distinct values. Opaque
+/// values are hashed via their serialized payload.
+///
 /// TODO: Add more constants
 /// TODO: bigger/smaller integers.
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -59,6 +133,8 @@ pub enum ConstValue {
     },
     /// Double precision float
     F64(f64),
+    /// A rotation angle, of type [`angle::ANGLE`].
+    Angle(AngleValue),
     /// A constant specifying a variant of a Sum type.
     Sum {
         tag: usize,
@@ -95,7 +171,11 @@ impl PartialEq for ConstValue {
             ) => tag == t1 && variants == type1 && val == v1,
 
             (Self::Tuple(v1), Self::Tuple(v2)) => v1.eq(v2),
-            (Self::F64(f1), Self::F64(f2)) => f1 == f2,
+            (Self::F64(f1), Self::F64(f2)) => f1.to_bits() == f2.to_bits(),
+            (Self::Angle(AngleValue::F64(f1)), Self::Angle(AngleValue::F64(f2))) => {
+                f1.to_bits() == f2.to_bits()
+            }
+            (Self::Angle(a1), Self::Angle(a2)) => a1 == a2,
 
             _ => false,
         }
@@ -104,6 +184,37 @@ impl PartialEq for ConstValue {
 
 impl Eq for ConstValue {}
 
+impl Hash for ConstValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Int { value, width } => {
+                value.hash(state);
+                width.hash(state);
+            }
+            Self::F64(f) => f.to_bits().hash(state),
+            Self::Angle(a) => {
+                std::mem::discriminant(a).hash(state);
+                match a {
+                    AngleValue::F64(f) => f.to_bits().hash(state),
+                    AngleValue::Rational(r) => r.hash(state),
+                }
+            }
+            // The variant types are not hashed, values differing only in them
+            // will collide but still compare unequal.
+            Self::Sum { tag, val, .. } => {
+                tag.hash(state);
+                val.hash(state);
+            }
+            Self::Tuple(vals) => vals.hash(state),
+            Self::Opaque(_, v) => match serde_json::to_vec(v) {
+                Ok(bytes) => bytes.hash(state),
+                Err(_) => v.name().hash(state),
+            },
+        }
+    }
+}
+
 impl Default for ConstValue {
     fn default() -> Self {
         Self::Int {
@@ -130,6 +241,7 @@ impl ConstValue {
                 ClassicType::Container(Container::Tuple(Box::new(row.into())))
             }
             Self::F64(_) => ClassicType::F64,
+            Self::Angle(_) => angle::ANGLE,
         }
     }
     /// Unique name of the constant.
@@ -137,6 +249,8 @@ impl ConstValue {
         match self {
             Self::Int { value, width } => format!("const:int<{width}>:{value}"),
             Self::F64(f) => format!("const:float:{f}"),
+            Self::Angle(AngleValue::F64(f)) => format!("const:angle:{f}π"),
+            Self::Angle(AngleValue::Rational(r)) => format!("const:angle:{}π", r.0),
             Self::Opaque(_, v) => format!("const:{}", v.name()),
             Self::Sum { tag, val, .. } => {
                 format!("const:sum:{{tag:{tag}, val:{}}}", val.name())
@@ -230,6 +344,9 @@ pub trait CustomConst:
     fn const_type(&self) -> ClassicType;
 
     /// Compare two constants for equality, using downcasting and comparing the definitions.
+    ///
+    /// Constants that compare equal must have the same serialized payload, as
+    /// that is used to hash them.
     fn eq(&self, other: &dyn CustomConst) -> bool {
         let _ = other;
         false
@@ -241,12 +358,15 @@ impl_box_clone!(CustomConst, CustomConstBoxClone);
 
 #[cfg(test)]
 mod test {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
     use super::ConstValue;
     use crate::{
         builder::{BuildError, Container, DFGBuilder, Dataflow, DataflowHugr},
         hugr::{typecheck::ConstTypeError, ValidationError},
         type_row,
-        types::{ClassicType, SimpleType, TypeRow},
+        types::{angle::AngleValue, ClassicType, SimpleType, TypeRow},
     };
 
     #[test]
@@ -310,4 +430,67 @@ mod test {
             )))
         );
     }
+
+    fn hash_of(val: &ConstValue) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_hash_consistent_with_eq() {
+        let pred_rows = vec![
+            type_row![SimpleType::Classic(ClassicType::i64())],
+            type_row![],
+        ];
+        let values = || {
+            vec![
+                ConstValue::i64(3),
+                ConstValue::i64(4),
+                ConstValue::Int {
+                    value: 3,
+                    width: 32,
+                },
+                ConstValue::F64(1.5),
+                ConstValue::F64(f64::NAN),
+                ConstValue::F64(0.0),
+                ConstValue::F64(-0.0),
+                ConstValue::Angle(AngleValue::rational(1, 2).unwrap()),
+                ConstValue::Angle(AngleValue::F64(0.5)),
+                ConstValue::Angle(AngleValue::F64(f64::NAN)),
+                ConstValue::unit(),
+                ConstValue::true_val(),
+                ConstValue::false_val(),
+                ConstValue::Tuple(vec![ConstValue::i64(3), ConstValue::F64(1.5)]),
+                ConstValue::Tuple(vec![ConstValue::Tuple(vec![ConstValue::true_val()])]),
+                ConstValue::predicate(
+                    0,
+                    ConstValue::Tuple(vec![ConstValue::i64(3)]),
+                    pred_rows.clone(),
+                ),
+                ConstValue::predicate(1, ConstValue::unit(), pred_rows.clone()),
+                crate::extensions::rotation::Constant::Quaternion(cgmath::Quaternion::new(
+                    1.0, 0.0, 0.0, 0.0,
+                ))
+                .into(),
+            ]
+        };
+
+        for (i, a) in values().iter().enumerate() {
+            for (j, b) in values().iter().enumerate() {
+                if a == b {
+                    assert_eq!(hash_of(a), hash_of(b), "{a:?} == {b:?}");
+                }
+                if i == j && !matches!(a, ConstValue::Opaque(..)) {
+                    assert_eq!(a, b);
+                } else if i != j {
+                    assert_ne!(a, b);
+                }
+            }
+        }
+
+        let set: std::collections::HashSet<_> = values().into_iter().chain(values()).collect();
+        // Opaque constants without an `eq` implementation never compare equal.
+        assert_eq!(set.len(), values().len() + 1);
+    }
 }
@@ -3,8 +3,9 @@
 use smol_str::SmolStr;
 
 use crate::types::{EdgeKind, Signature, SimpleType, TypeRow};
+use crate::Direction;
 
-use super::dataflow::DataflowOpTrait;
+use super::dataflow::{DataflowOpTrait, DataflowParent};
 use super::OpTag;
 use super::{impl_op_name, OpName, OpTrait, StaticTag};
 
@@ -38,6 +39,12 @@ impl DataflowOpTrait for TailLoop {
     }
 }
 
+impl DataflowParent for TailLoop {
+    fn inner_signature(&self) -> Signature {
+        Signature::new_df(self.body_input_row(), self.body_output_row())
+    }
+}
+
 impl TailLoop {
     /// Build the output TypeRow of the child graph of a TailLoop node.
     pub(crate) fn body_output_row(&self) -> TypeRow {
@@ -57,6 +64,14 @@ impl TailLoop {
 }
 
 /// Conditional operation, defined by child `Case` nodes for each branch.
+///
+/// The predicate input is always a Sum of tuples. A bit is not accepted as a
+/// predicate, so that there is a single representation of two-way branches;
+/// it must be converted with a [`LeafOp::BitToPredicate`] operation, which
+/// [`Dataflow::conditional_builder`] inserts when given a bit wire.
+///
+/// [`LeafOp::BitToPredicate`]: super::LeafOp::BitToPredicate
+/// [`Dataflow::conditional_builder`]: crate::builder::Dataflow::conditional_builder
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Conditional {
     /// The possible rows of the predicate input
@@ -77,15 +92,17 @@ impl DataflowOpTrait for Conditional {
 
     fn signature(&self) -> Signature {
         let mut inputs = self.other_inputs.clone();
-        inputs.to_mut().insert(
-            0,
-            SimpleType::new_predicate(self.predicate_inputs.clone().into_iter()),
-        );
+        inputs.to_mut().insert(0, self.predicate_type());
         Signature::new_df(inputs, self.outputs.clone())
     }
 }
 
 impl Conditional {
+    /// The type of the predicate input, selecting the case to execute.
+    pub fn predicate_type(&self) -> SimpleType {
+        SimpleType::new_predicate(self.predicate_inputs.iter().cloned())
+    }
+
     /// Build the input TypeRow of the nth child graph of a Conditional node.
     pub(crate) fn case_input_row(&self, case: usize) -> Option<TypeRow> {
         let mut inputs = self.predicate_inputs.get(case)?.clone();
@@ -170,9 +187,37 @@ impl OpTrait for BasicBlock {
     fn other_output(&self) -> Option<EdgeKind> {
         Some(EdgeKind::ControlFlow)
     }
+
+    /// A single predecessor port, and an outgoing port for each successor.
+    fn non_df_port_count(&self, dir: Direction) -> usize {
+        match dir {
+            Direction::Incoming => 1,
+            Direction::Outgoing => self.num_successors(),
+        }
+    }
 }
 
 impl BasicBlock {
+    /// The signature of the contained dataflow graph, which outputs the
+    /// predicate of the block followed by its other outputs.
+    ///
+    /// Returns `None` for exit blocks, which have no children.
+    pub fn inner_signature(&self) -> Option<Signature> {
+        match self {
+            BasicBlock::DFB {
+                inputs,
+                other_outputs,
+                predicate_variants,
+            } => {
+                let predicate = SimpleType::new_predicate(predicate_variants.clone());
+                let mut outputs = vec![predicate];
+                outputs.extend_from_slice(other_outputs);
+                Some(Signature::new_df(inputs.clone(), outputs))
+            }
+            BasicBlock::Exit { .. } => None,
+        }
+    }
+
     /// The input signature of the contained dataflow graph.
     pub fn dataflow_input(&self) -> &TypeRow {
         match self {
@@ -181,6 +226,17 @@ impl BasicBlock {
         }
     }
 
+    /// The number of successors of the block, one for each variant of its
+    /// predicate. Exit blocks have no successors.
+    pub fn num_successors(&self) -> usize {
+        match self {
+            BasicBlock::DFB {
+                predicate_variants, ..
+            } => predicate_variants.len(),
+            BasicBlock::Exit { .. } => 0,
+        }
+    }
+
     /// The correct inputs of any successors. Returns None if successor is not a
     /// valid index.
     pub fn successor_input(&self, successor: usize) -> Option<TypeRow> {
@@ -202,6 +258,12 @@ impl BasicBlock {
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 /// Case ops - nodes valid inside Conditional nodes.
 pub struct Case {
+    /// The index of the predicate variant selecting this case. It must match
+    /// the position of the case among the children of its Conditional.
+    ///
+    /// Cases in HUGRs serialized without tags are given their position when
+    /// loaded.
+    pub tag: usize,
     /// The signature of the contained dataflow graph.
     pub signature: Signature,
 }
@@ -222,7 +284,18 @@ impl OpTrait for Case {
     }
 }
 
+impl DataflowParent for Case {
+    fn inner_signature(&self) -> Signature {
+        self.signature.clone()
+    }
+}
+
 impl Case {
+    /// The index of the predicate variant selecting this case.
+    pub fn tag(&self) -> usize {
+        self.tag
+    }
+
     /// The input signature of the contained dataflow graph.
     pub fn dataflow_input(&self) -> &TypeRow {
         &self.signature.input
@@ -1,25 +1,30 @@
 //! Extensible operations.
 
+use lazy_static::lazy_static;
 use smol_str::SmolStr;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use thiserror::Error;
 
 use crate::hugr::{HugrMut, HugrView};
-use crate::resource::{OpDef, ResourceId, ResourceSet, SignatureError};
+use crate::resource::{
+    OpDef, ResourceId, ResourceRegistry, ResourceSet, ResourceVersion, SignatureError,
+};
 use crate::types::{type_param::TypeArg, Signature, SignatureDescription};
-use crate::{Hugr, Node, Resource};
+use crate::{Hugr, Node};
 
 use super::tag::OpTag;
-use super::{LeafOp, OpName, OpTrait, OpType};
+use super::{ConstValue, LeafOp, OpId, OpName, OpTrait, OpType};
 
 /// An instantiation of an operation (declared by a resource) with values for the type arguments
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(into = "OpaqueOp", from = "OpaqueOp")]
 pub enum ExternalOp {
-    /// When we've found (loaded) the [Resource] definition and identified the [OpDef]
+    /// When we've found (loaded) the [Resource](crate::resource::Resource)
+    /// definition and identified the [OpDef]
     Resource(ResourceOp),
-    /// When we either haven't tried to identify the [Resource] or failed to find it.
+    /// When we either haven't tried to identify the
+    /// [Resource](crate::resource::Resource) or failed to find it.
     Opaque(OpaqueOp),
 }
 
@@ -38,6 +43,44 @@ impl From<OpaqueOp> for ExternalOp {
     }
 }
 
+impl ExternalOp {
+    /// The definition of the operation, taken from the operation itself if
+    /// it has been resolved, or looked up in `resource_registry` otherwise.
+    pub fn op_def<'a>(&'a self, resource_registry: &'a ResourceRegistry) -> Option<&'a OpDef> {
+        match self {
+            Self::Opaque(op) => resource_registry
+                .get(&op.resource)?
+                .operations()
+                .get(&op.op_name)
+                .map(AsRef::as_ref),
+            Self::Resource(ResourceOp { def, .. }) => Some(def),
+        }
+    }
+
+    /// The type arguments of the operation.
+    pub fn args(&self) -> &[TypeArg] {
+        match self {
+            Self::Opaque(op) => &op.args,
+            Self::Resource(op) => &op.args,
+        }
+    }
+
+    /// The [`OpId`] of the operation, shared by all the operations with the
+    /// same qualified name whether they have been resolved or not.
+    pub fn op_id(&self) -> OpId {
+        lazy_static! {
+            static ref CUSTOM_OP_IDS: RwLock<HashMap<SmolStr, u32>> = RwLock::default();
+        }
+        let name = self.name();
+        if let Some(&id) = CUSTOM_OP_IDS.read().unwrap().get(&name) {
+            return OpId::Custom(id);
+        }
+        let mut ids = CUSTOM_OP_IDS.write().unwrap();
+        let next_id = ids.len() as u32;
+        OpId::Custom(*ids.entry(name).or_insert(next_id))
+    }
+}
+
 impl From<ExternalOp> for LeafOp {
     fn from(value: ExternalOp) -> Self {
         LeafOp::CustomOp(value)
@@ -75,15 +118,39 @@ impl OpTrait for ExternalOp {
 
     /// Note the case of an OpaqueOp without a signature should already
     /// have been detected in [resolve_extension_ops]
+    ///
+    /// The resource defining the operation is always required by both its
+    /// inputs and outputs.
     fn signature(&self) -> Signature {
         match self {
-            Self::Opaque(op) => op.signature.clone().unwrap(),
+            Self::Opaque(op) => {
+                let mut sig = op.signature.clone().unwrap();
+                sig.input_resources.insert(&op.resource);
+                sig.output_resources.insert(&op.resource);
+                sig
+            }
             Self::Resource(ResourceOp { signature, .. }) => signature.clone(),
         }
     }
+
+    fn creates_or_destroys_linear(&self) -> bool {
+        match self {
+            Self::Opaque(op) => op.creates_or_destroys_linear,
+            Self::Resource(ResourceOp { def, .. }) => def.creates_or_destroys_linear,
+        }
+    }
+
+    /// Only resolved operations can be folded, using the constant folder of
+    /// their [OpDef].
+    fn const_fold(&self, consts: &[ConstValue]) -> Option<Vec<ConstValue>> {
+        match self {
+            Self::Opaque(_) => None,
+            Self::Resource(ResourceOp { def, .. }) => def.constant_fold(consts),
+        }
+    }
 }
 
-/// An operation defined by an [OpDef] from a loaded [Resource].
+/// An operation defined by an [OpDef] from a loaded [Resource](crate::resource::Resource).
 // Note *not* Serializable: container (ExternalOp) is serialized as an OpaqueOp instead.
 #[derive(Clone, Debug)]
 pub struct ResourceOp {
@@ -122,10 +189,12 @@ impl From<ResourceOp> for OpaqueOp {
         };
         OpaqueOp {
             resource: def.resource.clone(),
+            version: def.resource_version,
             op_name: def.name.clone(),
             description: def.description.clone(),
             args,
             signature: opt_sig,
+            creates_or_destroys_linear: def.creates_or_destroys_linear,
         }
     }
 }
@@ -142,10 +211,17 @@ impl Eq for ResourceOp {}
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct OpaqueOp {
     resource: ResourceId,
+    /// The version of the resource the operation was created against.
+    #[serde(default)]
+    version: ResourceVersion,
     op_name: SmolStr,
     description: String, // cache in advance so description() can return &str
     args: Vec<TypeArg>,
     signature: Option<Signature>,
+    /// Cached from the [`OpDef`], as it is needed before the operation is
+    /// resolved.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    creates_or_destroys_linear: bool,
 }
 
 fn qualify_name(res_id: &ResourceId, op_name: &SmolStr) -> SmolStr {
@@ -163,24 +239,62 @@ impl OpaqueOp {
     ) -> Self {
         Self {
             resource,
+            version: ResourceVersion::default(),
             op_name: op_name.into(),
             description,
             args: args.into(),
             signature,
+            creates_or_destroys_linear: false,
         }
     }
+
+    /// The resource defining the operation.
+    pub fn resource(&self) -> &ResourceId {
+        &self.resource
+    }
+
+    /// Change the resource defining the operation, e.g. after it has been
+    /// renamed.
+    pub fn set_resource(&mut self, resource: ResourceId) {
+        self.resource = resource;
+    }
+
+    /// The version of the resource the operation was created against.
+    pub fn version(&self) -> ResourceVersion {
+        self.version
+    }
+
+    /// Record the version of the resource the operation is created against.
+    pub fn with_version(mut self, version: ResourceVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Declare that the operation may allocate or discard linear values, as
+    /// its [`OpDef`] does. See [`OpTrait::creates_or_destroys_linear`].
+    pub fn with_creates_or_destroys_linear(mut self, flag: bool) -> Self {
+        self.creates_or_destroys_linear = flag;
+        self
+    }
 }
 
 /// Resolve serialized names of operations into concrete implementation (OpDefs) where possible
 #[allow(dead_code)]
 pub fn resolve_extension_ops(
     h: &mut Hugr,
-    resource_registry: &HashMap<SmolStr, Resource>,
+    resource_registry: &ResourceRegistry,
 ) -> Result<(), CustomOpError> {
     let mut replacements = Vec::new();
     for n in h.nodes() {
         if let OpType::LeafOp(LeafOp::CustomOp(op @ ExternalOp::Opaque(opaque))) = h.get_optype(n) {
             if let Some(r) = resource_registry.get(&opaque.resource) {
+                if !r.version().is_compatible_with(&opaque.version) {
+                    return Err(CustomOpError::VersionMismatch(
+                        op.name(),
+                        opaque.version,
+                        r.version(),
+                    ));
+                }
                 // Fail if the Resource was found but did not have the expected operation
                 let Some(def) = r.operations().get(&opaque.op_name) else {
                     return Err(CustomOpError::OpNotFoundInResource(opaque.op_name.to_string(), r.name().to_string()));
@@ -189,12 +303,14 @@ pub fn resolve_extension_ops(
                 let op = ExternalOp::Resource(
                     ResourceOp::new(def.clone(), &opaque.args, &ResourceSet::default()).unwrap(),
                 );
-                if let Some(sig) = &opaque.signature {
-                    if sig != &op.signature() {
+                if opaque.signature.is_some() {
+                    // Compare the signatures including the implied resource requirements.
+                    let sig = ExternalOp::Opaque(opaque.clone()).signature();
+                    if sig != op.signature() {
                         return Err(CustomOpError::SignatureMismatch(
                             def.name.to_string(),
                             op.signature(),
-                            sig.clone(),
+                            sig,
                         ));
                     };
                 };
@@ -205,8 +321,9 @@ pub fn resolve_extension_ops(
         }
     }
     // Only now can we perform the replacements as the 'for' loop was borrowing 'h' preventing use from using it mutably
+    // Leaf operations are replaced with leaf operations, so the children are unaffected.
     for (n, op) in replacements {
-        h.replace_op(n, Into::<LeafOp>::into(op));
+        h.replace_op_unchecked(n, Into::<LeafOp>::into(op));
     }
     Ok(())
 }
@@ -224,4 +341,141 @@ pub enum CustomOpError {
     /// Resource and OpDef found, but computed signature did not match stored
     #[error("Resolved {0} to a concrete implementation which computed a conflicting signature: {1:?} vs stored {2:?}")]
     SignatureMismatch(String, Signature, Signature),
+    /// The Resource was found with a version incompatible with the stored one
+    #[error("Operation {0} was created against version {1} of its Resource, incompatible with the available version {2}")]
+    VersionMismatch(SmolStr, ResourceVersion, ResourceVersion),
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::resource::{CustomSignatureFunc, Resource};
+    use crate::type_row;
+    use crate::types::TypeRow;
+
+    /// A signature with no inputs or outputs, requiring the `ids` resource.
+    struct EmptySig;
+
+    impl CustomSignatureFunc for EmptySig {
+        fn compute_signature(
+            &self,
+            _name: &SmolStr,
+            _arg_values: &[TypeArg],
+            _misc: &HashMap<String, serde_yaml::Value>,
+        ) -> Result<(TypeRow, TypeRow, ResourceSet), SignatureError> {
+            Ok((
+                type_row![],
+                type_row![],
+                ResourceSet::singleton(&"ids".into()),
+            ))
+        }
+    }
+
+    #[test]
+    fn custom_op_ids() {
+        let resource_id: ResourceId = "ids".into();
+        let opaque = |name: &str| {
+            let sig = Signature::new_df(type_row![], type_row![]);
+            ExternalOp::from(OpaqueOp::new(
+                resource_id.clone(),
+                name,
+                String::new(),
+                vec![],
+                Some(sig),
+            ))
+        };
+        let mut resource = Resource::new(resource_id.clone());
+        let def = OpDef::new_with_custom_sig(
+            "op".into(),
+            String::new(),
+            vec![],
+            HashMap::new(),
+            EmptySig,
+        );
+        resource.add_op(def).unwrap();
+        let registry = ResourceRegistry::from([(resource_id.clone(), resource)]);
+
+        let mut hugr = Hugr::new(LeafOp::from(opaque("op")));
+        let id = hugr.get_optype(hugr.root()).op_id();
+        assert_eq!(id, opaque("op").op_id());
+        assert_ne!(id, opaque("other").op_id());
+        assert_ne!(id, OpType::from(LeafOp::H).op_id());
+
+        resolve_extension_ops(&mut hugr, &registry).unwrap();
+        let resolved = hugr.get_optype(hugr.root());
+        assert!(matches!(
+            resolved,
+            OpType::LeafOp(LeafOp::CustomOp(ExternalOp::Resource(_)))
+        ));
+        assert_eq!(resolved.op_id(), id);
+    }
+
+    #[test]
+    fn resource_versions() {
+        let resource_id: ResourceId = "ids".into();
+        let sig = Signature::new_df(type_row![], type_row![]);
+        let hugr_with = |version: ResourceVersion| {
+            let op = OpaqueOp::new(
+                resource_id.clone(),
+                "op",
+                String::new(),
+                vec![],
+                Some(sig.clone()),
+            )
+            .with_version(version);
+            Hugr::new(LeafOp::from(ExternalOp::from(op)))
+        };
+        let registry_with = |version: ResourceVersion| {
+            let mut resource = Resource::new_versioned(resource_id.clone(), version);
+            let def = OpDef::new_with_custom_sig(
+                "op".into(),
+                String::new(),
+                vec![],
+                HashMap::new(),
+                EmptySig,
+            );
+            resource.add_op(def).unwrap();
+            ResourceRegistry::from([(resource_id.clone(), resource)])
+        };
+        let resolve = |stored, available| {
+            resolve_extension_ops(&mut hugr_with(stored), &registry_with(available))
+        };
+
+        assert!(resolve(ResourceVersion(1, 2, 0), ResourceVersion(1, 2, 0)).is_ok());
+        assert!(resolve(ResourceVersion(1, 2, 3), ResourceVersion(1, 4, 0)).is_ok());
+        assert!(resolve(ResourceVersion::default(), ResourceVersion(2, 0, 0)).is_ok());
+        for (stored, available) in [
+            (ResourceVersion(1, 2, 0), ResourceVersion(1, 1, 9)),
+            (ResourceVersion(1, 2, 0), ResourceVersion(2, 2, 0)),
+        ] {
+            assert_matches!(
+                resolve(stored, available),
+                Err(CustomOpError::VersionMismatch(name, s, a)) => {
+                    assert_eq!(name, "ids.op");
+                    assert_eq!((s, a), (stored, available));
+                }
+            );
+        }
+
+        // Resolved operations are saved with the version of their resource.
+        let mut hugr = hugr_with(ResourceVersion(1, 0, 0));
+        resolve_extension_ops(&mut hugr, &registry_with(ResourceVersion(1, 3, 0))).unwrap();
+        let json = serde_json::to_value(hugr.get_optype(hugr.root())).unwrap();
+        let op: OpType = serde_json::from_value(json.clone()).unwrap();
+        let OpType::LeafOp(LeafOp::CustomOp(ExternalOp::Opaque(opaque))) = op else {
+            panic!("Expected an opaque operation, got {op:?}");
+        };
+        assert_eq!(opaque.version(), ResourceVersion(1, 3, 0));
+
+        // Operations saved without a version are unversioned.
+        let mut legacy = json;
+        legacy.as_object_mut().unwrap().remove("version");
+        let op: OpType = serde_json::from_value(legacy).unwrap();
+        let OpType::LeafOp(LeafOp::CustomOp(ExternalOp::Opaque(opaque))) = op else {
+            panic!("Expected an opaque operation, got {op:?}");
+        };
+        assert!(opaque.version().is_unversioned());
+    }
 }
@@ -28,6 +28,14 @@ pub(super) trait DataflowOpTrait {
     }
 }
 
+/// Trait implemented by the operations whose children form a dataflow graph,
+/// bounded by an Input and an Output node.
+pub trait DataflowParent {
+    /// The signature of the contained dataflow graph: the outputs of its Input
+    /// node and the inputs of its Output node.
+    fn inner_signature(&self) -> Signature;
+}
+
 /// Helpers to construct input and output nodes
 pub trait IOTrait {
     /// Construct a new I/O node from a type row with no resource requirements
@@ -43,6 +51,7 @@ pub struct Input {
     /// Input value types
     pub types: TypeRow,
     /// Resources attached to output wires
+    #[serde(default, skip_serializing_if = "ResourceSet::is_empty")]
     pub resources: ResourceSet,
 }
 
@@ -68,6 +77,7 @@ pub struct Output {
     /// Output value types
     pub types: TypeRow,
     /// Resources expected from input wires
+    #[serde(default, skip_serializing_if = "ResourceSet::is_empty")]
     pub resources: ResourceSet,
 }
 
@@ -224,6 +234,15 @@ impl DataflowOpTrait for LoadConstant {
 pub struct DFG {
     /// Signature of DFG node
     pub signature: Signature,
+    /// The resources required by the nested graph beyond those of its inputs.
+    ///
+    /// If set, the output resources of the node are its input resources and
+    /// this delta, and the validation checks that the delta is the one
+    /// introduced by the children. See [`HugrView::region_resource_delta`].
+    ///
+    /// [`HugrView::region_resource_delta`]: crate::hugr::HugrView::region_resource_delta
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_delta: Option<ResourceSet>,
 }
 
 impl_op_name!(DFG);
@@ -235,6 +254,16 @@ impl DataflowOpTrait for DFG {
     }
 
     fn signature(&self) -> Signature {
+        let mut signature = self.signature.clone();
+        if let Some(delta) = &self.resource_delta {
+            signature.output_resources = signature.input_resources.clone().union(delta);
+        }
+        signature
+    }
+}
+
+impl DataflowParent for DFG {
+    fn inner_signature(&self) -> Signature {
         self.signature.clone()
     }
 }
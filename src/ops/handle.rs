@@ -1,7 +1,7 @@
 //! Handles to nodes in HUGR.
 //!
 use crate::types::{ClassicType, Container, LinearType, SimpleType};
-use crate::Node;
+use crate::{Node, Port};
 
 use derive_more::From as DerFrom;
 use smol_str::SmolStr;
@@ -98,14 +98,20 @@ impl<const DEF: bool> AliasID<DEF> {
 }
 
 #[derive(DerFrom, Debug, Clone, PartialEq, Eq)]
-/// Handle to a [Const](crate::ops::OpType::Const) node.
-pub struct ConstID(Node, ClassicType);
+/// Handle to a [Const](crate::ops::OpType::Const) node, or to one of the
+/// values of a [ConstRow](crate::ops::OpType::ConstRow) node.
+pub struct ConstID(Node, ClassicType, usize);
 
 impl ConstID {
     /// Return the type of the constant.
     pub fn const_type(&self) -> ClassicType {
         self.1.clone()
     }
+
+    /// Return the static output port of the constant.
+    pub fn port(&self) -> Port {
+        Port::new_outgoing(self.2)
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, DerFrom, Debug)]
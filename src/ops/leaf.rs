@@ -3,12 +3,13 @@
 use smol_str::SmolStr;
 
 use super::custom::ExternalOp;
-use super::{OpName, OpTag, OpTrait, StaticTag};
+use super::{ConstValue, OpName, OpTag, OpTrait, StaticTag};
 use crate::{
     resource::{ResourceId, ResourceSet},
     type_row,
     types::{
-        ClassicType, EdgeKind, LinearType, Signature, SignatureDescription, SimpleType, TypeRow,
+        angle, ClassicType, EdgeKind, LinearType, Signature, SignatureDescription, SimpleType,
+        TypeRow,
     },
 };
 
@@ -40,6 +41,8 @@ pub enum LeafOp {
     CX,
     /// A maximally entangling ZZ phase gate.
     ZZMax,
+    /// A SWAP gate, exchanging the states of its two qubits.
+    Swap,
     /// A qubit reset operation.
     Reset,
     /// A no-op operation.
@@ -47,12 +50,32 @@ pub enum LeafOp {
         /// The type of edges connecting the Noop.
         ty: SimpleType,
     },
-    /// A qubit measurement operation.
+    /// An operation duplicating a classical value.
+    Copy {
+        /// The number of copies made.
+        n_copies: usize,
+        /// The type of the copied value.
+        ty: ClassicType,
+    },
+    /// A qubit measurement operation, returning the measured qubit and the
+    /// result.
     Measure,
+    /// A qubit measurement operation consuming the qubit, returning only the
+    /// result.
+    MeasureDestructive,
     /// A rotation of a qubit about the Pauli Z axis by an input float angle.
     RzF64,
+    /// A rotation of a qubit about the Pauli Z axis by an input
+    /// [angle].
+    Rz,
     /// A bitwise XOR operation.
     Xor,
+    /// An operation converting a bit into a predicate with two empty
+    /// variants, to branch on its value.
+    BitToPredicate,
+    /// An operation converting a predicate with two empty variants into a
+    /// bit, the inverse of [`LeafOp::BitToPredicate`].
+    PredicateToBit,
     /// An operation that packs all its inputs into a tuple.
     MakeTuple {
         ///Tuple element types.
@@ -104,14 +127,20 @@ impl OpName for LeafOp {
             LeafOp::Sadj => "Sadj",
             LeafOp::CX => "CX",
             LeafOp::ZZMax => "ZZMax",
+            LeafOp::Swap => "Swap",
             LeafOp::Reset => "Reset",
             LeafOp::Noop { ty: _ } => "Noop",
+            LeafOp::Copy { .. } => "Copy",
             LeafOp::Measure => "Measure",
+            LeafOp::MeasureDestructive => "MeasureDestructive",
             LeafOp::Xor => "Xor",
+            LeafOp::BitToPredicate => "BitToPredicate",
+            LeafOp::PredicateToBit => "PredicateToBit",
             LeafOp::MakeTuple { tys: _ } => "MakeTuple",
             LeafOp::UnpackTuple { tys: _ } => "UnpackTuple",
             LeafOp::Tag { .. } => "Tag",
             LeafOp::RzF64 => "RzF64",
+            LeafOp::Rz => "Rz",
             LeafOp::Lift { .. } => "Lift",
         }
         .into()
@@ -137,14 +166,20 @@ impl OpTrait for LeafOp {
             LeafOp::Sadj => "Adjoint S gate",
             LeafOp::CX => "Controlled X gate",
             LeafOp::ZZMax => "Maximally entangling ZZPhase gate",
+            LeafOp::Swap => "SWAP gate",
             LeafOp::Reset => "Qubit reset",
             LeafOp::Noop { ty: _ } => "Noop gate",
+            LeafOp::Copy { .. } => "Copy a classical value",
             LeafOp::Measure => "Qubit measurement gate",
+            LeafOp::MeasureDestructive => "Destructive qubit measurement",
             LeafOp::Xor => "Bitwise XOR",
+            LeafOp::BitToPredicate => "Convert a bit into a predicate",
+            LeafOp::PredicateToBit => "Convert a predicate into a bit",
             LeafOp::MakeTuple { tys: _ } => "MakeTuple operation",
             LeafOp::UnpackTuple { tys: _ } => "UnpackTuple operation",
             LeafOp::Tag { .. } => "Tag Sum operation",
             LeafOp::RzF64 => "Rz rotation.",
+            LeafOp::Rz => "Rz rotation by an angle.",
             LeafOp::Lift { .. } => "Add a resource requirement to an edge",
         }
     }
@@ -160,9 +195,14 @@ impl OpTrait for LeafOp {
         const Q: SimpleType = SimpleType::Linear(LinearType::Qubit);
         const B: SimpleType = SimpleType::Classic(ClassicType::bit());
         const F: SimpleType = SimpleType::Classic(ClassicType::F64);
+        const A: SimpleType = SimpleType::Classic(angle::ANGLE);
 
         match self {
             LeafOp::Noop { ty: typ } => Signature::new_df(vec![typ.clone()], vec![typ.clone()]),
+            LeafOp::Copy { n_copies, ty } => {
+                let ty = SimpleType::Classic(ty.clone());
+                Signature::new_df(vec![ty.clone()], vec![ty; *n_copies])
+            }
             LeafOp::H
             | LeafOp::Reset
             | LeafOp::T
@@ -172,9 +212,16 @@ impl OpTrait for LeafOp {
             | LeafOp::X
             | LeafOp::Y
             | LeafOp::Z => Signature::new_linear(type_row![Q]),
-            LeafOp::CX | LeafOp::ZZMax => Signature::new_linear(type_row![Q, Q]),
+            LeafOp::CX | LeafOp::ZZMax | LeafOp::Swap => Signature::new_linear(type_row![Q, Q]),
             LeafOp::Measure => Signature::new_df(type_row![Q], type_row![Q, B]),
+            LeafOp::MeasureDestructive => Signature::new_df(type_row![Q], type_row![B]),
             LeafOp::Xor => Signature::new_df(type_row![B, B], type_row![B]),
+            LeafOp::BitToPredicate => {
+                Signature::new_df(type_row![B], vec![SimpleType::new_simple_predicate(2)])
+            }
+            LeafOp::PredicateToBit => {
+                Signature::new_df(vec![SimpleType::new_simple_predicate(2)], type_row![B])
+            }
             LeafOp::CustomOp(ext) => ext.signature(),
             LeafOp::MakeTuple { tys: types } => {
                 Signature::new_df(types.clone(), vec![SimpleType::new_tuple(types.clone())])
@@ -182,11 +229,13 @@ impl OpTrait for LeafOp {
             LeafOp::UnpackTuple { tys: types } => {
                 Signature::new_df(vec![SimpleType::new_tuple(types.clone())], types.clone())
             }
-            LeafOp::Tag { tag, variants } => Signature::new_df(
-                vec![variants.get(*tag).expect("Not a valid tag").clone()],
-                vec![SimpleType::new_sum(variants.clone())],
-            ),
+            LeafOp::Tag { tag, variants } => {
+                let sum = SimpleType::new_sum(variants.clone());
+                let variant = sum.as_sum().and_then(|v| v.get(*tag));
+                Signature::new_df(vec![variant.expect("Not a valid tag").clone()], vec![sum])
+            }
             LeafOp::RzF64 => Signature::new_df(type_row![Q, F], type_row![Q]),
+            LeafOp::Rz => Signature::new_df(type_row![Q, A], type_row![Q]),
             LeafOp::Lift {
                 type_row,
                 input_resources,
@@ -216,6 +265,30 @@ impl OpTrait for LeafOp {
     fn other_output(&self) -> Option<EdgeKind> {
         Some(EdgeKind::StateOrder)
     }
+
+    fn creates_or_destroys_linear(&self) -> bool {
+        match self {
+            LeafOp::CustomOp(ext) => ext.creates_or_destroys_linear(),
+            LeafOp::MeasureDestructive => true,
+            _ => false,
+        }
+    }
+
+    fn const_fold(&self, consts: &[ConstValue]) -> Option<Vec<ConstValue>> {
+        match (self, consts) {
+            (LeafOp::CustomOp(ext), _) => ext.const_fold(consts),
+            (LeafOp::BitToPredicate, [ConstValue::Int { value, width: 1 }]) => {
+                Some(vec![ConstValue::simple_predicate(*value as usize, 2)])
+            }
+            (LeafOp::PredicateToBit, [ConstValue::Sum { tag, .. }]) => {
+                Some(vec![ConstValue::Int {
+                    value: *tag as _,
+                    width: 1,
+                }])
+            }
+            _ => None,
+        }
+    }
 }
 
 impl LeafOp {
@@ -2,8 +2,9 @@
 
 use smol_str::SmolStr;
 
-use crate::types::{ClassicType, EdgeKind, Signature, SimpleType};
+use crate::types::{ClassicType, EdgeKind, Signature, SignatureDescription, SimpleType};
 
+use super::dataflow::DataflowParent;
 use super::StaticTag;
 use super::{impl_op_name, OpTag, OpTrait};
 
@@ -36,9 +37,17 @@ pub struct FuncDefn {
     pub name: String,
     /// Signature of the function
     pub signature: Signature,
+    /// Names of the function parameters and results
+    #[serde(default, skip_serializing_if = "SignatureDescription::is_empty")]
+    pub signature_desc: SignatureDescription,
 }
 
 impl_op_name!(FuncDefn);
+impl DataflowParent for FuncDefn {
+    fn inner_signature(&self) -> Signature {
+        self.signature.clone()
+    }
+}
 impl StaticTag for FuncDefn {
     const TAG: OpTag = OpTag::FuncDefn;
 }
@@ -51,6 +60,10 @@ impl OpTrait for FuncDefn {
         <Self as StaticTag>::TAG
     }
 
+    fn signature_desc(&self) -> SignatureDescription {
+        self.signature_desc.clone()
+    }
+
     fn other_output(&self) -> Option<EdgeKind> {
         Some(EdgeKind::Static(ClassicType::graph_from_sig(
             self.signature.clone(),
@@ -65,6 +78,9 @@ pub struct FuncDecl {
     pub name: String,
     /// Signature of the function
     pub signature: Signature,
+    /// Names of the function parameters and results
+    #[serde(default, skip_serializing_if = "SignatureDescription::is_empty")]
+    pub signature_desc: SignatureDescription,
 }
 
 impl_op_name!(FuncDecl);
@@ -80,6 +96,10 @@ impl OpTrait for FuncDecl {
         <Self as StaticTag>::TAG
     }
 
+    fn signature_desc(&self) -> SignatureDescription {
+        self.signature_desc.clone()
+    }
+
     fn other_output(&self) -> Option<EdgeKind> {
         Some(EdgeKind::Static(ClassicType::graph_from_sig(
             self.signature.clone(),
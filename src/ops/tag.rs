@@ -9,7 +9,7 @@ use std::{cmp, fmt::Display};
 ///
 /// Uses a flat representation for all the variants, in contrast to the complex
 /// `OpType` structures.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize)]
 #[non_exhaustive]
 pub enum OpTag {
     /// All operations allowed.
@@ -33,6 +33,8 @@ pub enum OpTag {
 
     /// Node in a Dataflow Sibling Graph.
     DataflowChild,
+    /// Parent node of a Dataflow Sibling Graph.
+    DataflowParent,
     /// A nested data-flow operation.
     Dfg,
     /// A nested control-flow operation.
@@ -96,20 +98,21 @@ impl OpTag {
             OpTag::None => &[OpTag::Any],
             OpTag::ModuleOp => &[OpTag::Any],
             OpTag::DataflowChild => &[OpTag::Any],
+            OpTag::DataflowParent => &[OpTag::Any],
             OpTag::Input => &[OpTag::DataflowChild],
             OpTag::Output => &[OpTag::DataflowChild],
             OpTag::Function => &[OpTag::ModuleOp],
             OpTag::Alias => &[OpTag::ScopedDefn],
-            OpTag::FuncDefn => &[OpTag::Function, OpTag::ScopedDefn],
-            OpTag::BasicBlock => &[OpTag::Any],
+            OpTag::FuncDefn => &[OpTag::Function, OpTag::ScopedDefn, OpTag::DataflowParent],
+            OpTag::BasicBlock => &[OpTag::DataflowParent],
             OpTag::BasicBlockExit => &[OpTag::BasicBlock],
-            OpTag::Case => &[OpTag::Any],
+            OpTag::Case => &[OpTag::DataflowParent],
             OpTag::ModuleRoot => &[OpTag::Any],
             OpTag::Const => &[OpTag::ScopedDefn],
-            OpTag::Dfg => &[OpTag::DataflowChild],
+            OpTag::Dfg => &[OpTag::DataflowChild, OpTag::DataflowParent],
             OpTag::Cfg => &[OpTag::DataflowChild],
             OpTag::ScopedDefn => &[OpTag::DataflowChild, OpTag::ModuleOp],
-            OpTag::TailLoop => &[OpTag::DataflowChild],
+            OpTag::TailLoop => &[OpTag::DataflowChild, OpTag::DataflowParent],
             OpTag::Conditional => &[OpTag::DataflowChild],
             OpTag::FnCall => &[OpTag::DataflowChild],
             OpTag::LoadConst => &[OpTag::DataflowChild],
@@ -124,6 +127,7 @@ impl OpTag {
             OpTag::None => "None",
             OpTag::ModuleOp => "Module operations",
             OpTag::DataflowChild => "Node in a Dataflow Sibling Graph",
+            OpTag::DataflowParent => "Parent node of a Dataflow Sibling Graph",
             OpTag::Input => "Input node",
             OpTag::Output => "Output node",
             OpTag::FuncDefn => "Function definition",
@@ -200,4 +204,22 @@ mod test {
         assert!(!OpTag::None.is_superset(OpTag::DataflowChild));
         assert!(!OpTag::None.is_superset(OpTag::BasicBlock));
     }
+
+    #[test]
+    fn dataflow_parents() {
+        for tag in [
+            OpTag::Dfg,
+            OpTag::TailLoop,
+            OpTag::Case,
+            OpTag::BasicBlock,
+            OpTag::FuncDefn,
+        ] {
+            assert!(OpTag::DataflowParent.is_superset(tag));
+        }
+        for tag in [OpTag::Cfg, OpTag::Conditional, OpTag::Leaf, OpTag::Input] {
+            assert!(!OpTag::DataflowParent.is_superset(tag));
+        }
+        assert!(OpTag::DataflowChild.is_superset(OpTag::Dfg));
+        assert!(!OpTag::DataflowChild.is_superset(OpTag::Case));
+    }
 }
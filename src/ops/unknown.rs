@@ -0,0 +1,48 @@
+//! Operations of kinds unknown to this version of the library.
+
+use smol_str::SmolStr;
+
+use super::{OpName, OpTag, OpTrait};
+
+/// An operation of a kind unknown to this version of the library, typically
+/// produced by a newer version.
+///
+/// The operation is kept as its serialized representation, so a HUGR
+/// containing it can be loaded, inspected and saved back unchanged. Its ports
+/// are only known from the edges connected to it, and it is rejected by
+/// validation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownOp {
+    serialized: serde_json::Map<String, serde_json::Value>,
+}
+
+impl UnknownOp {
+    /// Create an unknown operation from its serialized representation.
+    pub fn new(serialized: serde_json::Map<String, serde_json::Value>) -> Self {
+        Self { serialized }
+    }
+
+    /// The serialized representation of the operation.
+    pub fn serialized(&self) -> &serde_json::Map<String, serde_json::Value> {
+        &self.serialized
+    }
+}
+
+impl OpName for UnknownOp {
+    fn name(&self) -> SmolStr {
+        match self.serialized.get("op") {
+            Some(serde_json::Value::String(name)) => name.into(),
+            _ => "UnknownOp".into(),
+        }
+    }
+}
+
+impl OpTrait for UnknownOp {
+    fn description(&self) -> &str {
+        "An operation unknown to this version of the library"
+    }
+
+    fn tag(&self) -> OpTag {
+        OpTag::Any
+    }
+}
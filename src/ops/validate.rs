@@ -10,10 +10,9 @@ use itertools::Itertools;
 use portgraph::{NodeIndex, PortOffset};
 use thiserror::Error;
 
-use crate::types::{SimpleType, TypeRow};
-use crate::Direction;
+use crate::types::{Signature, TypeRow};
 
-use super::{impl_validate_op, BasicBlock, OpTag, OpTrait, OpType, ValidateOp};
+use super::{impl_validate_op, BasicBlock, DataflowParent, OpTag, OpTrait, OpType, ValidateOp};
 
 /// A set of property flags required for an operation.
 #[non_exhaustive]
@@ -32,30 +31,12 @@ pub struct OpValidityFlags {
     pub requires_children: bool,
     /// Whether the children must form a DAG (no cycles).
     pub requires_dag: bool,
-    /// A strict requirement on the number of non-dataflow multiports.
-    ///
-    /// If not specified, the operation must have exactly one non-dataflow port
-    /// if the operation type has other_edges, or zero otherwise.
-    pub non_df_ports: (Option<usize>, Option<usize>),
     /// A validation check for edges between children
     ///
     // Enclosed in an `Option` to avoid iterating over the edges if not needed.
     pub edge_check: Option<fn(ChildrenEdgeData) -> Result<(), EdgeValidationError>>,
 }
 
-impl OpValidityFlags {
-    /// Get the number of non-dataflow multiports.
-    ///
-    /// If None, the operation must have exactly one non-dataflow port
-    /// if the operation type has other_edges, or zero otherwise.
-    pub fn non_df_port_count(&self, dir: Direction) -> Option<usize> {
-        match dir {
-            Direction::Incoming => self.non_df_ports.0,
-            Direction::Outgoing => self.non_df_ports.1,
-        }
-    }
-}
-
 impl Default for OpValidityFlags {
     fn default() -> Self {
         // Defaults to flags valid for non-container operations
@@ -65,7 +46,6 @@ impl Default for OpValidityFlags {
             allowed_second_child: OpTag::Any,
             requires_children: false,
             requires_dag: false,
-            non_df_ports: (None, None),
             edge_check: None,
         }
     }
@@ -97,12 +77,7 @@ impl ValidateOp for super::FuncDefn {
         &self,
         children: impl DoubleEndedIterator<Item = (NodeIndex, &'a OpType)>,
     ) -> Result<(), ChildrenValidationError> {
-        validate_io_nodes(
-            &self.signature.input,
-            &self.signature.output,
-            "function definition",
-            children,
-        )
+        validate_io_nodes(&self.inner_signature(), "function definition", children)
     }
 }
 
@@ -122,12 +97,7 @@ impl ValidateOp for super::DFG {
         &self,
         children: impl DoubleEndedIterator<Item = (NodeIndex, &'a OpType)>,
     ) -> Result<(), ChildrenValidationError> {
-        validate_io_nodes(
-            &self.signature.input,
-            &self.signature.output,
-            "nested graph",
-            children,
-        )
+        validate_io_nodes(&self.inner_signature(), "nested graph", children)
     }
 }
 
@@ -148,19 +118,27 @@ impl ValidateOp for super::Conditional {
         let children = children.collect_vec();
         // The first input to the ɣ-node is a predicate of Sum type,
         // whose arity matches the number of children of the ɣ-node.
-        if self.predicate_inputs.len() != children.len() {
+        let arity = self.predicate_type().as_sum().map_or(0, |v| v.len());
+        if arity != children.len() {
             return Err(ChildrenValidationError::InvalidConditionalPredicate {
                 child: children[0].0, // Pass an arbitrary child
                 expected_count: children.len(),
-                actual_count: self.predicate_inputs.len(),
+                actual_count: arity,
                 actual_predicate_rows: self.predicate_inputs.clone(),
             });
         }
 
-        // Each child must have its predicate variant's row and the rest of `inputs` as input,
-        // and matching output
+        // Each child must be tagged with its position, and have its predicate
+        // variant's row and the rest of `inputs` as input, and matching output
         for (i, (child, optype)) in children.into_iter().enumerate() {
             let OpType::Case(case_op) = optype else {panic!("Child check should have already checked valid ops.")};
+            if case_op.tag != i {
+                return Err(ChildrenValidationError::ConditionalCaseTag {
+                    child,
+                    position: i,
+                    tag: case_op.tag,
+                });
+            }
             let sig = &case_op.signature;
             if sig.input != self.case_input_row(i).unwrap() || sig.output != self.outputs {
                 return Err(ChildrenValidationError::ConditionalCaseSignature {
@@ -191,8 +169,7 @@ impl ValidateOp for super::TailLoop {
         children: impl DoubleEndedIterator<Item = (NodeIndex, &'a OpType)>,
     ) -> Result<(), ChildrenValidationError> {
         validate_io_nodes(
-            &self.body_input_row(),
-            &self.body_output_row(),
+            &self.inner_signature(),
             "tail-controlled loop graph",
             children,
         )
@@ -224,7 +201,7 @@ impl ValidateOp for super::CFG {
     }
 }
 /// Errors that can occur while checking the children of a node.
-#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error, serde::Serialize)]
 #[allow(missing_docs)]
 pub enum ChildrenValidationError {
     /// An CFG graph has an exit operation as a non-second child.
@@ -249,6 +226,14 @@ pub enum ChildrenValidationError {
     /// The signature of a child case in a conditional operation does not match the container's signature.
     #[error("A conditional case has optype {optype:?}, which differs from the signature of Conditional container")]
     ConditionalCaseSignature { child: NodeIndex, optype: OpType },
+    /// The tag of a child case in a conditional operation does not match its
+    /// position.
+    #[error("The conditional case at position {position} has tag {tag}.")]
+    ConditionalCaseTag {
+        child: NodeIndex,
+        position: usize,
+        tag: usize,
+    },
     /// The conditional container's branch predicate does not match the number of children.
     #[error("The conditional container's branch predicate input should be a sum with {expected_count} elements, but it had {actual_count} elements. Predicate rows: {actual_predicate_rows:?} ")]
     InvalidConditionalPredicate {
@@ -266,6 +251,7 @@ impl ChildrenValidationError {
             ChildrenValidationError::InternalIOChildren { child, .. } => *child,
             ChildrenValidationError::InternalExitChildren { child, .. } => *child,
             ChildrenValidationError::ConditionalCaseSignature { child, .. } => *child,
+            ChildrenValidationError::ConditionalCaseTag { child, .. } => *child,
             ChildrenValidationError::IOSignatureMismatch { child, .. } => *child,
             ChildrenValidationError::InvalidConditionalPredicate { child, .. } => *child,
         }
@@ -273,7 +259,7 @@ impl ChildrenValidationError {
 }
 
 /// Errors that can occur while checking the edges between children of a node.
-#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error, serde::Serialize)]
 #[allow(missing_docs)]
 pub enum EdgeValidationError {
     /// The dataflow signature of two connected basic blocks does not match.
@@ -294,7 +280,7 @@ impl EdgeValidationError {
 }
 
 /// Auxiliary structure passed as data to [`OpValidityFlags::edge_check`].
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
 pub struct ChildrenEdgeData {
     /// Source child.
     pub source: NodeIndex,
@@ -314,18 +300,15 @@ impl ValidateOp for BasicBlock {
     /// Returns the set of allowed parent operation types.
     fn validity_flags(&self) -> OpValidityFlags {
         match self {
-            BasicBlock::DFB {
-                predicate_variants, ..
-            } => OpValidityFlags {
+            BasicBlock::DFB { .. } => OpValidityFlags {
                 allowed_children: OpTag::DataflowChild,
                 allowed_first_child: OpTag::Input,
                 allowed_second_child: OpTag::Output,
                 requires_children: true,
                 requires_dag: true,
-                non_df_ports: (None, Some(predicate_variants.len())),
                 ..Default::default()
             },
-            // Default flags are valid for non-container operations
+            // Exit blocks are not containers
             BasicBlock::Exit { .. } => Default::default(),
         }
     }
@@ -335,18 +318,10 @@ impl ValidateOp for BasicBlock {
         &self,
         children: impl DoubleEndedIterator<Item = (NodeIndex, &'a OpType)>,
     ) -> Result<(), ChildrenValidationError> {
-        match self {
-            BasicBlock::DFB {
-                inputs,
-                predicate_variants,
-                other_outputs: outputs,
-            } => {
-                let predicate_type = SimpleType::new_predicate(predicate_variants.clone());
-                let node_outputs: TypeRow = [&[predicate_type], outputs.as_ref()].concat().into();
-                validate_io_nodes(inputs, &node_outputs, "basic block graph", children)
-            }
+        match self.inner_signature() {
+            Some(signature) => validate_io_nodes(&signature, "basic block graph", children),
             // Exit nodes do not have children
-            BasicBlock::Exit { .. } => Ok(()),
+            None => Ok(()),
         }
     }
 }
@@ -369,21 +344,15 @@ impl ValidateOp for super::Case {
         &self,
         children: impl DoubleEndedIterator<Item = (NodeIndex, &'a OpType)>,
     ) -> Result<(), ChildrenValidationError> {
-        validate_io_nodes(
-            &self.signature.input,
-            &self.signature.output,
-            "Conditional",
-            children,
-        )
+        validate_io_nodes(&self.inner_signature(), "Conditional", children)
     }
 }
 
 /// Checks a that the list of children nodes does not contain Input and Output
 /// nodes outside of the first and second elements respectively, and that those
-/// have the correct signature.
+/// have the rows of the expected inner signature of the container.
 fn validate_io_nodes<'a>(
-    expected_input: &TypeRow,
-    expected_output: &TypeRow,
+    expected: &Signature,
     container_desc: &'static str,
     mut children: impl Iterator<Item = (NodeIndex, &'a OpType)>,
 ) -> Result<(), ChildrenValidationError> {
@@ -391,20 +360,20 @@ fn validate_io_nodes<'a>(
     let (first, first_optype) = children.next().unwrap();
     let (second, second_optype) = children.next().unwrap();
 
-    if &first_optype.signature().output != expected_input {
+    if first_optype.signature().output != expected.input {
         return Err(ChildrenValidationError::IOSignatureMismatch {
             child: first,
             actual: first_optype.signature().output,
-            expected: expected_input.clone(),
+            expected: expected.input.clone(),
             node_desc: "Input",
             container_desc,
         });
     }
-    if &second_optype.signature().input != expected_output {
+    if second_optype.signature().input != expected.output {
         return Err(ChildrenValidationError::IOSignatureMismatch {
             child: second,
             actual: second_optype.signature().input,
-            expected: expected_output.clone(),
+            expected: expected.output.clone(),
             node_desc: "Output",
             container_desc,
         });
@@ -482,16 +451,18 @@ mod test {
             (2, &leaf_node),
             (3, &leaf_node),
         ];
+        let sig =
+            |input: &TypeRow, output: &TypeRow| Signature::new_df(input.clone(), output.clone());
         assert_eq!(
-            validate_io_nodes(&in_types, &out_types, "test", make_iter(&children)),
+            validate_io_nodes(&sig(&in_types, &out_types), "test", make_iter(&children)),
             Ok(())
         );
         assert_matches!(
-            validate_io_nodes(&out_types, &out_types, "test", make_iter(&children)),
+            validate_io_nodes(&sig(&out_types, &out_types), "test", make_iter(&children)),
             Err(ChildrenValidationError::IOSignatureMismatch { child, .. }) if child.index() == 0
         );
         assert_matches!(
-            validate_io_nodes(&in_types, &in_types, "test", make_iter(&children)),
+            validate_io_nodes(&sig(&in_types, &in_types), "test", make_iter(&children)),
             Err(ChildrenValidationError::IOSignatureMismatch { child, .. }) if child.index() == 1
         );
 
@@ -504,11 +475,71 @@ mod test {
             (3, &output_node),
         ];
         assert_matches!(
-            validate_io_nodes(&in_types, &out_types, "test", make_iter(&children)),
+            validate_io_nodes(&sig(&in_types, &out_types), "test", make_iter(&children)),
             Err(ChildrenValidationError::InternalIOChildren { child, .. }) if child.index() == 3
         );
     }
 
+    #[test]
+    fn inner_signature_matches_io_validation() {
+        const B: SimpleType = SimpleType::Classic(ClassicType::bit());
+        let sig = Signature::new_df(type_row![B], type_row![B, B]);
+        let parents: Vec<OpType> = vec![
+            ops::FuncDefn {
+                name: "f".into(),
+                signature: sig.clone(),
+                signature_desc: Default::default(),
+            }
+            .into(),
+            ops::DFG {
+                signature: sig.clone(),
+                resource_delta: None,
+            }
+            .into(),
+            ops::Case {
+                tag: 0,
+                signature: sig.clone(),
+            }
+            .into(),
+            ops::TailLoop {
+                just_inputs: type_row![B],
+                just_outputs: type_row![],
+                rest: type_row![B],
+            }
+            .into(),
+            ops::BasicBlock::DFB {
+                inputs: type_row![B],
+                other_outputs: type_row![B],
+                predicate_variants: vec![type_row![], type_row![B]],
+            }
+            .into(),
+        ];
+        for parent in parents {
+            let inner = parent.inner_signature().unwrap();
+            let input: OpType = ops::Input::new(inner.input.clone()).into();
+            let output: OpType = ops::Output::new(inner.output.clone()).into();
+            let children = vec![(0, &input), (1, &output)];
+            assert_eq!(
+                parent.validate_children(make_iter(&children)),
+                Ok(()),
+                "{parent:?}"
+            );
+            // The Input and Output rows are not interchangeable.
+            let children = vec![(0, &output), (1, &input)];
+            assert_matches!(
+                parent.validate_children(make_iter(&children)),
+                Err(ChildrenValidationError::IOSignatureMismatch { .. })
+            );
+        }
+
+        let exit: OpType = ops::BasicBlock::Exit {
+            cfg_outputs: type_row![B],
+        }
+        .into();
+        assert_eq!(exit.inner_signature(), None);
+        assert_eq!(OpType::from(ops::Module).inner_signature(), None);
+    }
+
     fn make_iter<'a>(
         children: &'a [(usize, &OpType)],
     ) -> impl DoubleEndedIterator<Item = (NodeIndex, &'a OpType)> {
@@ -517,7 +548,8 @@ mod test {
 }
 
 use super::{
-    AliasDecl, AliasDefn, Call, CallIndirect, Const, FuncDecl, Input, LeafOp, LoadConstant, Output,
+    AliasDecl, AliasDefn, Call, CallIndirect, Const, ConstRow, FuncDecl, Input, LeafOp,
+    LoadConstant, Output, UnknownOp,
 };
 impl_validate_op!(FuncDecl);
 impl_validate_op!(AliasDecl);
@@ -525,7 +557,9 @@ impl_validate_op!(AliasDefn);
 impl_validate_op!(Input);
 impl_validate_op!(Output);
 impl_validate_op!(Const);
+impl_validate_op!(ConstRow);
 impl_validate_op!(Call);
 impl_validate_op!(CallIndirect);
 impl_validate_op!(LoadConstant);
 impl_validate_op!(LeafOp);
+impl_validate_op!(UnknownOp);
@@ -4,13 +4,15 @@
 //! system (outside the `types` module), which also parses nested [`OpDef`]s.
 
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
 
+use smallvec::SmallVec;
 use smol_str::SmolStr;
 use thiserror::Error;
 
+use crate::ops::ConstValue;
 use crate::types::type_param::{check_type_arg, TypeArgError};
 use crate::types::TypeRow;
 use crate::types::{
@@ -125,6 +127,18 @@ impl Debug for LowerFunc {
     }
 }
 
+/// Custom binary code evaluating an operation on constant inputs, see
+/// [OpDef::set_constant_folder].
+type ConstFoldFunc = dyn Fn(&[ConstValue]) -> Option<Vec<ConstValue>> + Send + Sync;
+
+struct ConstFolder(Box<ConstFoldFunc>);
+
+impl Debug for ConstFolder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<constant folder>")
+    }
+}
+
 /// Serializable definition for dynamically loaded operations.
 ///
 /// TODO: Define a way to construct new OpDef's from a serialized definition.
@@ -132,6 +146,9 @@ impl Debug for LowerFunc {
 pub struct OpDef {
     /// The unique Resource owning this OpDef (of which this OpDef is a member)
     pub resource: ResourceId,
+    /// The version of the owning Resource.
+    #[serde(default)]
+    pub resource_version: ResourceVersion,
     /// Unique identifier of the operation. Used to look up OpDefs in the registry
     /// when deserializing nodes (which store only the name).
     pub name: SmolStr,
@@ -142,6 +159,12 @@ pub struct OpDef {
     /// Miscellaneous data associated with the operation.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub misc: HashMap<String, serde_yaml::Value>,
+    /// Whether the operation may allocate or discard linear values, see
+    /// [`OpTrait::creates_or_destroys_linear`].
+    ///
+    /// [`OpTrait::creates_or_destroys_linear`]: crate::ops::OpTrait::creates_or_destroys_linear
+    #[serde(default)]
+    pub creates_or_destroys_linear: bool,
 
     #[serde(flatten)]
     signature_func: SignatureFunc,
@@ -149,6 +172,9 @@ pub struct OpDef {
     // can only treat them as opaque/black-box ops.
     #[serde(flatten)]
     lower_funcs: Vec<LowerFunc>,
+    // Operations without a constant folder are never evaluated at compile time.
+    #[serde(skip)]
+    constant_folder: Option<ConstFolder>,
 }
 
 impl OpDef {
@@ -164,12 +190,15 @@ impl OpDef {
     ) -> Self {
         Self {
             resource: Default::default(), // Currently overwritten when OpDef added to Resource
+            resource_version: Default::default(),
             name,
             description,
             args,
             misc,
+            creates_or_destroys_linear: false,
             signature_func: SignatureFunc::FromYAML { inputs, outputs },
             lower_funcs: Vec::new(),
+            constant_folder: None,
         }
     }
 
@@ -183,12 +212,15 @@ impl OpDef {
     ) -> Self {
         Self {
             resource: Default::default(), // Currently overwritten when OpDef added to Resource
+            resource_version: Default::default(),
             name,
             description,
             args,
             misc,
+            creates_or_destroys_linear: false,
             signature_func: SignatureFunc::CustomFunc(Box::new(sig_func)),
             lower_funcs: Vec::new(),
+            constant_folder: None,
         }
     }
 
@@ -198,6 +230,36 @@ impl OpDef {
         self.lower_funcs.push(func);
     }
 
+    /// Sets the function used to evaluate operations when all their inputs are
+    /// constants. The function returns the constant outputs, or `None` if the
+    /// inputs cannot be folded.
+    ///
+    /// This must be set before the OpDef is added to a [Resource].
+    pub fn set_constant_folder(
+        &mut self,
+        folder: impl Fn(&[ConstValue]) -> Option<Vec<ConstValue>> + Send + Sync + 'static,
+    ) {
+        self.constant_folder = Some(ConstFolder(Box::new(folder)));
+    }
+
+    /// Evaluates an operation on constant inputs, if the OpDef has a
+    /// constant folder.
+    pub fn constant_fold(&self, consts: &[ConstValue]) -> Option<Vec<ConstValue>> {
+        self.constant_folder.as_ref().and_then(|f| (f.0)(consts))
+    }
+
+    /// Checks that the [TypeArg]s of a node fit the type parameters of this
+    /// OpDef.
+    pub fn check_args(&self, args: &[TypeArg]) -> Result<(), TypeArgError> {
+        if args.len() != self.args.len() {
+            return Err(TypeArgError::WrongNumber(args.len(), self.args.len()));
+        }
+        for (a, p) in args.iter().zip(self.args.iter()) {
+            check_type_arg(a, p)?;
+        }
+        Ok(())
+    }
+
     /// Computes the signature of a node, i.e. an instantiation of this
     /// OpDef with statically-provided [TypeArg]s.
     pub fn compute_signature(
@@ -205,15 +267,8 @@ impl OpDef {
         args: &[TypeArg],
         resources_in: &ResourceSet,
     ) -> Result<Signature, SignatureError> {
-        if args.len() != self.args.len() {
-            return Err(SignatureError::TypeArgMismatch(TypeArgError::WrongNumber(
-                args.len(),
-                self.args.len(),
-            )));
-        }
-        for (a, p) in args.iter().zip(self.args.iter()) {
-            check_type_arg(a, p).map_err(SignatureError::TypeArgMismatch)?;
-        }
+        self.check_args(args)
+            .map_err(SignatureError::TypeArgMismatch)?;
         let (ins, outs, res) = match &self.signature_func {
             SignatureFunc::FromYAML { .. } => {
                 // Sig should be computed solely from inputs + outputs + args.
@@ -223,7 +278,8 @@ impl OpDef {
         };
         assert!(res.contains(&self.resource));
         let mut sig = Signature::new_df(ins, outs);
-        sig.input_resources = resources_in.clone();
+        // The operation requires its own resource on its inputs.
+        sig.input_resources = ResourceSet::singleton(&self.resource).union(resources_in);
         sig.output_resources = res.union(resources_in); // Pass input requirements through
         Ok(sig)
     }
@@ -278,6 +334,9 @@ pub struct TypeDef {
     ///
     /// [`TypeArg`]: crate::types::type_param::TypeArg
     pub args: Vec<TypeParam>,
+    /// Whether instances of the type are linear.
+    #[serde(default)]
+    pub linear: bool,
 }
 
 /// A unique identifier for a resource.
@@ -285,6 +344,52 @@ pub struct TypeDef {
 /// The actual [`Resource`] is stored externally.
 pub type ResourceId = SmolStr;
 
+/// The version of a [`Resource`], as major, minor and patch numbers.
+///
+/// Operations record the version of the resource they were created against.
+/// They can be resolved against a resource with the same major version and
+/// an equal or newer minor version.
+///
+/// Version `0.0.0` is used for operations saved before versions were
+/// recorded, and is compatible with any version.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct ResourceVersion(pub u32, pub u32, pub u32);
+
+impl ResourceVersion {
+    /// Returns `true` if operations created against version `stored` can be
+    /// resolved against this version.
+    pub fn is_compatible_with(&self, stored: &Self) -> bool {
+        stored.is_unversioned() || (self.0 == stored.0 && self.1 >= stored.1)
+    }
+
+    /// Returns `true` for the version `0.0.0` of operations not recording
+    /// their resource version.
+    pub fn is_unversioned(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Display for ResourceVersion {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// The known resources, indexed by their [`ResourceId`].
+pub type ResourceRegistry = HashMap<ResourceId, Resource>;
+
 /// A resource is a set of capabilities required to execute a graph.
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct Resource {
@@ -295,6 +400,9 @@ pub struct Resource {
     /// computing the signature of any operation in this resource,
     /// for any possible [TypeArg].
     pub resource_reqs: ResourceSet,
+    /// The version of the resource, recorded by its operations.
+    #[serde(default)]
+    version: ResourceVersion,
     /// Types defined by this resource.
     types: HashMap<SmolStr, TypeDef>,
     /// Operation declarations with serializable definitions.
@@ -304,6 +412,9 @@ pub struct Resource {
     // and the other references to the OpDef are from ExternalOp's in the Hugr
     // (which are serialized as OpaqueOp's i.e. Strings).
     operations: HashMap<SmolStr, Arc<OpDef>>,
+    /// Named constant values provided by this resource.
+    #[serde(default)]
+    values: HashMap<SmolStr, ConstValue>,
 }
 
 impl Resource {
@@ -315,11 +426,50 @@ impl Resource {
         }
     }
 
+    /// Creates a new resource with the given name and version.
+    pub fn new_versioned(name: ResourceId, version: ResourceVersion) -> Self {
+        Self {
+            name,
+            version,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the version of the resource.
+    pub fn version(&self) -> ResourceVersion {
+        self.version
+    }
+
     /// Allows read-only access to the operations in this Resource
     pub fn operations(&self) -> &HashMap<SmolStr, Arc<OpDef>> {
         &self.operations
     }
 
+    /// Allows read-only access to the types in this Resource
+    pub fn types(&self) -> &HashMap<SmolStr, TypeDef> {
+        &self.types
+    }
+
+    /// Allows read-only access to the constant values in this Resource
+    pub fn values(&self) -> &HashMap<SmolStr, ConstValue> {
+        &self.values
+    }
+
+    /// Returns the operation definition with the given name.
+    pub fn get_op(&self, name: &str) -> Option<&Arc<OpDef>> {
+        self.operations.get(name)
+    }
+
+    /// Returns the type definition with the given name.
+    pub fn get_type(&self, name: &str) -> Option<&TypeDef> {
+        self.types.get(name)
+    }
+
+    /// Returns the constant value with the given name.
+    pub fn get_value(&self, name: &str) -> Option<&ConstValue> {
+        self.values.get(name)
+    }
+
     /// Returns the name of the resource.
     pub fn name(&self) -> &str {
         &self.name
@@ -335,6 +485,16 @@ impl Resource {
         }
     }
 
+    /// Add a named constant value to the resource.
+    pub fn add_value(&mut self, name: impl Into<SmolStr>, value: ConstValue) {
+        match self.values.entry(name.into()) {
+            Entry::Occupied(oe) => panic!("Resource already has a value called {}", oe.key()),
+            Entry::Vacant(ve) => {
+                ve.insert(value);
+            }
+        }
+    }
+
     /// Add an operation definition to the resource.
     pub fn add_op(&mut self, mut op: OpDef) -> Result<(), String> {
         // if op.resource != self.name {
@@ -348,6 +508,7 @@ impl Resource {
             Entry::Occupied(_) => panic!("Resource already has an op called {}", &op.name),
             Entry::Vacant(ve) => {
                 op.resource = self.name.clone();
+                op.resource_version = self.version;
                 ve.insert(Arc::new(op));
             }
         }
@@ -355,6 +516,25 @@ impl Resource {
     }
 }
 
+impl Display for Resource {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        fn sorted<V>(map: &HashMap<SmolStr, V>) -> Vec<&str> {
+            let mut names: Vec<&str> = map.keys().map(SmolStr::as_str).collect();
+            names.sort_unstable();
+            names
+        }
+        write!(
+            f,
+            "{} {}: ops={:?} types={:?} values={:?}",
+            self.name,
+            self.version,
+            sorted(&self.operations),
+            sorted(&self.types),
+            sorted(&self.values)
+        )
+    }
+}
+
 impl PartialEq for Resource {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
@@ -362,33 +542,44 @@ impl PartialEq for Resource {
 }
 
 /// A set of resources identified by their unique [`ResourceId`].
-#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-pub struct ResourceSet(HashSet<ResourceId>);
+///
+/// The resources are kept sorted, so iteration order is deterministic. Sets
+/// of up to two resources are stored inline, making clones cheap.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(from = "Vec<ResourceId>", into = "Vec<ResourceId>")]
+pub struct ResourceSet(SmallVec<[ResourceId; 2]>);
 
 impl ResourceSet {
     /// Creates a new empty resource set.
     pub fn new() -> Self {
-        Self(HashSet::new())
+        Self(SmallVec::new())
     }
 
     /// Adds a resource to the set.
     pub fn insert(&mut self, resource: &ResourceId) {
-        self.0.insert(resource.clone());
+        if let Err(pos) = self.0.binary_search(resource) {
+            self.0.insert(pos, resource.clone());
+        }
+    }
+
+    /// Returns `true` if the set contains no resources.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 
     /// Returns `true` if the set contains the given resource.
     pub fn contains(&self, resource: &ResourceId) -> bool {
-        self.0.contains(resource)
+        self.0.binary_search(resource).is_ok()
     }
 
     /// Returns `true` if the set is a subset of `other`.
     pub fn is_subset(&self, other: &Self) -> bool {
-        self.0.is_subset(&other.0)
+        self.0.len() <= other.0.len() && self.0.iter().all(|r| other.contains(r))
     }
 
     /// Returns `true` if the set is a superset of `other`.
     pub fn is_superset(&self, other: &Self) -> bool {
-        self.0.is_superset(&other.0)
+        other.is_subset(self)
     }
 
     /// Create a resource set with a single element.
@@ -400,13 +591,27 @@ impl ResourceSet {
 
     /// Returns the union of two resource sets.
     pub fn union(mut self, other: &Self) -> Self {
-        self.0.extend(other.0.iter().cloned());
+        for resource in other.iter() {
+            self.insert(resource);
+        }
         self
     }
 
     /// The things in other which are in not in self
     pub fn missing_from(&self, other: &Self) -> Self {
-        ResourceSet(HashSet::from_iter(other.0.difference(&self.0).cloned()))
+        ResourceSet(
+            other
+                .0
+                .iter()
+                .filter(|r| !self.contains(r))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Iterates over the resources in the set, in sorted order.
+    pub fn iter(&self) -> impl Iterator<Item = &ResourceId> {
+        self.0.iter()
     }
 }
 
@@ -418,6 +623,129 @@ impl Display for ResourceSet {
 
 impl FromIterator<ResourceId> for ResourceSet {
     fn from_iter<I: IntoIterator<Item = ResourceId>>(iter: I) -> Self {
-        Self(HashSet::from_iter(iter))
+        let mut resources: SmallVec<[ResourceId; 2]> = iter.into_iter().collect();
+        resources.sort_unstable();
+        resources.dedup();
+        Self(resources)
+    }
+}
+
+impl From<Vec<ResourceId>> for ResourceSet {
+    fn from(resources: Vec<ResourceId>) -> Self {
+        resources.into_iter().collect()
+    }
+}
+
+impl From<ResourceSet> for Vec<ResourceId> {
+    fn from(set: ResourceSet) -> Self {
+        set.0.into_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::ops::OpType;
+    use crate::type_row;
+    use crate::types::{ClassicType, SimpleType};
+    use crate::HugrView;
+
+    #[test]
+    fn resource_set_order() {
+        let ids: Vec<ResourceId> = ["C", "A", "B", "A"]
+            .into_iter()
+            .map(ResourceId::from)
+            .collect();
+        let set = ResourceSet::from_iter(ids.clone());
+        assert_eq!(set.iter().collect::<Vec<_>>(), ["A", "B", "C"]);
+        assert_eq!(set.to_string(), r#"["A", "B", "C"]"#);
+
+        // Equality does not depend on the insertion order.
+        let mut inserted = ResourceSet::new();
+        for id in ids.iter().rev() {
+            inserted.insert(id);
+        }
+        assert_eq!(inserted, set);
+        assert_eq!(ResourceSet::singleton(&ids[1]).union(&set), set);
+
+        let a_c = ResourceSet::from_iter(["C".into(), "A".into()]);
+        assert!(a_c.is_subset(&set));
+        assert!(set.is_superset(&a_c));
+        assert!(!set.is_subset(&a_c));
+        assert!(set.contains(&"B".into()) && !a_c.contains(&"B".into()));
+        assert_eq!(a_c.missing_from(&set), ResourceSet::singleton(&"B".into()));
+        assert!(set.missing_from(&a_c).is_empty());
+    }
+
+    #[test]
+    fn math_resource() -> Result<(), BuildError> {
+        let mut math = Resource::new("math".into());
+        let add = OpDef::new_with_yaml_types(
+            "add".into(),
+            "Float addition".into(),
+            vec![],
+            HashMap::new(),
+            "F64, F64".into(),
+            "F64".into(),
+        );
+        math.add_op(add).unwrap();
+        math.add_type(TypeDef {
+            name: "Real".into(),
+            args: vec![],
+            linear: false,
+        });
+        math.add_value("pi", ConstValue::F64(std::f64::consts::PI));
+
+        assert_eq!(
+            math.get_op("add").map(|op| op.resource.as_str()),
+            Some("math")
+        );
+        assert_eq!(math.get_type("Real").map(|ty| ty.linear), Some(false));
+        assert_eq!(
+            math.get_value("pi"),
+            Some(&ConstValue::F64(std::f64::consts::PI))
+        );
+        assert!(math.get_op("sub").is_none() && math.get_value("e").is_none());
+        assert_eq!(math.operations().len(), 1);
+        assert_eq!(math.types().keys().collect::<Vec<_>>(), ["Real"]);
+        assert_eq!(math.values().keys().collect::<Vec<_>>(), ["pi"]);
+        assert_eq!(
+            math.to_string(),
+            r#"math 0.0.0: ops=["add"] types=["Real"] values=["pi"]"#
+        );
+
+        let registry = ResourceRegistry::from([(math.name.clone(), math)]);
+        let f64 = SimpleType::Classic(ClassicType::F64);
+        let mut dfg = DFGBuilder::new(type_row![], vec![f64])?;
+        let pi = dfg.load_resource_value(&registry, "math", "pi")?;
+        assert_matches!(
+            dfg.load_resource_value(&registry, "math", "e"),
+            Err(BuildError::UnknownResourceValue { resource, name }) => {
+                assert_eq!(resource, "math");
+                assert_eq!(name, "e");
+            }
+        );
+        let hugr = dfg.finish_hugr_with_outputs([pi])?;
+        let consts: Vec<_> = hugr
+            .nodes()
+            .filter_map(|n| match hugr.get_optype(n) {
+                OpType::Const(c) => Some(c.0.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(consts, [ConstValue::F64(std::f64::consts::PI)]);
+        Ok(())
+    }
+
+    #[test]
+    fn resource_set_serialization() {
+        let set = ResourceSet::from_iter(["B".into(), "A".into()]);
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, r#"["A","B"]"#);
+        let unsorted: ResourceSet = serde_json::from_str(r#"["B","A","B"]"#).unwrap();
+        assert_eq!(unsorted, set);
     }
 }
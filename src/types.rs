@@ -1,5 +1,6 @@
 //! General wire types used in the compiler
 
+pub mod angle;
 pub mod custom;
 pub mod simple;
 pub mod type_param;
@@ -42,10 +43,43 @@ impl EdgeKind {
             _ => false,
         }
     }
+
+    /// The kind with all new-type wrappers of its type replaced by their
+    /// underlying type, see [`SimpleType::resolve_alias`].
+    pub fn resolve_alias(&self) -> Self {
+        match self {
+            EdgeKind::Value(t) => EdgeKind::Value(t.resolve_alias()),
+            EdgeKind::Static(t) => EdgeKind::Static(t.resolve_alias()),
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns whether the kinds are equal, ignoring the names of new types.
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        self.resolve_alias() == other.resolve_alias()
+    }
+}
+
+impl Display for EdgeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EdgeKind::ControlFlow => f.write_str("ControlFlow"),
+            EdgeKind::Value(t) => write!(f, "Value({t})"),
+            EdgeKind::Static(t) => write!(f, "Static({t})"),
+            EdgeKind::StateOrder => f.write_str("StateOrder"),
+        }
+    }
 }
 
 /// Describes the edges required to/from a node. This includes both the concept of "signature" in the spec,
 /// and also the target (value) of a call (static).
+///
+/// The kinds of the non-dataflow ports of a node, such as order edges, are not
+/// part of the signature. They are given by its operation, see
+/// [`OpTrait::other_input`] and [`OpTrait::other_output`].
+///
+/// [`OpTrait::other_input`]: crate::ops::OpTrait::other_input
+/// [`OpTrait::other_output`]: crate::ops::OpTrait::other_output
 #[cfg_attr(feature = "pyo3", pyclass)]
 #[derive(Clone, Default, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Signature {
@@ -53,11 +87,17 @@ pub struct Signature {
     pub input: TypeRow,
     /// Value outputs of the function.
     pub output: TypeRow,
-    /// Possible static input (for call / load-constant).
+    /// Static inputs (for call / load-constant), following the value inputs.
+    ///
+    /// These must be classical types. Signatures serialized with the legacy
+    /// `const_input` field, holding at most one type, are also accepted.
+    #[serde(alias = "const_input", deserialize_with = "deserialize_static_input")]
     pub static_input: TypeRow,
     /// The resource requirements of all the inputs
+    #[serde(default, skip_serializing_if = "ResourceSet::is_empty")]
     pub input_resources: ResourceSet,
     /// The resource requirements of all the outputs
+    #[serde(default, skip_serializing_if = "ResourceSet::is_empty")]
     pub output_resources: ResourceSet,
 }
 
@@ -230,6 +270,30 @@ impl Signature {
     }
 }
 
+/// Deserialize the static inputs of a [`Signature`], either as a row or in the
+/// legacy optional single-type form.
+fn deserialize_static_input<'de, D>(deserializer: D) -> Result<TypeRow, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum StaticInput {
+        Row(TypeRow),
+        Legacy(Option<ClassicType>),
+    }
+
+    let row = match <StaticInput as serde::Deserialize>::deserialize(deserializer)? {
+        StaticInput::Row(row) => row,
+        StaticInput::Legacy(typ) => typ
+            .into_iter()
+            .map(SimpleType::Classic)
+            .collect::<Vec<_>>()
+            .into(),
+    };
+    Ok(row)
+}
+
 impl Signature {
     /// Create a new signature.
     pub fn new(
@@ -0,0 +1,264 @@
+//! Rotation angles, represented as multiples of π.
+//!
+//! Angles are values of the `angle` type defined by the
+//! [`rotations`](crate::extensions::rotation) resource. Constant angles are
+//! stored in [`ConstValue::Angle`] as an [`AngleValue`], either an exact
+//! rational multiple of π or a floating point approximation.
+//!
+//! [`ConstValue::Angle`]: crate::ops::ConstValue::Angle
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use num_rational::Rational64;
+use num_traits::ToPrimitive;
+use smol_str::SmolStr;
+
+#[cfg(feature = "pyo3")]
+use pyo3::prelude::*;
+
+use super::{ClassicType, CustomType};
+
+/// The absolute tolerance used by [`approx_eq`].
+pub const TOLERANCE: f64 = 1e-10;
+
+/// The type of rotation angles, an opaque type defined by the
+/// [`rotations`](crate::extensions::rotation) resource.
+pub const ANGLE: ClassicType = CustomType::new_simple(SmolStr::new_inline("angle")).classic_type();
+
+/// Returns true if two floats are equal up to [`TOLERANCE`].
+#[inline]
+pub fn approx_eq(x: f64, y: f64) -> bool {
+    (x - y).abs() < TOLERANCE
+}
+
+/// A rational number, used for exact angles.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "pyo3", pyclass(name = "Rational"))]
+pub struct Rational(pub Rational64);
+
+impl From<Rational64> for Rational {
+    fn from(r: Rational64) -> Self {
+        Self(r)
+    }
+}
+
+/// A rotation angle, as a multiple of π radians.
+#[derive(Clone, PartialEq, Debug, Copy, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "pyo3", derive(FromPyObject))]
+pub enum AngleValue {
+    /// A floating point multiple of π.
+    F64(f64),
+    /// An exact rational multiple of π.
+    Rational(Rational),
+}
+
+impl AngleValue {
+    /// The angle `numer / denom * π`, or `None` if `denom` is zero.
+    #[inline]
+    pub fn rational(numer: i64, denom: i64) -> Option<Self> {
+        (denom != 0).then(|| AngleValue::Rational(Rational64::new(numer, denom).into()))
+    }
+
+    /// The zero angle.
+    #[inline]
+    pub fn zero() -> Self {
+        AngleValue::Rational(Rational64::from_integer(0).into())
+    }
+
+    fn binary_op<F: FnOnce(f64, f64) -> f64, G: FnOnce(Rational64, Rational64) -> Rational64>(
+        self,
+        rhs: Self,
+        opf: F,
+        opr: G,
+    ) -> Self {
+        match (self, rhs) {
+            (AngleValue::Rational(x), AngleValue::Rational(y)) => {
+                AngleValue::Rational(Rational(opr(x.0, y.0)))
+            }
+            (x, y) => AngleValue::F64(opf(x.to_f64(), y.to_f64())),
+        }
+    }
+
+    fn unary_op<F: FnOnce(f64) -> f64, G: FnOnce(Rational64) -> Rational64>(
+        self,
+        opf: F,
+        opr: G,
+    ) -> Self {
+        match self {
+            AngleValue::F64(x) => AngleValue::F64(opf(x)),
+            AngleValue::Rational(x) => AngleValue::Rational(Rational(opr(x.0))),
+        }
+    }
+
+    /// The angle as a floating point multiple of π.
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            AngleValue::F64(x) => *x,
+            AngleValue::Rational(x) => x.0.to_f64().expect("Floating point conversion error."),
+        }
+    }
+
+    /// The angle in radians.
+    pub fn radians(&self) -> f64 {
+        self.to_f64() * std::f64::consts::PI
+    }
+
+    /// The equivalent angle in the range [0, 2π).
+    pub fn canonicalize(self) -> Self {
+        let two = Rational64::from_integer(2);
+        self.unary_op(
+            |x| x.rem_euclid(2.0),
+            |x| {
+                let x = x % two;
+                if x < Rational64::from_integer(0) {
+                    x + two
+                } else {
+                    x
+                }
+            },
+        )
+    }
+
+    /// Returns true if the two angles describe the same rotation, modulo 2π.
+    ///
+    /// Rational angles are compared exactly, and floating point angles up to
+    /// [`TOLERANCE`].
+    pub fn approx_eq(&self, other: &Self) -> bool {
+        match (self - other).canonicalize() {
+            AngleValue::Rational(diff) => diff.0 == Rational64::from_integer(0),
+            AngleValue::F64(diff) => approx_eq(diff, 0.0) || approx_eq(diff, 2.0),
+        }
+    }
+}
+
+impl Add for AngleValue {
+    type Output = AngleValue;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.binary_op(rhs, |x, y| x + y, |x, y| x + y)
+    }
+}
+
+impl Sub for AngleValue {
+    type Output = AngleValue;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.binary_op(rhs, |x, y| x - y, |x, y| x - y)
+    }
+}
+
+impl Mul for AngleValue {
+    type Output = AngleValue;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.binary_op(rhs, |x, y| x * y, |x, y| x * y)
+    }
+}
+
+impl Div for AngleValue {
+    type Output = AngleValue;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        self.binary_op(rhs, |x, y| x / y, |x, y| x / y)
+    }
+}
+
+impl Neg for AngleValue {
+    type Output = AngleValue;
+
+    fn neg(self) -> Self::Output {
+        self.unary_op(|x| -x, |x| -x)
+    }
+}
+
+impl Add for &AngleValue {
+    type Output = AngleValue;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.binary_op(*rhs, |x, y| x + y, |x, y| x + y)
+    }
+}
+
+impl Sub for &AngleValue {
+    type Output = AngleValue;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.binary_op(*rhs, |x, y| x - y, |x, y| x - y)
+    }
+}
+
+impl Mul for &AngleValue {
+    type Output = AngleValue;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.binary_op(*rhs, |x, y| x * y, |x, y| x * y)
+    }
+}
+
+impl Div for &AngleValue {
+    type Output = AngleValue;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        self.binary_op(*rhs, |x, y| x / y, |x, y| x / y)
+    }
+}
+
+impl Neg for &AngleValue {
+    type Output = AngleValue;
+
+    fn neg(self) -> Self::Output {
+        self.unary_op(|x| -x, |x| -x)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The angle `numer / denom * π`, with a non-zero `denom`.
+    fn rational(numer: i64, denom: i64) -> AngleValue {
+        AngleValue::rational(numer, denom).unwrap()
+    }
+
+    #[test]
+    fn canonical_angles() {
+        assert_eq!(rational(5, 2).canonicalize(), rational(1, 2));
+        assert_eq!(rational(-1, 2).canonicalize(), rational(3, 2));
+        assert_eq!(AngleValue::F64(-0.5).canonicalize(), AngleValue::F64(1.5));
+        assert_eq!(rational(1, 2) + rational(1, 2), rational(1, 1));
+        assert_eq!(-rational(1, 4), rational(-1, 4));
+    }
+
+    #[test]
+    fn zero_denominator() {
+        assert_eq!(AngleValue::rational(1, 0), None);
+        assert_eq!(AngleValue::rational(0, 1), Some(AngleValue::zero()));
+    }
+
+    #[test]
+    fn mixed_operands() {
+        // The operands keep their order when a rational is combined with a
+        // float.
+        assert_eq!(
+            rational(1, 1) - AngleValue::F64(0.25),
+            AngleValue::F64(0.75)
+        );
+        assert_eq!(
+            AngleValue::F64(0.25) - rational(1, 1),
+            AngleValue::F64(-0.75)
+        );
+        assert_eq!(rational(1, 1) / AngleValue::F64(4.0), AngleValue::F64(0.25));
+        assert_eq!(AngleValue::F64(1.0) / rational(4, 1), AngleValue::F64(0.25));
+    }
+
+    #[test]
+    fn approx_eq_angles() {
+        assert!(rational(1, 2).approx_eq(&rational(5, 2)));
+        assert!(!rational(1, 2).approx_eq(&rational(3, 2)));
+        assert!(AngleValue::F64(0.5).approx_eq(&rational(1, 2)));
+        assert!(AngleValue::F64(1.0 / 3.0).approx_eq(&rational(7, 3)));
+        // Angles close to a full turn are close to zero.
+        assert!(AngleValue::F64(2.0 - 1e-12).approx_eq(&AngleValue::zero()));
+        assert!(!AngleValue::F64(0.5 + 1e-6).approx_eq(&AngleValue::F64(0.5)));
+    }
+}
@@ -69,6 +69,8 @@ pub enum Container<T: PrimType> {
     Array(Box<T>, usize),
     /// Alias defined in AliasDefn or AliasDecl nodes.
     Alias(SmolStr),
+    /// Nominal wrapper of T, see [`SimpleType::into_new_type`].
+    NewType(SmolStr, Box<T>),
 }
 
 impl<T: Display + PrimType> Display for Container<T> {
@@ -80,10 +82,30 @@ impl<T: Display + PrimType> Display for Container<T> {
             Container::Sum(row) => write!(f, "Sum({})", row.as_ref()),
             Container::Array(t, size) => write!(f, "Array({}, {})", t, size),
             Container::Alias(str) => f.write_str(str),
+            Container::NewType(name, _) => f.write_str(name),
         }
     }
 }
 
+impl<T: PrimType> Container<T> {
+    /// Replace the new-type wrappers in the container, or return its
+    /// underlying type if it is one.
+    fn resolve_alias(&self, resolve: fn(&T) -> T, wrap: fn(Self) -> T) -> T {
+        let container = match self {
+            Container::NewType(_, inner) => return resolve(inner),
+            Container::List(ty) => Container::List(Box::new(resolve(ty))),
+            Container::Map(tys) => {
+                Container::Map(Box::new((tys.0.resolve_alias(), resolve(&tys.1))))
+            }
+            Container::Tuple(row) => Container::Tuple(Box::new(row.resolve_alias())),
+            Container::Sum(row) => Container::Sum(Box::new(row.resolve_alias())),
+            Container::Array(ty, size) => Container::Array(Box::new(resolve(ty)), *size),
+            Container::Alias(name) => Container::Alias(name.clone()),
+        };
+        wrap(container)
+    }
+}
+
 impl From<Container<ClassicType>> for SimpleType {
     #[inline]
     fn from(value: Container<ClassicType>) -> Self {
@@ -161,6 +183,14 @@ impl ClassicType {
     pub fn new_simple_predicate(size: usize) -> Self {
         Self::new_predicate(std::iter::repeat(type_row![]).take(size))
     }
+
+    /// The type with all new-type wrappers replaced by their underlying type.
+    pub(crate) fn resolve_alias(&self) -> Self {
+        match self {
+            ClassicType::Container(c) => c.resolve_alias(Self::resolve_alias, Self::Container),
+            _ => self.clone(),
+        }
+    }
 }
 
 impl Default for ClassicType {
@@ -219,6 +249,16 @@ impl Display for LinearType {
     }
 }
 
+impl LinearType {
+    /// The type with all new-type wrappers replaced by their underlying type.
+    fn resolve_alias(&self) -> Self {
+        match self {
+            LinearType::Container(c) => c.resolve_alias(Self::resolve_alias, Self::Container),
+            _ => self.clone(),
+        }
+    }
+}
+
 impl PrimType for LinearType {
     const LINEAR: bool = true;
 }
@@ -264,6 +304,80 @@ impl SimpleType {
     pub fn new_simple_predicate(size: usize) -> Self {
         Self::Classic(ClassicType::new_simple_predicate(size))
     }
+
+    /// Returns the variants of a Sum type, or `None` for other types.
+    pub fn as_sum(&self) -> Option<&TypeRow> {
+        match self {
+            Self::Classic(ClassicType::Container(Container::Sum(variants)))
+            | Self::Linear(LinearType::Container(Container::Sum(variants))) => Some(variants),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of variants of a simple predicate, whose variants
+    /// are all empty Tuples. Returns `None` for other types.
+    pub fn predicate_arity(&self) -> Option<usize> {
+        let variants = self.as_sum()?;
+        variants
+            .iter()
+            .all(|variant| match variant {
+                Self::Classic(ClassicType::Container(Container::Tuple(row))) => row.is_empty(),
+                _ => false,
+            })
+            .then_some(variants.len())
+    }
+
+    /// Returns whether the type is a simple predicate with a single variant.
+    pub fn is_unit_predicate(&self) -> bool {
+        self.predicate_arity() == Some(1)
+    }
+
+    /// Wrap the type into a new type with the given name.
+    ///
+    /// The new type is displayed as its name, and is only equal to the new
+    /// types with the same name and underlying type. Use
+    /// [`SimpleType::structurally_eq`] to ignore the names.
+    pub fn into_new_type(self, name: impl Into<SmolStr>) -> Self {
+        let name = name.into();
+        match self {
+            SimpleType::Classic(ty) => Container::NewType(name, Box::new(ty)).into(),
+            SimpleType::Linear(ty) => Container::NewType(name, Box::new(ty)).into(),
+        }
+    }
+
+    /// Returns the name of a new type or of an alias, or `None` for other
+    /// types.
+    pub fn alias_name(&self) -> Option<&SmolStr> {
+        match self {
+            Self::Classic(ClassicType::Container(
+                Container::NewType(name, _) | Container::Alias(name),
+            ))
+            | Self::Linear(LinearType::Container(
+                Container::NewType(name, _) | Container::Alias(name),
+            )) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The structure of the type, with all new-type wrappers replaced by
+    /// their underlying type, including the nested ones.
+    ///
+    /// Aliases declared by AliasDefn or AliasDecl nodes are kept, as their
+    /// definition is not part of the type.
+    pub fn resolve_alias(&self) -> Self {
+        match self {
+            SimpleType::Classic(ty) => SimpleType::Classic(ty.resolve_alias()),
+            SimpleType::Linear(ty) => SimpleType::Linear(ty.resolve_alias()),
+        }
+    }
+
+    /// Returns whether the types have the same structure, ignoring the names
+    /// of new types.
+    ///
+    /// The default equality of types is nominal.
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        self.resolve_alias() == other.resolve_alias()
+    }
 }
 
 impl Default for SimpleType {
@@ -407,6 +521,14 @@ impl TypeRow {
             .collect_vec()
             .into()
     }
+
+    /// The row with all new-type wrappers replaced by their underlying type.
+    fn resolve_alias(&self) -> Self {
+        self.iter()
+            .map(SimpleType::resolve_alias)
+            .collect_vec()
+            .into()
+    }
 }
 
 impl Default for TypeRow {
@@ -437,3 +559,75 @@ impl DerefMut for TypeRow {
         self.types.to_mut()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn predicate_arity() {
+        let simple = SimpleType::new_simple_predicate(3);
+        assert_eq!(simple.as_sum().map(|v| v.len()), Some(3));
+        assert_eq!(simple.predicate_arity(), Some(3));
+        assert!(!simple.is_unit_predicate());
+        assert!(SimpleType::new_simple_predicate(1).is_unit_predicate());
+
+        // Predicates with values in their variants are not simple.
+        let rows = [
+            type_row![],
+            vec![SimpleType::Classic(ClassicType::bit())].into(),
+        ];
+        let predicate = SimpleType::new_predicate(rows.clone());
+        assert_eq!(
+            predicate.as_sum(),
+            Some(&TypeRow::predicate_variants_row(rows))
+        );
+        assert_eq!(predicate.predicate_arity(), None);
+
+        // Sums of non-tuple types are not predicates.
+        let qb = SimpleType::Linear(LinearType::Qubit);
+        let sum = SimpleType::new_sum(vec![qb.clone(), qb.clone()]);
+        assert!(sum.is_linear());
+        assert_eq!(sum.as_sum().map(|v| v.len()), Some(2));
+        assert_eq!(sum.predicate_arity(), None);
+        assert_eq!(SimpleType::new_tuple(type_row![]).as_sum(), None);
+        assert_eq!(qb.predicate_arity(), None);
+    }
+
+    #[test]
+    fn new_types() {
+        let nat = SimpleType::Classic(ClassicType::i64());
+        let qb = SimpleType::Linear(LinearType::Qubit);
+        let length = nat.clone().into_new_type("Length");
+        let register = SimpleType::new_tuple(vec![qb.clone(), length.clone()]);
+
+        // New types are displayed by name, also when nested.
+        assert_eq!(length.to_string(), "Length");
+        assert_eq!(register.to_string(), "Tuple([Qubit, Length])");
+        assert_eq!(length.alias_name().map(SmolStr::as_str), Some("Length"));
+        assert_eq!(nat.alias_name(), None);
+        let alias: SimpleType = Container::<ClassicType>::Alias("Decl".into()).into();
+        assert_eq!(alias.alias_name().map(SmolStr::as_str), Some("Decl"));
+
+        // Equality is nominal by default.
+        assert!(length.is_classical());
+        assert!(qb.clone().into_new_type("Reg").is_linear());
+        assert_ne!(length, nat);
+        assert_ne!(length, nat.clone().into_new_type("Width"));
+        assert_eq!(length, nat.clone().into_new_type("Length"));
+        assert!(length.structurally_eq(&nat));
+        assert!(length.structurally_eq(&nat.clone().into_new_type("Width")));
+        assert!(!length.structurally_eq(&qb));
+
+        // Resolution removes the nested wrappers, and keeps declared aliases.
+        assert_eq!(length.resolve_alias(), nat);
+        assert_eq!(
+            register.into_new_type("Register").resolve_alias(),
+            SimpleType::new_tuple(vec![qb, nat])
+        );
+        assert_eq!(alias.resolve_alias(), alias);
+
+        let json = serde_json::to_string(&length).unwrap();
+        assert_eq!(serde_json::from_str::<SimpleType>(&json).unwrap(), length);
+    }
+}
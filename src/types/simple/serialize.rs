@@ -61,6 +61,11 @@ pub(crate) enum SerSimpleType {
         name: SmolStr,
         l: bool,
     },
+    NewType {
+        name: SmolStr,
+        inner: Box<SimpleType>,
+        l: bool,
+    },
     Var {
         name: SmolStr,
     },
@@ -92,6 +97,11 @@ impl<T: PrimType + Into<SimpleType>> From<Container<T>> for SerSimpleType {
                 l: T::LINEAR,
             },
             Container::Alias(name) => SerSimpleType::Alias { name, l: T::LINEAR },
+            Container::NewType(name, inner) => SerSimpleType::NewType {
+                name,
+                inner: box_convert(*inner),
+                l: T::LINEAR,
+            },
         }
     }
 }
@@ -208,6 +218,7 @@ impl From<SerSimpleType> for SimpleType {
             } => Container::<ClassicType>::Array(box_convert_try(*inner), len).into(),
             SerSimpleType::Alias { name: s, l: true } => Container::<LinearType>::Alias(s).into(),
             SerSimpleType::Alias { name: s, l: false } => Container::<ClassicType>::Alias(s).into(),
+            SerSimpleType::NewType { name, inner, .. } => inner.into_new_type(name),
             SerSimpleType::Opaque { custom: c, l: true } => LinearType::Qpaque(c).into(),
             SerSimpleType::Opaque {
                 custom: c,
@@ -0,0 +1,291 @@
+//! Golden-file regression tests for the serialization and dot rendering of
+//! HUGRs.
+//!
+//! Each fixture is built programmatically and stored under `tests/fixtures/`
+//! as `<name>.json` (the serialized HUGR) and `<name>.dot` (its
+//! [`Hugr::dot_string`]). The tests deserialize every stored fixture,
+//! validate it, and check that serializing it again and rendering it
+//! reproduce the stored files byte for byte.
+//!
+//! Set the `HUGR_UPDATE_GOLDEN` environment variable to regenerate the
+//! fixtures after an intentional format change.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Once;
+
+use hugr::builder::{
+    BuildError, CFGBuilder, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+    HugrBuilder, ModuleBuilder, SubContainer, TailLoopBuilder,
+};
+use hugr::ops::{ConstValue, LeafOp};
+use hugr::type_row;
+use hugr::types::{ClassicType, LinearType, Signature, SimpleType};
+use hugr::{Hugr, HugrView};
+
+const QB: SimpleType = SimpleType::Linear(LinearType::Qubit);
+const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+const F64: SimpleType = SimpleType::Classic(ClassicType::F64);
+
+/// The environment variable requesting the fixtures to be regenerated.
+const UPDATE_VAR: &str = "HUGR_UPDATE_GOLDEN";
+
+/// The [`hugr::ops::OpType`] variants which the fixtures must cover.
+const OP_VARIANTS: [&str; 19] = [
+    "Module",
+    "FuncDefn",
+    "FuncDecl",
+    "AliasDecl",
+    "AliasDefn",
+    "Const",
+    "ConstRow",
+    "Input",
+    "Output",
+    "Call",
+    "CallIndirect",
+    "LoadConstant",
+    "DFG",
+    "LeafOp",
+    "BasicBlock",
+    "TailLoop",
+    "CFG",
+    "Conditional",
+    "Case",
+];
+
+/// A module with declarations, aliases, constants, and direct and indirect
+/// calls.
+fn module() -> Result<Hugr, BuildError> {
+    let mut module_builder = ModuleBuilder::new();
+    let bit_sig = Signature::new_df(type_row![BIT], type_row![BIT]);
+    let ext = module_builder.declare("ext", bit_sig.clone())?;
+    module_builder.add_alias_declare("Opaque", false)?;
+    module_builder.add_alias_def("Pair", SimpleType::new_tuple(type_row![BIT, F64]))?;
+
+    let graph = SimpleType::Classic(ClassicType::graph_from_sig(bit_sig));
+    let mut apply = module_builder
+        .define_function("apply", Signature::new_df(vec![graph, BIT], type_row![BIT]))?;
+    let [f, b] = apply.input_wires_arr();
+    let call = apply.call_indirect(f, [b])?;
+    apply.finish_with_outputs(call.outputs())?;
+
+    let mut main = module_builder.define_function(
+        "main",
+        Signature::new_df(
+            vec![BIT],
+            vec![BIT, SimpleType::new_simple_predicate(2), F64, F64],
+        ),
+    )?;
+    let [b] = main.input_wires_arr();
+    let call = main.call(&ext, [b])?;
+    let t = main.add_load_const(ConstValue::true_val())?;
+    let row = main.add_constant_row([ConstValue::F64(0.5), ConstValue::F64(-1.25)])?;
+    let x = main.load_const(&row[0])?;
+    let y = main.load_const(&row[1])?;
+    main.finish_with_outputs([call.out_wire(0), t, x, y])?;
+
+    module_builder.finish_hugr()
+}
+
+/// A quantum circuit with a nested dataflow graph.
+fn circuit() -> Result<Hugr, BuildError> {
+    let mut builder = DFGBuilder::new(type_row![QB, QB], type_row![QB, QB, BIT])?;
+    let [q0, q1] = builder.input_wires_arr();
+    let q0 = builder.add_dataflow_op(LeafOp::H, [q0])?.out_wire(0);
+    let [q0, q1] = builder.add_dataflow_op(LeafOp::CX, [q0, q1])?.outputs_arr();
+    let [q1, m] = builder
+        .add_dataflow_op(LeafOp::Measure, [q1])?
+        .outputs_arr();
+    let nested = {
+        let mut nested =
+            builder.dfg_builder(Signature::new_df(type_row![QB], type_row![QB]), [q0])?;
+        let [q] = nested.input_wires_arr();
+        let q = nested.add_dataflow_op(LeafOp::Z, [q])?.out_wire(0);
+        nested.finish_with_outputs([q])?
+    };
+    builder.finish_hugr_with_outputs([nested.out_wire(0), q1, m])
+}
+
+/// A control flow graph with an entry, a middle and an exit block.
+fn cfg() -> Result<Hugr, BuildError> {
+    let mut cfg_builder = CFGBuilder::new(type_row![NAT], type_row![NAT])?;
+    let sum2_variants = vec![type_row![NAT], type_row![NAT]];
+    let mut entry_b = cfg_builder.entry_builder(sum2_variants.clone(), type_row![])?;
+    let entry = {
+        let [inw] = entry_b.input_wires_arr();
+        let sum = entry_b.make_predicate(1, sum2_variants, [inw])?;
+        entry_b.finish_with_outputs(sum, [])?
+    };
+    let mut middle_b = cfg_builder.simple_block_builder(type_row![NAT], type_row![NAT], 1)?;
+    let middle = {
+        let c = middle_b.add_load_const(ConstValue::simple_unary_predicate())?;
+        let [inw] = middle_b.input_wires_arr();
+        middle_b.finish_with_outputs(c, [inw])?
+    };
+    let exit = cfg_builder.exit_block()?;
+    cfg_builder.branch(&entry, 0, &middle)?;
+    cfg_builder.branch(&middle, 0, &exit)?;
+    cfg_builder.branch(&entry, 1, &exit)?;
+    cfg_builder.finish_hugr()
+}
+
+/// A tail loop whose body decides whether to continue with a conditional.
+fn tail_loop() -> Result<Hugr, BuildError> {
+    let mut loop_b = TailLoopBuilder::new(type_row![BIT], type_row![], type_row![BIT])?;
+    let signature = loop_b.loop_signature()?.clone();
+    let [b] = loop_b.input_wires_arr();
+    let predicate = loop_b.add_load_const(ConstValue::simple_predicate(0, 2))?;
+    let conditional = {
+        let output_row = loop_b.internal_output_row()?;
+        let mut conditional_b = loop_b.conditional_builder(
+            (vec![type_row![]; 2], predicate),
+            [(BIT, b)],
+            output_row,
+        )?;
+
+        let mut case_0 = conditional_b.case_builder(0)?;
+        let [b] = case_0.input_wires_arr();
+        let continue_wire = case_0.make_continue(signature.clone(), [b])?;
+        case_0.finish_with_outputs([continue_wire])?;
+
+        let mut case_1 = conditional_b.case_builder(1)?;
+        let [b] = case_1.input_wires_arr();
+        let break_wire = case_1.make_break(signature, [b])?;
+        case_1.finish_with_outputs([break_wire])?;
+
+        conditional_b.finish_sub_container()?
+    };
+    loop_b.set_outputs(conditional.out_wire(0), [])?;
+    loop_b.finish_hugr()
+}
+
+/// The fixtures, by file name.
+///
+/// Serialization stores the nodes in hierarchy order, so a freshly built
+/// HUGR may not serialize like its deserialized copy. The fixtures are
+/// normalized by a first roundtrip, after which serialization is stable.
+fn fixtures() -> [(&'static str, Hugr); 4] {
+    [
+        ("module", module()),
+        ("circuit", circuit()),
+        ("cfg", cfg()),
+        ("tail_loop", tail_loop()),
+    ]
+    .map(|(name, hugr)| (name, normalize(&hugr.unwrap())))
+}
+
+fn normalize(hugr: &Hugr) -> Hugr {
+    serde_json::from_str(&to_json(hugr)).unwrap()
+}
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+}
+
+fn fixture_path(name: &str, extension: &str) -> PathBuf {
+    fixtures_dir().join(name).with_extension(extension)
+}
+
+fn to_json(hugr: &Hugr) -> String {
+    let mut json = serde_json::to_string_pretty(hugr).unwrap();
+    json.push('\n');
+    json
+}
+
+fn read_fixture(name: &str, extension: &str) -> String {
+    let path = fixture_path(name, extension);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "Could not read {}: {e}. Run with {UPDATE_VAR}=1 to generate it.",
+            path.display()
+        )
+    })
+}
+
+/// Write the fixtures once if regeneration was requested.
+fn update_fixtures() {
+    static UPDATE: Once = Once::new();
+    UPDATE.call_once(|| {
+        if std::env::var_os(UPDATE_VAR).is_none() {
+            return;
+        }
+        std::fs::create_dir_all(fixtures_dir()).unwrap();
+        for (name, hugr) in fixtures() {
+            std::fs::write(fixture_path(name, "json"), to_json(&hugr)).unwrap();
+            std::fs::write(fixture_path(name, "dot"), hugr.dot_string()).unwrap();
+        }
+    });
+}
+
+#[test]
+fn serialization_roundtrip() {
+    update_fixtures();
+    for (name, built) in fixtures() {
+        let stored = read_fixture(name, "json");
+        let hugr: Hugr = serde_json::from_str(&stored).unwrap();
+        hugr.validate()
+            .unwrap_or_else(|e| panic!("Fixture {name} is invalid: {e}"));
+        assert_eq!(to_json(&hugr), stored, "{name}.json does not roundtrip");
+        assert_eq!(
+            to_json(&built),
+            stored,
+            "{name}.json is out of date, run with {UPDATE_VAR}=1 to regenerate it"
+        );
+    }
+}
+
+#[test]
+fn dot_rendering() {
+    update_fixtures();
+    for (name, _) in fixtures() {
+        let hugr: Hugr = serde_json::from_str(&read_fixture(name, "json")).unwrap();
+        assert_eq!(
+            hugr.dot_string(),
+            read_fixture(name, "dot"),
+            "{name}.dot is out of date, run with {UPDATE_VAR}=1 to regenerate it"
+        );
+    }
+}
+
+#[test]
+fn op_coverage() {
+    let covered: BTreeSet<String> = fixtures()
+        .iter()
+        .flat_map(|(_, hugr)| {
+            hugr.nodes().map(|n| {
+                let debug = format!("{:?}", hugr.get_optype(n));
+                debug.split('(').next().unwrap().to_string()
+            })
+        })
+        .collect();
+    let missing: Vec<&str> = OP_VARIANTS
+        .into_iter()
+        .filter(|op| !covered.contains(*op))
+        .collect();
+    assert!(missing.is_empty(), "No fixture covers {missing:?}");
+}
+
+#[test]
+fn summaries() {
+    let expected = [
+        "nodes=17 (functions=3 consts=4 io=4 calls=2 loads=3 other=1) regions=3 max_region=8 \
+         mean_region=5.3 depth=2 inter_graph_edges=1 top_leaf_ops=[]",
+        "nodes=10 (containers=2 io=4 leaf=4) regions=2 max_region=6 mean_region=4.5 depth=2 \
+         inter_graph_edges=0 top_leaf_ops=[CX:1 H:1 Measure:1 Z:1]",
+        "nodes=12 (consts=1 containers=1 blocks=3 io=4 loads=1 leaf=2) regions=3 max_region=4 \
+         mean_region=3.7 depth=2 inter_graph_edges=0 top_leaf_ops=[MakeTuple:1 Tag:1]",
+        "nodes=16 (consts=1 containers=4 io=6 loads=1 leaf=4) regions=4 max_region=5 \
+         mean_region=3.8 depth=3 inter_graph_edges=0 top_leaf_ops=[MakeTuple:2 Tag:2]",
+    ];
+    let fixtures = fixtures();
+    assert_eq!(fixtures.len(), expected.len());
+    for ((name, _), expected) in fixtures.into_iter().zip(expected) {
+        let hugr: Hugr = serde_json::from_str(&read_fixture(name, "json")).unwrap();
+        let summary = hugr.summary();
+        assert_eq!(summary.nodes, hugr.node_count());
+        assert_eq!(summary.to_string(), expected, "summary of {name}");
+    }
+}